@@ -0,0 +1,274 @@
+# German.
+
+language-name = Deutsch
+
+## Relay states
+
+state-on = AN
+state-off = AUS
+state-unknown = ...
+state-error = FEHLER
+
+## Main window
+
+switched-on = Eingeschaltet { $ago }
+switched-off = Ausgeschaltet { $ago }
+pending = ausstehend
+pending-note = (ausstehend)
+assumed = angenommen: diese Platine meldet keinen Zustand
+assumed-note = (angenommen)
+all-off = ⚠ ALLE AUS
+all-off-hint = Alle Kanäle ausschalten ({ $shortcut })
+monitor-mode = 👁 Monitoransicht: nur lesen
+toolbar-sequences = Abläufe
+toolbar-schedules = Zeitpläne
+toolbar-rules = Regeln
+toolbar-settings = Einstellungen
+toolbar-traffic = Verkehr
+toolbar-history = Verlauf
+toolbar-console = Konsole
+toolbar-mini = Mini
+toolbar-mini-hint = Kleines Fenster, immer im Vordergrund
+toolbar-quit = Beenden
+queued = Warteschlange ({ $count })
+cancel = Abbrechen
+switch-failed = Schalten fehlgeschlagen
+switch-failed-on = { $channel } wurde nicht eingeschaltet, Zustand: { $state }
+switch-failed-off = { $channel } wurde nicht ausgeschaltet, Zustand: { $state }
+channel-state = { $channel }: { $state }
+switch-on = { $channel } einschalten
+switch-off = { $channel } ausschalten
+select-channel = { $channel } auswählen
+cancel-queued = { $command } abbrechen
+dismiss = Schließen
+full-window = Volles Fenster
+menubar-show = Fenster anzeigen
+kiosk-hold = Hier gedrückt halten, um den Kioskmodus zu verlassen
+kiosk-keep-holding = Weiter halten zum Verlassen
+remote-unreachable = Nicht erreichbar: { $error }
+remote-switch-failed = Schalten fehlgeschlagen: { $error }
+
+## Schedule grid
+
+grid-title = Wochenplan
+grid-clear = Leeren
+grid-help = Klicken oder ziehen, um EIN-Blöcke in halbstündigen Schritten zu malen.
+grid-others = { $count } weitere(r) Zeitplan/Zeitpläne für diesen Kanal bleiben unverändert.
+grid-all-schedules = Alle Zeitpläne
+grid-no-schedules = Noch keine Zeitpläne.
+grid-pause-hint = Haken entfernen, um den Zeitplan anzuhalten, bis er wieder gesetzt wird
+grid-snooze = Schlummern
+grid-snooze-hours = { $count } Std.
+grid-snoozed = schlummert bis { $time }
+grid-unsnooze = Wecken
+grid-save-first = Erst den Wochenplan speichern; dieser Zeitplan fehlt noch in der Konfiguration.
+
+## Schedule overrides
+
+override-title = Zeitplan übersteuern
+override-question = { $channel } hat einen Zeitplan. Zeitpläne übersteuern:
+override-next = Bis zum nächsten Zeitplan
+override-next-at = Bis zum nächsten Zeitplan um { $time }
+override-until-next = Übersteuert bis zum nächsten Zeitplan
+override-until-next-at = Übersteuert bis zum nächsten Zeitplan um { $time }
+override-until = Übersteuert bis { $time }
+override-for = Für
+override-hours = Stunden
+override-resume = Zeitplan fortsetzen
+override-resume-hint = Klicken, um den Kanal jetzt wieder seinen Zeitplänen zu überlassen
+override-resume-channel = Zeitpläne für { $channel } fortsetzen
+
+## Daily switch limit
+
+limit-reached = ⛔ { $count } Schaltungen heute
+limit-allow = Heute weitere erlauben
+limit-allow-hint = Klicken, damit der Kanal für den Rest des Tages wieder einschalten darf
+limit-allow-channel = { $channel } heute wieder einschalten lassen
+
+## Off confirmation
+
+confirm-off-title = Ausschalten bestätigen
+confirm-off-question = { $channel } wirklich ausschalten?
+confirm-off-type = Zum Bestätigen „{ $label }“ eingeben:
+confirm-off-button = Ausschalten
+
+## Lock
+
+lock-locked = 🔒 Gesperrt
+lock-pin = PIN
+lock-unlock = Entsperren
+lock-wrong = Falsche PIN
+lock-now = 🔒 Sperren
+
+## Relative times
+
+just-now = gerade eben
+minutes-ago = vor { $count } Min.
+hours-ago = vor { $count } Std.
+yesterday = gestern
+days-ago = vor { $count } Tagen
+
+## Durations
+
+duration-seconds = { $seconds } s
+duration-minutes = { $minutes } min
+duration-hours = { $hours } h { $minutes } min
+
+## Settings
+
+settings = Einstellungen
+settings-device = Gerät
+settings-port = Anschluss
+settings-detect = Automatisch erkennen
+settings-rescan = Neu suchen
+settings-detection = Die Erkennung akzeptiert zusätzlich, durch Kommas getrennt:
+settings-usb-ids = VID:PID
+settings-keywords = Stichwörter
+settings-write-only = Nur schreiben: die Platine antwortet nie, letzten Zustand anzeigen
+settings-strict = Streng: Antworten mit falscher Prüfsumme verwerfen
+settings-serial = Serielle Leitung (die meisten Platinen nutzen 9600 8N1):
+settings-baud-rate = Baudrate
+settings-common = Üblich
+settings-data-bits = Datenbits
+settings-parity = Parität
+settings-stop-bits = Stoppbits
+settings-flow-control = Flusssteuerung
+settings-buttons = Tasten
+settings-debounce = Mindestabstand zwischen Tastendrücken eines Kanals (0 = aus):
+settings-confirm-off = Nachfragen, bevor die AUS-Taste eines Kanals ihn ausschaltet:
+settings-safety = Sicherheit
+settings-max-on = Kanal ausschalten, wenn er so lange eingeschaltet war (0 = keine Grenze):
+settings-cooldown = Kanal so lange ausgeschaltet lassen, bevor er wieder einschalten darf (0 = aus):
+settings-max-on-column = Max. EIN
+settings-cooldown-column = Abkühlzeit
+settings-switch-limit = Kanal nach so vielen Schaltungen am Tag nicht mehr einschalten (0 = keine Grenze):
+settings-switch-limit-column = Pro Tag
+settings-lock = Sperre
+settings-lock-pin = PIN oder Passwort (leer = keine Sperre)
+settings-lock-idle = Wieder sperren nach Inaktivität von (0 = nie)
+settings-display = Anzeige
+settings-clock = Uhr
+settings-language = Sprache
+settings-system-default = Systemstandard
+clock-twelve-hour = 12-Stunden
+clock-twenty-four-hour = 24-Stunden
+settings-theme = Design
+settings-error = Fehler
+settings-shapes = Zustände auch durch Formen unterscheiden
+settings-shapes-hint = AN gefüllt, AUS hohl, unbekannt ?, Fehler Dreieck
+settings-reset-colors = Farben zurücksetzen
+settings-login = Beim Anmelden starten
+settings-login-enabled = Die App beim Anmelden starten
+settings-start-minimized = Minimiert starten
+settings-window = Fenster
+settings-scale = Skalierung
+settings-scale-hint = Zusätzlich zur Anzeigeskalierung des Systems; Strg+Plus/Minus zoomt bis zum Beenden
+settings-start-mini = Im Mini-Modus starten
+settings-start-kiosk = Im Kioskmodus starten (Vollbild für Touchscreens)
+settings-background = Weiterlaufen, wenn das Fenster geschlossen wird
+settings-background-hint = Schließen minimiert das Fenster; zum Beenden „Beenden“ in der Leiste
+settings-menu-bar = Aus der Menüleiste bedienen
+settings-menu-bar-hint = Startet mit verborgenem Fenster und ohne Dock-Symbol; das Menüleistensymbol zeigt und schaltet die Kanäle (nach einem Neustart)
+settings-exit = Beim Beenden
+settings-exit-policy = Wenn das Fenster schließt
+settings-sounds = Töne
+settings-sound-enabled = Beim Schalten und bei Fehlern einen Ton abspielen
+settings-preview = Vorhören
+settings-voice = Sprachbefehle
+settings-voice-enabled = Auf gesprochene Befehle hören
+settings-voice-hint = Offline mit Vosk erkannt: „power on“, „power off“ oder die Sätze aus der Konfigurationsdatei
+settings-voice-model = Vosk-Modellordner
+settings-lock-screen = Sperrbildschirm
+settings-lock-help = Kanäle schalten, wenn die Sitzung gesperrt oder entsperrt wird.
+settings-on-lock = Beim Sperren
+settings-on-unlock = Beim Entsperren
+settings-hotkeys = Globale Tastenkürzel
+settings-hotkeys-help = Funktionieren auch, wenn dieses Fenster im Hintergrund ist.
+settings-press-keys = Tasten drücken…
+settings-click-to-set = Zum Festlegen klicken
+settings-add-hotkey = + Tastenkürzel hinzufügen
+settings-remove-hotkey = Tastenkürzel entfernen
+settings-save = Speichern
+settings-saved = Gespeichert
+rules-title = Regeln
+rules-none = (keine)
+rules-new = Neu
+rules-delete = Löschen
+rules-default-name = Regel { $number }
+rules-empty = Noch keine Regeln.
+rules-name = Name
+rules-enabled = Aktiv
+rules-when = Wenn
+rules-only-if = Nur falls
+rules-then = Dann
+rules-add-condition = + Bedingung hinzufügen
+rules-add-action = + Aktion hinzufügen
+rules-remove = Entfernen
+rules-trigger-time = Um
+rules-trigger-ping = Ping schlägt fehl
+rules-trigger-usb-plugged = USB-Gerät eingesteckt
+rules-trigger-usb-unplugged = USB-Gerät abgezogen
+rules-trigger-api = API-Aufruf
+rules-trigger-switched = Kanal schaltet
+rules-condition-channel = Kanal ist
+rules-condition-between = Uhrzeit zwischen
+rules-condition-days = An Tagen
+rules-action-on = Einschalten
+rules-action-off = Ausschalten
+rules-action-pulse = Impuls
+rules-action-sequence = Ablauf starten
+rules-action-notify = Benachrichtigen
+
+## Sequences
+
+sequences-title = Abläufe
+sequences-none = (keiner)
+sequences-new = Neu
+sequences-delete = Löschen
+sequences-default-name = Ablauf { $number }
+sequences-empty = Noch keine Abläufe.
+sequences-name = Name
+sequences-then-wait = dann warten
+sequences-add-step = + Schritt hinzufügen
+sequences-start = ▶ Ablauf starten
+sequences-shutdown = ⏹ Herunterfahren
+sequences-save = Speichern
+sequences-saved = Gespeichert
+
+## Selected channels
+
+bulk-selected = { $count } ausgewählt
+bulk-clear = Leeren
+bulk-all-off = Alle AUS
+bulk-pulse = Impuls
+bulk-scene = Szene
+bulk-add-to-scene = Zur Szene hinzufügen
+bulk-added = { $count } Kanal/Kanäle zu „{ $scene }“ hinzugefügt
+bulk-label-prefix = Namenspräfix
+bulk-set-labels = Namen setzen
+bulk-copy-settings = Einstellungen hierher kopieren
+bulk-template = Vorlage
+bulk-apply-template = Vorlage anwenden
+bulk-choose-template = Vorlage auswählen
+
+## Traffic
+
+traffic-title = Verkehr
+traffic-frames = { $count } Frame(s)
+traffic-save = In Datei speichern
+traffic-saved = Gespeichert unter { $path }
+traffic-clear = Leeren
+
+## Hex console
+
+console-title = Hex-Konsole
+console-send = Senden
+console-clear = Leeren
+console-no-reply = (keine Antwort)
+
+## History
+
+history-title = Verlauf
+history-entries = { $count } Einträge
+history-reload = Neu laden
+history-empty = Noch nichts aufgezeichnet.
@@ -0,0 +1,277 @@
+# English, the built-in fallback for every other language.
+#
+# Copy this file to locales/<language>.ftl in the config directory to add a
+# translation; messages it leaves out stay in English.
+
+language-name = English
+
+## Relay states
+
+state-on = ON
+state-off = OFF
+state-unknown = ...
+state-error = ERROR
+
+## Main window
+
+switched-on = Switched on { $ago }
+switched-off = Switched off { $ago }
+pending = pending
+pending-note = (pending)
+assumed = assumed: this board sends no status
+assumed-note = (assumed)
+all-off = ⚠ ALL OFF
+all-off-hint = Switch every channel off ({ $shortcut })
+monitor-mode = 👁 Monitor mode: read-only
+toolbar-sequences = Sequences
+toolbar-schedules = Schedules
+toolbar-rules = Rules
+toolbar-settings = Settings
+toolbar-traffic = Traffic
+toolbar-history = History
+toolbar-console = Console
+toolbar-mini = Mini
+toolbar-mini-hint = Small always-on-top window
+toolbar-quit = Quit
+queued = Queued ({ $count })
+cancel = Cancel
+switch-failed = Switch failed
+switch-failed-on = { $channel } did not switch on, it is { $state }
+switch-failed-off = { $channel } did not switch off, it is { $state }
+channel-state = { $channel }: { $state }
+switch-on = Switch { $channel } on
+switch-off = Switch { $channel } off
+select-channel = Select { $channel }
+cancel-queued = Cancel { $command }
+dismiss = Dismiss
+full-window = Full window
+menubar-show = Show window
+kiosk-hold = Hold here to exit kiosk mode
+kiosk-keep-holding = Keep holding to exit
+remote-unreachable = Not reachable: { $error }
+remote-switch-failed = Switch failed: { $error }
+
+## Schedule grid
+
+grid-title = Weekly schedule
+grid-clear = Clear
+grid-help = Click or drag to paint ON blocks in half-hour steps.
+grid-others = { $count } other schedule(s) for this channel are kept as they are.
+grid-all-schedules = All schedules
+grid-no-schedules = No schedules yet.
+grid-pause-hint = Untick to pause the schedule until you tick it again
+grid-snooze = Snooze
+grid-snooze-hours = { $count } h
+grid-snoozed = snoozed until { $time }
+grid-unsnooze = Wake
+grid-save-first = Save the grid first; this schedule isn't in the config file yet.
+
+## Schedule overrides
+
+override-title = Override schedule
+override-question = { $channel } is scheduled. Override its schedules:
+override-next = Until the next schedule
+override-next-at = Until the next schedule at { $time }
+override-until-next = Overridden until the next schedule
+override-until-next-at = Overridden until the next schedule at { $time }
+override-until = Overridden until { $time }
+override-for = For
+override-hours = hours
+override-resume = Resume schedule
+override-resume-hint = Click to hand the channel back to its schedules now
+override-resume-channel = Resume schedules for { $channel }
+
+## Daily switch limit
+
+limit-reached = ⛔ { $count } switches today
+limit-allow = Allow more today
+limit-allow-hint = Click to let the channel switch on again for the rest of today
+limit-allow-channel = Allow { $channel } to switch on again today
+
+## Off confirmation
+
+confirm-off-title = Confirm switching off
+confirm-off-question = Really switch { $channel } off?
+confirm-off-type = Type "{ $label }" to confirm:
+confirm-off-button = Switch off
+
+## Lock
+
+lock-locked = 🔒 Locked
+lock-pin = PIN
+lock-unlock = Unlock
+lock-wrong = Wrong PIN
+lock-now = 🔒 Lock
+
+## Relative times
+
+just-now = just now
+minutes-ago = { $count } min ago
+hours-ago = { $count } h ago
+yesterday = yesterday
+days-ago = { $count } days ago
+
+## Durations
+
+duration-seconds = { $seconds }s
+duration-minutes = { $minutes }m
+duration-hours = { $hours }h { $minutes }m
+
+## Settings
+
+settings = Settings
+settings-device = Device
+settings-port = Port
+settings-detect = Detect automatically
+settings-rescan = Rescan
+settings-detection = Detection also accepts these, comma-separated:
+settings-usb-ids = VID:PID
+settings-keywords = Keywords
+settings-write-only = Write-only: the board never answers, show the last switched state
+settings-strict = Strict: discard replies with a bad checksum
+settings-serial = Serial line (most boards use 9600 8N1):
+settings-baud-rate = Baud rate
+settings-common = Common
+settings-data-bits = Data bits
+settings-parity = Parity
+settings-stop-bits = Stop bits
+settings-flow-control = Flow control
+settings-buttons = Buttons
+settings-debounce = Minimum time between presses of a channel's buttons (0 = off):
+settings-confirm-off = Confirm before a channel's OFF button switches it off:
+settings-safety = Safety
+settings-max-on = Switch a channel off after it has been on this long (0 = no limit):
+settings-cooldown = Keep a channel off this long before it may switch on again (0 = off):
+settings-max-on-column = Max ON
+settings-cooldown-column = Cooldown
+settings-switch-limit = Stop switching a channel on after this many changes a day (0 = no limit):
+settings-switch-limit-column = Per day
+settings-lock = Lock
+settings-lock-pin = PIN or passphrase (empty = no lock)
+settings-lock-idle = Lock again after no input for (0 = never)
+settings-display = Display
+settings-clock = Clock
+settings-language = Language
+settings-system-default = System default
+clock-twelve-hour = 12-hour
+clock-twenty-four-hour = 24-hour
+settings-theme = Theme
+settings-error = Error
+settings-shapes = Show states by shape as well as color
+settings-shapes-hint = ON filled, OFF hollow, unknown ?, error triangle
+settings-reset-colors = Reset colors
+settings-login = Start at login
+settings-login-enabled = Start the app when I log in
+settings-start-minimized = Start minimized
+settings-window = Window
+settings-scale = Scale
+settings-scale-hint = On top of the system display scale; Ctrl+Plus/Minus zooms for the session
+settings-start-mini = Start in mini mode
+settings-start-kiosk = Start in kiosk mode (fullscreen touch layout)
+settings-background = Keep running when the window is closed
+settings-background-hint = Closing minimizes the window; use Quit in the toolbar to exit
+settings-menu-bar = Run from the menu bar
+settings-menu-bar-hint = Starts with the window hidden and no Dock icon; the menu bar item shows and switches the channels (after a restart)
+settings-exit = On exit
+settings-exit-policy = When the window closes
+settings-sounds = Sounds
+settings-sound-enabled = Play a sound when switching or on errors
+settings-preview = Preview
+settings-voice = Voice commands
+settings-voice-enabled = Listen for spoken commands
+settings-voice-hint = Recognized offline with Vosk; say "power on" or "power off", or the phrases in the config file
+settings-voice-model = Vosk model folder
+settings-lock-screen = Lock screen
+settings-lock-help = Switch channels when the session is locked or unlocked.
+settings-on-lock = On lock
+settings-on-unlock = On unlock
+settings-hotkeys = Global hotkeys
+settings-hotkeys-help = Work even when this window is in the background.
+settings-press-keys = Press keys…
+settings-click-to-set = Click to set
+settings-add-hotkey = + Add hotkey
+settings-remove-hotkey = Remove hotkey
+settings-save = Save
+settings-saved = Saved
+rules-title = Rules
+rules-none = (none)
+rules-new = New
+rules-delete = Delete
+rules-default-name = Rule { $number }
+rules-empty = No rules yet.
+rules-name = Name
+rules-enabled = Enabled
+rules-when = When
+rules-only-if = Only if
+rules-then = Then
+rules-add-condition = + Add condition
+rules-add-action = + Add action
+rules-remove = Remove
+rules-trigger-time = At
+rules-trigger-ping = Ping fails
+rules-trigger-usb-plugged = USB device plugged in
+rules-trigger-usb-unplugged = USB device unplugged
+rules-trigger-api = API call
+rules-trigger-switched = Channel switches
+rules-condition-channel = Channel is
+rules-condition-between = Time between
+rules-condition-days = On days
+rules-action-on = Switch on
+rules-action-off = Switch off
+rules-action-pulse = Pulse
+rules-action-sequence = Run sequence
+rules-action-notify = Notify
+
+## Sequences
+
+sequences-title = Sequences
+sequences-none = (none)
+sequences-new = New
+sequences-delete = Delete
+sequences-default-name = Sequence { $number }
+sequences-empty = No sequences yet.
+sequences-name = Name
+sequences-then-wait = then wait
+sequences-add-step = + Add step
+sequences-start = ▶ Start Sequence
+sequences-shutdown = ⏹ Shutdown
+sequences-save = Save
+sequences-saved = Saved
+
+## Selected channels
+
+bulk-selected = { $count } selected
+bulk-clear = Clear
+bulk-all-off = All OFF
+bulk-pulse = Pulse
+bulk-scene = scene
+bulk-add-to-scene = Add to scene
+bulk-added = Added { $count } channel(s) to '{ $scene }'
+bulk-label-prefix = label prefix
+bulk-set-labels = Set labels
+bulk-copy-settings = Copy settings here
+bulk-template = template
+bulk-apply-template = Apply template
+bulk-choose-template = Choose a template
+
+## Traffic
+
+traffic-title = Traffic
+traffic-frames = { $count } frame(s)
+traffic-save = Save to file
+traffic-saved = Saved to { $path }
+traffic-clear = Clear
+
+## Hex console
+
+console-title = Hex console
+console-send = Send
+console-clear = Clear
+console-no-reply = (no reply)
+
+## History
+
+history-title = History
+history-entries = { $count } switch(es)
+history-reload = Reload
+history-empty = Nothing recorded yet.
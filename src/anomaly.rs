@@ -0,0 +1,217 @@
+//! History-aware anomaly detection.
+//!
+//! Learns, per channel and per (weekday, hour) slot, the fraction of time the
+//! relay has historically been ON, then flags a channel whose current state
+//! strongly contradicts that pattern (e.g. a heater ON at 3 a.m. on a weekday
+//! when it has never been on at that time before).
+//!
+//! History only records transitions, so the time between two events is
+//! attributed to the earlier state even if the app wasn't running. Time
+//! after a device error is not attributed to either state.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::history::{unix_now, History, HistoryEvent};
+use crate::locale::format_time;
+use crate::notify::Notifier;
+use crate::schedule::{LocalTime, TimeOfDay, Weekday};
+use crate::supervisor::SubsystemContext;
+use crate::RelayState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const MODEL_REFRESH_SECS: u64 = 3600;
+const SECS_PER_HOUR: u64 = 3600;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sensitivity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Sensitivity {
+    /// Maximum historical share of the opposite state that still counts as
+    /// a deviation.
+    fn threshold(&self) -> f64 {
+        match self {
+            Sensitivity::Low => 0.02,
+            Sensitivity::Medium => 0.05,
+            Sensitivity::High => 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyConfig {
+    pub enabled: bool,
+    pub sensitivity: Sensitivity,
+    /// A slot must have been observed for at least this many weeks before
+    /// it is trusted.
+    pub min_weeks: u32,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sensitivity: Sensitivity::Medium,
+            min_weeks: 2,
+        }
+    }
+}
+
+// ============================================================================
+// USAGE MODEL
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    observed_secs: u64,
+    on_secs: u64,
+}
+
+type WeekSlots = [[Slot; 24]; 7];
+
+/// Identifies a calendar hour: (year, month, day, hour).
+type HourKey = (i32, u8, u8, u8);
+
+#[derive(Default)]
+struct UsageModel {
+    channels: HashMap<u8, WeekSlots>,
+}
+
+impl UsageModel {
+    /// Builds the model from chronological history events up to `now`.
+    fn build(events: &[HistoryEvent], now: u64) -> Self {
+        let mut model = Self::default();
+        let mut by_channel: HashMap<u8, Vec<&HistoryEvent>> = HashMap::new();
+        for event in events {
+            by_channel.entry(event.channel).or_default().push(event);
+        }
+
+        for (channel, events) in by_channel {
+            let slots = model.channels.entry(channel).or_insert([[Slot::default(); 24]; 7]);
+            for (i, event) in events.iter().enumerate() {
+                if event.state == RelayState::Error {
+                    continue;
+                }
+                let end = events.get(i + 1).map_or(now, |next| next.time).min(now);
+                let mut cursor = event.time;
+
+                // Split the interval at hour boundaries
+                while cursor < end {
+                    let boundary = (cursor / SECS_PER_HOUR + 1) * SECS_PER_HOUR;
+                    let chunk_end = boundary.min(end);
+                    let local = LocalTime::from_unix(cursor);
+                    let slot = &mut slots[local.weekday.index()][local.hour as usize];
+                    slot.observed_secs += chunk_end - cursor;
+                    if event.state == RelayState::On {
+                        slot.on_secs += chunk_end - cursor;
+                    }
+                    cursor = chunk_end;
+                }
+            }
+        }
+
+        model
+    }
+
+    fn slot(&self, channel: u8, weekday: Weekday, hour: u8) -> Option<Slot> {
+        self.channels
+            .get(&channel)
+            .map(|slots| slots[weekday.index()][hour as usize])
+    }
+}
+
+/// The current state of `channel` if it deviates from the learned pattern,
+/// along with the historical share of time it spent in that state.
+fn deviation(
+    model: &UsageModel,
+    settings: &AnomalyConfig,
+    channel: u8,
+    state: RelayState,
+    now: &LocalTime,
+) -> Option<f64> {
+    let slot = model.slot(channel, now.weekday, now.hour)?;
+    let min_observed = u64::from(settings.min_weeks) * SECS_PER_HOUR;
+    if slot.observed_secs < min_observed.max(1) {
+        return None;
+    }
+
+    let on_share = slot.on_secs as f64 / slot.observed_secs as f64;
+    let share = match state {
+        RelayState::On => on_share,
+        RelayState::Off => 1.0 - on_share,
+        _ => return None,
+    };
+    (share <= settings.sensitivity.threshold()).then_some(share)
+}
+
+// ============================================================================
+// DETECTOR
+// ============================================================================
+
+/// Periodically compares each channel's latest recorded state against the
+/// learned usage model and raises a notification once per deviating hour.
+pub fn run_anomaly_detector(ctx: &SubsystemContext, notifier: &Notifier) -> Result<(), String> {
+    let mut model = UsageModel::default();
+    let mut built_at = 0;
+    let mut alerted: HashSet<(u8, HourKey)> = HashSet::new();
+    ctx.mark_running();
+
+    loop {
+        let config = Config::load()?;
+        let settings = config.anomaly;
+        if settings.enabled {
+            let events = History::load()?;
+            let now_secs = unix_now();
+            if now_secs.saturating_sub(built_at) >= MODEL_REFRESH_SECS {
+                // Learn only from completed hours so the current one can't
+                // vouch for itself
+                model = UsageModel::build(&events, now_secs - now_secs % SECS_PER_HOUR);
+                built_at = now_secs;
+            }
+
+            let mut latest: HashMap<u8, RelayState> = HashMap::new();
+            for event in &events {
+                latest.insert(event.channel, event.state);
+            }
+
+            let now = LocalTime::from_unix(now_secs);
+            let key = (now.year, now.month, now.day, now.hour);
+            for (&channel, &state) in &latest {
+                let Some(share) = deviation(&model, &settings, channel, state, &now) else {
+                    continue;
+                };
+                if alerted.insert((channel, key)) {
+                    notifier.notify(
+                        "Unusual activity",
+                        format!(
+                            "Channel {} is {} on {} at {}, which happened {:.0}% of the time before",
+                            channel,
+                            state.text(),
+                            now.weekday.text(),
+                            format_time(TimeOfDay { hour: now.hour, minute: 0 }, config.display.clock),
+                            share * 100.0
+                        ),
+                    );
+                }
+            }
+            alerted.retain(|(_, k)| *k == key);
+        }
+
+        std::thread::sleep(CHECK_INTERVAL);
+    }
+}
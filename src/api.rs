@@ -0,0 +1,791 @@
+//! Minimal HTTP control API.
+//!
+//! A small HTTP/1.1 server on std networking, disabled by default. Every
+//! response is JSON and every connection is closed after one request.
+//!
+//! | Method | Path                   | Effect                       |
+//! |--------|------------------------|------------------------------|
+//! | GET    | `/status`              | State of every channel       |
+//! | GET    | `/watch`               | States as server-sent events |
+//! | POST   | `/channels/<n>/on`     | Queue ON for channel `n`     |
+//! | POST   | `/channels/<n>/off`    | Queue OFF for channel `n`    |
+//! | POST   | `/all-off`             | Emergency stop, all channels |
+//! | POST   | `/batch`               | Run a batch, wait for result |
+//! | POST   | `/raw`                 | Send hex bytes, return reply |
+//! | POST   | `/rules/<name>`        | Fire a rule's `Api` trigger  |
+//! | GET    | `/openapi.json`        | OpenAPI document, no token   |
+//! | GET    | `/docs`                | Swagger UI, no token         |
+//! | GET    | `/`                    | Browser control page         |
+//!
+//! Once any token is configured in `api.tokens`, every request needs an
+//! `Authorization: Bearer` header with an enabled one; without tokens the API
+//! stays open, as it is meant for loopback. `/raw` always needs a token, and
+//! `advanced: true`. Switches are recorded in the history under the client's
+//! address and the name of its token.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::batch::{self, Batch};
+use crate::config::{Config, DeviceConfig};
+use crate::emergency::EmergencyStop;
+use crate::mdns;
+use crate::openapi;
+use crate::raw::{self, format_hex, parse_hex};
+use crate::rules::{RuleEvent, RuleEvents, Trigger};
+use crate::supervisor::SubsystemContext;
+use crate::webui;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEADER_LINES: usize = 64;
+/// Caps on one line of the request head and on the whole head, so a client
+/// that never sends a line feed can't grow a buffer.
+const MAX_LINE: u64 = 8 * 1024;
+const MAX_HEAD_BYTES: u64 = 32 * 1024;
+/// Connections served at once; more are turned away with 503.
+const MAX_CONNECTIONS: usize = 32;
+const MAX_BODY_BYTES: usize = 64 * 1024;
+/// How often `/watch` looks for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+/// Quiet time after which `/watch` writes a comment, to notice clients that
+/// went away.
+const WATCH_KEEPALIVE: Duration = Duration::from_secs(15);
+/// How often `/watch` checks its token again while nothing changes, so a
+/// revoked one stops seeing states.
+const WATCH_REAUTHORIZE: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    /// Listen address; keep it on loopback unless the LAN is trusted.
+    pub bind: String,
+    /// A single token, from before tokens had names; works like an entry
+    /// named `default` in `tokens`.
+    pub token: Option<String>,
+    pub tokens: Vec<ApiToken>,
+    /// Announce the API over mDNS while it listens on a LAN address.
+    pub advertise: bool,
+    /// Name shown when browsing; empty for `USB Power Relay on <host>`.
+    pub name: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:8787".to_string(),
+            token: None,
+            tokens: Vec::new(),
+            advertise: true,
+            name: String::new(),
+        }
+    }
+}
+
+/// A bearer token, named so the history shows who used it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiToken {
+    pub name: String,
+    pub token: String,
+    /// A disabled token is refused but still keeps the API closed.
+    pub enabled: bool,
+}
+
+impl Default for ApiToken {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            token: String::new(),
+            enabled: true,
+        }
+    }
+}
+
+impl ApiConfig {
+    fn all_tokens(&self) -> impl Iterator<Item = (&str, &str, bool)> {
+        let single = self.token.iter().map(|token| ("default", token.as_str(), true));
+        let named = self.tokens.iter().map(|t| (t.name.as_str(), t.token.as_str(), t.enabled));
+        single.chain(named).filter(|(_, token, _)| !token.is_empty())
+    }
+
+    /// Whether requests must present a token. Disabled tokens count too, so
+    /// switching off the last one doesn't leave the API open.
+    pub fn requires_token(&self) -> bool {
+        self.all_tokens().next().is_some()
+    }
+
+    /// The name of the enabled token `given` matches.
+    fn token_name(&self, given: &str) -> Option<String> {
+        // Compare with every token, so the time taken doesn't tell which one
+        // matched or how much of one was right
+        let mut found = None;
+        for (name, token, enabled) in self.all_tokens() {
+            if constant_time_eq(token.as_bytes(), given.as_bytes()) && enabled && found.is_none() {
+                found = Some(name.to_string());
+            }
+        }
+        found
+    }
+}
+
+/// Compares without stopping at the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        let (x, y) = (a.get(i).copied().unwrap_or(0), b.get(i).copied().unwrap_or(0));
+        diff |= (x ^ y) as usize;
+    }
+    std::hint::black_box(diff) == 0
+}
+
+// ============================================================================
+// HTTP
+// ============================================================================
+
+pub struct Request {
+    /// The client's address.
+    pub client: String,
+    /// The name of the token the client presented, once checked.
+    token: Option<String>,
+    pub method: String,
+    pub path: String,
+    /// Lowercased names.
+    headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    /// The path without its query.
+    pub fn bare_path(&self) -> &str {
+        self.path.split('?').next().unwrap_or("")
+    }
+
+    fn bearer(&self) -> Option<&str> {
+        self.header("authorization").and_then(|v| v.strip_prefix("Bearer "))
+    }
+
+    /// Whether a browser could have been tricked into sending this: a Host
+    /// that isn't this machine, as after DNS rebinding, or a POST from
+    /// another site's page. Without a token nothing else keeps those out.
+    fn is_foreign(&self, bind: &str, host_name: &str) -> bool {
+        let Some(host) = self.header("host") else {
+            return false;
+        };
+        let name = strip_port(host).to_ascii_lowercase();
+        let host_name = host_name.to_ascii_lowercase();
+        let known = name.parse::<IpAddr>().is_ok()
+            || name == "localhost"
+            || name == strip_port(bind).to_ascii_lowercase()
+            || (!host_name.is_empty() && (name == host_name || name == host_name + ".local"));
+        let own = format!("http://{}", host);
+        let other_site = self.header("origin").is_some_and(|o| !o.eq_ignore_ascii_case(&own));
+        let cross_site = self.method == "POST" && other_site;
+        !known || cross_site
+    }
+
+    /// Who sent the request, for the history.
+    fn source(&self) -> Source {
+        Source::Api {
+            client: self.client.clone(),
+            token: self.token.clone(),
+        }
+    }
+}
+
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: body.into(),
+        }
+    }
+
+    fn html(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    pub fn xml(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/xml; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, format!("{{\"error\":{}}}", json_string(message)))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// `host` of `host:port` or `[v6]:port`.
+fn strip_port(authority: &str) -> &str {
+    if let Some(rest) = authority.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => host,
+        _ => authority,
+    }
+}
+
+/// This machine's host name, looked up once.
+fn own_host_name() -> &'static str {
+    static NAME: OnceLock<String> = OnceLock::new();
+    NAME.get_or_init(mdns::host_name)
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `{"channels":[{"channel":1,"state":"on","label":"Lamp"},…]}`
+fn states_json(states: &[RelayState], device: &DeviceConfig) -> String {
+    let channels: Vec<String> = states
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let label = json_string(&device.label(i as u8 + 1));
+            format!("{{\"channel\":{},\"state\":\"{}\",\"label\":{}}}", i + 1, s.name(), label)
+        })
+        .collect();
+    format!("{{\"channels\":[{}]}}", channels.join(","))
+}
+
+/// Decodes `%XX` escapes in a path segment, e.g. `%20` for a space.
+pub fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // from_str_radix would take a sign, as in "%+1"
+        let hex = bytes.get(i + 1..i + 3).filter(|h| h.iter().all(u8::is_ascii_hexdigit));
+        let hex = hex.and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+pub fn read_request(stream: &TcpStream) -> Result<Request, Response> {
+    let client = stream.peer_addr().map_or_else(|_| "?".to_string(), |a| a.ip().to_string());
+    parse_request(BufReader::new(stream), client)
+}
+
+/// Reads one line of the request head into `line`, counting it against
+/// what is left of the head in `budget`.
+fn read_head_line(
+    reader: &mut impl BufRead,
+    line: &mut String,
+    budget: &mut u64,
+) -> Result<(), Response> {
+    let limit = MAX_LINE.min(*budget);
+    let read = reader.by_ref().take(limit).read_line(line);
+    let n = read.map_err(|_| Response::error(400, "Unreadable request"))?;
+    if n as u64 == limit && !line.ends_with('\n') {
+        return Err(Response::error(431, "Request head too large"));
+    }
+    *budget -= n as u64;
+    Ok(())
+}
+
+fn parse_request(mut reader: impl BufRead, client: String) -> Result<Request, Response> {
+    let bad = |msg: &str| Response::error(400, msg);
+    let mut budget = MAX_HEAD_BYTES;
+    let mut line = String::new();
+    read_head_line(&mut reader, &mut line, &mut budget)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| bad("Missing method"))?.to_string();
+    let path = parts.next().ok_or_else(|| bad("Missing path"))?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        read_head_line(&mut reader, &mut line, &mut budget)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() >= MAX_HEADER_LINES {
+            return Err(Response::error(431, "Too many headers"));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err(Response::error(413, "Body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| bad("Truncated body"))?;
+    let body = String::from_utf8(body).map_err(|_| bad("Body is not UTF-8"))?;
+
+    Ok(Request {
+        client,
+        token: None,
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+pub fn write_response(mut stream: &TcpStream, response: &Response) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+// ============================================================================
+// ROUTES
+// ============================================================================
+
+#[derive(Clone)]
+pub struct ApiContext {
+    pub state: AppState,
+    pub stop: EmergencyStop,
+    pub rules: RuleEvents,
+}
+
+impl ApiContext {
+    fn status(&self) -> Response {
+        let device = Config::load().map(|c| c.device).unwrap_or_default();
+        Response::json(200, states_json(&self.state.snapshot().relay_states, &device))
+    }
+
+    /// Streams the channel states as server-sent events: the current ones
+    /// at once, then again after every change, until the client goes away.
+    /// The token is checked again before every event and every few seconds,
+    /// and the stream ends once it is no longer accepted.
+    fn watch(&self, request: &mut Request, mut stream: &TcpStream) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+        )?;
+        let mut last = None;
+        let mut quiet_since = Instant::now();
+        let mut authorized_at = Instant::now();
+        loop {
+            let states = self.state.snapshot().relay_states;
+            let changed = last.as_ref() != Some(&states);
+            if changed || authorized_at.elapsed() >= WATCH_REAUTHORIZE {
+                if self.authorize(request).is_err() {
+                    return Ok(());
+                }
+                authorized_at = Instant::now();
+            }
+            if changed {
+                let device = Config::load().map(|c| c.device).unwrap_or_default();
+                write!(stream, "data: {}\n\n", states_json(&states, &device))?;
+                stream.flush()?;
+                last = Some(states);
+                quiet_since = Instant::now();
+            } else if quiet_since.elapsed() >= WATCH_KEEPALIVE {
+                stream.write_all(b": keepalive\n\n")?;
+                quiet_since = Instant::now();
+            }
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+    }
+
+    fn queue(&self, request: &Request, cmd: Command) -> Response {
+        self.state.send_command(cmd, request.source());
+        Response::json(202, "{\"queued\":true}")
+    }
+
+    /// Queues a batch and waits for its all-or-nothing result.
+    fn batch(&self, request: &Request) -> Response {
+        let batch = match Batch::parse(&request.body) {
+            Ok(batch) => batch,
+            Err(e) => return Response::error(400, &e),
+        };
+        if let Err(e) = batch.validate(self.channel_count() as u8) {
+            return Response::error(400, &e);
+        }
+
+        match batch::submit(&self.state, batch, request.source()) {
+            Some(Ok(changes)) => {
+                let changes: Vec<String> = changes
+                    .iter()
+                    .map(|(channel, s)| format!("{{\"channel\":{},\"state\":\"{}\"}}", channel, s.name()))
+                    .collect();
+                Response::json(200, format!("{{\"ok\":true,\"changes\":[{}]}}", changes.join(",")))
+            }
+            Some(Err(e)) => Response::json(
+                409,
+                format!(
+                    "{{\"ok\":false,\"step\":{},\"reason\":\"{}\",\"error\":{}}}",
+                    e.step + 1,
+                    e.kind.name(),
+                    json_string(&e.message)
+                ),
+            ),
+            None => Response::error(504, "No result from the device"),
+        }
+    }
+
+    /// Sends a raw frame to the board and returns its reply.
+    fn raw(&self, request: &Request) -> Response {
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => return Response::error(500, &e),
+        };
+        if !config.advanced {
+            return Response::error(403, "Raw frames are disabled; set advanced: true");
+        }
+        if request.token.is_none() {
+            return Response::error(403, "Raw frames need an API token to be set");
+        }
+
+        let bytes = match parse_hex(&request.body) {
+            Ok(bytes) => bytes,
+            Err(e) => return Response::error(400, &e),
+        };
+        match raw::submit(&self.state, bytes, request.source()) {
+            Some(Ok(reply)) => Response::json(
+                200,
+                format!("{{\"ok\":true,\"response\":\"{}\"}}", format_hex(&reply)),
+            ),
+            Some(Err(e)) => Response::error(409, &e),
+            None => Response::error(504, "No result from the device"),
+        }
+    }
+
+    /// Fires the rule named `name` if it has an `Api` trigger; its conditions
+    /// are checked by the rules engine.
+    fn fire_rule(&self, name: &str) -> Response {
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => return Response::error(500, &e),
+        };
+        let name = percent_decode(name);
+        let found = config.rules.iter().find(|r| r.name.eq_ignore_ascii_case(&name));
+        match found {
+            Some(rule) if rule.enabled && rule.trigger == Trigger::Api => {
+                let _ = self.rules.send(RuleEvent::Api(rule.name.clone()));
+                Response::json(202, "{\"queued\":true}")
+            }
+            Some(_) => Response::error(409, "Rule is disabled or not triggered by the API"),
+            None => Response::error(404, "No such rule"),
+        }
+    }
+
+    fn channel_count(&self) -> usize {
+        self.state.snapshot.borrow().relay_states.len()
+    }
+
+    fn route(&self, request: &Request) -> Response {
+        let path = request.bare_path();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        if request.method == "POST" && self.state.read_only {
+            return Response::error(403, "Monitor mode is read-only");
+        }
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["status"]) => self.status(),
+            ("GET", ["openapi.json"]) => Response::json(200, openapi::spec()),
+            ("GET", ["docs"]) => Response::html(openapi::DOCS_PAGE),
+            ("GET", [""]) => match Config::load() {
+                Ok(config) => Response::html(webui::page(&config.device, self.state.read_only)),
+                Err(e) => Response::error(500, &e),
+            },
+            ("POST", ["all-off"]) => {
+                self.stop.trigger();
+                Response::json(202, "{\"queued\":true}")
+            }
+            ("POST", ["batch"]) => self.batch(request),
+            ("POST", ["raw"]) => self.raw(request),
+            ("POST", ["rules", name]) => self.fire_rule(name),
+            ("POST", ["channels", channel, action]) => {
+                let channel = match channel.parse::<u8>() {
+                    Ok(c) if c >= 1 && (c as usize) <= self.channel_count() => c,
+                    _ => return Response::error(404, "No such channel"),
+                };
+                match *action {
+                    "on" => self.queue(request, Command::TurnOn(channel)),
+                    "off" => self.queue(request, Command::TurnOff(channel)),
+                    _ => Response::error(404, "Unknown action"),
+                }
+            }
+            (
+                _,
+                ["status"]
+                | ["watch"]
+                | ["openapi.json"]
+                | ["docs"]
+                | [""]
+                | ["all-off"]
+                | ["batch"]
+                | ["raw"]
+                | ["rules", _]
+                | ["channels", _, _],
+            ) => Response::error(405, "Method not allowed"),
+            _ => Response::error(404, "Not found"),
+        }
+    }
+
+    /// Checks the request's token, when the API needs one. The docs are
+    /// open, so integrators can find their way before they have a token,
+    /// and so is the control page, which asks for one itself. Without
+    /// tokens, only requests a browser can't have been tricked into pass.
+    fn authorize(&self, request: &mut Request) -> Result<(), Response> {
+        let api = Config::load().map_err(|e| Response::error(500, &e))?.api;
+        if !api.requires_token() {
+            return match request.is_foreign(&api.bind, own_host_name()) {
+                true => Err(Response::error(403, "Cross-origin request refused")),
+                false => Ok(()),
+            };
+        }
+        let docs = matches!(request.bare_path(), "/docs" | "/openapi.json" | "/");
+        if docs && request.method == "GET" {
+            return Ok(());
+        }
+        request.token = request.bearer().and_then(|given| api.token_name(given));
+        match request.token {
+            Some(_) => Ok(()),
+            None => Err(Response::error(401, "Missing or wrong token")),
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+
+        let response = match read_request(&stream) {
+            Ok(mut request) => match self.authorize(&mut request) {
+                Ok(()) if request.method == "GET" && request.bare_path() == "/watch" => {
+                    let _ = self.watch(&mut request, &stream);
+                    return;
+                }
+                Ok(()) => self.route(&request),
+                Err(response) => response,
+            },
+            Err(response) => response,
+        };
+        let _ = write_response(&stream, &response);
+    }
+}
+
+// ============================================================================
+// SERVER
+// ============================================================================
+
+/// One connection's share of [`MAX_CONNECTIONS`], given back when the
+/// handler ends, even by panicking.
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Serves the API until the listener fails. Exits cleanly when disabled.
+pub fn run_api(ctx: &SubsystemContext, api: &ApiContext) -> Result<(), String> {
+    let settings = Config::load()?.api;
+    // A socket passed by systemd means the unit wants the API served
+    let listener = match crate::systemd::tcp_listener() {
+        Some(listener) => listener,
+        None if !settings.enabled => return Ok(()),
+        None => TcpListener::bind(&settings.bind)
+            .map_err(|e| format!("Failed to bind {}: {}", settings.bind, e))?,
+    };
+    ctx.mark_running();
+
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| format!("Accept failed: {}", e))?;
+        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+            let _ = write_response(&stream, &Response::error(503, "Too many connections"));
+            continue;
+        }
+        let api = api.clone();
+        let slot = Slot(active.clone());
+        std::thread::spawn(move || {
+            api.handle(stream);
+            drop(slot);
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &[u8]) -> Result<Request, Response> {
+        parse_request(raw, "127.0.0.1".to_string())
+    }
+
+    fn status(raw: &[u8]) -> u16 {
+        parse(raw).err().map_or(0, |response| response.status)
+    }
+
+    #[test]
+    fn reads_line_headers_and_body() {
+        let raw = b"POST /batch?wait=1 HTTP/1.1\r\nHost: relay\r\nContent-Type: text/plain\r\n\
+                    Content-Length: 10\r\n\r\non:1,off:2 trailing";
+        let request = parse(raw).ok().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/batch?wait=1");
+        assert_eq!(request.bare_path(), "/batch");
+        assert_eq!(request.header("content-type"), Some("text/plain"));
+        assert_eq!(request.header("host"), Some("relay"));
+        assert_eq!(request.body, "on:1,off:2");
+    }
+
+    #[test]
+    fn accepts_bare_line_feeds_and_no_body() {
+        let request = parse(b"GET /status HTTP/1.1\nAuthorization: Bearer abc\n\n").ok().unwrap();
+        assert_eq!(request.bearer(), Some("abc"));
+        assert_eq!(request.body, "");
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        assert_eq!(status(b""), 400);
+        assert_eq!(status(b"GET\r\n\r\n"), 400);
+        assert_eq!(status(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort"), 400);
+        assert_eq!(status(b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n\xff\xfe"), 400);
+    }
+
+    #[test]
+    fn limits_headers_and_body() {
+        let mut raw = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..=MAX_HEADER_LINES {
+            raw.extend(format!("X-{}: 1\r\n", i).bytes());
+        }
+        raw.extend(b"\r\n");
+        assert_eq!(status(&raw), 431);
+
+        let long = "a".repeat(MAX_LINE as usize);
+        assert_eq!(status(format!("GET /{} HTTP/1.1\r\n\r\n", long).as_bytes()), 431);
+        let mut raw = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..MAX_HEADER_LINES {
+            raw.extend(format!("X-{}: {}\r\n", i, "a".repeat(MAX_LINE as usize / 2)).bytes());
+        }
+        assert_eq!(status(&raw), 431);
+
+        let raw = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1);
+        assert_eq!(status(raw.as_bytes()), 413);
+    }
+
+    #[test]
+    fn refuses_foreign_hosts_and_origins() {
+        let foreign = |raw: &[u8]| parse(raw).ok().unwrap().is_foreign("127.0.0.1:8787", "bench");
+        assert!(!foreign(b"GET / HTTP/1.1\r\n\r\n"));
+        assert!(!foreign(b"GET / HTTP/1.1\r\nHost: 192.168.1.20:8787\r\n\r\n"));
+        assert!(!foreign(b"GET / HTTP/1.1\r\nHost: [::1]:8787\r\n\r\n"));
+        assert!(!foreign(b"GET / HTTP/1.1\r\nHost: Bench.local:8787\r\n\r\n"));
+        assert!(!foreign(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"));
+        assert!(foreign(b"GET / HTTP/1.1\r\nHost: evil.example:8787\r\n\r\n"));
+
+        let post = |origin: &str| {
+            let head = "POST /all-off HTTP/1.1\r\nHost: bench:8787\r\n";
+            let raw = format!("{}Origin: {}\r\n\r\n", head, origin);
+            parse(raw.as_bytes()).ok().unwrap().is_foreign("0.0.0.0:8787", "bench")
+        };
+        assert!(!post("http://bench:8787"));
+        assert!(post("http://evil.example"));
+        assert!(post("null"));
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("Living%20room%2FTV"), "Living room/TV");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%+1"), "%zz%+1");
+        assert_eq!(percent_decode("%C3%A4"), "ä");
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
@@ -0,0 +1,104 @@
+//! Source attribution for the switching history.
+//!
+//! Every command carries the [`Source`] that sent it, from the channel into
+//! the queue and through the device worker, which writes it next to each
+//! change it causes in the history: a click in the window, the CLI, an API
+//! client's address, a schedule, a rule or a watchdog by name. Changes the
+//! board reports without being asked, like its own buttons or a relay
+//! dropping out, are the device's.
+
+use std::fmt;
+
+// ============================================================================
+// SOURCES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A button in the window, or the macOS menu bar item.
+    Window,
+    Cli,
+    /// An HTTP API client, by address and the name of its token.
+    Api { client: String, token: Option<String> },
+    Dbus,
+    Hotkey,
+    Schedule(u32),
+    Rule(String),
+    Watchdog(String),
+    Sequence(String),
+    Process(String),
+    /// A whitelisted Telegram chat, by ID.
+    Telegram(i64),
+    /// A voice assistant switching the emulated Hue bridge, by address.
+    Hue(String),
+    /// An SNMP manager, by address.
+    Snmp(String),
+    /// A serial bridge client, by address.
+    Bridge(String),
+    /// A line-based TCP client, by address.
+    Tcp(String),
+    /// A MIDI control surface.
+    Midi,
+    /// A gamepad or foot switch.
+    Gamepad,
+    /// A spoken command.
+    Voice,
+    /// A lock screen trigger.
+    Session,
+    /// A maximum ON time running out.
+    Safety,
+    /// ALL OFF, from wherever it was pressed.
+    Emergency,
+    /// State restore after a reconnect.
+    Restore,
+    /// The exit policy.
+    Exit,
+    /// Status queries and whatever the board reports on its own.
+    Device,
+}
+
+impl Source {
+    /// Whether a person switches through it directly, so that a quick burst
+    /// of switches from it is one intent rather than steps that each have
+    /// to run.
+    pub fn is_interactive(&self) -> bool {
+        matches!(
+            self,
+            Source::Window | Source::Hotkey | Source::Midi | Source::Gamepad | Source::Voice
+        )
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Window => write!(f, "window"),
+            Source::Cli => write!(f, "cli"),
+            Source::Api { client, token: None } => write!(f, "api {}", client),
+            Source::Api { client, token: Some(name) } => {
+                write!(f, "api {} (token '{}')", client, name)
+            }
+            Source::Dbus => write!(f, "dbus"),
+            Source::Hotkey => write!(f, "hotkey"),
+            Source::Schedule(id) => write!(f, "schedule #{}", id),
+            Source::Rule(name) => write!(f, "rule '{}'", name),
+            Source::Watchdog(name) => write!(f, "watchdog '{}'", name),
+            Source::Sequence(name) => write!(f, "sequence '{}'", name),
+            Source::Process(name) => write!(f, "process '{}'", name),
+            Source::Telegram(chat) => write!(f, "telegram {}", chat),
+            Source::Hue(client) => write!(f, "hue {}", client),
+            Source::Snmp(client) => write!(f, "snmp {}", client),
+            Source::Bridge(client) => write!(f, "bridge {}", client),
+            Source::Tcp(client) => write!(f, "tcp {}", client),
+            Source::Midi => write!(f, "midi"),
+            Source::Gamepad => write!(f, "gamepad"),
+            Source::Voice => write!(f, "voice"),
+            Source::Session => write!(f, "lock screen"),
+            Source::Safety => write!(f, "safety limit"),
+            Source::Emergency => write!(f, "all off"),
+            Source::Restore => write!(f, "state restore"),
+            Source::Exit => write!(f, "exit policy"),
+            Source::Device => write!(f, "device"),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Starting the app at login.
+//!
+//! Registers the running executable with the platform's login mechanism: a
+//! value under the `Run` registry key on Windows, a LaunchAgent on macOS and
+//! an XDG autostart `.desktop` file elsewhere. The registration lives with
+//! the system rather than in the config file, so the settings window reads
+//! it back each time it opens.
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Passed by the login entry when the app should start minimized.
+pub const MINIMIZED_FLAG: &str = "--minimized";
+
+// ============================================================================
+// REGISTRATION
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Autostart {
+    pub enabled: bool,
+    pub minimized: bool,
+}
+
+/// What is currently registered.
+pub fn current() -> Autostart {
+    match platform::read() {
+        Some(args) => Autostart {
+            enabled: true,
+            minimized: args.contains(MINIMIZED_FLAG),
+        },
+        None => Autostart::default(),
+    }
+}
+
+/// Registers or unregisters the app to match `autostart`.
+pub fn apply(autostart: Autostart) -> Result<(), String> {
+    if !autostart.enabled {
+        return platform::remove();
+    }
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the executable: {}", e))?;
+    let args: &[&str] = if autostart.minimized { &[MINIMIZED_FLAG] } else { &[] };
+    platform::write(&exe, args)
+}
+
+/// Quotes an `Exec` argument of a desktop entry as the spec requires; the
+/// URL scheme handler writes one too.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    fn entry_path() -> Result<PathBuf, String> {
+        let dirs = directories::BaseDirs::new().ok_or("No home directory")?;
+        Ok(dirs.config_dir().join("autostart").join("usb-power-relay.desktop"))
+    }
+
+    /// The `Exec` line of the entry, unless it is missing or hidden.
+    pub fn read() -> Option<String> {
+        let text = std::fs::read_to_string(entry_path().ok()?).ok()?;
+        if text.lines().any(|line| line.trim() == "Hidden=true") {
+            return None;
+        }
+        text.lines().find_map(|line| line.strip_prefix("Exec=")).map(str::to_string)
+    }
+
+    pub fn write(exe: &Path, args: &[&str]) -> Result<(), String> {
+        let path = entry_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let mut exec = super::quote_exec_arg(&exe.to_string_lossy());
+        for arg in args {
+            exec.push(' ');
+            exec.push_str(arg);
+        }
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=USB Relay\nExec={}\n\
+             X-GNOME-Autostart-enabled=true\n",
+            exec
+        );
+        std::fs::write(&path, entry)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn remove() -> Result<(), String> {
+        let path = entry_path()?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    const LABEL: &str = "com.usbpowerswitch.relay";
+
+    fn agent_path() -> Result<PathBuf, String> {
+        let dirs = directories::BaseDirs::new().ok_or("No home directory")?;
+        let file = format!("{}.plist", LABEL);
+        Ok(dirs.home_dir().join("Library").join("LaunchAgents").join(file))
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    }
+
+    /// The agent file, unless it is missing.
+    pub fn read() -> Option<String> {
+        std::fs::read_to_string(agent_path().ok()?).ok()
+    }
+
+    pub fn write(exe: &Path, args: &[&str]) -> Result<(), String> {
+        let path = agent_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let program: String = std::iter::once(exe.to_string_lossy().into_owned())
+            .chain(args.iter().map(|arg| arg.to_string()))
+            .map(|arg| format!("        <string>{}</string>\n", escape(&arg)))
+            .collect();
+        let agent = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <plist version=\"1.0\">\n\
+             <dict>\n\
+             \x20   <key>Label</key>\n\
+             \x20   <string>{}</string>\n\
+             \x20   <key>ProgramArguments</key>\n\
+             \x20   <array>\n{}    </array>\n\
+             \x20   <key>RunAtLoad</key>\n\
+             \x20   <true/>\n\
+             </dict>\n\
+             </plist>\n",
+            LABEL, program
+        );
+        std::fs::write(&path, agent)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn remove() -> Result<(), String> {
+        let path = agent_path()?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    type Hkey = isize;
+
+    const HKEY_CURRENT_USER: Hkey = 0x8000_0001u32 as i32 as Hkey;
+    const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+    const VALUE_NAME: &str = "USB Relay";
+    const KEY_QUERY_VALUE: u32 = 0x0001;
+    const KEY_SET_VALUE: u32 = 0x0002;
+    const REG_SZ: u32 = 1;
+    const ERROR_SUCCESS: i32 = 0;
+    const ERROR_FILE_NOT_FOUND: i32 = 2;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn RegOpenKeyExW(
+            key: Hkey,
+            sub_key: *const u16,
+            options: u32,
+            access: u32,
+            result: *mut Hkey,
+        ) -> i32;
+        fn RegQueryValueExW(
+            key: Hkey,
+            name: *const u16,
+            reserved: *mut u32,
+            kind: *mut u32,
+            data: *mut u8,
+            len: *mut u32,
+        ) -> i32;
+        fn RegSetValueExW(
+            key: Hkey,
+            name: *const u16,
+            reserved: u32,
+            kind: u32,
+            data: *const u8,
+            len: u32,
+        ) -> i32;
+        fn RegDeleteValueW(key: Hkey, name: *const u16) -> i32;
+        fn RegCloseKey(key: Hkey) -> i32;
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        OsStr::new(text).encode_wide().chain(Some(0)).collect()
+    }
+
+    fn failed(action: &str, code: i32) -> String {
+        format!("Failed to {}: {}", action, std::io::Error::from_raw_os_error(code))
+    }
+
+    /// Runs `f` with the `Run` key open for `access`.
+    fn with_run_key<T>(access: u32, f: impl FnOnce(Hkey) -> T) -> Result<T, String> {
+        let mut key: Hkey = 0;
+        // SAFETY: the path is NUL-terminated and `key` receives the handle
+        let code = unsafe {
+            RegOpenKeyExW(HKEY_CURRENT_USER, wide(RUN_KEY).as_ptr(), 0, access, &mut key)
+        };
+        if code != ERROR_SUCCESS {
+            return Err(failed("open the Run registry key", code));
+        }
+        let result = f(key);
+        // SAFETY: `key` was opened above
+        unsafe {
+            RegCloseKey(key);
+        }
+        Ok(result)
+    }
+
+    /// The registered command line, unless there is none.
+    pub fn read() -> Option<String> {
+        with_run_key(KEY_QUERY_VALUE, |key| {
+            let name = wide(VALUE_NAME);
+            let mut data = vec![0u16; 1024];
+            let mut len = (data.len() * 2) as u32;
+            // SAFETY: `data` holds `len` bytes
+            let code = unsafe {
+                RegQueryValueExW(
+                    key,
+                    name.as_ptr(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    data.as_mut_ptr().cast(),
+                    &mut len,
+                )
+            };
+            if code != ERROR_SUCCESS {
+                return None;
+            }
+            data.truncate(len as usize / 2);
+            let text = String::from_utf16_lossy(&data);
+            Some(text.trim_end_matches('\0').to_string())
+        })
+        .ok()
+        .flatten()
+    }
+
+    pub fn write(exe: &Path, args: &[&str]) -> Result<(), String> {
+        let mut command = format!("\"{}\"", exe.display());
+        for arg in args {
+            command.push(' ');
+            command.push_str(arg);
+        }
+        let data = wide(&command);
+        let code = with_run_key(KEY_SET_VALUE, |key| {
+            // SAFETY: both strings are NUL-terminated; the length counts
+            // the terminator, as REG_SZ requires
+            unsafe {
+                RegSetValueExW(
+                    key,
+                    wide(VALUE_NAME).as_ptr(),
+                    0,
+                    REG_SZ,
+                    data.as_ptr().cast(),
+                    (data.len() * 2) as u32,
+                )
+            }
+        })?;
+        match code {
+            ERROR_SUCCESS => Ok(()),
+            code => Err(failed("register the app", code)),
+        }
+    }
+
+    pub fn remove() -> Result<(), String> {
+        let code = with_run_key(KEY_SET_VALUE, |key| {
+            // SAFETY: the name is NUL-terminated
+            unsafe { RegDeleteValueW(key, wide(VALUE_NAME).as_ptr()) }
+        })?;
+        match code {
+            ERROR_SUCCESS | ERROR_FILE_NOT_FOUND => Ok(()),
+            code => Err(failed("unregister the app", code)),
+        }
+    }
+}
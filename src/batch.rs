@@ -0,0 +1,212 @@
+//! Command batches.
+//!
+//! A batch is a list of steps ("channel 1 off, channel 3 on, then pulse
+//! channel 4") that the device worker executes as one unit: nothing else is
+//! interleaved, an emergency stop interrupts it between steps, and the
+//! submitter gets a single result, either every change made or the step that
+//! failed. Scenes run as batches, and remote clients submit them through the
+//! HTTP API.
+//!
+//! In text form steps are separated by commas or newlines:
+//! `off:1, on:3, pulse:4:500, wait:1000`.
+
+use std::fmt;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::config::Scene;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long a submitted batch may wait in the queue before it is rejected.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+/// Allowance for the serial round trips of a batch on top of its waits.
+const RUN_MARGIN: Duration = Duration::from_secs(10);
+
+// ============================================================================
+// STEPS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchStep {
+    On(u8),
+    Off(u8),
+    /// Switch on, wait this many milliseconds, switch off.
+    Pulse(u8, u64),
+    Wait(u64),
+}
+
+impl BatchStep {
+    /// Parses `on:<ch>`, `off:<ch>`, `pulse:<ch>:<ms>` or `wait:<ms>`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid step '{}', expected on:<ch>, off:<ch>, pulse:<ch>:<ms> or wait:<ms>",
+                text
+            )
+        };
+        let fields: Vec<&str> = text.trim().split(':').map(str::trim).collect();
+        let channel = |field: &str| field.parse::<u8>().ok().filter(|&c| c > 0).ok_or_else(invalid);
+        let millis = |field: &str| field.parse::<u64>().map_err(|_| invalid());
+
+        match fields.as_slice() {
+            [kind, ch] if kind.eq_ignore_ascii_case("on") => Ok(BatchStep::On(channel(ch)?)),
+            [kind, ch] if kind.eq_ignore_ascii_case("off") => Ok(BatchStep::Off(channel(ch)?)),
+            [kind, ch, ms] if kind.eq_ignore_ascii_case("pulse") => {
+                Ok(BatchStep::Pulse(channel(ch)?, millis(ms)?))
+            }
+            [kind, ms] if kind.eq_ignore_ascii_case("wait") => Ok(BatchStep::Wait(millis(ms)?)),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn channel(&self) -> Option<u8> {
+        match self {
+            BatchStep::On(channel) | BatchStep::Off(channel) | BatchStep::Pulse(channel, _) => {
+                Some(*channel)
+            }
+            BatchStep::Wait(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for BatchStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchStep::On(channel) => write!(f, "on:{}", channel),
+            BatchStep::Off(channel) => write!(f, "off:{}", channel),
+            BatchStep::Pulse(channel, ms) => write!(f, "pulse:{}:{}", channel, ms),
+            BatchStep::Wait(ms) => write!(f, "wait:{}", ms),
+        }
+    }
+}
+
+// ============================================================================
+// BATCHES
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Batch {
+    pub steps: Vec<BatchStep>,
+    /// The batch is rejected instead of run if the device can't start it by
+    /// then, so a submitter that gave up never sees it run later.
+    pub start_by: Option<Instant>,
+}
+
+impl Batch {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let steps = text
+            .split([',', '\n'])
+            .filter(|step| !step.trim().is_empty())
+            .map(BatchStep::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if steps.is_empty() {
+            return Err("Empty batch".to_string());
+        }
+        Ok(Self { steps, start_by: None })
+    }
+
+    pub fn from_scene(scene: &Scene) -> Self {
+        let steps = scene
+            .steps
+            .iter()
+            .map(|step| {
+                if step.on {
+                    BatchStep::On(step.channel)
+                } else {
+                    BatchStep::Off(step.channel)
+                }
+            })
+            .collect();
+        Self { steps, start_by: None }
+    }
+
+    /// Rejects steps for channels the board doesn't have.
+    pub fn validate(&self, channels: u8) -> Result<(), String> {
+        for step in &self.steps {
+            if let Some(channel) = step.channel().filter(|c| !(1..=channels).contains(c)) {
+                return Err(format!("{}: channel {} is outside 1..={}", step, channel, channels));
+            }
+        }
+        Ok(())
+    }
+
+    /// Total time the batch spends waiting, used to bound how long a
+    /// submitter waits for the result.
+    pub fn duration(&self) -> Duration {
+        let millis = self
+            .steps
+            .iter()
+            .map(|step| match step {
+                BatchStep::Pulse(_, ms) | BatchStep::Wait(ms) => *ms,
+                _ => 0,
+            })
+            .sum();
+        Duration::from_millis(millis)
+    }
+}
+
+// ============================================================================
+// RESULTS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchErrorKind {
+    /// The device failed; the worker reconnects afterwards.
+    Device,
+    /// Stopped by the emergency stop.
+    Interrupted,
+    /// Not started before its `start_by` deadline.
+    Expired,
+    /// Removed from the queue before it started.
+    Cancelled,
+    /// Would have switched a channel on during its cooldown.
+    Rejected,
+}
+
+impl BatchErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchErrorKind::Device => "device",
+            BatchErrorKind::Interrupted => "interrupted",
+            BatchErrorKind::Expired => "expired",
+            BatchErrorKind::Cancelled => "cancelled",
+            BatchErrorKind::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    /// Index of the step that didn't complete.
+    pub step: usize,
+    pub kind: BatchErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Step {} failed: {}", self.step + 1, self.message)
+    }
+}
+
+/// Every change a batch made, or why it stopped.
+pub type BatchResult = Result<Vec<(u8, RelayState)>, BatchError>;
+
+/// Where the device worker sends a batch's result.
+pub type BatchReply = mpsc::Sender<BatchResult>;
+
+/// Queues `batch` and waits for its result, for the CLI and the API. It
+/// expires unless it starts within [`START_TIMEOUT`]; None means no result
+/// arrived in time.
+pub fn submit(state: &AppState, mut batch: Batch, source: Source) -> Option<BatchResult> {
+    let timeout = START_TIMEOUT + batch.duration() + RUN_MARGIN;
+    batch.start_by = Some(Instant::now() + START_TIMEOUT);
+    let (tx, rx) = mpsc::channel();
+    state.send_command(Command::Batch(batch, Some(tx)), source);
+    rx.recv_timeout(timeout).ok()
+}
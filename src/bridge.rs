@@ -0,0 +1,183 @@
+//! Serial-over-TCP bridge.
+//!
+//! With `bridge.enabled`, the board's serial port is also reachable as a
+//! raw TCP socket, like ser2net's raw mode, so tools written against ser2net
+//! keep working while the app owns the port. Bytes from the client go to the
+//! board unchanged and whatever the board sends goes back.
+//!
+//! Access is arbitrated through the command queue: the first bytes of a
+//! burst queue a session, and once the device worker reaches it the client
+//! has the port to itself until it has been quiet for `hold_ms`. The app's
+//! own commands wait meanwhile, and every channel is requeried afterwards,
+//! since the client may have switched any of them. ALL OFF ends a running
+//! session at once, and hangs up on a client whose session was still queued.
+
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::supervisor::SubsystemContext;
+use crate::traffic::Direction;
+use crate::{AppState, Command, RelayController};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long each direction is waited on before the other is checked.
+const SLICE: Duration = Duration::from_millis(10);
+/// Longest turn at the port, so a client that never goes quiet still lets
+/// the app's own commands through.
+const MAX_SESSION: Duration = Duration::from_secs(5);
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BridgeConfig {
+    pub enabled: bool,
+    /// Listen address; the bridge has no authentication.
+    pub bind: String,
+    /// How long a client keeps the port after its last byte.
+    pub hold_ms: u64,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:2000".to_string(),
+            hold_ms: 500,
+        }
+    }
+}
+
+// ============================================================================
+// SESSIONS
+// ============================================================================
+
+/// A client's turn at the port, run by the device worker.
+#[derive(Debug, Clone)]
+pub struct BridgeSession {
+    stream: Arc<TcpStream>,
+    /// The bytes that started the burst.
+    first: Vec<u8>,
+    hold: Duration,
+    /// Told when the session ends; dropped unsent if it never runs.
+    done: mpsc::Sender<()>,
+}
+
+impl BridgeSession {
+    /// Passes bytes both ways until the client goes quiet or away, or
+    /// `interrupted` returns true. Only serial errors are returned.
+    pub fn run(
+        &self,
+        controller: &mut RelayController,
+        interrupted: &dyn Fn() -> bool,
+    ) -> Result<(), String> {
+        let result = self.relay(controller, interrupted);
+        let _ = self.done.send(());
+        result
+    }
+
+    fn relay(
+        &self,
+        controller: &mut RelayController,
+        interrupted: &dyn Fn() -> bool,
+    ) -> Result<(), String> {
+        let mut stream = &*self.stream;
+        let _ = stream.set_read_timeout(Some(SLICE));
+        controller.drain()?;
+
+        let started = Instant::now();
+        let mut pending = self.first.clone();
+        let mut last_input = started;
+        let mut buf = [0u8; 256];
+        loop {
+            if !pending.is_empty() {
+                controller.port.write_all(&pending)
+                    .map_err(|e| format!("Write failed: {}", e))?;
+                controller.log(Direction::Sent, &pending);
+                controller.port.flush()
+                    .map_err(|e| format!("Flush failed: {}", e))?;
+                pending.clear();
+                last_input = Instant::now();
+            }
+
+            let n = controller.read_until(Instant::now() + SLICE, &mut buf)?;
+            if n > 0 {
+                controller.log(Direction::Received, &buf[..n]);
+                if stream.write_all(&buf[..n]).is_err() {
+                    return Ok(());
+                }
+            }
+            let expired = started.elapsed() >= MAX_SESSION;
+            if interrupted() || expired || last_input.elapsed() >= self.hold {
+                return Ok(());
+            }
+
+            match stream.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => pending.extend_from_slice(&buf[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Queues a session for every burst from `stream` until it closes.
+fn serve_client(stream: TcpStream, state: &AppState, hold: Duration) {
+    let client = stream.peer_addr().map_or_else(|_| "?".to_string(), |a| a.ip().to_string());
+    let stream = Arc::new(stream);
+    let mut buf = [0u8; 256];
+    loop {
+        // A session leaves its short read timeout behind
+        let _ = stream.set_read_timeout(None);
+        let n = match (&*stream).read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        let (done, finished) = mpsc::channel();
+        let session = BridgeSession {
+            stream: stream.clone(),
+            first: buf[..n].to_vec(),
+            hold,
+            done,
+        };
+        state.send_command(Command::Bridge(session), Source::Bridge(client.clone()));
+        // Cancelled, by ALL OFF or a lost board: hang up
+        if finished.recv().is_err() {
+            return;
+        }
+    }
+}
+
+/// Serves one client at a time while enabled; others wait to connect.
+/// Exits cleanly when disabled or in monitor mode.
+pub fn run_bridge(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    let settings = Config::load()?.bridge;
+    if !settings.enabled {
+        return Ok(());
+    }
+    if state.read_only {
+        eprintln!("The serial bridge is off in monitor mode");
+        return Ok(());
+    }
+    let listener = TcpListener::bind(&settings.bind)
+        .map_err(|e| format!("Failed to bind {}: {}", settings.bind, e))?;
+    ctx.mark_running();
+
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| format!("Accept failed: {}", e))?;
+        serve_client(stream, state, Duration::from_millis(settings.hold_ms));
+    }
+
+    Ok(())
+}
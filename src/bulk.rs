@@ -0,0 +1,226 @@
+//! Multi-channel selection and bulk actions.
+//!
+//! On boards with many channels the GUI lets the user tick several channels
+//! (checkboxes or ctrl-click) and apply one action to all of them.
+
+use eframe::egui;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::audit::Source;
+use crate::config::{LiveConfig, Scene, SceneStep};
+use crate::emergency::EmergencyStop;
+use crate::i18n::{tr, tr_args};
+use crate::sequence;
+use crate::{Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const DEFAULT_PULSE_MS: u64 = 500;
+
+// ============================================================================
+// SELECTION
+// ============================================================================
+
+pub struct BulkActions {
+    selected: BTreeSet<u8>,
+    pulse_ms: u64,
+    scene_name: String,
+    label_prefix: String,
+    copy_from: u8,
+    template: String,
+    status: Option<String>,
+}
+
+impl BulkActions {
+    pub fn new() -> Self {
+        Self {
+            selected: BTreeSet::new(),
+            pulse_ms: DEFAULT_PULSE_MS,
+            scene_name: String::new(),
+            label_prefix: String::new(),
+            copy_from: 1,
+            template: String::new(),
+            status: None,
+        }
+    }
+
+    pub fn is_selected(&self, channel: u8) -> bool {
+        self.selected.contains(&channel)
+    }
+
+    pub fn set_selected(&mut self, channel: u8, selected: bool) {
+        if selected {
+            self.selected.insert(channel);
+        } else {
+            self.selected.remove(&channel);
+        }
+    }
+
+    pub fn toggle(&mut self, channel: u8) {
+        let selected = !self.is_selected(channel);
+        self.set_selected(channel, selected);
+    }
+
+    /// Draws the action bar for the current selection, if any.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        config: &mut LiveConfig,
+        relay_states: &[RelayState],
+        stop: &EmergencyStop,
+    ) {
+        if self.selected.is_empty() {
+            return;
+        }
+        let channels: Vec<u8> = self.selected.iter().copied().collect();
+
+        ui.group(|ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(tr_args("bulk-selected", &[("count", &channels.len())]));
+                if ui.small_button(tr("bulk-clear")).clicked() {
+                    self.selected.clear();
+                }
+
+                if ui.button(tr("bulk-all-off")).clicked() {
+                    let guard = stop.guard(Source::Window);
+                    for &channel in &channels {
+                        guard.send(Command::TurnOff(channel));
+                    }
+                }
+
+                if ui.button(tr("bulk-pulse")).clicked() {
+                    sequence::pulse(
+                        &channels,
+                        Duration::from_millis(self.pulse_ms),
+                        stop,
+                        Source::Window,
+                    );
+                }
+                ui.add(
+                    egui::DragValue::new(&mut self.pulse_ms)
+                        .range(50..=60_000)
+                        .speed(10)
+                        .suffix(" ms"),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.scene_name)
+                        .hint_text(tr("bulk-scene"))
+                        .desired_width(120.0),
+                );
+                let add = ui.button(tr("bulk-add-to-scene")).clicked();
+                if add && !self.scene_name.trim().is_empty() {
+                    let name = self.scene_name.trim().to_string();
+                    let steps: Vec<SceneStep> = channels
+                        .iter()
+                        .map(|&channel| SceneStep {
+                            channel,
+                            on: relay_states.get(channel as usize - 1) == Some(&RelayState::On),
+                        })
+                        .collect();
+                    let result = config.update(|c| add_to_scene(&mut c.scenes, &name, steps));
+                    self.status = Some(match result {
+                        Ok(()) => {
+                            let count = channels.len();
+                            tr_args("bulk-added", &[("count", &count), ("scene", &name)])
+                        }
+                        Err(e) => e,
+                    });
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.label_prefix)
+                        .hint_text(tr("bulk-label-prefix"))
+                        .desired_width(120.0),
+                );
+                let set = ui.button(tr("bulk-set-labels")).clicked();
+                if set && !self.label_prefix.trim().is_empty() {
+                    let prefix = self.label_prefix.trim().to_string();
+                    let result = config.update(|c| {
+                        for (n, &channel) in channels.iter().enumerate() {
+                            c.device.channel_mut(channel).label = format!("{} {}", prefix, n + 1);
+                        }
+                    });
+                    self.status = result.err();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let channel_count = config.get().device.channels;
+                ui.add(
+                    egui::DragValue::new(&mut self.copy_from)
+                        .range(1..=channel_count)
+                        .prefix("CH "),
+                );
+                if ui.button(tr("bulk-copy-settings")).clicked() {
+                    let from = self.copy_from;
+                    let result = config.update(|c| {
+                        for &to in channels.iter().filter(|&&to| to != from) {
+                            c.device.copy_channel(from, to);
+                        }
+                    });
+                    self.status = result.err();
+                }
+            });
+
+            let templates: Vec<String> = config.get().templates.iter().map(|t| t.name.clone()).collect();
+            if !templates.is_empty() {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("bulk_template")
+                        .selected_text(match self.template.is_empty() {
+                            true => tr("bulk-template"),
+                            false => self.template.clone(),
+                        })
+                        .show_ui(ui, |ui| {
+                            for name in &templates {
+                                ui.selectable_value(&mut self.template, name.clone(), name);
+                            }
+                        });
+                    if ui.button(tr("bulk-apply-template")).clicked() {
+                        let result = match config.get().template(&self.template).cloned() {
+                            Some(template) => config.update(|c| {
+                                for &channel in &channels {
+                                    *c.device.channel_mut(channel) = template.instantiate(channel);
+                                }
+                            }),
+                            None => Err(tr("bulk-choose-template")),
+                        };
+                        self.status = result.err();
+                    }
+                });
+            }
+
+            if let Some(status) = &self.status {
+                ui.label(status);
+            }
+        });
+    }
+}
+
+/// Merges `steps` into the named scene, creating it if needed. Existing steps
+/// for the same channels are replaced.
+fn add_to_scene(scenes: &mut Vec<Scene>, name: &str, steps: Vec<SceneStep>) {
+    let scene = match scenes.iter().position(|s| s.name.eq_ignore_ascii_case(name)) {
+        Some(index) => &mut scenes[index],
+        None => {
+            scenes.push(Scene {
+                name: name.to_string(),
+                steps: Vec::new(),
+            });
+            scenes.last_mut().unwrap()
+        }
+    };
+
+    scene
+        .steps
+        .retain(|existing| !steps.iter().any(|s| s.channel == existing.channel));
+    scene.steps.extend(steps);
+    scene.steps.sort_by_key(|s| s.channel);
+}
@@ -0,0 +1,1205 @@
+//! Command-line interface.
+//!
+//! With no arguments the binary launches the GUI; otherwise the first
+//! arguments select a subcommand. Commands that switch relays, activate a
+//! scene or change a schedule go through a running instance when there is
+//! one, since it holds the serial port and acts on schedule changes at once;
+//! otherwise they open the port or edit the config file themselves.
+//! With `--json`, commands that report something print it as one JSON
+//! object instead, errors included, for scripts and monitoring checks.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use crate::api::json_string;
+use crate::completions;
+use crate::config::{Config, DeviceBinding};
+use crate::desired::DesiredState;
+use crate::history::{unix_now, History};
+use crate::ical;
+use crate::import;
+use crate::ipc;
+use crate::locale::{format_moment, format_time};
+use crate::ports::{self, UsbId};
+use crate::raw::{format_hex, parse_hex};
+use crate::report::{self, ReportPeriod};
+use crate::batch::{Batch, BatchStep};
+use crate::schedule::{LocalTime, Schedule, ScheduleAction, TimeOfDay, Weekday};
+use crate::sequence::Direction;
+use crate::urlscheme::{self, UrlAction};
+use crate::winservice;
+use crate::{Command, RelayController, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const USAGE: &str = "\
+Usage: usb-power-relay [COMMAND]
+
+Without a command the GUI is started.
+
+Commands:
+  --daemon                              Run without a window: device, schedules,
+                                          HTTP API (stop with Ctrl+C/SIGTERM)
+  --install-service                     Run the daemon as a Windows service
+                                          under LocalSystem (elevated prompt)
+  --uninstall-service                   Stop and remove the Windows service
+  --register-url-scheme                 Open usbrelay:// links with this executable
+  --unregister-url-scheme               Stop handling usbrelay:// links
+  usbrelay://<action>                   Carry out a link: on, off or toggle
+                                          [?channel=<ch>,...], scene?name=<name>
+  --monitor                             Start a read-only window that only shows
+                                          the channels' states
+  --script <file>                       Run a file of pipe commands (see pipe),
+                                          stopping at the first that fails
+  show                                  Bring the running app's window back
+  status [<ch>]                         Show every channel's state, or one's;
+                                          the exit code tells the state (below)
+  on <ch>...                            Switch channels on
+  off <ch>...                           Switch channels off
+  scene list                            List configured scenes
+  scene activate <name>                 Apply a scene to the relay
+  schedule list                         List schedules
+  schedule add <HH:MM> <action> [days] [--tz <zone>]
+                                        Add a schedule
+                                          action: on[:<ch>] | off[:<ch>] | scene:<name>
+                                          days:   daily | weekdays | weekends | mon,tue,...
+                                          zone:   e.g. Europe/Berlin (default: local time)
+  schedule remove <id>                  Delete a schedule
+  schedule enable <id>                  Enable a schedule
+  schedule disable <id>                 Disable a schedule
+  schedule snooze <id> <duration>       Skip a schedule for a while, e.g. 1h, 4h, 90m
+  schedule unsnooze <id>                End a schedule's snooze
+  schedule import <file.ics> [--channel <n>] [--dry-run]
+                                        Sync a calendar's events into ON/OFF
+                                          schedules, replacing its last import
+  channel list                          List channels and their settings
+  channel copy <from> <to>...           Copy a channel's settings (except label)
+  channel allow <ch>                    Lift the daily switch limit for today
+  template list                         List channel templates
+  template apply <name> [--overwrite]   Apply a template to unconfigured channels
+  sequence list                         List power sequences
+  sequence start <name>                 Switch a sequence on in order
+  sequence shutdown <name>              Switch a sequence off in reverse order
+  rule test <name>                      Show whether a rule's conditions hold now
+                                          and what it would do, without switching
+  port list                             List serial ports (* = the one used)
+  port bind <port>                      Always use this board (by USB serial number)
+  port unbind                           Use the first CH340 found again
+  import <file> [--dry-run]             Import labels, channels and the board from
+                                          usbrelay udev rules/output, CommandApp
+                                          scripts or a '<ch>=<label>' list
+  batch <step>...                       Run steps as one unit: on:<ch> off:<ch>
+                                          pulse:<ch>:<ms> wait:<ms>
+  pipe                                  Run commands from stdin, one per line:
+                                          on [<ch>], off [<ch>], pulse [<ch>] <time>,
+                                          wait <time>, status
+  send-raw <hex>...                     Send raw bytes and print the reply
+                                          (needs advanced: true)
+  report [daily|weekly]                 Summarize the last day or week of history
+  history [<count>]                     Show the latest switches and what caused
+                                          them (default: 20)
+  discover                              Find relays advertising their API on
+                                          the LAN
+  completions <bash|zsh|fish>           Print a shell completion script
+  help                                  Show this message
+
+Options:
+  --json                                Print the result as JSON, for status, on,
+                                          off, scene activate, sequence start/
+                                          shutdown, batch, pipe and --script (a line
+                                          each), port list, history and discover;
+                                          errors too
+
+Exit codes:
+  0 success, 1 error, 2 usage error; status exits 0 when the channels are ON,
+  10 when any is OFF, 11 when any is unknown (or none reported) and 12 when
+  any reports an error";
+
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+/// `status` when a channel is OFF, unknown or in error; all ON is
+/// [`EXIT_OK`].
+const EXIT_STATE_OFF: i32 = 10;
+const EXIT_STATE_UNKNOWN: i32 = 11;
+const EXIT_STATE_ERROR: i32 = 12;
+
+/// Set by `--json`, for the rest of the process.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+// ============================================================================
+// ENTRY POINT
+// ============================================================================
+
+/// Runs a subcommand and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    attach_console();
+
+    let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let json = args.contains(&"--json");
+    args.retain(|arg| *arg != "--json");
+    JSON.store(json, Ordering::SeqCst);
+    if json && !supports_json(&args) {
+        print_error("--json isn't supported by this command");
+        return EXIT_USAGE;
+    }
+
+    let result = match args.as_slice() {
+        ["--daemon"] => return crate::daemon::run(),
+        ["--run-as-service"] => return winservice::run(),
+        ["--install-service"] => winservice::install().map(|_| {
+            println!("Installed and started the USB Power Relay service");
+        }),
+        ["--uninstall-service"] => winservice::uninstall().map(|_| {
+            println!("Removed the USB Power Relay service");
+        }),
+        ["--register-url-scheme"] => urlscheme::register().map(|_| {
+            println!("usbrelay:// links now open with this executable");
+        }),
+        ["--unregister-url-scheme"] => urlscheme::unregister().map(|_| {
+            println!("usbrelay:// links are no longer handled");
+        }),
+        [url] if urlscheme::is_url(url) => open_url(url),
+        ["show"] => show(),
+        ["status"] => return finish(status(None)),
+        ["status", channel] => return finish(status(Some(channel))),
+        ["on", channels @ ..] if !channels.is_empty() => switch(channels, true),
+        ["off", channels @ ..] if !channels.is_empty() => switch(channels, false),
+        ["scene", "list"] => scene_list(),
+        ["scene", "activate", name] => scene_activate(name),
+        ["schedule", "list"] => schedule_list(),
+        ["schedule", "add", _, _]
+        | ["schedule", "add", _, _, _]
+        | ["schedule", "add", _, _, "--tz", _]
+        | ["schedule", "add", _, _, _, "--tz", _]
+        | ["schedule", "remove" | "enable" | "disable" | "unsnooze", _]
+        | ["schedule", "snooze", _, _] => schedule_change(&args[1..]),
+        ["schedule", "import", path, options @ ..] => schedule_import(path, options),
+        ["channel", "list"] => channel_list(),
+        ["channel", "copy", from, targets @ ..] if !targets.is_empty() => channel_copy(from, targets),
+        ["channel", "allow", channel] => channel_allow(channel),
+        ["template", "list"] => template_list(),
+        ["template", "apply", name] => template_apply(name, false),
+        ["template", "apply", name, "--overwrite"] => template_apply(name, true),
+        ["sequence", "list"] => sequence_list(),
+        ["sequence", "start", name] => sequence_run(name, Direction::Startup),
+        ["sequence", "shutdown", name] => sequence_run(name, Direction::Shutdown),
+        ["rule", "test", name] => rule_test(name),
+        ["port", "list"] => port_list(),
+        ["port", "bind", name] => port_bind(name),
+        ["port", "unbind"] => port_unbind(),
+        ["import", path] => import_file(path, false),
+        ["import", path, "--dry-run"] => import_file(path, true),
+        ["batch", steps @ ..] if !steps.is_empty() => batch_run(&steps.join(",")),
+        ["pipe"] => return finish(pipe()),
+        ["--script", path] => return finish(script(path)),
+        ["completions", shell] => completions::script(shell).map(|script| print!("{}", script)),
+        ["send-raw", bytes @ ..] if !bytes.is_empty() => send_raw(&bytes.join(" ")),
+        ["report"] => report_print("daily"),
+        ["report", period] => report_print(period),
+        ["history"] => history_print("20"),
+        ["history", count] => history_print(count),
+        ["discover"] => discover(),
+        ["help"] | ["--help"] | ["-h"] => {
+            println!("{}", USAGE);
+            return EXIT_OK;
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    finish(result.map(|()| EXIT_OK))
+}
+
+fn finish(result: Result<i32, String>) -> i32 {
+    result.unwrap_or_else(|e| {
+        print_error(&e);
+        EXIT_ERROR
+    })
+}
+
+fn supports_json(args: &[&str]) -> bool {
+    matches!(
+        args,
+        ["status"]
+            | ["status", _]
+            | ["on" | "off" | "batch", _, ..]
+            | ["scene", "activate", _]
+            | ["sequence", "start" | "shutdown", _]
+            | ["port", "list"]
+            | ["history"]
+            | ["history", _]
+            | ["discover"]
+            | ["pipe"]
+            | ["--script", _]
+    )
+}
+
+fn json_output() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+fn print_error(message: &str) {
+    match json_output() {
+        true => println!("{{\"ok\":false,\"error\":{}}}", json_string(message)),
+        false => eprintln!("Error: {}", message),
+    }
+}
+
+/// The release binary uses the Windows GUI subsystem, so it has no console
+/// of its own; borrow the parent's so CLI output is visible.
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    // SAFETY: AttachConsole has no memory-safety preconditions
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// ============================================================================
+// SWITCHING
+// ============================================================================
+
+fn show() -> Result<(), String> {
+    match ipc::request("focus") {
+        Some(reply) => reply.map(|_| ()),
+        None => Err("The app is not running".to_string()),
+    }
+}
+
+/// Prints the channels' states and returns the exit code they come to: the
+/// worst of them, ERROR before unknown before OFF. No state at all, as when
+/// the board reported nothing, is unknown rather than ON.
+fn status(channel: Option<&str>) -> Result<i32, String> {
+    let started = Instant::now();
+    let config = Config::load()?;
+    let channel = channel.map(parse_channel).transpose()?;
+    if let Some(channel) = channel.filter(|c| !(1..=config.device.channels).contains(c)) {
+        return Err(format!("No channel {}", channel));
+    }
+    let (mut states, port) = match ipc::request("status") {
+        Some(reply) => (reply_states(&reply?), instance_port(&config)),
+        None => {
+            let mut controller = RelayController::new(&config.device)?;
+            (controller.execute(&Command::Poll)?, controller.port.name())
+        }
+    };
+    states.retain(|&(c, _)| channel.is_none_or(|wanted| c == wanted));
+    print_states(&config, &states, port, started)?;
+
+    let any = |wanted: RelayState| states.iter().any(|&(_, state)| state == wanted);
+    Ok(if any(RelayState::Error) {
+        EXIT_STATE_ERROR
+    } else if states.is_empty() || any(RelayState::Unknown) {
+        EXIT_STATE_UNKNOWN
+    } else if any(RelayState::Off) {
+        EXIT_STATE_OFF
+    } else {
+        EXIT_OK
+    })
+}
+
+fn switch(channels: &[&str], on: bool) -> Result<(), String> {
+    let channels = channels.iter().map(|c| parse_channel(c)).collect::<Result<Vec<_>, _>>()?;
+    switch_channels(&channels, |_| on)
+}
+
+/// Switches each channel on or off as `on` decides, in one batch.
+fn switch_channels(channels: &[u8], on: impl Fn(u8) -> bool) -> Result<(), String> {
+    let config = Config::load()?;
+    let steps = channels
+        .iter()
+        .map(|&c| if on(c) { BatchStep::On(c) } else { BatchStep::Off(c) })
+        .collect();
+    let batch = Batch { steps, start_by: None };
+    batch.validate(config.device.channels)?;
+    run_batch(&config, batch)
+}
+
+/// Switches channels that are ON off and the others on.
+fn toggle(channels: &[u8]) -> Result<(), String> {
+    let states = current_states(&Config::load()?)?;
+    let is_on = |channel: u8| states.contains(&(channel, RelayState::On));
+    switch_channels(channels, |channel| !is_on(channel))
+}
+
+/// Every channel's state, from the running instance or the board.
+fn current_states(config: &Config) -> Result<Vec<(u8, RelayState)>, String> {
+    match ipc::request("status") {
+        Some(reply) => Ok(reply_states(&reply?)),
+        None => RelayController::new(&config.device)?.execute(&Command::Poll),
+    }
+}
+
+fn open_url(url: &str) -> Result<(), String> {
+    match urlscheme::parse(url)? {
+        UrlAction::On(channels) => switch_channels(&channels, |_| true),
+        UrlAction::Off(channels) => switch_channels(&channels, |_| false),
+        UrlAction::Toggle(channels) => toggle(&channels),
+        UrlAction::Scene(name) => scene_activate(&name),
+    }
+}
+
+// ============================================================================
+// SCENES
+// ============================================================================
+
+fn scene_list() -> Result<(), String> {
+    let config = Config::load()?;
+    if config.scenes.is_empty() {
+        println!("No scenes configured ({})", Config::path()?.display());
+        return Ok(());
+    }
+
+    for scene in &config.scenes {
+        let steps: Vec<String> = scene
+            .steps
+            .iter()
+            .map(|s| format!("{}:{}", s.channel, if s.on { "on" } else { "off" }))
+            .collect();
+        println!("{:<20} {}", scene.name, steps.join(" "));
+    }
+    Ok(())
+}
+
+/// Activates a scene by name in the running instance, which looks it up in
+/// its own config, or on the board directly when there is none.
+fn scene_activate(name: &str) -> Result<(), String> {
+    let started = Instant::now();
+    let config = Config::load()?;
+    if let Some(reply) = ipc::request(&format!("scene {}", name)) {
+        return print_reply(&config, reply?, started);
+    }
+    let scene = config
+        .scene(name)
+        .ok_or_else(|| format!("Unknown scene '{}'", name))?;
+
+    run_batch_locally(&config, Batch::from_scene(scene), started)
+}
+
+/// Runs a batch through the running instance, or on the board directly when
+/// there is none.
+fn run_batch(config: &Config, batch: Batch) -> Result<(), String> {
+    let started = Instant::now();
+    let steps: Vec<String> = batch.steps.iter().map(BatchStep::to_string).collect();
+    if let Some(reply) = ipc::request(&format!("batch {}", steps.join(","))) {
+        return print_reply(config, reply?, started);
+    }
+    run_batch_locally(config, batch, started)
+}
+
+fn run_batch_locally(config: &Config, batch: Batch, started: Instant) -> Result<(), String> {
+    let mut controller = RelayController::new(&config.device)?;
+    let mut desired = DesiredState::load()?;
+    let changes = controller.execute(&Command::Batch(batch, None))?;
+    print_states(config, &changes, controller.port.name(), started)?;
+    // Remembered as the desired state, for the GUI to restore
+    desired.update(&changes)
+}
+
+fn print_lines(lines: Vec<String>) -> Result<(), String> {
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Prints a running instance's `Channel <n>: <STATE>` reply.
+fn print_reply(config: &Config, lines: Vec<String>, started: Instant) -> Result<(), String> {
+    if !json_output() {
+        return print_lines(lines);
+    }
+    print_states(config, &reply_states(&lines), instance_port(config), started)
+}
+
+/// The states in a running instance's reply.
+fn reply_states(lines: &[String]) -> Vec<(u8, RelayState)> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let (channel, text) = line.strip_prefix("Channel ")?.split_once(": ")?;
+            let states = [RelayState::On, RelayState::Off, RelayState::Unknown, RelayState::Error];
+            Some((channel.parse().ok()?, states.into_iter().find(|s| s.text() == text)?))
+        })
+        .collect()
+}
+
+/// The port a running instance uses; it picks its board the same way.
+fn instance_port(config: &Config) -> Option<String> {
+    ports::select(&config.device).ok().map(|port| port.port_name)
+}
+
+/// Prints channel states as `Channel <n>: <STATE>` lines or, with `--json`,
+/// with their labels, the port and how long the command took.
+fn print_states(
+    config: &Config,
+    states: &[(u8, RelayState)],
+    port: Option<String>,
+    started: Instant,
+) -> Result<(), String> {
+    if !json_output() {
+        for &(channel, state) in states {
+            println!("Channel {}: {}", channel, state.text());
+        }
+        return Ok(());
+    }
+    let channels: Vec<String> = states
+        .iter()
+        .map(|&(channel, state)| {
+            format!(
+                "{{\"channel\":{},\"label\":{},\"state\":\"{}\"}}",
+                channel,
+                json_string(&config.device.label(channel)),
+                state.name()
+            )
+        })
+        .collect();
+    println!(
+        "{{\"ok\":true,\"port\":{},\"latency_ms\":{},\"channels\":[{}]}}",
+        port.as_deref().map_or_else(|| "null".to_string(), json_string),
+        started.elapsed().as_millis(),
+        channels.join(",")
+    );
+    Ok(())
+}
+
+// ============================================================================
+// SCHEDULES
+// ============================================================================
+
+fn schedule_list() -> Result<(), String> {
+    let config = Config::load()?;
+    if config.schedules.is_empty() {
+        println!("No schedules configured");
+        return Ok(());
+    }
+
+    println!(
+        "{:<4} {:<8} {:<28} {:<20} {:<8} {:<16} SNOOZED",
+        "ID", "TIME", "DAYS", "ACTION", "ENABLED", "ZONE"
+    );
+    let now = unix_now();
+    for s in &config.schedules {
+        let snoozed = match s.snoozed_until.filter(|_| s.snoozed(now)) {
+            Some(until) => snooze_text(until, &config),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<4} {:<8} {:<28} {:<20} {:<8} {:<16} {}",
+            s.id,
+            format_time(s.time, config.display.clock),
+            s.days_text(),
+            s.action.to_string(),
+            if s.enabled { "yes" } else { "no" },
+            s.timezone.as_deref().unwrap_or("local"),
+            snoozed
+        );
+    }
+    Ok(())
+}
+
+/// Changes a schedule through the running instance, which applies it at
+/// once, or in the config file when there is none. `args` follow
+/// `schedule`, as in `add 07:30 on weekdays`.
+fn schedule_change(args: &[&str]) -> Result<(), String> {
+    // Tabs keep arguments with spaces, like scene names, apart
+    match ipc::request(&format!("schedule {}", args.join("\t"))) {
+        Some(reply) => print_lines(reply?),
+        None => print_lines(vec![change_schedule(args)?]),
+    }
+}
+
+/// Carries out a `schedule` change on the config file and describes it;
+/// the running instance does this for the CLI.
+pub fn change_schedule(args: &[&str]) -> Result<String, String> {
+    match args {
+        ["add", time, action] => schedule_add(time, action, "daily", None),
+        ["add", time, action, "--tz", zone] => schedule_add(time, action, "daily", Some(zone)),
+        ["add", time, action, days] => schedule_add(time, action, days, None),
+        ["add", time, action, days, "--tz", zone] => schedule_add(time, action, days, Some(zone)),
+        ["remove", id] => schedule_remove(id),
+        ["enable", id] => schedule_set_enabled(id, true),
+        ["disable", id] => schedule_set_enabled(id, false),
+        ["snooze", id, duration] => schedule_snooze(id, Some(duration)),
+        ["unsnooze", id] => schedule_snooze(id, None),
+        _ => Err(format!("Unknown schedule change '{}'", args.join(" "))),
+    }
+}
+
+/// e.g. `until 14:30`.
+fn snooze_text(until: u64, config: &Config) -> String {
+    format!("until {}", format_moment(until, config.display.clock))
+}
+
+fn schedule_add(
+    time: &str,
+    action: &str,
+    days: &str,
+    zone: Option<&str>,
+) -> Result<String, String> {
+    let mut config = Config::load()?;
+    let action = ScheduleAction::parse(action)?;
+    if let ScheduleAction::Scene(name) = &action {
+        config
+            .scene(name)
+            .ok_or_else(|| format!("Unknown scene '{}'", name))?;
+    }
+
+    let id = config.schedules.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+    config.schedules.push(Schedule {
+        id,
+        time: TimeOfDay::parse(time)?,
+        days: Weekday::parse_list(days)?,
+        date: None,
+        timezone: zone.map(str::to_string),
+        action,
+        enabled: true,
+        snoozed_until: None,
+        source: None,
+    });
+    config.save()?;
+
+    Ok(format!("Added schedule #{}", id))
+}
+
+fn parse_id(id: &str) -> Result<u32, String> {
+    id.parse().map_err(|_| format!("Invalid schedule id '{}'", id))
+}
+
+fn schedule_remove(id: &str) -> Result<String, String> {
+    let id = parse_id(id)?;
+    let mut config = Config::load()?;
+    let before = config.schedules.len();
+    config.schedules.retain(|s| s.id != id);
+    if config.schedules.len() == before {
+        return Err(format!("No schedule #{}", id));
+    }
+    config.save()?;
+
+    Ok(format!("Removed schedule #{}", id))
+}
+
+fn schedule_set_enabled(id: &str, enabled: bool) -> Result<String, String> {
+    let id = parse_id(id)?;
+    let mut config = Config::load()?;
+    let schedule = config
+        .schedules
+        .iter_mut()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("No schedule #{}", id))?;
+    schedule.enabled = enabled;
+    config.save()?;
+
+    Ok(format!("Schedule #{} {}", id, if enabled { "enabled" } else { "disabled" }))
+}
+
+/// Parses a snooze length like `4h` or `90m` into seconds.
+fn parse_snooze(text: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid duration '{}', expected e.g. 1h, 4h or 90m", text);
+    let (number, unit) = match (text.strip_suffix('h'), text.strip_suffix('m')) {
+        (Some(hours), _) => (hours, 3600),
+        (_, Some(minutes)) => (minutes, 60),
+        _ => return Err(invalid()),
+    };
+    let number: u64 = number.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?;
+    Ok(number * unit)
+}
+
+fn schedule_snooze(id: &str, duration: Option<&str>) -> Result<String, String> {
+    let id = parse_id(id)?;
+    let until = duration.map(parse_snooze).transpose()?.map(|secs| unix_now() + secs);
+    let mut config = Config::load()?;
+    let schedule = config
+        .schedules
+        .iter_mut()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("No schedule #{}", id))?;
+    schedule.snoozed_until = until;
+    config.save()?;
+
+    Ok(match until {
+        Some(until) => format!("Schedule #{} snoozed {}", id, snooze_text(until, &config)),
+        None => format!("Schedule #{} no longer snoozed", id),
+    })
+}
+
+fn schedule_import(path: &str, options: &[&str]) -> Result<(), String> {
+    let (channel, dry_run) = match options {
+        [] => (None, false),
+        ["--dry-run"] => (None, true),
+        ["--channel", n] => (Some(parse_channel(n)?), false),
+        ["--channel", n, "--dry-run"] | ["--dry-run", "--channel", n] => {
+            (Some(parse_channel(n)?), true)
+        }
+        _ => return Err(format!("Unknown options: {}", options.join(" "))),
+    };
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    // The same file found by another path still replaces its last import
+    let source = std::fs::canonicalize(path)
+        .map_or_else(|_| path.to_string(), |p| p.display().to_string());
+
+    let mut config = Config::load()?;
+    let found = ical::import(&text, &config.device, channel, &source, LocalTime::now().date());
+    for skipped in &found.skipped {
+        println!("Skipped {}", skipped);
+    }
+    for note in &found.notes {
+        println!("Note: {}", note);
+    }
+
+    let before = config.schedules.len();
+    config.schedules.retain(|s| s.source.as_deref() != Some(source.as_str()));
+    let removed = before - config.schedules.len();
+    let added = found.schedules.len();
+    let mut id = config.schedules.iter().map(|s| s.id).max().unwrap_or(0);
+    for mut schedule in found.schedules {
+        id += 1;
+        schedule.id = id;
+        println!(
+            "{} {} {}",
+            schedule.days_text(),
+            format_time(schedule.time, config.display.clock),
+            schedule.action
+        );
+        config.schedules.push(schedule);
+    }
+    println!("{} schedule(s) from {}, replacing {}", added, path, removed);
+
+    if !dry_run {
+        config.save()?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// CHANNELS AND TEMPLATES
+// ============================================================================
+
+fn parse_channel(text: &str) -> Result<u8, String> {
+    text.parse().map_err(|_| format!("Invalid channel '{}'", text))
+}
+
+fn channel_list() -> Result<(), String> {
+    let config = Config::load()?;
+    for channel in 1..=config.device.channels {
+        println!("{:<4} {}", channel, config.device.display_name(channel));
+    }
+    Ok(())
+}
+
+fn channel_copy(from: &str, targets: &[&str]) -> Result<(), String> {
+    let from = parse_channel(from)?;
+    let targets = targets
+        .iter()
+        .map(|t| parse_channel(t))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut config = Config::load()?;
+    for &to in &targets {
+        config.device.copy_channel(from, to);
+    }
+    config.save()?;
+
+    println!("Copied channel {} settings to {} channel(s)", from, targets.len());
+    Ok(())
+}
+
+/// The counts live in the running instance, so there is nothing to lift
+/// without one.
+fn channel_allow(channel: &str) -> Result<(), String> {
+    let channel = parse_channel(channel)?;
+    match ipc::request(&format!("allow {}", channel)) {
+        Some(reply) => print_lines(reply?),
+        None => Err("The app is not running".to_string()),
+    }
+}
+
+fn template_list() -> Result<(), String> {
+    let config = Config::load()?;
+    if config.templates.is_empty() {
+        println!("No templates configured");
+        return Ok(());
+    }
+
+    for template in &config.templates {
+        println!("{:<20} {}", template.name, template.label_pattern);
+    }
+    Ok(())
+}
+
+fn template_apply(name: &str, overwrite: bool) -> Result<(), String> {
+    let mut config = Config::load()?;
+    let template = config
+        .template(name)
+        .cloned()
+        .ok_or_else(|| format!("Unknown template '{}'", name))?;
+    let applied = config.device.apply_template(&template, overwrite);
+    config.save()?;
+
+    println!("Applied '{}' to {} channel(s)", template.name, applied);
+    Ok(())
+}
+
+// ============================================================================
+// SEQUENCES
+// ============================================================================
+
+fn sequence_list() -> Result<(), String> {
+    let config = Config::load()?;
+    if config.sequences.is_empty() {
+        println!("No sequences configured");
+        return Ok(());
+    }
+
+    for sequence in &config.sequences {
+        let steps: Vec<String> = sequence
+            .steps
+            .iter()
+            .map(|s| format!("{} (+{}ms)", s.channel, s.delay_ms))
+            .collect();
+        println!("{:<20} {}", sequence.name, steps.join(" -> "));
+    }
+    Ok(())
+}
+
+fn sequence_run(name: &str, direction: Direction) -> Result<(), String> {
+    let config = Config::load()?;
+    let sequence = config
+        .sequence(name)
+        .ok_or_else(|| format!("Unknown sequence '{}'", name))?;
+
+    // As one batch, so a running instance can carry it out
+    let mut steps = Vec::new();
+    for (cmd, delay) in sequence.plan(direction) {
+        steps.push(match cmd {
+            Command::TurnOn(channel) => BatchStep::On(channel),
+            Command::TurnOff(channel) => BatchStep::Off(channel),
+            _ => continue,
+        });
+        if !delay.is_zero() {
+            steps.push(BatchStep::Wait(delay.as_millis() as u64));
+        }
+    }
+    // Nothing follows the last step
+    if matches!(steps.last(), Some(BatchStep::Wait(_))) {
+        steps.pop();
+    }
+    run_batch(&config, Batch { steps, start_by: None })
+}
+
+// ============================================================================
+// RULES
+// ============================================================================
+
+/// Shows whether a rule's conditions hold right now and what it would do if
+/// triggered; nothing is switched.
+fn rule_test(name: &str) -> Result<(), String> {
+    let config = Config::load()?;
+    let rule = config
+        .rules
+        .iter()
+        .find(|r| r.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Unknown rule '{}'", name))?;
+    let mut states = vec![RelayState::Unknown; config.device.channels as usize];
+    let known = current_states(&config).unwrap_or_else(|e| {
+        println!("Channel states are unknown: {}", e);
+        Vec::new()
+    });
+    for (channel, state) in known {
+        if let Some(slot) = states.get_mut((channel as usize).wrapping_sub(1)) {
+            *slot = state;
+        }
+    }
+
+    let checks = rule.check(&states, &LocalTime::now());
+    println!("Rule '{}'{}", rule.name, if rule.enabled { "" } else { " (disabled)" });
+    println!("Trigger: {}", rule.trigger);
+    for (condition, holds) in &checks {
+        println!("  [{}] {}", if *holds { "x" } else { " " }, condition);
+    }
+    if !rule.enabled {
+        println!("Disabled; nothing would run");
+    } else if checks.iter().any(|(_, holds)| !holds) {
+        println!("A condition doesn't hold; nothing would run");
+    } else {
+        println!("Triggered now, it would:");
+        for action in &rule.actions {
+            println!("  {}", action);
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// PORTS
+// ============================================================================
+
+fn port_list() -> Result<(), String> {
+    let config = Config::load()?;
+    let candidates = ports::candidates(&config.device)?;
+    let selected = ports::select(&config.device).map(|port| port.port_name).ok();
+    if json_output() {
+        let text = |value: Option<&str>| value.map_or_else(|| "null".to_string(), json_string);
+        let ports: Vec<String> = candidates
+            .iter()
+            .map(|candidate| {
+                let name = &candidate.info.port_name;
+                let usb = candidate.usb();
+                let usb_id = usb.map(|usb| UsbId { vid: usb.vid, pid: usb.pid }.to_string());
+                format!(
+                    "{{\"port\":{},\"selected\":{},\"usb_id\":{},\"serial\":{},\"match\":{},\
+                     \"product\":{}}}",
+                    json_string(name),
+                    selected.as_deref() == Some(name.as_str()),
+                    text(usb_id.as_deref()),
+                    text(usb.and_then(|usb| usb.serial_number.as_deref())),
+                    text(candidate.matched.map(|m| m.text())),
+                    text(usb.and_then(|usb| usb.product.as_deref()))
+                )
+            })
+            .collect();
+        println!("{{\"ok\":true,\"ports\":[{}]}}", ports.join(","));
+        return Ok(());
+    }
+    if candidates.is_empty() {
+        println!("No serial ports found");
+        return Ok(());
+    }
+
+    println!(
+        "  {:<16} {:<10} {:<20} {:<14} PRODUCT",
+        "PORT", "VID:PID", "SERIAL", "MATCH"
+    );
+    for candidate in &candidates {
+        let name = &candidate.info.port_name;
+        let marker = if selected.as_deref() == Some(name.as_str()) { "*" } else { " " };
+        let matched = candidate.matched.map_or("-", |m| m.text());
+        match candidate.usb() {
+            Some(usb) => println!(
+                "{} {:<16} {:<10} {:<20} {:<14} {}",
+                marker,
+                name,
+                UsbId { vid: usb.vid, pid: usb.pid }.to_string(),
+                usb.serial_number.as_deref().unwrap_or("-"),
+                matched,
+                usb.product.as_deref().unwrap_or("-")
+            ),
+            None => println!("{} {:<16} {:<10} {:<20} {:<14} -", marker, name, "-", "-", matched),
+        }
+    }
+    Ok(())
+}
+
+/// Pins the board to `name`'s USB serial number, or to the port itself when
+/// the device reports none.
+fn port_bind(name: &str) -> Result<(), String> {
+    let ports = serialport::available_ports().map_err(|e| format!("Failed to list ports: {}", e))?;
+    let port = ports
+        .iter()
+        .find(|port| port.port_name == name)
+        .ok_or_else(|| format!("No serial port named '{}'", name))?;
+
+    let mut config = Config::load()?;
+    config.device.bind = ports::binding_for(port);
+    config.save()?;
+
+    println!("Bound the relay to {}", config.device.bind.describe());
+    Ok(())
+}
+
+fn port_unbind() -> Result<(), String> {
+    let mut config = Config::load()?;
+    config.device.bind = DeviceBinding::default();
+    config.save()?;
+    println!("The first CH340 found is used again");
+    Ok(())
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+fn import_file(path: &str, dry_run: bool) -> Result<(), String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let found = import::parse(&text);
+
+    let mut config = Config::load()?;
+    let changes = found.apply(&mut config.device);
+    if changes.is_empty() {
+        println!("Nothing to import from {}", path);
+    }
+    for change in &changes {
+        println!("{}", change);
+    }
+    if found.extra_boards > 0 {
+        println!(
+            "Ignored {} rule(s) for further boards; only the first is used",
+            found.extra_boards
+        );
+    }
+    if found.skipped > 0 {
+        println!("Skipped {} unrecognized line(s)", found.skipped);
+    }
+
+    if !dry_run && !changes.is_empty() {
+        config.save()?;
+        println!("Saved {} change(s)", changes.len());
+    }
+    Ok(())
+}
+
+// ============================================================================
+// BATCHES
+// ============================================================================
+
+fn batch_run(steps: &str) -> Result<(), String> {
+    let config = Config::load()?;
+    let batch = Batch::parse(steps)?;
+    batch.validate(config.device.channels)?;
+    run_batch(&config, batch)
+}
+
+// ============================================================================
+// PIPE
+// ============================================================================
+
+/// Runs commands from stdin until it closes. A failed command is reported
+/// and the rest still run; the exit code tells whether any failed.
+fn pipe() -> Result<i32, String> {
+    run_lines(std::io::stdin().lock(), None)
+}
+
+/// Runs a saved procedure, stopping at the first command that fails.
+fn script(path: &str) -> Result<i32, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    run_lines(BufReader::new(file), Some(path))
+}
+
+/// Runs commands, one per line, in order, answering each. Failures stop a
+/// script, whose `path` is given, and are only reported otherwise.
+fn run_lines(reader: impl BufRead, path: Option<&str>) -> Result<i32, String> {
+    let config = Config::load()?;
+    let mut board = None;
+    let mut failed = false;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path.unwrap_or("stdin"), e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let started = Instant::now();
+        let result = pipe_step(&config, line)
+            .and_then(|step| pipe_run(&config, &mut board, step, started));
+        match (result, path) {
+            (Ok(()), _) => {}
+            (Err(e), Some(path)) => return Err(format!("{}:{}: {}: {}", path, number + 1, line, e)),
+            (Err(e), None) => {
+                print_error(&format!("{}: {}", line, e));
+                failed = true;
+            }
+        }
+    }
+    Ok(if failed { EXIT_ERROR } else { EXIT_OK })
+}
+
+/// What a line asks for: `on [<ch>]`, `off [<ch>]`, `pulse [<ch>] <time>`,
+/// `wait <time>` or `status`, the last as `None`. A channel is its number or
+/// label and may be left out on a one-channel board; times are `500ms`, `2s`
+/// or plain milliseconds.
+fn pipe_step(config: &Config, line: &str) -> Result<Option<BatchStep>, String> {
+    let device = &config.device;
+    let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let argument = argument.trim();
+    let channel = |name: &str| match (name, device.channels) {
+        ("", 1) => Ok(1),
+        ("", _) => Err("Which channel?".to_string()),
+        (name, _) => device.find_channel(name).ok_or_else(|| format!("No channel '{}'", name)),
+    };
+    let millis = |text: &str| {
+        let (number, scale) = match (text.strip_suffix("ms"), text.strip_suffix('s')) {
+            (Some(ms), _) => (ms, 1),
+            (_, Some(secs)) => (secs, 1000),
+            _ => (text, 1),
+        };
+        number
+            .trim()
+            .parse::<u64>()
+            .map(|n| n * scale)
+            .map_err(|_| format!("Invalid time '{}', expected e.g. 500ms or 2s", text))
+    };
+
+    match command.to_ascii_lowercase().as_str() {
+        "on" => Ok(Some(BatchStep::On(channel(argument)?))),
+        "off" => Ok(Some(BatchStep::Off(channel(argument)?))),
+        "pulse" => {
+            let (name, time) = argument.rsplit_once(char::is_whitespace).unwrap_or(("", argument));
+            Ok(Some(BatchStep::Pulse(channel(name.trim())?, millis(time)?)))
+        }
+        "wait" => Ok(Some(BatchStep::Wait(millis(argument)?))),
+        "status" if argument.is_empty() => Ok(None),
+        _ => Err("Unknown command; use on, off, pulse, wait or status".to_string()),
+    }
+}
+
+/// Runs one step, or a status query for `None`, through the running
+/// instance, or on the board, which stays open for the following lines.
+fn pipe_run(
+    config: &Config,
+    board: &mut Option<(RelayController, DesiredState)>,
+    step: Option<BatchStep>,
+    started: Instant,
+) -> Result<(), String> {
+    let request = match &step {
+        Some(step) => format!("batch {}", step),
+        None => "status".to_string(),
+    };
+    if let Some(reply) = ipc::request(&request) {
+        return print_reply(config, reply?, started);
+    }
+    let (controller, desired) = match board {
+        Some(board) => board,
+        None => board.insert((RelayController::new(&config.device)?, DesiredState::load()?)),
+    };
+    let cmd = match step {
+        Some(step) => Command::Batch(Batch { steps: vec![step], start_by: None }, None),
+        None => Command::Poll,
+    };
+    let changes = controller.execute(&cmd)?;
+    print_states(config, &changes, controller.port.name(), started)?;
+    match cmd {
+        Command::Poll => Ok(()),
+        _ => desired.update(&changes),
+    }
+}
+
+// ============================================================================
+// RAW FRAMES
+// ============================================================================
+
+fn send_raw(hex: &str) -> Result<(), String> {
+    let config = Config::load()?;
+    if !config.advanced {
+        return Err("Raw frames are disabled; set advanced: true in the config".to_string());
+    }
+    let bytes = parse_hex(hex)?;
+    if let Some(reply) = ipc::request(&format!("raw {}", format_hex(&bytes))) {
+        return print_lines(reply?);
+    }
+
+    let mut controller = RelayController::new(&config.device)?;
+    let reply = controller.send_raw(&bytes)?;
+    println!("> {}", format_hex(&bytes));
+    if reply.is_empty() {
+        println!("< (no reply)");
+    } else {
+        println!("< {}", format_hex(&reply));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// REPORTS
+// ============================================================================
+
+fn report_print(period: &str) -> Result<(), String> {
+    let period = ReportPeriod::parse(period)?;
+    let config = Config::load()?;
+    println!("{}", report::render(&History::load()?, &config.device, period, unix_now()));
+    Ok(())
+}
+
+fn history_print(count: &str) -> Result<(), String> {
+    let count: usize = count.parse().map_err(|_| format!("Invalid count '{}'", count))?;
+    let config = Config::load()?;
+    let events = History::load()?;
+    let latest = &events[events.len().saturating_sub(count)..];
+    if json_output() {
+        let events: Vec<String> = latest
+            .iter()
+            .map(|event| {
+                format!(
+                    "{{\"time\":{},\"channel\":{},\"label\":{},\"state\":\"{}\",\"source\":{}}}",
+                    event.time,
+                    event.channel,
+                    json_string(&config.device.label(event.channel)),
+                    event.state.name(),
+                    json_string(&event.source)
+                )
+            })
+            .collect();
+        println!("{{\"ok\":true,\"events\":[{}]}}", events.join(","));
+        return Ok(());
+    }
+    if latest.is_empty() {
+        println!("No history yet");
+    }
+    for event in latest {
+        let time = LocalTime::from_unix(event.time);
+        let source = if event.source.is_empty() { "-" } else { &event.source };
+        println!(
+            "{} {:02}:{:02}:{:02}  {:<20} {:<6} {}",
+            time.date(),
+            time.hour,
+            time.minute,
+            time.second,
+            config.device.label(event.channel),
+            event.state.text(),
+            source
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// DISCOVERY
+// ============================================================================
+
+fn discover() -> Result<(), String> {
+    let found = crate::mdns::discover()?;
+    if json_output() {
+        let relays: Vec<String> = found
+            .iter()
+            .map(|relay| {
+                let txt: Vec<String> = relay.txt.iter().map(|entry| json_string(entry)).collect();
+                format!(
+                    "{{\"name\":{},\"host\":{},\"ip\":{},\"port\":{},\"txt\":[{}]}}",
+                    json_string(&relay.instance),
+                    json_string(&relay.host),
+                    relay.ip.map_or_else(|| "null".to_string(), |ip| format!("\"{}\"", ip)),
+                    relay.port,
+                    txt.join(",")
+                )
+            })
+            .collect();
+        println!("{{\"ok\":true,\"relays\":[{}]}}", relays.join(","));
+        return Ok(());
+    }
+    if found.is_empty() {
+        println!("No relays found");
+        return Ok(());
+    }
+    for relay in &found {
+        let address = relay.ip.map_or_else(|| relay.host.clone(), |ip| ip.to_string());
+        let txt = relay.txt.join(" ");
+        println!("{:<32} http://{}:{}/  {}", relay.instance, address, relay.port, txt);
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! System clock sanity check.
+//!
+//! A host without a working RTC (e.g. a Raspberry Pi) can boot with a clock
+//! that is hours or years off, which would fire schedules at the wrong time.
+//! Before schedules run, the clock is compared with an NTP server, or with
+//! the `Date` header of a plain HTTP server, and schedules are held while it
+//! is off by more than `max_skew_secs`. A clock before 2024 is never trusted.
+//! When no reference can be reached the clock is assumed to be right.
+
+use serde::{Deserialize, Serialize};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use crate::history::unix_now;
+use crate::http;
+use crate::locale::format_duration;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const NTP_PORT: u16 = 123;
+/// Seconds from the NTP epoch (1900) to the Unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often the clock is rechecked while it looks right...
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+/// ...and while it doesn't, or no reference was reachable.
+const RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// 2024-01-01T00:00:00Z; anything earlier is a clock that was never set.
+const MIN_PLAUSIBLE_UNIX: u64 = 1_704_067_200;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockCheckConfig {
+    pub enabled: bool,
+    /// Queried in order until one answers.
+    pub ntp_servers: Vec<String>,
+    /// `http://` URL whose `Date` header is used when no NTP server answers.
+    pub http_url: Option<String>,
+    pub max_skew_secs: u64,
+}
+
+impl Default for ClockCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ntp_servers: vec!["pool.ntp.org".to_string()],
+            http_url: None,
+            max_skew_secs: 300,
+        }
+    }
+}
+
+// ============================================================================
+// REFERENCE TIME
+// ============================================================================
+
+/// Asks an NTP server for the current Unix time (SNTP, RFC 4330).
+fn ntp_time(server: &str) -> Result<u64, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("NTP: {}", e))?;
+    socket
+        .set_read_timeout(Some(NETWORK_TIMEOUT))
+        .map_err(|e| format!("NTP: {}", e))?;
+    socket
+        .connect((server, NTP_PORT))
+        .map_err(|e| format!("NTP {}: {}", server, e))?;
+
+    // LI 0, version 4, mode 3 (client)
+    let mut packet = [0u8; 48];
+    packet[0] = 0x23;
+    socket.send(&packet).map_err(|e| format!("NTP {}: {}", server, e))?;
+    let len = socket.recv(&mut packet).map_err(|e| format!("NTP {}: {}", server, e))?;
+
+    // Mode 4 (server) with a non-zero stratum; stratum 0 is a kiss-of-death
+    if len < 48 || packet[0] & 0x07 != 4 || packet[1] == 0 {
+        return Err(format!("NTP {}: invalid reply", server));
+    }
+    let transmit = u32::from_be_bytes([packet[40], packet[41], packet[42], packet[43]]);
+    u64::from(transmit)
+        .checked_sub(NTP_UNIX_OFFSET)
+        .ok_or_else(|| format!("NTP {}: invalid timestamp", server))
+}
+
+/// Reads the `Date` header of a plain HTTP server.
+fn http_time(url: &str) -> Result<u64, String> {
+    let response = http::request("HEAD", url, &[], &[], NETWORK_TIMEOUT)?;
+    let date = response
+        .header("date")
+        .ok_or_else(|| format!("{}: no Date header", url))?;
+    parse_http_date(date).ok_or_else(|| format!("{}: invalid Date header '{}'", url, date))
+}
+
+/// Parses an IMF-fixdate like `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(text: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = fields.as_slice() else {
+        return None;
+    };
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (h, m, s) = (hms.next()??, hms.next()??, hms.next()??);
+
+    // Days since the Unix epoch for a proleptic Gregorian date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    u64::try_from(days * 86_400 + h * 3600 + m * 60 + s).ok()
+}
+
+/// The first reference time that can be reached.
+fn reference_time(config: &ClockCheckConfig) -> Result<u64, String> {
+    let mut errors = Vec::new();
+    for server in &config.ntp_servers {
+        match ntp_time(server) {
+            Ok(time) => return Ok(time),
+            Err(e) => errors.push(e),
+        }
+    }
+    if let Some(url) = &config.http_url {
+        match http_time(url) {
+            Ok(time) => return Ok(time),
+            Err(e) => errors.push(e),
+        }
+    }
+    Err(if errors.is_empty() {
+        "No time reference configured".to_string()
+    } else {
+        errors.join("; ")
+    })
+}
+
+// ============================================================================
+// CLOCK GUARD
+// ============================================================================
+
+/// Decides whether the system clock can be trusted, rechecking it against
+/// the network now and then.
+#[derive(Default)]
+pub struct ClockGuard {
+    last_check: Option<Instant>,
+    /// Reference minus system time at the last successful check.
+    skew: Option<i64>,
+}
+
+impl ClockGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns why schedules should be held, or `None` if the clock looks
+    /// right.
+    pub fn hold_reason(&mut self, config: &ClockCheckConfig) -> Option<String> {
+        if !config.enabled {
+            return None;
+        }
+        let now = unix_now();
+        if now < MIN_PLAUSIBLE_UNIX {
+            return Some("The system clock has not been set".to_string());
+        }
+
+        let trusted = self
+            .skew
+            .is_some_and(|skew| skew.unsigned_abs() <= config.max_skew_secs);
+        let interval = if trusted { CHECK_INTERVAL } else { RETRY_INTERVAL };
+        if self.last_check.is_none_or(|at| at.elapsed() >= interval) {
+            self.last_check = Some(Instant::now());
+            self.skew = match reference_time(config) {
+                Ok(reference) => Some(reference as i64 - unix_now() as i64),
+                Err(e) => {
+                    eprintln!("Clock check skipped: {}", e);
+                    None
+                }
+            };
+        }
+
+        let skew = self.skew?;
+        (skew.unsigned_abs() > config.max_skew_secs).then(|| {
+            let direction = if skew > 0 { "behind" } else { "ahead" };
+            format!(
+                "The system clock is {} {}",
+                format_duration(skew.unsigned_abs()),
+                direction
+            )
+        })
+    }
+}
@@ -0,0 +1,194 @@
+//! Shell completion scripts.
+//!
+//! `completions <shell>` prints a script that completes the CLI's commands
+//! and subcommands in bash, zsh or fish. The scripts are generated from
+//! [`COMMANDS`], which has to follow the CLI's `USAGE` by hand; scene,
+//! sequence and template names are completed from the `list` commands at
+//! completion time.
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// Top-level commands and the words that may follow them.
+const COMMANDS: &[(&str, &[&str])] = &[
+    ("--daemon", &[]),
+    ("--install-service", &[]),
+    ("--uninstall-service", &[]),
+    ("--register-url-scheme", &[]),
+    ("--unregister-url-scheme", &[]),
+    ("--monitor", &[]),
+    ("--script", &[]),
+    ("show", &[]),
+    ("status", &[]),
+    ("on", &[]),
+    ("off", &[]),
+    ("scene", &["list", "activate"]),
+    (
+        "schedule",
+        &["list", "add", "remove", "enable", "disable", "snooze", "unsnooze", "import"],
+    ),
+    ("channel", &["list", "copy", "allow"]),
+    ("template", &["list", "apply"]),
+    ("sequence", &["list", "start", "shutdown"]),
+    ("rule", &["test"]),
+    ("port", &["list", "bind", "unbind"]),
+    ("import", &[]),
+    ("batch", &[]),
+    ("pipe", &[]),
+    ("send-raw", &[]),
+    ("report", &["daily", "weekly"]),
+    ("history", &[]),
+    ("discover", &[]),
+    ("completions", &["bash", "zsh", "fish"]),
+    ("help", &[]),
+];
+
+/// Subcommands followed by a configured name, and the command listing the
+/// names; the name is the first column of its output, which starts with
+/// `No ` when there are none.
+const NAMED: &[(&str, &str, &str)] = &[
+    ("scene", "activate", "scene list"),
+    ("sequence", "start", "sequence list"),
+    ("sequence", "shutdown", "sequence list"),
+    ("template", "apply", "template list"),
+];
+
+const BIN: &str = "usb-power-relay";
+/// Cuts the names out of a `list` command's output, in bash and zsh.
+const FIRST_COLUMN: &str = "awk '!/^No /{print $1}'";
+/// The same with fish's builtins, as fish has no other quoting to spare.
+const FISH_FIRST_COLUMN: &str = "string match -rv '^No ' | string replace -r '\\s.*' ''";
+
+// ============================================================================
+// SCRIPTS
+// ============================================================================
+
+/// The completion script for `shell`.
+pub fn script(shell: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash()),
+        "zsh" => Ok(zsh()),
+        "fish" => Ok(fish()),
+        _ => Err(format!("Unknown shell '{}', expected bash, zsh or fish", shell)),
+    }
+}
+
+fn top_level() -> String {
+    let mut words: Vec<&str> = COMMANDS.iter().map(|(command, _)| *command).collect();
+    words.push("--json");
+    words.join(" ")
+}
+
+fn bash() -> String {
+    let mut cases = String::new();
+    for (command, words) in COMMANDS.iter().filter(|(_, words)| !words.is_empty()) {
+        cases += &format!("        {}) words=\"{}\" ;;\n", command, words.join(" "));
+    }
+    let mut named = String::new();
+    for (command, sub, list) in NAMED {
+        named += &format!(
+            "    [[ $cmd == {} && $prev == {} ]] && words=$({} {} 2>/dev/null | {})\n",
+            command, sub, BIN, list, FIRST_COLUMN
+        );
+    }
+    format!(
+        "# bash completion for {bin}; source it from ~/.bashrc
+_usb_power_relay() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    local cmd=${{COMP_WORDS[1]}} words=\"\"
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        words=\"{top}\"
+    elif [[ $COMP_CWORD -eq 2 ]]; then
+        case $cmd in
+{cases}        esac
+    fi
+    if [[ $prev == --script || $cmd == import ]]; then
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+        return
+    fi
+{named}    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))
+}}
+complete -F _usb_power_relay {bin}
+",
+        bin = BIN,
+        top = top_level(),
+        cases = cases,
+        named = named,
+    )
+}
+
+fn zsh() -> String {
+    let mut cases = String::new();
+    for (command, words) in COMMANDS.iter().filter(|(_, words)| !words.is_empty()) {
+        cases += &format!("        {}) compadd -- {} ;;\n", command, words.join(" "));
+    }
+    let mut named = String::new();
+    for (command, sub, list) in NAMED {
+        named += &format!(
+            "    [[ $words[2] == {} && $words[3] == {} ]] && \
+             compadd -- ${{(f)\"$({} {} 2>/dev/null | {})\"}} && return\n",
+            command, sub, BIN, list, FIRST_COLUMN
+        );
+    }
+    format!(
+        "#compdef {bin}
+# zsh completion for {bin}; put it on $fpath as _{bin}, or source it
+_usb_power_relay() {{
+    if (( CURRENT == 2 )); then
+        compadd -- {top}
+        return
+    fi
+    [[ $words[2] == --script || $words[2] == import ]] && {{ _files; return }}
+{named}    if (( CURRENT == 3 )); then
+        case $words[2] in
+{cases}        esac
+    fi
+}}
+# Autoloaded from $fpath this file is the completion itself; sourced, it
+# registers one
+if [[ $zsh_eval_context[-1] == loadautofunc ]]; then
+    _usb_power_relay \"$@\"
+else
+    compdef _usb_power_relay {bin}
+fi
+",
+        bin = BIN,
+        top = top_level(),
+        cases = cases,
+        named = named,
+    )
+}
+
+fn fish() -> String {
+    let mut out = format!(
+        "# fish completion for {bin}; save it as ~/.config/fish/completions/{bin}.fish
+complete -c {bin} -f
+complete -c {bin} -n __fish_use_subcommand -a '{top}'
+complete -c {bin} -n '__fish_seen_subcommand_from --script import' -F
+",
+        bin = BIN,
+        top = top_level(),
+    );
+    for (command, words) in COMMANDS.iter().filter(|(_, words)| !words.is_empty()) {
+        out += &format!(
+            "complete -c {} -n '__fish_seen_subcommand_from {}; \
+             and test (count (commandline -opc)) -eq 2' -a '{}'\n",
+            BIN,
+            command,
+            words.join(" ")
+        );
+    }
+    for (command, sub, list) in NAMED {
+        out += &format!(
+            "complete -c {bin} -n '__fish_seen_subcommand_from {}; \
+             and __fish_seen_subcommand_from {}' -a \"({bin} {} 2>/dev/null | {})\"\n",
+            command,
+            sub,
+            list,
+            FISH_FIRST_COLUMN,
+            bin = BIN
+        );
+    }
+    out
+}
@@ -0,0 +1,599 @@
+//! Persistent configuration (device, scenes, schedules, sequences, hotkeys,
+//! session triggers, anomaly detection, reports, display, sounds, exit
+//! policy, HTTP API).
+//!
+//! Stored as RON in the platform config directory, e.g.
+//! `~/.config/usb-power-relay/config.ron` on Linux. The running app reloads
+//! the file when it changes, so the CLI can edit it underneath the GUI.
+
+use serde::{Deserialize, Serialize};
+use serialport::{SerialPortInfo, SerialPortType};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::anomaly::AnomalyConfig;
+use crate::api::ApiConfig;
+use crate::bridge::BridgeConfig;
+use crate::clock::ClockCheckConfig;
+use crate::confirm::ConfirmOff;
+use crate::cooldown::CooldownMode;
+use crate::debounce::DebounceMode;
+use crate::email::EmailConfig;
+use crate::gamepad::GamepadConfig;
+use crate::hotkeys::{HotkeyBinding, Shortcut};
+use crate::hue::HueConfig;
+use crate::influx::InfluxConfig;
+use crate::locale::DisplayConfig;
+use crate::lock::LockConfig;
+use crate::midi::MidiConfig;
+use crate::monitor::MonitorConfig;
+use crate::ports::{DetectionConfig, SerialConfig};
+use crate::process::ProcessTrigger;
+use crate::remotes::RemoteConfig;
+use crate::push::PushConfig;
+use crate::report::ReportConfig;
+use crate::rules::Rule;
+use crate::schedule::Schedule;
+use crate::sequence::Sequence;
+use crate::session::SessionTrigger;
+use crate::snmp::SnmpConfig;
+use crate::sound::SoundConfig;
+use crate::startup::StartupPolicy;
+use crate::tcp::TcpConfig;
+use crate::telegram::TelegramConfig;
+use crate::theme::ThemeConfig;
+use crate::tz::TimeZone;
+use crate::voice::VoiceConfig;
+use crate::watchdog::WatchdogConfig;
+use crate::webhook::WebhookConfig;
+use crate::window::WindowConfig;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const CONFIG_FILE: &str = "config.ron";
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// ============================================================================
+// DEVICE
+// ============================================================================
+
+/// Channels that must never be on at the same time, e.g. forward/reverse
+/// motor contactors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterlockGroup {
+    pub name: String,
+    pub channels: Vec<u8>,
+}
+
+/// Per-channel settings; channels without an entry use the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelConfig {
+    pub channel: u8,
+    pub label: String,
+    /// Short glyph shown before the label, e.g. an emoji.
+    pub icon: String,
+    /// Minimum time between button presses; 0 disables debouncing.
+    pub debounce_ms: u64,
+    /// What happens to a press that comes too soon.
+    pub debounce: DebounceMode,
+    /// What the OFF button needs before it switches the channel off.
+    pub confirm_off: ConfirmOff,
+    /// Switches the channel off after it has been on this long; 0 means
+    /// no limit.
+    pub max_on_secs: u64,
+    /// How long the channel must stay off before it may switch on again.
+    pub cooldown_secs: u64,
+    /// What happens to an ON that comes sooner.
+    pub cooldown: CooldownMode,
+    /// Refuses to switch on after this many changes in a day, until
+    /// overridden; 0 means no limit.
+    pub max_switches_per_day: u32,
+}
+
+/// Reusable channel settings for provisioning identical boards. `{n}` in
+/// the label pattern is replaced with the channel number.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub label_pattern: String,
+    #[serde(default)]
+    pub settings: ChannelConfig,
+}
+
+impl ChannelTemplate {
+    pub fn instantiate(&self, channel: u8) -> ChannelConfig {
+        ChannelConfig {
+            channel,
+            label: self.label_pattern.replace("{n}", &channel.to_string()),
+            ..self.settings.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    /// Number of relays on the board, addressed as channels 1..=channels.
+    pub channels: u8,
+    pub interlocks: Vec<InterlockGroup>,
+    pub channel_settings: Vec<ChannelConfig>,
+    /// Template for channels that have no settings of their own.
+    pub template: Option<String>,
+    /// Reapply the last commanded state on startup and after reconnecting.
+    pub restore_state: bool,
+    /// Query every channel this often (seconds) to detect state drift; 0
+    /// disables polling.
+    pub poll_secs: u64,
+    /// Tune the reply timeout to the board's measured response times, and
+    /// poll faster right after a command and slower when idle.
+    pub adaptive_timing: bool,
+    /// For boards that never answer: skip every read and show the state
+    /// each channel was last switched to.
+    pub write_only: bool,
+    /// Reject reply frames whose checksum or state byte is invalid instead
+    /// of guessing a state from them.
+    pub strict: bool,
+    /// What to do when the board is absent at startup.
+    pub startup: StartupPolicy,
+    /// Which serial port is the board; unset picks the first CH340.
+    pub bind: DeviceBinding,
+    /// Extra VID:PIDs and keywords that identify a board.
+    pub detection: DetectionConfig,
+    pub serial: SerialConfig,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            channels: 1,
+            interlocks: Vec::new(),
+            channel_settings: Vec::new(),
+            template: None,
+            restore_state: true,
+            poll_secs: 0,
+            adaptive_timing: true,
+            write_only: false,
+            strict: false,
+            startup: StartupPolicy::default(),
+            bind: DeviceBinding::default(),
+            detection: DetectionConfig::default(),
+            serial: SerialConfig::default(),
+        }
+    }
+}
+
+impl DeviceConfig {
+    /// The channel's label, or `CH <n>` when none is set.
+    pub fn label(&self, channel: u8) -> String {
+        self.channel_settings
+            .iter()
+            .find(|c| c.channel == channel && !c.label.is_empty())
+            .map_or_else(|| format!("CH {}", channel), |c| c.label.clone())
+    }
+
+    /// The channel `name` refers to, by number or label.
+    pub fn find_channel(&self, name: &str) -> Option<u8> {
+        match name.parse::<u8>() {
+            Ok(channel) => (1..=self.channels).contains(&channel).then_some(channel),
+            Err(_) => (1..=self.channels).find(|&c| self.label(c).eq_ignore_ascii_case(name)),
+        }
+    }
+
+    /// Icon and label for display.
+    pub fn display_name(&self, channel: u8) -> String {
+        let icon = self
+            .channel_settings
+            .iter()
+            .find(|c| c.channel == channel)
+            .map_or("", |c| c.icon.as_str());
+        if icon.is_empty() {
+            self.label(channel)
+        } else {
+            format!("{} {}", icon, self.label(channel))
+        }
+    }
+
+    /// Settings for `channel`, or the defaults when it has none.
+    pub fn channel(&self, channel: u8) -> ChannelConfig {
+        self.channel_settings
+            .iter()
+            .find(|c| c.channel == channel)
+            .cloned()
+            .unwrap_or(ChannelConfig {
+                channel,
+                ..Default::default()
+            })
+    }
+
+    /// Settings for `channel`, created on first use.
+    pub fn channel_mut(&mut self, channel: u8) -> &mut ChannelConfig {
+        let index = match self.channel_settings.iter().position(|c| c.channel == channel) {
+            Some(index) => index,
+            None => {
+                self.channel_settings.push(ChannelConfig {
+                    channel,
+                    ..Default::default()
+                });
+                self.channel_settings.len() - 1
+            }
+        };
+        &mut self.channel_settings[index]
+    }
+
+    /// Copies every setting except the label from one channel to another.
+    pub fn copy_channel(&mut self, from: u8, to: u8) {
+        let source = self.channel_mut(from).clone();
+        let target = self.channel_mut(to);
+        *target = ChannelConfig {
+            channel: to,
+            label: std::mem::take(&mut target.label),
+            ..source
+        };
+    }
+
+    /// Writes the template's settings into every channel, or only into
+    /// channels without settings unless `overwrite` is set. Returns how many
+    /// channels changed.
+    pub fn apply_template(&mut self, template: &ChannelTemplate, overwrite: bool) -> usize {
+        let mut applied = 0;
+        for channel in 1..=self.channels {
+            let exists = self.channel_settings.iter().any(|c| c.channel == channel);
+            if overwrite || !exists {
+                *self.channel_mut(channel) = template.instantiate(channel);
+                applied += 1;
+            }
+        }
+        applied
+    }
+}
+
+/// Pins the board to a specific USB device. Every field that is set must
+/// match; a port path may be a symlink such as `/dev/serial/by-path/...`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceBinding {
+    pub serial_number: Option<String>,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub port: Option<String>,
+}
+
+impl DeviceBinding {
+    pub fn is_set(&self) -> bool {
+        self.serial_number.is_some()
+            || self.vid.is_some()
+            || self.pid.is_some()
+            || self.port.is_some()
+    }
+
+    pub fn matches(&self, port: &SerialPortInfo) -> bool {
+        let usb = match &port.port_type {
+            SerialPortType::UsbPort(usb) => Some(usb),
+            _ => None,
+        };
+        let serial_ok = self.serial_number.as_ref().is_none_or(|wanted| {
+            usb.and_then(|u| u.serial_number.as_ref()).is_some_and(|s| s == wanted)
+        });
+        let vid_ok = self.vid.is_none_or(|vid| usb.is_some_and(|u| u.vid == vid));
+        let pid_ok = self.pid.is_none_or(|pid| usb.is_some_and(|u| u.pid == pid));
+        let port_ok = self.port.as_ref().is_none_or(|wanted| same_port(wanted, &port.port_name));
+        serial_ok && vid_ok && pid_ok && port_ok
+    }
+
+    /// Short description for error messages, e.g. `serial A1B2, 1a86:7523`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(serial) = &self.serial_number {
+            parts.push(format!("serial {}", serial));
+        }
+        match (self.vid, self.pid) {
+            (Some(vid), Some(pid)) => parts.push(format!("{:04x}:{:04x}", vid, pid)),
+            (Some(vid), None) => parts.push(format!("VID {:04x}", vid)),
+            (None, Some(pid)) => parts.push(format!("PID {:04x}", pid)),
+            (None, None) => {}
+        }
+        if let Some(port) = &self.port {
+            parts.push(format!("port {}", port));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Compares port names, resolving symlinks so stable aliases match the
+/// name the OS reports.
+fn same_port(wanted: &str, name: &str) -> bool {
+    if wanted.eq_ignore_ascii_case(name) {
+        return true;
+    }
+    match (std::fs::canonicalize(wanted), std::fs::canonicalize(name)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// ============================================================================
+// SCENES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneStep {
+    #[serde(default = "default_channel")]
+    pub channel: u8,
+    pub on: bool,
+}
+
+fn default_channel() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scene {
+    pub name: String,
+    pub steps: Vec<SceneStep>,
+}
+
+// ============================================================================
+// EXIT POLICY
+// ============================================================================
+
+/// What happens to the relays when the GUI is closed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitPolicy {
+    /// Leave every channel as it is.
+    #[default]
+    Leave,
+    ForceOff,
+    ForceOn,
+}
+
+impl ExitPolicy {
+    pub const ALL: [ExitPolicy; 3] = [ExitPolicy::Leave, ExitPolicy::ForceOff, ExitPolicy::ForceOn];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            ExitPolicy::Leave => "Leave as is",
+            ExitPolicy::ForceOff => "Switch all off",
+            ExitPolicy::ForceOn => "Switch all on",
+        }
+    }
+}
+
+// ============================================================================
+// CONFIG
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub device: DeviceConfig,
+    pub scenes: Vec<Scene>,
+    pub schedules: Vec<Schedule>,
+    pub clock_check: ClockCheckConfig,
+    pub sequences: Vec<Sequence>,
+    pub templates: Vec<ChannelTemplate>,
+    pub hotkeys: Vec<HotkeyBinding>,
+    pub session: Vec<SessionTrigger>,
+    pub anomaly: AnomalyConfig,
+    pub report: ReportConfig,
+    pub display: DisplayConfig,
+    pub sound: SoundConfig,
+    pub theme: ThemeConfig,
+    pub exit: ExitPolicy,
+    pub api: ApiConfig,
+    pub webhooks: Vec<WebhookConfig>,
+    pub influx: InfluxConfig,
+    pub push: PushConfig,
+    pub email: EmailConfig,
+    pub telegram: TelegramConfig,
+    pub hue: HueConfig,
+    pub snmp: SnmpConfig,
+    pub bridge: BridgeConfig,
+    pub tcp: TcpConfig,
+    pub midi: MidiConfig,
+    pub gamepad: GamepadConfig,
+    pub voice: VoiceConfig,
+    pub watchdogs: Vec<WatchdogConfig>,
+    pub processes: Vec<ProcessTrigger>,
+    pub rules: Vec<Rule>,
+    pub window: WindowConfig,
+    pub lock: LockConfig,
+    pub monitor: MonitorConfig,
+    /// Daemons elsewhere whose channels the window shows too.
+    pub remotes: Vec<RemoteConfig>,
+    /// Enables raw frame passthrough (`/raw` and `send-raw`).
+    pub advanced: bool,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf, String> {
+        directories::ProjectDirs::from("", "", "usb-power-relay")
+            .map(|dirs| dirs.config_dir().join(CONFIG_FILE))
+            .ok_or_else(|| "No config directory available".to_string())
+    }
+
+    /// Loads the config file, falling back to defaults when it doesn't exist.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let mut config: Self = ron::from_str(&text)
+                    .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+                config.validate()?;
+
+                // Provision channels of a newly added board from its template
+                if let Some(name) = config.device.template.clone() {
+                    let template = config.template(&name).cloned().ok_or_else(|| {
+                        format!("device.template: unknown template '{}'", name)
+                    })?;
+                    config.device.apply_template(&template, false);
+                }
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.validate()?;
+
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        std::fs::write(&path, text)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Modification time of the config file, used to detect external edits.
+    pub fn modified() -> Option<SystemTime> {
+        let path = Self::path().ok()?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Rejects references to channels the board doesn't have.
+    pub fn validate(&self) -> Result<(), String> {
+        let channels = self.device.channels;
+        if channels == 0 {
+            return Err("device.channels must be at least 1".to_string());
+        }
+        self.device.serial.validate()?;
+        let check = |channel: u8, context: String| {
+            if (1..=channels).contains(&channel) {
+                Ok(())
+            } else {
+                Err(format!("{}: channel {} is outside 1..={}", context, channel, channels))
+            }
+        };
+
+        for settings in &self.device.channel_settings {
+            check(settings.channel, "Channel settings".to_string())?;
+        }
+        for group in &self.device.interlocks {
+            for &channel in &group.channels {
+                check(channel, format!("Interlock '{}'", group.name))?;
+            }
+        }
+        for scene in &self.scenes {
+            for step in &scene.steps {
+                check(step.channel, format!("Scene '{}'", scene.name))?;
+            }
+        }
+        for schedule in &self.schedules {
+            if let Some(channel) = schedule.action.channel() {
+                check(channel, format!("Schedule #{}", schedule.id))?;
+            }
+            if let Some(zone) = &schedule.timezone {
+                TimeZone::named(zone).map_err(|e| format!("Schedule #{}: {}", schedule.id, e))?;
+            }
+        }
+        for sequence in &self.sequences {
+            for step in &sequence.steps {
+                check(step.channel, format!("Sequence '{}'", sequence.name))?;
+            }
+        }
+        for binding in &self.hotkeys {
+            let context = format!("Hotkey '{}'", binding.shortcut);
+            Shortcut::parse(&binding.shortcut).map_err(|e| format!("{}: {}", context, e))?;
+            if let Some(channel) = binding.action.channel() {
+                check(channel, context)?;
+            }
+        }
+        for trigger in &self.session {
+            check(trigger.channel, "Session trigger".to_string())?;
+        }
+        for hook in &self.webhooks {
+            if !hook.url.starts_with("http://") {
+                return Err(format!("Webhook '{}': only http:// URLs are supported", hook.url));
+            }
+            for &channel in &hook.channels {
+                check(channel, format!("Webhook '{}'", hook.url))?;
+            }
+        }
+        for trigger in &self.processes {
+            let context = format!("Process trigger '{}'", trigger.describe());
+            if trigger.name.is_empty() && trigger.pid.is_none() {
+                return Err(format!("{}: needs a name or a pid", context));
+            }
+            check(trigger.channel, context)?;
+        }
+        for watchdog in &self.watchdogs {
+            watchdog.validate()?;
+            check(watchdog.channel, format!("Watchdog '{}'", watchdog.display_name()))?;
+        }
+        for rule in &self.rules {
+            rule.validate(&self.sequences)?;
+            for channel in rule.channels() {
+                check(channel, format!("Rule '{}'", rule.name))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn scene(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn template(&self, name: &str) -> Option<&ChannelTemplate> {
+        self.templates.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn sequence(&self, name: &str) -> Option<&Sequence> {
+        self.sequences.iter().find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+}
+
+// ============================================================================
+// LIVE CONFIG
+// ============================================================================
+
+/// A config copy for the GUI that follows edits made on disk, checking the
+/// file at most once a second.
+pub struct LiveConfig {
+    config: Config,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl LiveConfig {
+    pub fn load() -> Self {
+        Self {
+            modified: Config::modified(),
+            config: Config::load().unwrap_or_default(),
+            checked: Instant::now(),
+        }
+    }
+
+    pub fn get(&mut self) -> &Config {
+        if self.checked.elapsed() >= RELOAD_CHECK_INTERVAL {
+            self.checked = Instant::now();
+            let modified = Config::modified();
+            if modified != self.modified {
+                self.modified = modified;
+                if let Ok(config) = Config::load() {
+                    self.config = config;
+                }
+            }
+        }
+        &self.config
+    }
+
+    /// Applies `edit` to a fresh copy of the file and saves it.
+    pub fn update(&mut self, edit: impl FnOnce(&mut Config)) -> Result<(), String> {
+        let mut config = Config::load()?;
+        edit(&mut config);
+        config.save()?;
+
+        self.modified = Config::modified();
+        self.config = config;
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+//! Confirmation before switching off.
+//!
+//! Channels feeding something that must not lose power by accident, like a
+//! RAID array mid-write, can ask before their OFF button takes effect:
+//! either a yes/no dialog, or one that only goes ahead once the channel's
+//! label is typed in. Only presses in the window ask; schedules, rules,
+//! scripts and ALL OFF switch as configured.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{tr, tr_args};
+use crate::Command;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+/// What an OFF press on a channel needs before it is sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConfirmOff {
+    #[default]
+    Never,
+    Dialog,
+    /// The channel's label must be typed in.
+    TypeLabel,
+}
+
+impl ConfirmOff {
+    pub const ALL: [ConfirmOff; 3] = [ConfirmOff::Never, ConfirmOff::Dialog, ConfirmOff::TypeLabel];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            ConfirmOff::Never => "Never",
+            ConfirmOff::Dialog => "Ask",
+            ConfirmOff::TypeLabel => "Type label",
+        }
+    }
+}
+
+// ============================================================================
+// PROMPT
+// ============================================================================
+
+pub enum ConfirmOutcome {
+    Open,
+    Cancelled,
+    Confirmed,
+}
+
+/// Asks before a press switches a protected channel off.
+pub struct ConfirmPrompt {
+    pub channel: u8,
+    pub cmd: Command,
+    mode: ConfirmOff,
+    typed: String,
+}
+
+impl ConfirmPrompt {
+    pub fn new(channel: u8, cmd: Command, mode: ConfirmOff) -> Self {
+        Self {
+            channel,
+            cmd,
+            mode,
+            typed: String::new(),
+        }
+    }
+
+    /// `label` is what has to be typed for [`ConfirmOff::TypeLabel`].
+    pub fn show(&mut self, ctx: &egui::Context, name: &str, label: &str) -> ConfirmOutcome {
+        let mut outcome = ConfirmOutcome::Open;
+        let mut open = true;
+        egui::Window::new(tr("confirm-off-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(tr_args("confirm-off-question", &[("channel", &name)]));
+                ui.add_space(6.0);
+
+                let matches = match self.mode {
+                    ConfirmOff::TypeLabel => {
+                        ui.label(tr_args("confirm-off-type", &[("label", &label)]));
+                        let field = ui.text_edit_singleline(&mut self.typed);
+                        field.request_focus();
+                        self.typed.trim().eq_ignore_ascii_case(label.trim())
+                    }
+                    ConfirmOff::Never | ConfirmOff::Dialog => true,
+                };
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    let off = ui.add_enabled(matches, egui::Button::new(tr("confirm-off-button")));
+                    if off.clicked() {
+                        outcome = ConfirmOutcome::Confirmed;
+                    }
+                    if ui.button(tr("cancel")).clicked() {
+                        outcome = ConfirmOutcome::Cancelled;
+                    }
+                });
+            });
+        if !open {
+            outcome = ConfirmOutcome::Cancelled;
+        }
+        outcome
+    }
+}
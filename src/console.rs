@@ -0,0 +1,177 @@
+//! Raw hex console.
+//!
+//! A developer window for characterizing unknown boards: type hex bytes, send
+//! them on the open port and read a timestamped dump of the reply. It uses the
+//! same passthrough as `send-raw`, so it needs `advanced: true`, and channel
+//! states are requeried after every frame.
+
+use eframe::egui;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::emergency::EmergencyStop;
+use crate::i18n::tr;
+use crate::raw::{format_hex, parse_hex, RawResult};
+use crate::locale::format_timestamp;
+use crate::traffic::unix_millis;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Oldest entries are dropped beyond this.
+const MAX_ENTRIES: usize = 500;
+
+/// How long to wait for the device worker, which may be reconnecting.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a waiting console checks for the reply.
+const REPLY_POLL: Duration = Duration::from_millis(100);
+
+// ============================================================================
+// ENTRIES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Sent,
+    Received,
+    Note,
+}
+
+struct Entry {
+    time: String,
+    direction: Direction,
+    text: String,
+}
+
+// ============================================================================
+// WINDOW
+// ============================================================================
+
+pub struct HexConsole {
+    input: String,
+    entries: Vec<Entry>,
+    /// Reply to the frame in flight and when it was sent; one frame is
+    /// sent at a time.
+    pending: Option<(mpsc::Receiver<RawResult>, Instant)>,
+}
+
+impl HexConsole {
+    pub fn new() -> Self {
+        Self {
+            input: String::new(),
+            entries: Vec::new(),
+            pending: None,
+        }
+    }
+
+    fn push(&mut self, direction: Direction, text: String) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(Entry {
+            time: format_timestamp(unix_millis()),
+            direction,
+            text,
+        });
+    }
+
+    fn send(&mut self, stop: &EmergencyStop) {
+        let bytes = match parse_hex(&self.input) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.push(Direction::Note, e);
+                return;
+            }
+        };
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.push(Direction::Sent, format_hex(&bytes));
+        if stop.guard(Source::Window).send(Command::Raw(bytes, Some(reply_tx))) {
+            self.pending = Some((reply_rx, Instant::now()));
+        } else {
+            self.push(Direction::Note, "Not sent: an emergency stop is in progress".to_string());
+        }
+    }
+
+    fn poll_reply(&mut self) {
+        let Some((reply, sent)) = &self.pending else {
+            return;
+        };
+        let result = match reply.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) if sent.elapsed() < REPLY_TIMEOUT => return,
+            Err(mpsc::TryRecvError::Empty) => Err("No answer from the device worker".to_string()),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err("The device worker dropped the frame".to_string())
+            }
+        };
+        self.pending = None;
+        match result {
+            Ok(bytes) if bytes.is_empty() => self.push(Direction::Note, tr("console-no-reply")),
+            Ok(bytes) => self.push(Direction::Received, format_hex(&bytes)),
+            Err(e) => self.push(Direction::Note, e),
+        }
+    }
+
+    /// Draws the console; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context, stop: &EmergencyStop) -> bool {
+        self.poll_reply();
+        if self.pending.is_some() {
+            ctx.request_repaint_after(REPLY_POLL);
+        }
+
+        let mut open = true;
+        egui::Window::new(tr("console-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| self.contents(ui, stop));
+        open
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, stop: &EmergencyStop) {
+        ui.horizontal(|ui| {
+            let input = ui.add(
+                egui::TextEdit::singleline(&mut self.input)
+                    .hint_text("A0 01 05 A6")
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(220.0),
+            );
+            let entered = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let idle = self.pending.is_none();
+            let send = ui.add_enabled(idle, egui::Button::new(tr("console-send"))).clicked();
+            if (send || entered) && idle {
+                self.send(stop);
+                input.request_focus();
+            }
+            if ui.button(tr("console-clear")).clicked() {
+                self.entries.clear();
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(240.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for entry in &self.entries {
+                    let (arrow, color) = match entry.direction {
+                        Direction::Sent => (">", egui::Color32::from_rgb(96, 165, 250)),
+                        Direction::Received => ("<", egui::Color32::from_rgb(34, 197, 94)),
+                        Direction::Note => ("!", egui::Color32::from_rgb(156, 163, 175)),
+                    };
+                    ui.label(
+                        egui::RichText::new(format!("{} {} {}", entry.time, arrow, entry.text))
+                            .monospace()
+                            .color(color),
+                    );
+                }
+                if self.pending.is_some() {
+                    ui.spinner();
+                }
+            });
+    }
+}
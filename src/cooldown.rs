@@ -0,0 +1,105 @@
+//! Switching cooldowns.
+//!
+//! A channel with a `cooldown_secs` must stay off that long before it is
+//! switched on again, protecting compressors from short-cycling and power
+//! supplies from repeated inrush. An ON that comes too soon is either held
+//! in the queue until the cooldown is over (shown there, and cancellable) or
+//! rejected with a notification; inside a batch it waits or fails the batch.
+//! The time off counts from the last OFF the board reported, seeded from
+//! history when the worker starts.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::config::DeviceConfig;
+use crate::{Command, LastChange, RelayState};
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+/// What happens to an ON during the cooldown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CooldownMode {
+    /// Held back until the cooldown is over.
+    #[default]
+    Delay,
+    Reject,
+}
+
+impl CooldownMode {
+    pub const ALL: [CooldownMode; 2] = [CooldownMode::Delay, CooldownMode::Reject];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            CooldownMode::Delay => "Delay",
+            CooldownMode::Reject => "Reject",
+        }
+    }
+}
+
+// ============================================================================
+// COOLDOWNS
+// ============================================================================
+
+/// Per channel: how long it must stay off, what to do otherwise, and since
+/// when it has been off.
+pub struct Cooldowns {
+    channels: Vec<(Duration, CooldownMode, Option<Instant>)>,
+}
+
+impl Cooldowns {
+    pub fn new(device: &DeviceConfig) -> Self {
+        let channels = (1..=device.channels)
+            .map(|channel| {
+                let settings = device.channel(channel);
+                (Duration::from_secs(settings.cooldown_secs), settings.cooldown, None)
+            })
+            .collect();
+        Self { channels }
+    }
+
+    /// Starts the channels that were last seen switching off at the time
+    /// history recorded, rather than now.
+    pub fn seed(&mut self, last_changes: &[LastChange], now: u64) {
+        for (slot, change) in self.channels.iter_mut().zip(last_changes) {
+            if let Some((RelayState::Off, time)) = change {
+                let ago = Duration::from_secs(now.saturating_sub(*time));
+                slot.2 = Instant::now().checked_sub(ago);
+            }
+        }
+    }
+
+    /// Records a state the board reported for `channel`.
+    pub fn update(&mut self, channel: u8, state: RelayState) {
+        let Some(slot) = self.channels.get_mut((channel as usize).wrapping_sub(1)) else {
+            return;
+        };
+        match state {
+            RelayState::Off => {
+                slot.2.get_or_insert_with(Instant::now);
+            }
+            RelayState::On => slot.2 = None,
+            RelayState::Unknown | RelayState::Error => {}
+        }
+    }
+
+    /// How much longer `channel` must stay off, and what to do with an ON
+    /// before then; `None` if it may switch on now.
+    pub fn left(&self, channel: u8) -> Option<(Duration, CooldownMode)> {
+        let &(limit, mode, off_since) = self.channels.get((channel as usize).wrapping_sub(1))?;
+        let left = limit.checked_sub(off_since?.elapsed()).filter(|d| !d.is_zero())?;
+        Some((left, mode))
+    }
+
+    /// The channel `command` would switch on during its cooldown, if any.
+    /// A toggle counts when the channel is known to be off.
+    pub fn blocking(&self, command: &Command) -> Option<(u8, Duration, CooldownMode)> {
+        match *command {
+            Command::TurnOn(channel) | Command::Toggle(channel) => {
+                self.left(channel).map(|(left, mode)| (channel, left, mode))
+            }
+            _ => None,
+        }
+    }
+}
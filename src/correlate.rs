@@ -0,0 +1,102 @@
+//! Matching replies to the frames that caused them.
+//!
+//! Reply frames carry no sequence number, only the channel, so a reply that
+//! arrives after its command timed out would be taken for the answer to the
+//! next command on that channel. The correlator numbers every request and
+//! remembers the ones that went unanswered; the next frame for such a channel
+//! settles the oldest debt instead of being attributed to the current
+//! request. Debts expire, since many boards simply never answer.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long a late reply is still expected.
+const LATE_WINDOW: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// CORRELATOR
+// ============================================================================
+
+/// What a request asks for; boards often answer one kind but not the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    Status,
+    Switch,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Unanswered {
+    seq: u64,
+    channel: u8,
+    sent: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Correlator {
+    next_seq: u64,
+    unanswered: VecDeque<Unanswered>,
+    /// Whether the board has ever answered each kind of request; silence is
+    /// only suspicious from a board that normally replies.
+    answers_status: bool,
+    answers_switch: bool,
+}
+
+impl Correlator {
+    /// Numbers a request about to be sent.
+    pub fn begin(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq
+    }
+
+    /// Records that a request of `kind` was answered.
+    pub fn answered(&mut self, kind: RequestKind) {
+        match kind {
+            RequestKind::Status => self.answers_status = true,
+            RequestKind::Switch => self.answers_switch = true,
+        }
+    }
+
+    /// Records that request `seq` timed out, so its reply may still come.
+    pub fn unanswered(&mut self, seq: u64, channel: u8, kind: RequestKind) {
+        let replies = match kind {
+            RequestKind::Status => self.answers_status,
+            RequestKind::Switch => self.answers_switch,
+        };
+        if replies {
+            self.unanswered.push_back(Unanswered {
+                seq,
+                channel,
+                sent: Instant::now(),
+            });
+        }
+    }
+
+    /// Decides whether a frame for `channel` is a late reply. If so its debt
+    /// is settled and the sequence number it answered is returned.
+    pub fn stale(&mut self, channel: u8) -> Option<u64> {
+        self.unanswered.retain(|u| u.sent.elapsed() < LATE_WINDOW);
+        let index = self.unanswered.iter().position(|u| u.channel == channel)?;
+        self.unanswered.remove(index).map(|u| u.seq)
+    }
+}
+
+/// Splits received bytes into 4-byte frames, resynchronizing on the start
+/// byte when junk precedes a frame. Also returns how many bytes were used;
+/// the rest is the start of a frame still arriving.
+pub fn frames(bytes: &[u8], start: u8) -> (Vec<[u8; 4]>, usize) {
+    let mut frames = Vec::new();
+    let mut i = 0;
+    while i + 4 <= bytes.len() {
+        if bytes[i] == start {
+            frames.push([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+            i += 4;
+        } else {
+            i += 1;
+        }
+    }
+    (frames, i)
+}
@@ -0,0 +1,124 @@
+//! Daily switching limits.
+//!
+//! A channel with `max_switches_per_day` refuses to switch on once its relay
+//! has changed state that many times today (local time), catching a runaway
+//! rule or script before it wears out the contacts. Switching off stays
+//! possible, so the limit never keeps something powered. An explicit
+//! override, from the window or `channel allow <ch>`, lifts the limit for
+//! the rest of the day. Counts start from today's history, so a restart
+//! doesn't reset them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::batch::BatchStep;
+use crate::config::DeviceConfig;
+use crate::history::HistoryEvent;
+use crate::schedule::{Date, LocalTime};
+use crate::{Command, RelayState};
+
+// ============================================================================
+// COUNTER
+// ============================================================================
+
+struct Inner {
+    day: Date,
+    counts: BTreeMap<u8, u32>,
+    /// Channels allowed past their limit today.
+    allowed: BTreeSet<u8>,
+}
+
+/// Today's state changes per channel, shared by the worker and the GUI.
+#[derive(Clone)]
+pub struct SwitchCounter {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl SwitchCounter {
+    /// Starts from the changes `history` recorded today.
+    pub fn new(history: &[HistoryEvent]) -> Self {
+        let day = LocalTime::now().date();
+        let mut counts = BTreeMap::new();
+        let mut last = BTreeMap::new();
+        let definite = |e: &&HistoryEvent| matches!(e.state, RelayState::On | RelayState::Off);
+        for event in history.iter().filter(definite) {
+            let previous = last.insert(event.channel, event.state);
+            let changed = previous.is_some_and(|p| p != event.state);
+            if changed && LocalTime::from_unix(event.time).date() == day {
+                *counts.entry(event.channel).or_insert(0) += 1;
+            }
+        }
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                day,
+                counts,
+                allowed: BTreeSet::new(),
+            })),
+        }
+    }
+
+    /// The counts, started afresh when the day has changed.
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let today = LocalTime::now().date();
+        if inner.day != today {
+            inner.day = today;
+            inner.counts.clear();
+            inner.allowed.clear();
+        }
+        inner
+    }
+
+    /// Counts a change of `channel` between ON and OFF.
+    pub fn record(&self, channel: u8) {
+        *self.lock().counts.entry(channel).or_insert(0) += 1;
+    }
+
+    pub fn count(&self, channel: u8) -> u32 {
+        self.lock().counts.get(&channel).copied().unwrap_or(0)
+    }
+
+    /// Lets `channel` switch past its limit for the rest of today.
+    pub fn allow(&self, channel: u8) {
+        self.lock().allowed.insert(channel);
+    }
+
+    /// Whether `channel` has used up its daily limit and may not switch on.
+    pub fn exhausted(&self, device: &DeviceConfig, channel: u8) -> bool {
+        let limit = device.channel(channel).max_switches_per_day;
+        let inner = self.lock();
+        let count = inner.counts.get(&channel).copied().unwrap_or(0);
+        limit > 0 && count >= limit && !inner.allowed.contains(&channel)
+    }
+
+    /// The first channel `command` would switch on past its limit, with the
+    /// batch step doing it. `states` tells which way toggles go.
+    pub fn blocking(
+        &self,
+        device: &DeviceConfig,
+        command: &Command,
+        states: &[RelayState],
+    ) -> Option<(u8, usize)> {
+        let is_on = |channel: u8| {
+            states.get((channel as usize).wrapping_sub(1)) == Some(&RelayState::On)
+        };
+        let switched_on: Vec<u8> = match command {
+            Command::TurnOn(channel) => vec![*channel],
+            Command::Toggle(channel) if !is_on(*channel) => vec![*channel],
+            Command::Batch(batch, _) => batch
+                .steps
+                .iter()
+                .map(|step| match *step {
+                    BatchStep::On(channel) | BatchStep::Pulse(channel, _) => channel,
+                    BatchStep::Off(_) | BatchStep::Wait(_) => 0,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        switched_on
+            .into_iter()
+            .enumerate()
+            .find(|&(_, channel)| channel > 0 && self.exhausted(device, channel))
+            .map(|(step, channel)| (channel, step))
+    }
+}
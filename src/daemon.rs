@@ -0,0 +1,111 @@
+//! Headless daemon mode.
+//!
+//! `--daemon` runs the device worker, the scheduler, the HTTP API and the
+//! other background subsystems without a window, for servers and Raspberry
+//! Pi hosts. The CLI reaches it over the local control socket, and it also
+//! serves the HTTP API and picks up config edits automatically.
+//! Notifications go to stderr. SIGINT or SIGTERM applies the exit policy
+//! and stops it.
+//!
+//! Under systemd the main loop also pings the watchdog, so a wedged daemon
+//! is restarted. As a Windows service the same loop runs under the service
+//! control manager, which asks it to stop through [`request_stop`].
+
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::LiveConfig;
+use crate::ipc;
+use crate::systemd::{self, sd_notify};
+use crate::{apply_exit_policy, Core};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How often the main thread checks for a stop request.
+const STOP_CHECK: Duration = Duration::from_millis(200);
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// ============================================================================
+// ENTRY POINT
+// ============================================================================
+
+/// Runs until asked to stop and returns the process exit code.
+pub fn run() -> i32 {
+    if ipc::running() {
+        eprintln!("Another instance is already running");
+        return 1;
+    }
+    install_stop_handler();
+    serve()
+}
+
+/// Runs the subsystems until [`request_stop`], then applies the exit policy;
+/// the caller has made sure no other instance is running.
+pub fn serve() -> i32 {
+    // Nothing draws, so repaint requests go nowhere
+    let core = Core::start(egui::Context::default(), None);
+    eprintln!("Running headless; stop with Ctrl+C or SIGTERM");
+
+    let watchdog = systemd::watchdog_interval();
+    let mut last_ping = Instant::now();
+    while !STOP_REQUESTED.load(Ordering::SeqCst) {
+        std::thread::sleep(STOP_CHECK);
+        if watchdog.is_some_and(|interval| last_ping.elapsed() >= interval) {
+            sd_notify("WATCHDOG=1");
+            last_ping = Instant::now();
+        }
+    }
+
+    eprintln!("Stopping");
+    sd_notify("STOPPING=1");
+    apply_exit_policy(LiveConfig::load().get(), &core.state, &core.stop);
+    0
+}
+
+// ============================================================================
+// SIGNALS
+// ============================================================================
+
+/// Makes [`serve`] stop on its next check.
+pub fn request_stop() {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn install_stop_handler() {
+    extern "C" fn handle(_signal: libc::c_int) {
+        request_stop();
+    }
+
+    let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is
+    // async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(windows)]
+fn install_stop_handler() {
+    type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+    }
+
+    unsafe extern "system" fn handle(_event: u32) -> i32 {
+        request_stop();
+        1
+    }
+
+    // SAFETY: the handler only stores to an atomic
+    unsafe {
+        SetConsoleCtrlHandler(Some(handle), 1);
+    }
+}
@@ -0,0 +1,790 @@
+//! D-Bus service on Linux, and the desktop portal's global shortcuts.
+//!
+//! Owns `com.usbpowerswitch.Relay1` on the session bus so desktop
+//! automation tools and shell extensions can drive the relay:
+//!
+//! | Member                  | Signature | Effect                          |
+//! |-------------------------|-----------|---------------------------------|
+//! | `SetState(channel, on)` | `ub`      | Queue ON or OFF for a channel   |
+//! | `GetState(channel)`     | `u` → `s` | `on`, `off`, `unknown`, `error` |
+//! | `StateChanged` signal   | `us`      | A channel's state changed       |
+//!
+//! The object lives at `/com/usbpowerswitch/Relay1` and is introspectable.
+//! Only the small part of the wire protocol this needs is implemented:
+//! EXTERNAL authentication over a Unix socket and the handful of basic
+//! types above. Without a session bus the service doesn't start.
+//!
+//! The same connection code asks the `GlobalShortcuts` desktop portal for
+//! the hotkeys on Wayland, where clients can't grab keys themselves (see
+//! [`ShortcutPortal`]).
+
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use crate::audit::Source;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const BUS_NAME: &str = "com.usbpowerswitch.Relay1";
+const OBJECT_PATH: &str = "/com/usbpowerswitch/Relay1";
+const INTERFACE: &str = "com.usbpowerswitch.Relay1";
+const INTROSPECTABLE: &str = "org.freedesktop.DBus.Introspectable";
+
+/// How often state changes are checked for while the bus is quiet.
+const SIGNAL_CHECK: Duration = Duration::from_millis(200);
+
+/// Largest message accepted; the bus daemon's own limit is far higher, but
+/// nothing this service handles comes close.
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+const INTROSPECTION: &str = r#"<node>
+  <interface name="com.usbpowerswitch.Relay1">
+    <method name="SetState">
+      <arg name="channel" type="u" direction="in"/>
+      <arg name="on" type="b" direction="in"/>
+    </method>
+    <method name="GetState">
+      <arg name="channel" type="u" direction="in"/>
+      <arg name="state" type="s" direction="out"/>
+    </method>
+    <signal name="StateChanged">
+      <arg name="channel" type="u"/>
+      <arg name="state" type="s"/>
+    </signal>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="xml" type="s" direction="out"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+// Message types and header fields from the D-Bus specification
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+const NO_REPLY_EXPECTED: u8 = 0x1;
+
+const FIELD_PATH: u8 = 1;
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_ERROR_NAME: u8 = 4;
+const FIELD_REPLY_SERIAL: u8 = 5;
+const FIELD_DESTINATION: u8 = 6;
+const FIELD_SENDER: u8 = 7;
+const FIELD_SIGNATURE: u8 = 8;
+
+/// `RequestName` flag and the replies that mean the name is ours.
+const DO_NOT_QUEUE: u32 = 0x4;
+const PRIMARY_OWNER: u32 = 1;
+const ALREADY_OWNER: u32 = 4;
+
+// ============================================================================
+// MARSHALLING
+// ============================================================================
+
+/// Little-endian writer; offsets are relative to the start of the buffer,
+/// which is always 8-aligned within the message.
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self, n: usize) {
+        while !self.buf.len().is_multiple_of(n) {
+            self.buf.push(0);
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// A string or object path.
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn signature(&mut self, value: &str) {
+        self.u8(value.len() as u8);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    /// An array of elements aligned to `align`, written by `items`.
+    fn array(&mut self, align: usize, items: impl FnOnce(&mut Self)) {
+        self.u32(0);
+        let len_at = self.buf.len() - 4;
+        self.align(align);
+        let start = self.buf.len();
+        items(self);
+        let len = (self.buf.len() - start) as u32;
+        self.buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    /// An `a{sv}` dictionary of string values.
+    fn string_dict(&mut self, entries: &[(&str, &str)]) {
+        self.array(8, |w| {
+            for (key, value) in entries {
+                w.align(8);
+                w.string(key);
+                w.signature("s");
+                w.string(value);
+            }
+        });
+    }
+}
+
+enum Field<'a> {
+    Str(u8, &'a str),
+    Path(&'a str),
+    Signature(&'a str),
+    Serial(u32),
+}
+
+/// A complete message with the given header fields and body.
+fn message(
+    kind: u8,
+    flags: u8,
+    serial: u32,
+    fields: &[Field],
+    signature: &str,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.u8(b'l');
+    w.u8(kind);
+    w.u8(flags);
+    w.u8(1);
+    w.u32(body.len() as u32);
+    w.u32(serial);
+
+    let mut array = Writer { buf: vec![0; 16] };
+    let signature_field = Field::Signature(signature);
+    let typed = (!signature.is_empty()).then_some(&signature_field);
+    for field in fields.iter().chain(typed) {
+        array.align(8);
+        match *field {
+            Field::Str(code, value) => {
+                array.u8(code);
+                array.signature("s");
+                array.string(value);
+            }
+            Field::Path(value) => {
+                array.u8(FIELD_PATH);
+                array.signature("o");
+                array.string(value);
+            }
+            Field::Signature(value) => {
+                array.u8(FIELD_SIGNATURE);
+                array.signature("g");
+                array.signature(value);
+            }
+            Field::Serial(value) => {
+                array.u8(FIELD_REPLY_SERIAL);
+                array.signature("u");
+                array.u32(value);
+            }
+        }
+    }
+    // The array is laid out as if it followed the 16-byte fixed header
+    let array = &array.buf[16..];
+    w.u32(array.len() as u32);
+    w.buf.extend_from_slice(array);
+    w.align(8);
+    w.buf.extend_from_slice(body);
+    w.buf
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn align(&mut self, n: usize) {
+        self.pos = self.pos.div_ceil(n) * n;
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let value = *self.buf.get(self.pos)?;
+        self.pos += 1;
+        Some(value)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.align(4);
+        let bytes: [u8; 4] = self.buf.get(self.pos..self.pos + 4)?.try_into().ok()?;
+        self.pos += 4;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a str> {
+        let text = std::str::from_utf8(self.buf.get(self.pos..self.pos + len)?).ok()?;
+        // Skip the terminating NUL too
+        self.pos += len + 1;
+        Some(text)
+    }
+
+    fn string(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn signature(&mut self) -> Option<&'a str> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    /// The string value under `wanted` in an `a{sv}` dictionary; gives up
+    /// at the first value of another type.
+    fn dict_string(&mut self, wanted: &str) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        self.align(8);
+        let end = self.pos + len;
+        while self.pos < end {
+            self.align(8);
+            let key = self.string()?;
+            let value = match self.signature()? {
+                "s" | "o" => self.string()?,
+                _ => return None,
+            };
+            if key == wanted {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+/// The parts of a received message this service looks at.
+#[derive(Default)]
+struct Message {
+    kind: u8,
+    flags: u8,
+    serial: u32,
+    big_endian: bool,
+    path: String,
+    interface: String,
+    member: String,
+    sender: String,
+    signature: String,
+    reply_serial: Option<u32>,
+    body: Vec<u8>,
+}
+
+impl Message {
+    fn body(&self) -> Reader<'_> {
+        Reader {
+            buf: &self.body,
+            pos: 0,
+            big_endian: self.big_endian,
+        }
+    }
+}
+
+/// Splits a complete message off the front of `buf`, or returns `None`
+/// until enough bytes have arrived.
+fn parse_message(buf: &[u8]) -> Result<Option<(Message, usize)>, String> {
+    if buf.len() < 16 {
+        return Ok(None);
+    }
+    let big_endian = match buf[0] {
+        b'l' => false,
+        b'B' => true,
+        _ => return Err("Malformed message from the bus".to_string()),
+    };
+    let mut fixed = Reader { buf, pos: 4, big_endian };
+    let body_len = fixed.u32().unwrap_or(0) as usize;
+    let serial = fixed.u32().unwrap_or(0);
+    let fields_len = fixed.u32().unwrap_or(0) as usize;
+    let body_start = (16 + fields_len).div_ceil(8) * 8;
+    let total = body_start + body_len;
+    if total > MAX_MESSAGE_BYTES {
+        return Err("Oversized message from the bus".to_string());
+    }
+    if buf.len() < total {
+        return Ok(None);
+    }
+
+    let mut message = Message {
+        kind: buf[1],
+        flags: buf[2],
+        serial,
+        big_endian,
+        body: buf[body_start..total].to_vec(),
+        ..Message::default()
+    };
+    let mut r = Reader { buf: &buf[..16 + fields_len], pos: 16, big_endian };
+    while r.pos < 16 + fields_len {
+        r.align(8);
+        let (Some(code), Some(kind)) = (r.u8(), r.signature()) else {
+            break;
+        };
+        let malformed = || "Malformed header field from the bus".to_string();
+        match kind {
+            "s" | "o" => {
+                let value = r.string().ok_or_else(malformed)?.to_string();
+                match code {
+                    FIELD_PATH => message.path = value,
+                    FIELD_INTERFACE => message.interface = value,
+                    FIELD_MEMBER => message.member = value,
+                    FIELD_SENDER => message.sender = value,
+                    _ => {}
+                }
+            }
+            "g" => {
+                let value = r.signature().ok_or_else(malformed)?;
+                if code == FIELD_SIGNATURE {
+                    message.signature = value.to_string();
+                }
+            }
+            "u" => {
+                let value = r.u32().ok_or_else(malformed)?;
+                if code == FIELD_REPLY_SERIAL {
+                    message.reply_serial = Some(value);
+                }
+            }
+            _ => return Err(malformed()),
+        }
+    }
+    Ok(Some((message, total)))
+}
+
+// ============================================================================
+// CONNECTION
+// ============================================================================
+
+struct Connection {
+    stream: UnixStream,
+    inbox: Vec<u8>,
+    serial: u32,
+}
+
+impl Connection {
+    /// Connects to the session bus and authenticates as the current user.
+    fn session() -> Result<Option<Self>, String> {
+        let Ok(address) = std::env::var("DBUS_SESSION_BUS_ADDRESS") else {
+            return Ok(None);
+        };
+        let mut stream = connect(&address)?;
+
+        // SAFETY: getuid has no preconditions
+        let uid = unsafe { libc::getuid() }.to_string();
+        let hex: String = uid.bytes().map(|b| format!("{:02x}", b)).collect();
+        let auth = format!("\0AUTH EXTERNAL {}\r\n", hex);
+        stream.write_all(auth.as_bytes()).map_err(|e| format!("Bus write failed: {}", e))?;
+        let mut line = Vec::new();
+        let mut byte = [0u8];
+        while !line.ends_with(b"\r\n") && line.len() < 512 {
+            match stream.read(&mut byte) {
+                Ok(1) => line.push(byte[0]),
+                _ => return Err("The bus closed the connection during authentication".to_string()),
+            }
+        }
+        if !line.starts_with(b"OK ") {
+            return Err("The bus rejected authentication".to_string());
+        }
+        stream.write_all(b"BEGIN\r\n").map_err(|e| format!("Bus write failed: {}", e))?;
+
+        Ok(Some(Self {
+            stream,
+            inbox: Vec::new(),
+            serial: 0,
+        }))
+    }
+
+    fn send(&mut self, build: impl FnOnce(u32) -> Vec<u8>) -> Result<u32, String> {
+        self.serial += 1;
+        let bytes = build(self.serial);
+        self.stream.write_all(&bytes).map_err(|e| format!("Bus write failed: {}", e))?;
+        Ok(self.serial)
+    }
+
+    /// Calls a method on the bus daemon itself.
+    fn call_bus(&mut self, member: &str, signature: &str, body: &[u8]) -> Result<u32, String> {
+        let fields = [
+            Field::Path("/org/freedesktop/DBus"),
+            Field::Str(FIELD_INTERFACE, "org.freedesktop.DBus"),
+            Field::Str(FIELD_MEMBER, member),
+            Field::Str(FIELD_DESTINATION, "org.freedesktop.DBus"),
+        ];
+        self.send(|serial| message(METHOD_CALL, 0, serial, &fields, signature, body))
+    }
+
+    /// The next message, or `None` if nothing arrived within the read
+    /// timeout.
+    fn next(&mut self) -> Result<Option<Message>, String> {
+        loop {
+            if let Some((message, used)) = parse_message(&self.inbox)? {
+                self.inbox.drain(..used);
+                return Ok(Some(message));
+            }
+            let mut chunk = [0u8; 4096];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("The bus closed the connection".to_string()),
+                Ok(n) => self.inbox.extend_from_slice(&chunk[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(format!("Bus read failed: {}", e)),
+            }
+        }
+    }
+}
+
+/// Connects to the first usable `unix:` address in a bus address list.
+fn connect(addresses: &str) -> Result<UnixStream, String> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    for address in addresses.split(';') {
+        let Some(params) = address.strip_prefix("unix:") else {
+            continue;
+        };
+        for param in params.split(',') {
+            let stream = if let Some(path) = param.strip_prefix("path=") {
+                UnixStream::connect(unescape(path))
+            } else if let Some(name) = param.strip_prefix("abstract=") {
+                SocketAddr::from_abstract_name(unescape(name).as_bytes())
+                    .and_then(|addr| UnixStream::connect_addr(&addr))
+            } else {
+                continue;
+            };
+            if let Ok(stream) = stream {
+                return Ok(stream);
+            }
+        }
+    }
+    Err(format!("No usable session bus address in '{}'", addresses))
+}
+
+/// Undoes the `%xx` escaping of bus address values.
+fn unescape(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (b, escaped) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// ============================================================================
+// SERVICE
+// ============================================================================
+
+/// Serves the interface until the bus connection fails. Exits cleanly
+/// without a session bus.
+pub fn run_dbus(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    let Some(mut bus) = Connection::session()? else {
+        return Ok(());
+    };
+    bus.call_bus("Hello", "", &[])?;
+    let mut body = Writer::default();
+    body.string(BUS_NAME);
+    body.u32(DO_NOT_QUEUE);
+    let request = bus.call_bus("RequestName", "su", &body.buf)?;
+    bus.stream
+        .set_read_timeout(Some(SIGNAL_CHECK))
+        .map_err(|e| format!("Bus setup failed: {}", e))?;
+
+    let mut states = state.snapshot.subscribe();
+    let mut shown = states.borrow_and_update().relay_states.clone();
+
+    loop {
+        if let Some(message) = bus.next()? {
+            match message.kind {
+                METHOD_RETURN if message.reply_serial == Some(request) => {
+                    let owner = message.body().u32();
+                    if !matches!(owner, Some(PRIMARY_OWNER | ALREADY_OWNER)) {
+                        return Err(format!("{} is owned by another process", BUS_NAME));
+                    }
+                    ctx.mark_running();
+                }
+                ERROR if message.reply_serial == Some(request) => {
+                    return Err(format!("Could not claim {}", BUS_NAME));
+                }
+                METHOD_CALL => handle_call(&mut bus, state, &message)?,
+                _ => {}
+            }
+        }
+
+        if states.has_changed().unwrap_or(false) {
+            let current = states.borrow_and_update().relay_states.clone();
+            for (i, relay_state) in current.iter().enumerate() {
+                if shown.get(i) != Some(relay_state) {
+                    emit_state_changed(&mut bus, i as u32 + 1, *relay_state)?;
+                }
+            }
+            shown = current;
+        }
+    }
+}
+
+fn handle_call(bus: &mut Connection, state: &AppState, call: &Message) -> Result<(), String> {
+    let reply = match (call.interface.as_str(), call.member.as_str()) {
+        _ if call.path != OBJECT_PATH => Err((
+            "org.freedesktop.DBus.Error.UnknownObject",
+            format!("No object at {}", call.path),
+        )),
+        (INTROSPECTABLE, "Introspect") => {
+            let mut body = Writer::default();
+            body.string(INTROSPECTION);
+            Ok(("s", body.buf))
+        }
+        (INTERFACE | "", "SetState") if call.signature == "ub" => {
+            let mut args = call.body();
+            let (channel, on) = (args.u32().unwrap_or(0), args.u32().unwrap_or(0) != 0);
+            match checked_channel(state, channel) {
+                Ok(channel) => {
+                    let cmd = if on { Command::TurnOn(channel) } else { Command::TurnOff(channel) };
+                    state.send_command(cmd, Source::Dbus);
+                    Ok(("", Vec::new()))
+                }
+                Err(e) => Err(e),
+            }
+        }
+        (INTERFACE | "", "GetState") if call.signature == "u" => {
+            match checked_channel(state, call.body().u32().unwrap_or(0)) {
+                Ok(channel) => {
+                    let relay_state = state.snapshot.borrow().relay_states[channel as usize - 1];
+                    let mut body = Writer::default();
+                    body.string(relay_state.name());
+                    Ok(("s", body.buf))
+                }
+                Err(e) => Err(e),
+            }
+        }
+        _ => Err((
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            format!("No method {}.{}({})", call.interface, call.member, call.signature),
+        )),
+    };
+
+    if call.flags & NO_REPLY_EXPECTED != 0 {
+        return Ok(());
+    }
+    let destination = Field::Str(FIELD_DESTINATION, &call.sender);
+    let reply_serial = Field::Serial(call.serial);
+    match reply {
+        Ok((signature, body)) => bus.send(|serial| {
+            let fields = [reply_serial, destination];
+            message(METHOD_RETURN, NO_REPLY_EXPECTED, serial, &fields, signature, &body)
+        }),
+        Err((name, text)) => bus.send(|serial| {
+            let mut body = Writer::default();
+            body.string(&text);
+            let fields = [reply_serial, destination, Field::Str(FIELD_ERROR_NAME, name)];
+            message(ERROR, NO_REPLY_EXPECTED, serial, &fields, "s", &body.buf)
+        }),
+    }
+    .map(|_| ())
+}
+
+fn checked_channel(state: &AppState, channel: u32) -> Result<u8, (&'static str, String)> {
+    let channels = state.snapshot.borrow().relay_states.len() as u32;
+    if (1..=channels).contains(&channel) {
+        Ok(channel as u8)
+    } else {
+        Err((
+            "org.freedesktop.DBus.Error.InvalidArgs",
+            format!("Channel {} is outside 1..={}", channel, channels),
+        ))
+    }
+}
+
+fn emit_state_changed(
+    bus: &mut Connection,
+    channel: u32,
+    relay_state: RelayState,
+) -> Result<(), String> {
+    let mut body = Writer::default();
+    body.u32(channel);
+    body.string(relay_state.name());
+    let fields = [
+        Field::Path(OBJECT_PATH),
+        Field::Str(FIELD_INTERFACE, INTERFACE),
+        Field::Str(FIELD_MEMBER, "StateChanged"),
+    ];
+    bus.send(|serial| message(SIGNAL, NO_REPLY_EXPECTED, serial, &fields, "us", &body.buf))
+        .map(|_| ())
+}
+
+// ============================================================================
+// GLOBAL SHORTCUTS PORTAL
+// ============================================================================
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SHORTCUTS_INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+/// How long the portal may take to answer before the dialog, if any.
+const PORTAL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Request tokens; each binding has a connection of its own, so they
+/// don't need to differ between bindings.
+const SESSION_TOKEN: &str = "usb_power_relay_session";
+const BIND_TOKEN: &str = "usb_power_relay_bind";
+
+/// Hotkeys bound through the desktop portal. The desktop may show a dialog
+/// to confirm or change them, and keeps them for as long as the portal
+/// holds, this connection.
+pub struct ShortcutPortal {
+    bus: Connection,
+    session: String,
+    /// Where the answer to the binding arrives, until it has.
+    binding: Option<String>,
+}
+
+impl ShortcutPortal {
+    /// Asks for `shortcuts`, each a description and the preferred trigger
+    /// in the portal's `CTRL+ALT+F9` notation. Fails without a session bus
+    /// or a desktop offering the portal.
+    pub fn bind(shortcuts: &[(String, String)]) -> Result<Self, String> {
+        let mut bus = Connection::session()?.ok_or("No session bus")?;
+        bus.stream
+            .set_read_timeout(Some(SIGNAL_CHECK))
+            .map_err(|e| format!("Bus setup failed: {}", e))?;
+        let hello = bus.call_bus("Hello", "", &[])?;
+        let welcome = bus.wait(|m| m.kind == METHOD_RETURN && m.reply_serial == Some(hello))?;
+        let unique_name = welcome.body().string().unwrap_or_default().to_string();
+        let signals = [(REQUEST_INTERFACE, "Response"), (SHORTCUTS_INTERFACE, "Activated")];
+        for (interface, member) in signals {
+            let mut rule = Writer::default();
+            rule.string(&format!("type='signal',interface='{}',member='{}'", interface, member));
+            bus.call_bus("AddMatch", "s", &rule.buf)?;
+        }
+        // Answers arrive on a path made of the caller's name and the token
+        let sender = unique_name.trim_start_matches(':').replace('.', "_");
+        let request_path = |token: &str| format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+
+        let mut options = Writer::default();
+        options.string_dict(&[("handle_token", SESSION_TOKEN), ("session_handle_token", "relay")]);
+        let create = bus.call_portal("CreateSession", "a{sv}", &options.buf)?;
+        let answer = request_path(SESSION_TOKEN);
+        let response = bus.wait(|m| {
+            (m.kind == ERROR && m.reply_serial == Some(create))
+                || (m.kind == SIGNAL && m.member == "Response" && m.path == answer)
+        })?;
+        if response.kind == ERROR {
+            return Err("The desktop has no global shortcuts portal".to_string());
+        }
+        let mut results = response.body();
+        let session = match results.u32() {
+            Some(0) => results.dict_string("session_handle").map(str::to_string),
+            _ => None,
+        };
+        let session = session.ok_or("The desktop portal refused a shortcuts session")?;
+
+        let mut body = Writer::default();
+        body.string(&session);
+        body.array(8, |w| {
+            for (i, (description, trigger)) in shortcuts.iter().enumerate() {
+                w.align(8);
+                w.string(&format!("hotkey-{}", i));
+                w.string_dict(&[("description", description), ("preferred_trigger", trigger)]);
+            }
+        });
+        body.string("");
+        body.string_dict(&[("handle_token", BIND_TOKEN)]);
+        bus.call_portal("BindShortcuts", "oa(sa{sv})sa{sv}", &body.buf)?;
+        bus.stream
+            .set_nonblocking(true)
+            .map_err(|e| format!("Bus setup failed: {}", e))?;
+
+        Ok(Self {
+            bus,
+            session,
+            binding: Some(request_path(BIND_TOKEN)),
+        })
+    }
+
+    /// Index of the next activated shortcut, if any.
+    pub fn poll(&mut self) -> Result<Option<usize>, String> {
+        while let Some(message) = self.bus.next()? {
+            if message.kind != SIGNAL {
+                continue;
+            }
+            let mut args = message.body();
+            match (message.interface.as_str(), message.member.as_str()) {
+                (REQUEST_INTERFACE, "Response") if self.binding.as_ref() == Some(&message.path) => {
+                    self.binding = None;
+                    // Failing would ask again on every restart; the
+                    // shortcuts stay unbound until the config changes
+                    if args.u32() != Some(0) {
+                        eprintln!("The desktop declined the global shortcuts");
+                    }
+                }
+                (SHORTCUTS_INTERFACE, "Activated") => {
+                    if args.string() != Some(self.session.as_str()) {
+                        continue;
+                    }
+                    let id = args.string().and_then(|id| id.strip_prefix("hotkey-"));
+                    if let Some(index) = id.and_then(|index| index.parse().ok()) {
+                        return Ok(Some(index));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Connection {
+    /// Calls a method of the global shortcuts portal.
+    fn call_portal(&mut self, member: &str, signature: &str, body: &[u8]) -> Result<u32, String> {
+        let fields = [
+            Field::Path(PORTAL_PATH),
+            Field::Str(FIELD_INTERFACE, SHORTCUTS_INTERFACE),
+            Field::Str(FIELD_MEMBER, member),
+            Field::Str(FIELD_DESTINATION, PORTAL_NAME),
+        ];
+        self.send(|serial| message(METHOD_CALL, 0, serial, &fields, signature, body))
+    }
+
+    /// The first message `wanted` accepts, skipping the others.
+    fn wait(&mut self, wanted: impl Fn(&Message) -> bool) -> Result<Message, String> {
+        let deadline = std::time::Instant::now() + PORTAL_TIMEOUT;
+        while std::time::Instant::now() < deadline {
+            if let Some(message) = self.next()? {
+                if wanted(&message) {
+                    return Ok(message);
+                }
+            }
+        }
+        Err("The desktop portal did not answer".to_string())
+    }
+}
@@ -0,0 +1,94 @@
+//! Button debounce.
+//!
+//! A press on a channel less than its `debounce_ms` after the previous one is
+//! either ignored or held back until the window has passed, so rapid
+//! double-clicks can't chatter the relay coil. Of several held presses the
+//! latest wins.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::config::ChannelConfig;
+use crate::Command;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+/// What happens to a press inside the debounce window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DebounceMode {
+    #[default]
+    Ignore,
+    /// Sent once the window has passed.
+    Queue,
+}
+
+impl DebounceMode {
+    pub const ALL: [DebounceMode; 2] = [DebounceMode::Ignore, DebounceMode::Queue];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            DebounceMode::Ignore => "Ignore",
+            DebounceMode::Queue => "Delay",
+        }
+    }
+}
+
+// ============================================================================
+// DEBOUNCER
+// ============================================================================
+
+#[derive(Default)]
+pub struct Debouncer {
+    last: BTreeMap<u8, Instant>,
+    /// Held presses and when they may be sent.
+    held: BTreeMap<u8, (Command, Instant)>,
+}
+
+impl Debouncer {
+    /// Returns the command when it may be sent now.
+    pub fn press(&mut self, settings: &ChannelConfig, command: Command) -> Option<Command> {
+        let now = Instant::now();
+        let window = Duration::from_millis(settings.debounce_ms);
+        let until = self.last.get(&settings.channel).map(|&last| last + window);
+        match until {
+            Some(until) if until > now => {
+                if settings.debounce == DebounceMode::Queue {
+                    self.held.insert(settings.channel, (command, until));
+                }
+                None
+            }
+            _ => {
+                self.last.insert(settings.channel, now);
+                self.held.remove(&settings.channel);
+                Some(command)
+            }
+        }
+    }
+
+    /// Held presses whose window has passed.
+    pub fn due(&mut self) -> Vec<Command> {
+        let now = Instant::now();
+        let ready: Vec<u8> = self
+            .held
+            .iter()
+            .filter(|(_, (_, at))| *at <= now)
+            .map(|(&channel, _)| channel)
+            .collect();
+        ready
+            .into_iter()
+            .filter_map(|channel| {
+                self.last.insert(channel, now);
+                self.held.remove(&channel).map(|(command, _)| command)
+            })
+            .collect()
+    }
+
+    /// How long until the next held press is due.
+    pub fn next_due(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.held.values().map(|(_, at)| at.saturating_duration_since(now)).min()
+    }
+}
@@ -0,0 +1,116 @@
+//! Desired relay state.
+//!
+//! Remembers the last commanded state of every channel in `desired.tsv` in
+//! the platform data directory (`<channel>\t<ON|OFF>` per line), so it can be
+//! reapplied when the app starts or the device reconnects after a USB glitch.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::{Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const DESIRED_FILE: &str = "desired.tsv";
+
+// ============================================================================
+// DESIRED STATE
+// ============================================================================
+
+pub struct DesiredState {
+    path: PathBuf,
+    channels: BTreeMap<u8, RelayState>,
+}
+
+impl DesiredState {
+    pub fn path() -> Result<PathBuf, String> {
+        directories::ProjectDirs::from("", "", "usb-power-relay")
+            .map(|dirs| dirs.data_dir().join(DESIRED_FILE))
+            .ok_or_else(|| "No data directory available".to_string())
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path()?;
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+
+        let channels = text
+            .lines()
+            .filter_map(|line| {
+                let (channel, state) = line.split_once('\t')?;
+                let state = match state {
+                    "ON" => RelayState::On,
+                    "OFF" => RelayState::Off,
+                    _ => return None,
+                };
+                Some((channel.parse().ok()?, state))
+            })
+            .collect();
+        Ok(Self { path, channels })
+    }
+
+    /// The last commanded state of `channel`, if it was ever switched.
+    pub fn get(&self, channel: u8) -> Option<RelayState> {
+        self.channels.get(&channel).copied()
+    }
+
+    /// Records the outcome of a command; only definite ON/OFF states count.
+    pub fn update(&mut self, changes: &[(u8, RelayState)]) -> Result<(), String> {
+        let mut changed = false;
+        for &(channel, state) in changes {
+            if matches!(state, RelayState::On | RelayState::Off)
+                && self.channels.insert(channel, state) != Some(state)
+            {
+                changed = true;
+            }
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text: String = self
+            .channels
+            .iter()
+            .map(|(channel, state)| format!("{}\t{}\n", channel, state.text()))
+            .collect();
+
+        // Write then rename so a crash never leaves a half-written file
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, text)
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, &self.path)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    /// Commands that bring the reported `current` states back to the desired
+    /// ones. Channels are switched off before any are switched on, so
+    /// interlocked loads break before they make.
+    pub fn restore_commands(&self, current: &[(u8, RelayState)]) -> Vec<Command> {
+        let differs = |channel: u8, desired: RelayState| {
+            current.iter().any(|&(c, state)| c == channel && state != desired)
+        };
+        let offs = self
+            .channels
+            .iter()
+            .filter(|&(&channel, &state)| state == RelayState::Off && differs(channel, state))
+            .map(|(&channel, _)| Command::TurnOff(channel));
+        let ons = self
+            .channels
+            .iter()
+            .filter(|&(&channel, &state)| state == RelayState::On && differs(channel, state))
+            .map(|(&channel, _)| Command::TurnOn(channel));
+        offs.chain(ons).collect()
+    }
+}
@@ -0,0 +1,200 @@
+//! Email alerts.
+//!
+//! Critical events, the board going away, a watchdog power-cycling its load
+//! and a maximum ON time switching a channel off, can be mailed through an
+//! SMTP server, as can the usage report. Subjects and bodies come from per-event templates with
+//! `{placeholders}`. Like push notifications, mail goes out through `curl`,
+//! which handles `smtps://` and STARTTLS; each on its own thread.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::Config;
+use crate::push::{curl, curl_option};
+use crate::schedule::LocalTime;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmailEvent {
+    /// The board stopped answering while in use.
+    DeviceLost,
+    /// A watchdog power-cycled its channel.
+    WatchdogCycle,
+    /// A maximum ON time switched a channel off.
+    SafetyLimit,
+    /// The daily or weekly usage report.
+    Report,
+}
+
+impl EmailEvent {
+    pub const ALL: [EmailEvent; 4] = [
+        EmailEvent::DeviceLost,
+        EmailEvent::WatchdogCycle,
+        EmailEvent::SafetyLimit,
+        EmailEvent::Report,
+    ];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            EmailEvent::DeviceLost => "Device lost",
+            EmailEvent::WatchdogCycle => "Watchdog power cycle",
+            EmailEvent::SafetyLimit => "Maximum ON time",
+            EmailEvent::Report => "Usage report",
+        }
+    }
+
+    fn default_template(&self) -> (&'static str, &'static str) {
+        match self {
+            EmailEvent::DeviceLost => (
+                "Relay board lost",
+                "The relay board stopped answering at {time}:\n\n{message}",
+            ),
+            EmailEvent::WatchdogCycle => (
+                "Watchdog '{watchdog}' power-cycled {label}",
+                "At {time}, watchdog '{watchdog}' power-cycled channel {channel} ({label}):\n\n\
+                 {message}",
+            ),
+            EmailEvent::SafetyLimit => (
+                "{label} was switched off after its maximum ON time",
+                "At {time}: {message}",
+            ),
+            EmailEvent::Report => ("{title}", "{report}"),
+        }
+    }
+}
+
+/// Replaces the built-in subject and body of one event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailTemplate {
+    pub event: EmailEvent,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    pub enabled: bool,
+    /// `smtps://host:465`, or `smtp://host:587` upgraded with STARTTLS. A
+    /// path, as in `smtp://host:587/example.org`, is the name to greet with.
+    pub server: String,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Refuse to send over `smtp://` without STARTTLS.
+    pub require_tls: bool,
+    /// Events to mail; all of them by default.
+    pub events: Vec<EmailEvent>,
+    pub templates: Vec<EmailTemplate>,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server: String::new(),
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            to: Vec::new(),
+            require_tls: true,
+            events: EmailEvent::ALL.to_vec(),
+            templates: Vec::new(),
+        }
+    }
+}
+
+impl EmailConfig {
+    /// The subject and body for `event`, placeholders filled in.
+    fn render(&self, event: EmailEvent, values: &[(&str, String)]) -> (String, String) {
+        let (subject, body) = match self.templates.iter().find(|t| t.event == event) {
+            Some(template) => (template.subject.as_str(), template.body.as_str()),
+            None => event.default_template(),
+        };
+        let fill = |text: &str| {
+            values.iter().fold(text.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+        };
+        (fill(subject), fill(body))
+    }
+}
+
+// ============================================================================
+// SENDING
+// ============================================================================
+
+/// Mails `event` in the background, if it is enabled. `values` fill the
+/// template's placeholders; `{time}` is always available, and `{label}`
+/// along with a `{channel}`.
+pub fn send(event: EmailEvent, values: Vec<(&'static str, String)>) {
+    std::thread::spawn(move || {
+        let Ok(config) = Config::load() else {
+            return;
+        };
+        let settings = config.email;
+        if !settings.enabled || !settings.events.contains(&event) || settings.to.is_empty() {
+            return;
+        }
+        let now = LocalTime::now();
+        let time = format!("{} {:02}:{:02}:{:02}", now.date(), now.hour, now.minute, now.second);
+        let mut values = values;
+        values.push(("time", time));
+        let channel = values.iter().find(|(name, _)| *name == "channel");
+        if let Some(channel) = channel.and_then(|(_, value)| value.parse().ok()) {
+            values.push(("label", config.device.label(channel)));
+        }
+        let (subject, body) = settings.render(event, &values);
+        if let Err(e) = deliver(&settings, &subject, &body) {
+            eprintln!("Email ({}): {}", event.text(), e);
+        }
+    });
+}
+
+fn deliver(settings: &EmailConfig, subject: &str, body: &str) -> Result<(), String> {
+    // Headers end at a line break
+    let header = |value: &str| value.replace(['\r', '\n'], " ");
+    let message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        header(&settings.from),
+        header(&settings.to.join(", ")),
+        header(subject),
+        body.replace('\n', "\r\n")
+    );
+
+    // The message goes in a file; curl's options, with the password, on stdin
+    static SENT: AtomicU64 = AtomicU64::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "usb-power-relay-{}-{}.eml",
+        std::process::id(),
+        SENT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, message)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    // Without a path curl greets with whatever it picks, e.g. the file name
+    let url = match settings.server.splitn(4, '/').nth(3) {
+        Some(path) if !path.is_empty() => settings.server.clone(),
+        _ => format!("{}/localhost", settings.server.trim_end_matches('/')),
+    };
+    let mut options = curl_option("url", &url)
+        + &curl_option("mail-from", &settings.from)
+        + &curl_option("upload-file", &path.to_string_lossy());
+    for to in &settings.to {
+        options += &curl_option("mail-rcpt", to);
+    }
+    if !settings.username.is_empty() {
+        options += &curl_option("user", &format!("{}:{}", settings.username, settings.password));
+    }
+    if settings.require_tls {
+        options += "ssl-reqd\n";
+    }
+    let result = curl(&options);
+    let _ = std::fs::remove_file(&path);
+    result
+}
@@ -0,0 +1,81 @@
+//! Emergency "all channels off".
+//!
+//! Triggering the stop bumps a shared epoch and queues [`Command::AllOff`].
+//! The device worker notices the new epoch and discards everything still
+//! queued before switching off, and running sequences stop issuing further
+//! steps, so nothing queued earlier can switch a load back on. ALL OFF is
+//! recorded as [`Source::Emergency`] unless the stop is triggered on behalf
+//! of something else, like the exit policy.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::Command;
+
+#[derive(Clone)]
+pub struct EmergencyStop {
+    epoch: Arc<AtomicU64>,
+    /// Whoever triggered the stop last.
+    source: Arc<Mutex<Source>>,
+    commands: mpsc::UnboundedSender<(Command, Source)>,
+}
+
+impl EmergencyStop {
+    pub fn new(commands: mpsc::UnboundedSender<(Command, Source)>) -> Self {
+        Self {
+            epoch: Arc::new(AtomicU64::new(0)),
+            source: Arc::new(Mutex::new(Source::Emergency)),
+            commands,
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.trigger_as(Source::Emergency);
+    }
+
+    /// Triggers the stop with ALL OFF recorded as `source`'s.
+    pub fn trigger_as(&self, source: Source) {
+        // Set before the epoch changes, as the worker reads it then
+        if let Ok(mut last) = self.source.lock() {
+            *last = source.clone();
+        }
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        let _ = self.commands.send((Command::AllOff, source));
+    }
+
+    /// Whoever triggered the stop last.
+    pub fn source(&self) -> Source {
+        self.source.lock().map_or(Source::Emergency, |source| source.clone())
+    }
+
+    /// Changes every time the stop is triggered.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// A command sender for background runners that goes dead once the stop
+    /// is triggered after this call. Its commands are `source`'s.
+    pub fn guard(&self, source: Source) -> StopGuard {
+        StopGuard {
+            started: self.epoch(),
+            stop: self.clone(),
+            source,
+        }
+    }
+}
+
+pub struct StopGuard {
+    started: u64,
+    stop: EmergencyStop,
+    source: Source,
+}
+
+impl StopGuard {
+    /// Queues `cmd` unless the stop fired; returns false once it has.
+    pub fn send(&self, cmd: Command) -> bool {
+        self.stop.epoch() == self.started
+            && self.stop.commands.send((cmd, self.source.clone())).is_ok()
+    }
+}
@@ -0,0 +1,279 @@
+//! Gamepad and foot switch buttons.
+//!
+//! With `gamepad.enabled`, buttons on a gamepad, joystick or USB foot pedal
+//! drive the same actions as hotkeys, so the relay can be switched with both
+//! hands busy:
+//!
+//! ```ron
+//! gamepad: (
+//!     enabled: true,
+//!     mappings: [
+//!         (button: South, action: Toggle(1)),
+//!         (button: Start, action: AllOff),
+//!     ],
+//! ),
+//! ```
+//!
+//! A button fires when it is pressed. Devices are read through the Linux
+//! input subsystem (`/dev/input/event*`); the first gamepad or joystick is
+//! used unless `device` names another. Pedals that pretend to be keyboards
+//! are mapped by key code and are best grabbed, so their key presses don't
+//! also reach the focused application.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::hotkeys::HotkeyAction;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long to wait when no button has changed.
+const READ_INTERVAL: Duration = Duration::from_millis(10);
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Key codes of the first joystick and gamepad buttons; a device reporting
+/// either is taken for a controller.
+const BTN_JOYSTICK: u16 = 0x120;
+const BTN_GAMEPAD: u16 = 0x130;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+/// A button, by its position on a standard gamepad or by its Linux key code
+/// (`Code(304)`, as `evtest` shows it) for anything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    RightTrigger,
+    LeftTrigger2,
+    RightTrigger2,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    Code(u16),
+}
+
+impl GamepadButton {
+    fn code(self) -> u16 {
+        match self {
+            GamepadButton::South => BTN_GAMEPAD,
+            GamepadButton::East => 0x131,
+            GamepadButton::North => 0x133,
+            GamepadButton::West => 0x134,
+            GamepadButton::LeftTrigger => 0x136,
+            GamepadButton::RightTrigger => 0x137,
+            GamepadButton::LeftTrigger2 => 0x138,
+            GamepadButton::RightTrigger2 => 0x139,
+            GamepadButton::Select => 0x13a,
+            GamepadButton::Start => 0x13b,
+            GamepadButton::Mode => 0x13c,
+            GamepadButton::LeftThumb => 0x13d,
+            GamepadButton::RightThumb => 0x13e,
+            GamepadButton::Code(code) => code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GamepadMapping {
+    pub button: GamepadButton,
+    pub action: HotkeyAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub enabled: bool,
+    /// Input device, e.g. `/dev/input/by-id/usb-PCsensor_FootSwitch-event-kbd`;
+    /// empty for the first gamepad or joystick found.
+    pub device: String,
+    /// Take the device's buttons for this application alone.
+    pub grab: bool,
+    pub mappings: Vec<GamepadMapping>,
+}
+
+// ============================================================================
+// DEVICE
+// ============================================================================
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs::File;
+    use std::io::{ErrorKind, Read};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    use super::{BTN_GAMEPAD, BTN_JOYSTICK};
+
+    const EV_KEY: u16 = 0x01;
+    const KEY_MAX: usize = 0x2ff;
+    const KEY_BITS: usize = KEY_MAX / 8 + 1;
+    /// `EVIOCGBIT(EV_KEY, KEY_BITS)` and `EVIOCGRAB` from `linux/input.h`.
+    const EVIOCGBIT_KEY: u64 = (2 << 30) | ((KEY_BITS as u64) << 16) | (0x45 << 8) | 0x21;
+    const EVIOCGRAB: u64 = (1 << 30) | (4 << 16) | (0x45 << 8) | 0x90;
+    const EVENT_SIZE: usize = std::mem::size_of::<libc::input_event>();
+
+    pub fn open(path: &str, grab: bool) -> Result<File, String> {
+        let device = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        // SAFETY: the descriptor is open and EVIOCGRAB takes an int by value
+        if grab && unsafe { libc::ioctl(device.as_raw_fd(), EVIOCGRAB as _, 1) } < 0 {
+            let error = std::io::Error::last_os_error();
+            return Err(format!("Failed to grab {}: {}", path, error));
+        }
+        Ok(device)
+    }
+
+    /// Whether the device has joystick or gamepad buttons.
+    fn is_controller(path: &str) -> bool {
+        let Ok(device) = open(path, false) else {
+            return false;
+        };
+        let mut bits = [0u8; KEY_BITS];
+        // SAFETY: the buffer is as long as the size encoded in the request
+        if unsafe { libc::ioctl(device.as_raw_fd(), EVIOCGBIT_KEY as _, bits.as_mut_ptr()) } < 0 {
+            return false;
+        }
+        let has = |code: u16| bits[usize::from(code / 8)] & (1 << (code % 8)) != 0;
+        has(BTN_GAMEPAD) || has(BTN_JOYSTICK)
+    }
+
+    /// The first event device that is a gamepad or joystick.
+    pub fn find() -> Option<String> {
+        let mut numbers: Vec<u32> = std::fs::read_dir("/dev/input")
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.strip_prefix("event")?.parse().ok()
+            })
+            .collect();
+        numbers.sort_unstable();
+        numbers
+            .into_iter()
+            .map(|n| format!("/dev/input/event{}", n))
+            .find(|path| is_controller(path))
+    }
+
+    /// Key codes pressed since the last call; `None` once nothing is waiting.
+    pub fn presses(device: &mut File) -> Result<Option<Vec<u16>>, String> {
+        let mut buf = [0u8; EVENT_SIZE * 32];
+        let n = match device.read(&mut buf) {
+            Ok(0) => return Err("The device was disconnected".to_string()),
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(format!("Reading the device failed: {}", e)),
+        };
+        Ok(Some(
+            buf[..n - n % EVENT_SIZE]
+                .chunks_exact(EVENT_SIZE)
+                .filter_map(|chunk| {
+                    // SAFETY: the chunk holds a whole event as the kernel wrote it
+                    let event: libc::input_event =
+                        unsafe { std::ptr::read_unaligned(chunk.as_ptr().cast()) };
+                    // 1 is a press; 0 is a release and 2 a key repeat
+                    (event.type_ == EV_KEY && event.value == 1).then_some(event.code)
+                })
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::fs::File;
+
+    pub fn open(_path: &str, _grab: bool) -> Result<File, String> {
+        Err("Gamepad input is only available on Linux".to_string())
+    }
+
+    pub fn find() -> Option<String> {
+        None
+    }
+
+    pub fn presses(_device: &mut File) -> Result<Option<Vec<u16>>, String> {
+        Ok(None)
+    }
+}
+
+// ============================================================================
+// LISTENER
+// ============================================================================
+
+fn open(settings: &GamepadConfig) -> Result<(String, File), String> {
+    let path = match settings.device.as_str() {
+        "" => platform::find().ok_or_else(|| "No gamepad found; set gamepad.device".to_string())?,
+        path => path.to_string(),
+    };
+    let device = platform::open(&path, settings.grab)?;
+    Ok((path, device))
+}
+
+fn fire(action: HotkeyAction, state: &AppState, stop: &EmergencyStop) {
+    let cmd = match action {
+        HotkeyAction::Toggle(channel) => Command::Toggle(channel),
+        HotkeyAction::On(channel) => Command::TurnOn(channel),
+        HotkeyAction::Off(channel) => Command::TurnOff(channel),
+        HotkeyAction::AllOff => return stop.trigger(),
+    };
+    state.send_command(cmd, Source::Gamepad);
+}
+
+/// Follows the gamepad's buttons while enabled, reopening it whenever the
+/// config file changes. Exits cleanly when disabled.
+pub fn run_gamepad(
+    ctx: &SubsystemContext,
+    state: &AppState,
+    stop: &EmergencyStop,
+) -> Result<(), String> {
+    loop {
+        let modified = Config::modified();
+        let settings = Config::load()?.gamepad;
+        if !settings.enabled {
+            return Ok(());
+        }
+        let (path, mut device) = open(&settings)?;
+        ctx.mark_running();
+
+        let mut checked = Instant::now();
+        loop {
+            match platform::presses(&mut device).map_err(|e| format!("{}: {}", path, e))? {
+                Some(codes) if !state.read_only => {
+                    for code in codes {
+                        let pressed = settings.mappings.iter().filter(|m| m.button.code() == code);
+                        for mapping in pressed {
+                            fire(mapping.action, state, stop);
+                        }
+                    }
+                }
+                Some(_) => {}
+                None => std::thread::sleep(READ_INTERVAL),
+            }
+
+            if checked.elapsed() >= RELOAD_CHECK_INTERVAL {
+                checked = Instant::now();
+                if Config::modified() != modified {
+                    break;
+                }
+            }
+        }
+    }
+}
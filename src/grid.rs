@@ -0,0 +1,408 @@
+//! Weekly schedule grid.
+//!
+//! A 7-day timeline per channel in half-hour slots, where ON blocks are
+//! painted with the mouse. The grid is a view of ordinary schedules: it
+//! shows the channel's weekly ON/OFF schedules and, on saving, replaces them
+//! with an ON at the start and an OFF at the end of every block, merged
+//! across days that share a time. Schedules it can't show (dated, imported,
+//! in another time zone, disabled, snoozed, scenes or off the half hour)
+//! are left as they are. Below the grid every schedule can be paused or
+//! snoozed; those changes are saved right away.
+
+use eframe::egui;
+
+use crate::config::Config;
+use crate::history::unix_now;
+use crate::i18n::{tr, tr_args};
+use crate::locale::format_moment;
+use crate::schedule::{Schedule, ScheduleAction, TimeOfDay, Weekday, SNOOZE_HOURS};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const SLOT_MINUTES: usize = 30;
+const DAY_SLOTS: usize = 24 * 60 / SLOT_MINUTES;
+const WEEK_SLOTS: usize = 7 * DAY_SLOTS;
+
+const CELL_WIDTH: f32 = 10.0;
+const CELL_HEIGHT: f32 = 22.0;
+const LABEL_WIDTH: f32 = 36.0;
+
+// ============================================================================
+// WEEK MODEL
+// ============================================================================
+
+/// Whether the channel is on in each slot, Monday 00:00 first.
+type Week = [bool; WEEK_SLOTS];
+
+/// Whether the grid shows and replaces this schedule of `channel`.
+fn in_grid(schedule: &Schedule, channel: u8) -> bool {
+    schedule.enabled
+        && !schedule.snoozed(unix_now())
+        && schedule.date.is_none()
+        && schedule.timezone.is_none()
+        && schedule.source.is_none()
+        && schedule.action.channel() == Some(channel)
+        && usize::from(schedule.time.minute) % SLOT_MINUTES == 0
+}
+
+/// The slot of a weekday and time.
+fn slot(day: Weekday, time: TimeOfDay) -> usize {
+    let minutes = usize::from(time.hour) * 60 + usize::from(time.minute);
+    day.index() * DAY_SLOTS + minutes / SLOT_MINUTES
+}
+
+/// The week `channel`'s grid schedules produce, repeating: the state at the
+/// start of the week is the one the week ends in.
+fn week_of(schedules: &[Schedule], channel: u8) -> Week {
+    let mut switches: Vec<(usize, bool)> = Vec::new();
+    for schedule in schedules.iter().filter(|s| in_grid(s, channel)) {
+        let on = matches!(schedule.action, ScheduleAction::On(_));
+        let days: &[Weekday] = match schedule.days.as_slice() {
+            [] => &Weekday::ALL,
+            days => days,
+        };
+        switches.extend(days.iter().map(|&day| (slot(day, schedule.time), on)));
+    }
+    // A stable sort keeps the config's order within a slot, which is the
+    // order the scheduler sends them in, so the last one wins
+    switches.sort_by_key(|&(slot, _)| slot);
+
+    let mut week = [false; WEEK_SLOTS];
+    let Some(&(_, mut on)) = switches.last() else {
+        return week;
+    };
+    let mut next = 0;
+    for (i, cell) in week.iter_mut().enumerate() {
+        while next < switches.len() && switches[next].0 == i {
+            on = switches[next].1;
+            next += 1;
+        }
+        *cell = on;
+    }
+    week
+}
+
+/// Schedules for `channel` that produce `week`, numbered from `first_id`.
+fn schedules_of(week: &Week, channel: u8, first_id: u32) -> Vec<Schedule> {
+    // Switches by slot of the day, with the days they happen on
+    let mut switches: Vec<(usize, bool, Vec<Weekday>)> = Vec::new();
+    for i in 0..WEEK_SLOTS {
+        let before = week[(i + WEEK_SLOTS - 1) % WEEK_SLOTS];
+        if week[i] == before {
+            continue;
+        }
+        let (day, at) = (Weekday::ALL[i / DAY_SLOTS], i % DAY_SLOTS);
+        match switches.iter_mut().find(|(slot, on, _)| *slot == at && *on == week[i]) {
+            Some((_, _, days)) => days.push(day),
+            None => switches.push((at, week[i], vec![day])),
+        }
+    }
+    // On all week: one daily ON keeps it that way
+    if switches.is_empty() && week[0] {
+        switches.push((0, true, Weekday::ALL.to_vec()));
+    }
+    switches.sort_by_key(|&(slot, on, _)| (slot, on));
+
+    switches
+        .into_iter()
+        .zip(first_id..)
+        .map(|((at, on, days), id)| {
+            let minutes = at * SLOT_MINUTES;
+            Schedule {
+                id,
+                time: TimeOfDay { hour: (minutes / 60) as u8, minute: (minutes % 60) as u8 },
+                days: if days.len() == 7 { Vec::new() } else { days },
+                date: None,
+                timezone: None,
+                action: if on {
+                    ScheduleAction::On(channel)
+                } else {
+                    ScheduleAction::Off(channel)
+                },
+                enabled: true,
+                snoozed_until: None,
+                source: None,
+            }
+        })
+        .collect()
+}
+
+// ============================================================================
+// EDITOR
+// ============================================================================
+
+pub struct ScheduleGrid {
+    config: Config,
+    channel: u8,
+    week: Week,
+    /// Whether `week` was painted since it was loaded.
+    changed: bool,
+    /// The channel's schedules the grid leaves as they are.
+    others: usize,
+    /// What the current drag paints, decided by the cell it started on.
+    paint: Option<bool>,
+    status: Option<String>,
+}
+
+impl ScheduleGrid {
+    pub fn new() -> Self {
+        let (config, status) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        let mut grid = Self {
+            config,
+            channel: 1,
+            week: [false; WEEK_SLOTS],
+            changed: false,
+            others: 0,
+            paint: None,
+            status,
+        };
+        grid.load();
+        grid
+    }
+
+    fn load(&mut self) {
+        let channel = self.channel;
+        self.week = week_of(&self.config.schedules, channel);
+        self.changed = false;
+        self.others = self
+            .config
+            .schedules
+            .iter()
+            .filter(|s| s.affects(&self.config, channel) && !in_grid(s, channel))
+            .count();
+    }
+
+    /// Replaces the channel's grid schedules in `config` with the painted week.
+    fn apply(&mut self) {
+        if !self.changed {
+            return;
+        }
+        let channel = self.channel;
+        self.config.schedules.retain(|s| !in_grid(s, channel));
+        let first_id = self.config.schedules.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        let schedules = schedules_of(&self.week, channel, first_id);
+        self.config.schedules.extend(schedules);
+        self.changed = false;
+    }
+
+    /// Draws the grid window; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        egui::Window::new(tr("grid-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui));
+        open
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let mut channel = self.channel;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("grid_channel")
+                .selected_text(self.config.device.display_name(channel))
+                .show_ui(ui, |ui| {
+                    for c in 1..=self.config.device.channels {
+                        ui.selectable_value(&mut channel, c, self.config.device.display_name(c));
+                    }
+                });
+            if ui.button(tr("grid-clear")).clicked() {
+                self.week = [false; WEEK_SLOTS];
+                self.changed = true;
+            }
+        });
+        if channel != self.channel {
+            self.apply();
+            self.channel = channel;
+            self.load();
+        }
+        ui.weak(tr("grid-help"));
+        ui.add_space(4.0);
+
+        self.grid_ui(ui);
+
+        if self.others > 0 {
+            ui.weak(tr_args("grid-others", &[("count", &self.others)]));
+        }
+        ui.separator();
+        if ui.button(tr("settings-save")).clicked() {
+            self.apply();
+            self.status = Some(match self.config.validate().and_then(|()| self.config.save()) {
+                Ok(()) => tr("settings-saved"),
+                Err(e) => e,
+            });
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        egui::CollapsingHeader::new(tr("grid-all-schedules")).show(ui, |ui| {
+            egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| self.list_ui(ui));
+        });
+    }
+
+    /// Every schedule with its pause toggle and snooze actions.
+    fn list_ui(&mut self, ui: &mut egui::Ui) {
+        if self.config.schedules.is_empty() {
+            ui.weak(tr("grid-no-schedules"));
+        }
+        let clock = self.config.display.clock;
+        let now = unix_now();
+        let mut change: Option<(u32, bool, Option<u64>)> = None;
+        for schedule in &self.config.schedules {
+            ui.horizontal(|ui| {
+                let mut enabled = schedule.enabled;
+                let text = format!(
+                    "#{} {} {} {}",
+                    schedule.id, schedule.time, schedule.days_text(), schedule.action
+                );
+                let toggle = ui.checkbox(&mut enabled, text).on_hover_text(tr("grid-pause-hint"));
+                if toggle.changed() {
+                    change = Some((schedule.id, enabled, schedule.snoozed_until));
+                }
+                match schedule.snoozed_until.filter(|_| schedule.snoozed(now)) {
+                    Some(until) => {
+                        let until = format_moment(until, clock);
+                        ui.weak(tr_args("grid-snoozed", &[("time", &until)]));
+                        if ui.small_button(tr("grid-unsnooze")).clicked() {
+                            change = Some((schedule.id, schedule.enabled, None));
+                        }
+                    }
+                    None => {
+                        ui.menu_button(tr("grid-snooze"), |ui| {
+                            for hours in SNOOZE_HOURS {
+                                let label = tr_args("grid-snooze-hours", &[("count", &hours)]);
+                                if ui.button(label).clicked() {
+                                    let until = now + hours * 3600;
+                                    change = Some((schedule.id, schedule.enabled, Some(until)));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                }
+            });
+        }
+        if let Some((id, enabled, snoozed_until)) = change {
+            self.update(id, enabled, snoozed_until);
+        }
+    }
+
+    /// Pauses or snoozes a schedule in the config file right away, leaving
+    /// unsaved painting alone, and in the copy being edited.
+    fn update(&mut self, id: u32, enabled: bool, snoozed_until: Option<u64>) {
+        let set = |schedules: &mut [Schedule]| {
+            let schedule = schedules.iter_mut().find(|s| s.id == id);
+            schedule.map(|s| {
+                s.enabled = enabled;
+                s.snoozed_until = snoozed_until;
+            })
+        };
+        let saved = Config::load().and_then(|mut config| {
+            set(&mut config.schedules).ok_or_else(|| tr("grid-save-first"))?;
+            config.save()
+        });
+        match saved {
+            Ok(()) => {
+                set(&mut self.config.schedules);
+                // Paused and snoozed schedules leave the grid
+                if !self.changed {
+                    self.load();
+                }
+            }
+            Err(e) => self.status = Some(e),
+        }
+    }
+
+    /// The hour ruler, one row per day and the painting.
+    fn grid_ui(&mut self, ui: &mut egui::Ui) {
+        let size = egui::vec2(
+            LABEL_WIDTH + DAY_SLOTS as f32 * CELL_WIDTH,
+            CELL_HEIGHT * (Weekday::ALL.len() + 1) as f32,
+        );
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let cells = egui::Rect::from_min_max(
+            rect.min + egui::vec2(LABEL_WIDTH, CELL_HEIGHT),
+            rect.max,
+        );
+
+        // Press on a cell to flip it, then drag to paint the same value
+        match response.interact_pointer_pos().filter(|pos| cells.contains(*pos)) {
+            Some(pos) => {
+                let offset = pos - cells.min;
+                let day = ((offset.y / CELL_HEIGHT) as usize).min(6);
+                let at = ((offset.x / CELL_WIDTH) as usize).min(DAY_SLOTS - 1);
+                let cell = &mut self.week[day * DAY_SLOTS + at];
+                let value = *self.paint.get_or_insert(!*cell);
+                self.changed |= *cell != value;
+                *cell = value;
+            }
+            None if !response.is_pointer_button_down_on() => self.paint = None,
+            None => {}
+        }
+
+        let painter = ui.painter_at(rect);
+        let visuals = ui.visuals();
+        let text = visuals.text_color();
+        let on = self.config.theme.on_fill();
+        let font = egui::FontId::proportional(11.0);
+
+        for hour in (0..24).step_by(3) {
+            let x = cells.min.x + (hour * 60 / SLOT_MINUTES) as f32 * CELL_WIDTH;
+            painter.text(
+                egui::pos2(x + 1.0, rect.min.y + CELL_HEIGHT / 2.0),
+                egui::Align2::LEFT_CENTER,
+                format!("{:02}", hour),
+                font.clone(),
+                text,
+            );
+        }
+        for (i, day) in Weekday::ALL.iter().enumerate() {
+            let y = cells.min.y + i as f32 * CELL_HEIGHT;
+            painter.text(
+                egui::pos2(rect.min.x, y + CELL_HEIGHT / 2.0),
+                egui::Align2::LEFT_CENTER,
+                day.text(),
+                font.clone(),
+                text,
+            );
+            for at in 0..DAY_SLOTS {
+                let cell = egui::Rect::from_min_size(
+                    egui::pos2(cells.min.x + at as f32 * CELL_WIDTH, y),
+                    egui::vec2(CELL_WIDTH, CELL_HEIGHT),
+                )
+                .shrink(0.5);
+                let fill = if self.week[i * DAY_SLOTS + at] {
+                    on
+                } else {
+                    visuals.extreme_bg_color
+                };
+                painter.rect_filled(cell, 0.0, fill);
+            }
+        }
+        // Hour lines, stronger every 6 hours
+        for hour in 0..=24 {
+            let x = cells.min.x + (hour * 60 / SLOT_MINUTES) as f32 * CELL_WIDTH;
+            let stroke = if hour % 6 == 0 {
+                visuals.widgets.noninteractive.fg_stroke
+            } else {
+                visuals.widgets.noninteractive.bg_stroke
+            };
+            painter.line_segment([egui::pos2(x, cells.min.y), egui::pos2(x, cells.max.y)], stroke);
+        }
+
+        let hovered = response.hover_pos().filter(|pos| cells.contains(*pos));
+        if let Some(pos) = hovered {
+            let offset = pos - cells.min;
+            let day = Weekday::ALL[((offset.y / CELL_HEIGHT) as usize).min(6)];
+            let minutes = ((offset.x / CELL_WIDTH) as usize).min(DAY_SLOTS - 1) * SLOT_MINUTES;
+            let time = format!("{} {:02}:{:02}", day.text(), minutes / 60, minutes % 60);
+            response.on_hover_text(time);
+        }
+    }
+}
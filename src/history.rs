@@ -0,0 +1,210 @@
+//! Switching history.
+//!
+//! Every ON/OFF transition is appended to `history.tsv` in the platform data
+//! directory as `<unix seconds>\t<channel>\t<ON|OFF|ERROR>\t<source>`, where
+//! ERROR marks the device failing while the channel was in use and the source
+//! is whatever caused the change (see [`Source`]). Lines written before
+//! sources were recorded have three fields. The format is plain text so it
+//! can be inspected or exported with standard tools. The History window
+//! lists it newest first.
+
+use eframe::egui;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audit::Source;
+use crate::config::DeviceConfig;
+use crate::i18n::{tr, tr_args};
+use crate::locale::{format_moment, ClockFormat};
+use crate::RelayState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const HISTORY_FILE: &str = "history.tsv";
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEvent {
+    /// Unix timestamp in seconds.
+    pub time: u64,
+    pub channel: u8,
+    pub state: RelayState,
+    /// What caused the change; empty for old entries.
+    pub source: String,
+}
+
+impl HistoryEvent {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let time = fields.next()?.parse().ok()?;
+        let channel = fields.next()?.parse().ok()?;
+        let state = match fields.next()? {
+            "ON" => RelayState::On,
+            "OFF" => RelayState::Off,
+            "ERROR" => RelayState::Error,
+            _ => return None,
+        };
+        let source = fields.next().unwrap_or("").to_string();
+        Some(Self {
+            time,
+            channel,
+            state,
+            source,
+        })
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// ============================================================================
+// HISTORY LOG
+// ============================================================================
+
+pub struct History {
+    path: PathBuf,
+    last: HashMap<u8, RelayState>,
+}
+
+impl History {
+    pub fn path() -> Result<PathBuf, String> {
+        directories::ProjectDirs::from("", "", "usb-power-relay")
+            .map(|dirs| dirs.data_dir().join(HISTORY_FILE))
+            .ok_or_else(|| "No data directory available".to_string())
+    }
+
+    /// Opens the log, remembering the last recorded state of each channel so
+    /// repeated reports of the same state aren't duplicated.
+    pub fn open() -> Result<Self, String> {
+        let path = Self::path()?;
+        let mut last = HashMap::new();
+        for event in Self::load()? {
+            last.insert(event.channel, event.state);
+        }
+        Ok(Self { path, last })
+    }
+
+    /// Reads every recorded event in chronological order.
+    pub fn load() -> Result<Vec<HistoryEvent>, String> {
+        let path = Self::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Ok(text.lines().filter_map(HistoryEvent::parse).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Appends a transition and its cause. Unknown states are not recorded.
+    pub fn record(
+        &mut self,
+        channel: u8,
+        state: RelayState,
+        source: &Source,
+    ) -> Result<(), String> {
+        if state == RelayState::Unknown {
+            return Ok(());
+        }
+        if self.last.get(&channel) == Some(&state) {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        // Names come from the config; keep them from breaking the columns
+        let source = source.to_string().replace(['\t', '\n', '\r'], " ");
+        writeln!(file, "{}\t{}\t{}\t{}", unix_now(), channel, state.text(), source)
+            .map_err(|e| format!("Failed to write history: {}", e))?;
+
+        self.last.insert(channel, state);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// WINDOW
+// ============================================================================
+
+/// The recorded switches, newest first.
+pub struct HistoryWindow {
+    events: Result<Vec<HistoryEvent>, String>,
+}
+
+impl HistoryWindow {
+    pub fn new() -> Self {
+        Self { events: Self::newest_first() }
+    }
+
+    fn newest_first() -> Result<Vec<HistoryEvent>, String> {
+        History::load().map(|mut events| {
+            events.reverse();
+            events
+        })
+    }
+
+    /// Draws the history; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context, device: &DeviceConfig, clock: ClockFormat) -> bool {
+        let mut open = true;
+        egui::Window::new(tr("history-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .show(ctx, |ui| self.contents(ui, device, clock));
+        open
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, device: &DeviceConfig, clock: ClockFormat) {
+        let count = self.events.as_ref().map_or(0, Vec::len);
+        ui.horizontal(|ui| {
+            ui.label(tr_args("history-entries", &[("count", &count)]));
+            if ui.button(tr("history-reload")).clicked() {
+                self.events = Self::newest_first();
+            }
+        });
+        ui.separator();
+
+        let events = match &self.events {
+            Ok(events) if events.is_empty() => {
+                ui.label(tr("history-empty"));
+                return;
+            }
+            Ok(events) => events,
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                return;
+            }
+        };
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        egui::ScrollArea::vertical()
+            .max_height(320.0)
+            .show_rows(ui, row_height, events.len(), |ui, rows| {
+                egui::Grid::new("history_rows").striped(true).show(ui, |ui| {
+                    for event in &events[rows] {
+                        ui.label(format_moment(event.time, clock));
+                        ui.label(device.display_name(event.channel));
+                        ui.label(event.state.local_text());
+                        ui.weak(&event.source);
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+}
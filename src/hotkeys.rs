@@ -0,0 +1,567 @@
+//! System-wide hotkeys.
+//!
+//! Bindings from the config file are registered with the OS (a key grab on
+//! the X11 root window on Linux, `RegisterHotKey` on Windows), so they fire
+//! even when the window is unfocused or minimized. Wayland doesn't let
+//! clients grab keys, so there they are asked for through the desktop's
+//! `GlobalShortcuts` portal, falling back to X11 (which only sees XWayland
+//! windows) without one. Shortcuts are written as `Ctrl+Alt+F9`; modifiers
+//! are `Ctrl`, `Alt`, `Shift` and `Super`.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::supervisor::SubsystemContext;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+const LETTERS: [egui::Key; 26] = {
+    use egui::Key::*;
+    [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z]
+};
+
+const DIGITS: [egui::Key; 10] = {
+    use egui::Key::*;
+    [Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9]
+};
+
+const FUNCTION_KEYS: [egui::Key; 24] = {
+    use egui::Key::*;
+    [
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19,
+        F20, F21, F22, F23, F24,
+    ]
+};
+
+/// Other supported keys with their X11 keysym and Windows virtual-key code.
+const NAMED_KEYS: &[(egui::Key, u32, u32)] = &[
+    (egui::Key::ArrowLeft, 0xff51, 0x25),
+    (egui::Key::ArrowUp, 0xff52, 0x26),
+    (egui::Key::ArrowRight, 0xff53, 0x27),
+    (egui::Key::ArrowDown, 0xff54, 0x28),
+    (egui::Key::Escape, 0xff1b, 0x1b),
+    (egui::Key::Tab, 0xff09, 0x09),
+    (egui::Key::Enter, 0xff0d, 0x0d),
+    (egui::Key::Space, 0x0020, 0x20),
+    (egui::Key::Insert, 0xff63, 0x2d),
+    (egui::Key::Delete, 0xffff, 0x2e),
+    (egui::Key::Home, 0xff50, 0x24),
+    (egui::Key::End, 0xff57, 0x23),
+    (egui::Key::PageUp, 0xff55, 0x21),
+    (egui::Key::PageDown, 0xff56, 0x22),
+];
+
+/// X11 keysym and Windows virtual-key code for `key`, if it can be bound.
+fn key_codes(key: egui::Key) -> Option<(u32, u32)> {
+    let offset = |keys: &[egui::Key]| keys.iter().position(|&k| k == key).map(|i| i as u32);
+
+    if let Some(i) = offset(&LETTERS) {
+        return Some((0x61 + i, 0x41 + i));
+    }
+    if let Some(i) = offset(&DIGITS) {
+        return Some((0x30 + i, 0x30 + i));
+    }
+    if let Some(i) = offset(&FUNCTION_KEYS) {
+        return Some((0xffbe + i, 0x70 + i));
+    }
+    NAMED_KEYS
+        .iter()
+        .find(|(k, _, _)| *k == key)
+        .map(|&(_, keysym, vk)| (keysym, vk))
+}
+
+// ============================================================================
+// BINDINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HotkeyAction {
+    Toggle(u8),
+    On(u8),
+    Off(u8),
+    /// Emergency stop, same as the ALL OFF button.
+    AllOff,
+}
+
+impl HotkeyAction {
+    pub fn channel(&self) -> Option<u8> {
+        match self {
+            HotkeyAction::Toggle(channel) | HotkeyAction::On(channel) | HotkeyAction::Off(channel) => {
+                Some(*channel)
+            }
+            HotkeyAction::AllOff => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub shortcut: String,
+    pub action: HotkeyAction,
+}
+
+// ============================================================================
+// SHORTCUTS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shortcut {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// The Windows/Super/Command key.
+    pub logo: bool,
+    pub key: egui::Key,
+}
+
+impl Shortcut {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key_name = parts.pop().filter(|k| !k.is_empty()).ok_or("Missing key")?;
+        let key = egui::Key::from_name(key_name)
+            .or_else(|| egui::Key::from_name(&key_name.to_ascii_uppercase()))
+            .ok_or_else(|| format!("Unknown key '{}'", key_name))?;
+
+        let mut shortcut = Self {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            key,
+        };
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => shortcut.ctrl = true,
+                "alt" => shortcut.alt = true,
+                "shift" => shortcut.shift = true,
+                "super" | "win" | "cmd" => shortcut.logo = true,
+                _ => return Err(format!("Unknown modifier '{}'", modifier)),
+            }
+        }
+        shortcut.checked()
+    }
+
+    /// Builds a shortcut from a key press captured in the GUI.
+    pub fn from_egui(key: egui::Key, modifiers: egui::Modifiers) -> Result<Self, String> {
+        Self {
+            ctrl: modifiers.ctrl,
+            alt: modifiers.alt,
+            shift: modifiers.shift,
+            logo: modifiers.mac_cmd,
+            key,
+        }
+        .checked()
+    }
+
+    /// Rejects keys the OS backends can't grab, and plain keys that would
+    /// swallow normal typing.
+    fn checked(self) -> Result<Self, String> {
+        if key_codes(self.key).is_none() {
+            return Err(format!("{} can't be used as a global hotkey", self.key.name()));
+        }
+        let has_modifier = self.ctrl || self.alt || self.shift || self.logo;
+        if !has_modifier && !FUNCTION_KEYS.contains(&self.key) {
+            return Err("Hotkeys need a modifier unless they use a function key".to_string());
+        }
+        Ok(self)
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl"),
+            (self.alt, "Alt"),
+            (self.shift, "Shift"),
+            (self.logo, "Super"),
+        ] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+// ============================================================================
+// PLATFORM BACKENDS
+// ============================================================================
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{key_codes, HotkeyAction, Shortcut, FUNCTION_KEYS};
+    use crate::dbus::ShortcutPortal;
+    use eframe::egui;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ConnectionExt, GrabMode, Keycode, ModMask, Window};
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    /// Caps Lock and Num Lock change the modifier state of a key press, so
+    /// each shortcut is grabbed once per combination of them.
+    const LOCK_MASKS: [u16; 4] = [0, 1 << 1, 1 << 4, (1 << 1) | (1 << 4)];
+
+    /// Keysym names of the named keys, for the portal's triggers.
+    const KEYSYM_NAMES: &[(egui::Key, &str)] = &[
+        (egui::Key::ArrowLeft, "Left"),
+        (egui::Key::ArrowUp, "Up"),
+        (egui::Key::ArrowRight, "Right"),
+        (egui::Key::ArrowDown, "Down"),
+        (egui::Key::Escape, "Escape"),
+        (egui::Key::Tab, "Tab"),
+        (egui::Key::Enter, "Return"),
+        (egui::Key::Space, "space"),
+        (egui::Key::Insert, "Insert"),
+        (egui::Key::Delete, "Delete"),
+        (egui::Key::Home, "Home"),
+        (egui::Key::End, "End"),
+        (egui::Key::PageUp, "Page_Up"),
+        (egui::Key::PageDown, "Page_Down"),
+    ];
+
+    pub enum Registration {
+        X11(Box<X11Grabs>),
+        Portal(ShortcutPortal),
+    }
+
+    impl Registration {
+        pub fn register(bindings: &[(Shortcut, HotkeyAction)]) -> Result<Self, String> {
+            let x11 = || X11Grabs::register(bindings).map(|x| Registration::X11(Box::new(x)));
+            if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+                return x11();
+            }
+            let shortcuts: Vec<(String, String)> = bindings
+                .iter()
+                .map(|(shortcut, action)| (describe(*action), trigger(shortcut)))
+                .collect();
+            match ShortcutPortal::bind(&shortcuts) {
+                Ok(portal) => Ok(Registration::Portal(portal)),
+                Err(e) => x11().map_err(|_| e),
+            }
+        }
+
+        /// Index of the next pressed shortcut, if any.
+        pub fn poll(&mut self) -> Result<Option<usize>, String> {
+            match self {
+                Registration::X11(grabs) => grabs.poll(),
+                Registration::Portal(portal) => portal.poll(),
+            }
+        }
+    }
+
+    /// What the desktop's shortcut settings call a binding.
+    fn describe(action: HotkeyAction) -> String {
+        match action {
+            HotkeyAction::Toggle(channel) => format!("Toggle channel {}", channel),
+            HotkeyAction::On(channel) => format!("Switch channel {} on", channel),
+            HotkeyAction::Off(channel) => format!("Switch channel {} off", channel),
+            HotkeyAction::AllOff => "Switch all channels off".to_string(),
+        }
+    }
+
+    /// `shortcut` in the portal's notation, e.g. `CTRL+ALT+F9`.
+    fn trigger(shortcut: &Shortcut) -> String {
+        let mut text = String::new();
+        for (held, name) in [
+            (shortcut.ctrl, "CTRL+"),
+            (shortcut.alt, "ALT+"),
+            (shortcut.shift, "SHIFT+"),
+            (shortcut.logo, "LOGO+"),
+        ] {
+            if held {
+                text += name;
+            }
+        }
+        let named = KEYSYM_NAMES.iter().find(|(key, _)| *key == shortcut.key);
+        match named {
+            Some((_, name)) => text += name,
+            None if FUNCTION_KEYS.contains(&shortcut.key) => text += shortcut.key.name(),
+            // Letters and digits
+            None => text += &shortcut.key.name().to_ascii_lowercase(),
+        }
+        text
+    }
+
+    pub struct X11Grabs {
+        conn: RustConnection,
+        root: Window,
+        grabs: Vec<(Keycode, u16)>,
+    }
+
+    fn modifier_mask(shortcut: &Shortcut) -> u16 {
+        let mut mask = 0;
+        if shortcut.shift {
+            mask |= u16::from(ModMask::SHIFT);
+        }
+        if shortcut.ctrl {
+            mask |= u16::from(ModMask::CONTROL);
+        }
+        if shortcut.alt {
+            mask |= u16::from(ModMask::M1);
+        }
+        if shortcut.logo {
+            mask |= u16::from(ModMask::M4);
+        }
+        mask
+    }
+
+    impl X11Grabs {
+        fn register(bindings: &[(Shortcut, HotkeyAction)]) -> Result<Self, String> {
+            let (conn, screen) =
+                x11rb::connect(None).map_err(|e| format!("No X11 display: {}", e))?;
+            let setup = conn.setup();
+            let root = setup.roots[screen].root;
+            let (min, max) = (setup.min_keycode, setup.max_keycode);
+
+            let mapping = conn
+                .get_keyboard_mapping(min, max - min + 1)
+                .map_err(|e| format!("Keyboard mapping failed: {}", e))?
+                .reply()
+                .map_err(|e| format!("Keyboard mapping failed: {}", e))?;
+            let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+
+            let mut registration = Self {
+                conn,
+                root,
+                grabs: Vec::new(),
+            };
+            for (shortcut, _) in bindings {
+                let (keysym, _) = key_codes(shortcut.key).ok_or("Unsupported key")?;
+                let keycode = mapping
+                    .keysyms
+                    .chunks(per_keycode)
+                    .position(|syms| syms.contains(&keysym))
+                    .map(|i| min + i as u8)
+                    .ok_or_else(|| format!("{} is not on this keyboard", shortcut))?;
+
+                let mask = modifier_mask(shortcut);
+                for lock in LOCK_MASKS {
+                    registration
+                        .conn
+                        .grab_key(
+                            false,
+                            root,
+                            ModMask::from(mask | lock),
+                            keycode,
+                            GrabMode::ASYNC,
+                            GrabMode::ASYNC,
+                        )
+                        .map_err(|e| format!("Grab failed: {}", e))?
+                        .check()
+                        .map_err(|_| format!("{} is already in use by another application", shortcut))?;
+                }
+                registration.grabs.push((keycode, mask));
+            }
+            Ok(registration)
+        }
+
+        fn poll(&mut self) -> Result<Option<usize>, String> {
+            let ignored = LOCK_MASKS[3];
+            while let Some(event) = self
+                .conn
+                .poll_for_event()
+                .map_err(|e| format!("X11 connection lost: {}", e))?
+            {
+                if let Event::KeyPress(press) = event {
+                    let state = u16::from(press.state) & 0xff & !ignored;
+                    if let Some(index) = self
+                        .grabs
+                        .iter()
+                        .position(|&(keycode, mask)| keycode == press.detail && mask == state)
+                    {
+                        return Ok(Some(index));
+                    }
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    impl Drop for X11Grabs {
+        fn drop(&mut self) {
+            for &(keycode, mask) in &self.grabs {
+                for lock in LOCK_MASKS {
+                    let _ = self.conn.ungrab_key(keycode, self.root, ModMask::from(mask | lock));
+                }
+            }
+            let _ = self.conn.flush();
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{key_codes, HotkeyAction, Shortcut};
+    use std::ffi::c_void;
+
+    const MOD_ALT: u32 = 0x0001;
+    const MOD_CONTROL: u32 = 0x0002;
+    const MOD_SHIFT: u32 = 0x0004;
+    const MOD_WIN: u32 = 0x0008;
+    const MOD_NOREPEAT: u32 = 0x4000;
+    const WM_HOTKEY: u32 = 0x0312;
+    const PM_REMOVE: u32 = 0x0001;
+
+    #[repr(C)]
+    struct Msg {
+        hwnd: *mut c_void,
+        message: u32,
+        wparam: usize,
+        lparam: isize,
+        time: u32,
+        pt_x: i32,
+        pt_y: i32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn RegisterHotKey(hwnd: *mut c_void, id: i32, modifiers: u32, vk: u32) -> i32;
+        fn UnregisterHotKey(hwnd: *mut c_void, id: i32) -> i32;
+        fn PeekMessageW(msg: *mut Msg, hwnd: *mut c_void, min: u32, max: u32, remove: u32) -> i32;
+    }
+
+    /// Hotkeys registered without a window are posted to the registering
+    /// thread, so registration and polling must stay on the same thread.
+    pub struct Registration {
+        ids: Vec<i32>,
+    }
+
+    impl Registration {
+        pub fn register(bindings: &[(Shortcut, HotkeyAction)]) -> Result<Self, String> {
+            let mut registration = Self { ids: Vec::new() };
+            for (i, (shortcut, _)) in bindings.iter().enumerate() {
+                let (_, vk) = key_codes(shortcut.key).ok_or("Unsupported key")?;
+                let mut modifiers = MOD_NOREPEAT;
+                for (held, flag) in [
+                    (shortcut.alt, MOD_ALT),
+                    (shortcut.ctrl, MOD_CONTROL),
+                    (shortcut.shift, MOD_SHIFT),
+                    (shortcut.logo, MOD_WIN),
+                ] {
+                    if held {
+                        modifiers |= flag;
+                    }
+                }
+
+                let id = i as i32 + 1;
+                // SAFETY: a null window handle is allowed and no pointers are kept
+                if unsafe { RegisterHotKey(std::ptr::null_mut(), id, modifiers, vk) } == 0 {
+                    return Err(format!("{} is already in use by another application", shortcut));
+                }
+                registration.ids.push(id);
+            }
+            Ok(registration)
+        }
+
+        /// Index of the next pressed shortcut, if any.
+        pub fn poll(&mut self) -> Result<Option<usize>, String> {
+            // SAFETY: Msg matches the Win32 MSG layout and PeekMessageW only
+            // writes into it
+            let (found, msg) = unsafe {
+                let mut msg: Msg = std::mem::zeroed();
+                let found =
+                    PeekMessageW(&mut msg, std::ptr::null_mut(), WM_HOTKEY, WM_HOTKEY, PM_REMOVE);
+                (found, msg)
+            };
+            Ok((found != 0 && msg.message == WM_HOTKEY).then(|| msg.wparam.wrapping_sub(1)))
+        }
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            for &id in &self.ids {
+                // SAFETY: only unregisters ids this thread registered
+                unsafe {
+                    UnregisterHotKey(std::ptr::null_mut(), id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use super::{HotkeyAction, Shortcut};
+
+    pub struct Registration;
+
+    impl Registration {
+        pub fn register(_bindings: &[(Shortcut, HotkeyAction)]) -> Result<Self, String> {
+            Err("Global hotkeys are not supported on this platform".to_string())
+        }
+
+        pub fn poll(&mut self) -> Result<Option<usize>, String> {
+            Ok(None)
+        }
+    }
+}
+
+// ============================================================================
+// LISTENER
+// ============================================================================
+
+fn fire(action: HotkeyAction, tx: &mpsc::UnboundedSender<(Command, Source)>, stop: &EmergencyStop) {
+    let cmd = match action {
+        HotkeyAction::Toggle(channel) => Command::Toggle(channel),
+        HotkeyAction::On(channel) => Command::TurnOn(channel),
+        HotkeyAction::Off(channel) => Command::TurnOff(channel),
+        HotkeyAction::AllOff => return stop.trigger(),
+    };
+    let _ = tx.send((cmd, Source::Hotkey));
+}
+
+/// Registers the configured hotkeys and dispatches their actions,
+/// re-registering whenever the config file changes.
+pub fn run_hotkeys(
+    ctx: &SubsystemContext,
+    tx: &mpsc::UnboundedSender<(Command, Source)>,
+    stop: &EmergencyStop,
+) -> Result<(), String> {
+    loop {
+        let modified = Config::modified();
+        let bindings = Config::load()?.hotkeys;
+        let shortcuts = bindings
+            .iter()
+            .map(|b| Shortcut::parse(&b.shortcut).map(|shortcut| (shortcut, b.action)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Don't touch the display server until something is bound
+        let mut registration = if shortcuts.is_empty() {
+            None
+        } else {
+            Some(platform::Registration::register(&shortcuts)?)
+        };
+        ctx.mark_running();
+
+        let mut checked = Instant::now();
+        loop {
+            if let Some(registration) = registration.as_mut() {
+                while let Some(index) = registration.poll()? {
+                    if let Some(binding) = bindings.get(index) {
+                        fire(binding.action, tx, stop);
+                    }
+                }
+            }
+
+            if checked.elapsed() >= RELOAD_CHECK_INTERVAL {
+                checked = Instant::now();
+                if Config::modified() != modified {
+                    break;
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
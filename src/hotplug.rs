@@ -0,0 +1,383 @@
+//! USB hot-plug detection.
+//!
+//! Listens for device arrival and removal events from the OS (kernel uevents
+//! on Linux, `WM_DEVICECHANGE` on Windows). An arrival ends the device
+//! worker's reconnect backoff so a freshly plugged board is picked up at
+//! once; a removal queues a status poll, which fails and marks the channels
+//! unavailable without waiting for the next command. Both also reach the
+//! rules engine as USB triggers.
+
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::rules::{RuleEvent, RuleEvents};
+use crate::supervisor::{SubsystemContext, Supervisor};
+use crate::Command;
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotplugEvent {
+    Arrived,
+    Removed,
+}
+
+// ============================================================================
+// PLATFORM BACKENDS
+// ============================================================================
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::HotplugEvent;
+
+    /// Kernel uevents are multicast on netlink group 1.
+    const UEVENT_GROUP: u32 = 1;
+    const WAIT_MS: i32 = 500;
+
+    /// A netlink socket subscribed to kernel uevents.
+    pub struct Watcher {
+        fd: i32,
+    }
+
+    impl Watcher {
+        pub fn open() -> Result<Self, String> {
+            // SAFETY: plain socket calls; the address struct is zeroed and
+            // sized as passed
+            unsafe {
+                let fd = libc::socket(
+                    libc::AF_NETLINK,
+                    libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                    libc::NETLINK_KOBJECT_UEVENT,
+                );
+                if fd < 0 {
+                    return Err(format!("uevent socket: {}", std::io::Error::last_os_error()));
+                }
+                let watcher = Self { fd };
+
+                let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+                addr.nl_family = libc::AF_NETLINK as u16;
+                addr.nl_groups = UEVENT_GROUP;
+                let bound = libc::bind(
+                    fd,
+                    (&addr as *const libc::sockaddr_nl).cast(),
+                    std::mem::size_of::<libc::sockaddr_nl>() as u32,
+                );
+                if bound < 0 {
+                    return Err(format!("uevent bind: {}", std::io::Error::last_os_error()));
+                }
+                Ok(watcher)
+            }
+        }
+
+        /// Waits briefly and returns the serial port events that arrived.
+        pub fn wait(&mut self) -> Result<Vec<HotplugEvent>, String> {
+            let mut pfd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+            // SAFETY: one valid pollfd is passed
+            if unsafe { libc::poll(&mut pfd, 1, WAIT_MS) } <= 0 {
+                return Ok(Vec::new());
+            }
+
+            let mut events = Vec::new();
+            let mut buf = [0u8; 8192];
+            loop {
+                // SAFETY: the length passed matches the buffer
+                let len = unsafe {
+                    libc::recv(self.fd, buf.as_mut_ptr().cast(), buf.len(), libc::MSG_DONTWAIT)
+                };
+                if len <= 0 {
+                    break;
+                }
+                events.extend(parse_uevent(&buf[..len as usize]));
+            }
+            Ok(events)
+        }
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            // SAFETY: the descriptor is owned by this watcher
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+
+    /// A uevent is `action@devpath` followed by NUL-separated `KEY=value`
+    /// pairs; only serial ports (`SUBSYSTEM=tty`) matter here.
+    fn parse_uevent(message: &[u8]) -> Option<HotplugEvent> {
+        let mut action = None;
+        let mut tty = false;
+        for field in message.split(|&b| b == 0) {
+            match field {
+                b"ACTION=add" => action = Some(HotplugEvent::Arrived),
+                b"ACTION=remove" => action = Some(HotplugEvent::Removed),
+                b"SUBSYSTEM=tty" => tty = true,
+                _ => {}
+            }
+        }
+        action.filter(|_| tty)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::HotplugEvent;
+    use std::cell::RefCell;
+    use std::ffi::c_void;
+
+    const WM_DEVICECHANGE: u32 = 0x0219;
+    const DBT_DEVICEARRIVAL: usize = 0x8000;
+    const DBT_DEVICEREMOVECOMPLETE: usize = 0x8004;
+    const DBT_DEVTYP_DEVICEINTERFACE: u32 = 5;
+    const DEVICE_NOTIFY_ALL_INTERFACE_CLASSES: u32 = 4;
+    const PM_REMOVE: u32 = 0x0001;
+    const WAIT: std::time::Duration = std::time::Duration::from_millis(100);
+
+    /// Parent handle that makes a message-only window.
+    const HWND_MESSAGE: isize = -3;
+
+    type WndProc = unsafe extern "system" fn(*mut c_void, u32, usize, isize) -> isize;
+
+    #[repr(C)]
+    struct WndClass {
+        style: u32,
+        wnd_proc: WndProc,
+        cls_extra: i32,
+        wnd_extra: i32,
+        instance: *mut c_void,
+        icon: *mut c_void,
+        cursor: *mut c_void,
+        background: *mut c_void,
+        menu_name: *const u16,
+        class_name: *const u16,
+    }
+
+    #[repr(C)]
+    struct Msg {
+        hwnd: *mut c_void,
+        message: u32,
+        wparam: usize,
+        lparam: isize,
+        time: u32,
+        pt_x: i32,
+        pt_y: i32,
+    }
+
+    #[repr(C)]
+    struct DevBroadcastInterface {
+        size: u32,
+        device_type: u32,
+        reserved: u32,
+        class_guid: [u8; 16],
+        name: [u16; 1],
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn RegisterClassW(class: *const WndClass) -> u16;
+        fn CreateWindowExW(
+            ex_style: u32,
+            class_name: *const u16,
+            window_name: *const u16,
+            style: u32,
+            x: i32,
+            y: i32,
+            width: i32,
+            height: i32,
+            parent: *mut c_void,
+            menu: *mut c_void,
+            instance: *mut c_void,
+            param: *mut c_void,
+        ) -> *mut c_void;
+        fn DestroyWindow(hwnd: *mut c_void) -> i32;
+        fn DefWindowProcW(hwnd: *mut c_void, msg: u32, wparam: usize, lparam: isize) -> isize;
+        fn PeekMessageW(msg: *mut Msg, hwnd: *mut c_void, min: u32, max: u32, remove: u32) -> i32;
+        fn DispatchMessageW(msg: *const Msg) -> isize;
+        fn RegisterDeviceNotificationW(
+            recipient: *mut c_void,
+            filter: *const c_void,
+            flags: u32,
+        ) -> *mut c_void;
+        fn UnregisterDeviceNotification(handle: *mut c_void) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleW(name: *const u16) -> *mut c_void;
+    }
+
+    thread_local! {
+        /// Filled by the window procedure, which runs on the watcher thread.
+        static EVENTS: RefCell<Vec<HotplugEvent>> = const { RefCell::new(Vec::new()) };
+    }
+
+    unsafe extern "system" fn wnd_proc(
+        hwnd: *mut c_void,
+        msg: u32,
+        wparam: usize,
+        lparam: isize,
+    ) -> isize {
+        if msg == WM_DEVICECHANGE {
+            let event = match wparam {
+                DBT_DEVICEARRIVAL => Some(HotplugEvent::Arrived),
+                DBT_DEVICEREMOVECOMPLETE => Some(HotplugEvent::Removed),
+                _ => None,
+            };
+            if let Some(event) = event {
+                EVENTS.with(|events| events.borrow_mut().push(event));
+            }
+            return 1;
+        }
+        DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+
+    /// A message-only window registered for device interface changes. It
+    /// must be polled from the thread that created it.
+    pub struct Watcher {
+        hwnd: *mut c_void,
+        notification: *mut c_void,
+    }
+
+    impl Watcher {
+        pub fn open() -> Result<Self, String> {
+            let class_name: Vec<u16> = "UsbPowerRelayHotplug\0".encode_utf16().collect();
+            // SAFETY: the class and filter structs outlive the calls that
+            // read them, and every handle is checked before use
+            unsafe {
+                let instance = GetModuleHandleW(std::ptr::null());
+                let class = WndClass {
+                    style: 0,
+                    wnd_proc,
+                    cls_extra: 0,
+                    wnd_extra: 0,
+                    instance,
+                    icon: std::ptr::null_mut(),
+                    cursor: std::ptr::null_mut(),
+                    background: std::ptr::null_mut(),
+                    menu_name: std::ptr::null(),
+                    class_name: class_name.as_ptr(),
+                };
+                // Fails harmlessly when a previous watcher registered it
+                RegisterClassW(&class);
+
+                let hwnd = CreateWindowExW(
+                    0,
+                    class_name.as_ptr(),
+                    std::ptr::null(),
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    HWND_MESSAGE as *mut c_void,
+                    std::ptr::null_mut(),
+                    instance,
+                    std::ptr::null_mut(),
+                );
+                if hwnd.is_null() {
+                    return Err("Could not create the device notification window".to_string());
+                }
+
+                let filter = DevBroadcastInterface {
+                    size: std::mem::size_of::<DevBroadcastInterface>() as u32,
+                    device_type: DBT_DEVTYP_DEVICEINTERFACE,
+                    reserved: 0,
+                    class_guid: [0; 16],
+                    name: [0],
+                };
+                let notification = RegisterDeviceNotificationW(
+                    hwnd,
+                    (&filter as *const DevBroadcastInterface).cast(),
+                    DEVICE_NOTIFY_ALL_INTERFACE_CLASSES,
+                );
+                if notification.is_null() {
+                    DestroyWindow(hwnd);
+                    return Err("Could not register for device notifications".to_string());
+                }
+                Ok(Self { hwnd, notification })
+            }
+        }
+
+        /// Pumps the window's messages and returns the events they carried.
+        pub fn wait(&mut self) -> Result<Vec<HotplugEvent>, String> {
+            // SAFETY: Msg matches the Win32 MSG layout; sent messages are
+            // delivered to wnd_proc on this thread while peeking
+            unsafe {
+                let mut msg: Msg = std::mem::zeroed();
+                while PeekMessageW(&mut msg, self.hwnd, 0, 0, PM_REMOVE) != 0 {
+                    DispatchMessageW(&msg);
+                }
+            }
+            let events = EVENTS.with(|events| std::mem::take(&mut *events.borrow_mut()));
+            if events.is_empty() {
+                std::thread::sleep(WAIT);
+            }
+            Ok(events)
+        }
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            // SAFETY: both handles are owned by this watcher
+            unsafe {
+                UnregisterDeviceNotification(self.notification);
+                DestroyWindow(self.hwnd);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use super::HotplugEvent;
+
+    pub struct Watcher;
+
+    impl Watcher {
+        pub fn open() -> Result<Self, String> {
+            Err("Hot-plug detection is not supported on this platform".to_string())
+        }
+
+        pub fn wait(&mut self) -> Result<Vec<HotplugEvent>, String> {
+            Ok(Vec::new())
+        }
+    }
+}
+
+// ============================================================================
+// WATCHER
+// ============================================================================
+
+/// Forwards hot-plug events to the device worker. Without OS support the
+/// subsystem stops and reconnects keep relying on the supervisor's backoff.
+pub fn run_hotplug(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+    supervisor: &Supervisor,
+    rules: &RuleEvents,
+) -> Result<(), String> {
+    let mut watcher = match platform::Watcher::open() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(());
+        }
+    };
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        for event in watcher.wait()? {
+            let _ = rules.send(RuleEvent::Usb(event));
+            match event {
+                HotplugEvent::Arrived => supervisor.retry_now("device"),
+                HotplugEvent::Removed => {
+                    let _ = commands.send((Command::Poll, Source::Device));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Minimal plain-HTTP client.
+//!
+//! Enough HTTP/1.1 for webhooks, the clock check and watchdogs: one request
+//! per connection and `http://` URLs only. [`request`] reads only the status
+//! line and headers of the response, [`request_with_body`] the body too, up
+//! to a limit.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// ============================================================================
+// URLS
+// ============================================================================
+
+struct HttpUrl<'a> {
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+fn parse_url(url: &str) -> Result<HttpUrl<'_>, String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("{}: only http:// URLs are supported", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| format!("{}: invalid port", url))?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(format!("{}: missing host", url));
+    }
+    Ok(HttpUrl { host, port, path })
+}
+
+// ============================================================================
+// REQUESTS
+// ============================================================================
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Empty unless read with [`request_with_body`].
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sends one request and reads the response head.
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    timeout: Duration,
+) -> Result<HttpResponse, String> {
+    send(method, url, headers, body, timeout).map(|(response, _)| response)
+}
+
+/// Like [`request`], but also reads up to `limit` bytes of the body.
+pub fn request_with_body(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    timeout: Duration,
+    limit: usize,
+) -> Result<HttpResponse, String> {
+    let (mut response, mut reader) = send(method, url, headers, &[], timeout)?;
+    let chunked = response
+        .header("Transfer-Encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+    let read = if chunked {
+        read_chunked(&mut reader, limit)
+    } else {
+        let mut body = Vec::new();
+        reader.take(limit as u64).read_to_end(&mut body).map(|_| body)
+    };
+    response.body = read.map_err(|e| format!("{}: {}", url, e))?;
+    Ok(response)
+}
+
+/// Reads a chunked body until its last chunk or `limit` bytes.
+fn read_chunked(reader: &mut impl BufRead, limit: usize) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    while body.len() < limit {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "bad chunk size"))?;
+        if size == 0 {
+            break;
+        }
+        let mut chunk = vec![0; size.min(limit - body.len())];
+        reader.read_exact(&mut chunk)?;
+        body.extend_from_slice(&chunk);
+        if chunk.len() < size {
+            break;
+        }
+        // The CRLF after the chunk
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+    Ok(body)
+}
+
+fn send(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    timeout: Duration,
+) -> Result<(HttpResponse, BufReader<TcpStream>), String> {
+    let parsed = parse_url(url)?;
+    let addr = (parsed.host, parsed.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("{}: {}", url, e))?
+        .next()
+        .ok_or_else(|| format!("{}: no address", url))?;
+    let mut stream =
+        TcpStream::connect_timeout(&addr, timeout).map_err(|e| format!("{}: {}", url, e))?;
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: usb-power-relay\r\nConnection: close\r\n",
+        method, parsed.path, parsed.host
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !body.is_empty() || method == "POST" {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|()| stream.write_all(body))
+        .map_err(|e| format!("{}: {}", url, e))?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| format!("{}: {}", url, e))?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| format!("{}: invalid response", url))?;
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| format!("{}: {}", url, e))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let response = HttpResponse {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    Ok((response, reader))
+}
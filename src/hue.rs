@@ -0,0 +1,309 @@
+//! Philips Hue bridge emulation.
+//!
+//! With `hue.enabled`, the app answers SSDP discovery on the LAN as a Hue
+//! bridge and serves the small part of the Hue API that voice assistants
+//! use, with each channel as an on/off plug named after its label. An Echo
+//! then finds the channels with "discover devices" and switches them by
+//! voice, without any cloud account or skill. Echo devices only look for
+//! bridges on port 80.
+//!
+//! | Method | Path                          | Effect                     |
+//! |--------|-------------------------------|----------------------------|
+//! | GET    | `/description.xml`            | UPnP description           |
+//! | POST   | `/api`                        | "Pairing", always accepted |
+//! | GET    | `/api/<user>/lights`          | Every exposed channel      |
+//! | GET    | `/api/<user>/lights/<n>`      | Channel `n`                |
+//! | PUT    | `/api/<user>/lights/<n>/state`| `{"on":true}` switches it  |
+
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream, UdpSocket};
+use std::time::Duration;
+
+use crate::api::{json_string, read_request, write_response, Request, Response};
+use crate::audit::Source;
+use crate::config::{Config, DeviceConfig};
+use crate::mdns::{host_name, local_ip_towards};
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+/// Search targets a Hue bridge answers to.
+const SEARCH_TARGETS: [&str; 3] =
+    ["ssdp:all", "upnp:rootdevice", "urn:schemas-upnp-org:device:basic:1"];
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// The username handed out on "pairing"; any username is accepted.
+const USERNAME: &str = "usb-power-relay";
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HueConfig {
+    pub enabled: bool,
+    /// Port of the emulated bridge; Echo devices only try 80.
+    pub port: u16,
+    /// Channels to expose; all of them when empty.
+    pub channels: Vec<u8>,
+}
+
+impl Default for HueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 80,
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl HueConfig {
+    fn exposes(&self, device: &DeviceConfig, channel: u8) -> bool {
+        (1..=device.channels).contains(&channel)
+            && (self.channels.is_empty() || self.channels.contains(&channel))
+    }
+}
+
+// ============================================================================
+// IDENTITY
+// ============================================================================
+
+/// A bridge serial derived from the host name, so it stays the same across
+/// restarts and differs between machines: `001788` and six hex digits.
+fn serial() -> String {
+    let name = host_name();
+    // FNV-1a, folded to 24 bits
+    let hash = name
+        .bytes()
+        .fold(0x811c_9dc5_u32, |h, b| (h ^ b as u32).wrapping_mul(0x0100_0193));
+    format!("001788{:06x}", (hash >> 24) ^ (hash & 0xff_ffff))
+}
+
+// ============================================================================
+// DISCOVERY
+// ============================================================================
+
+fn search_response(target: &str, ip: IpAddr, port: u16, serial: &str) -> String {
+    let usn = match target {
+        "upnp:rootdevice" => format!("uuid:2f402f80-da50-11e1-9b23-{}::upnp:rootdevice", serial),
+        _ => format!("uuid:2f402f80-da50-11e1-9b23-{}", serial),
+    };
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         HOST: {}:{}\r\n\
+         CACHE-CONTROL: max-age=100\r\n\
+         EXT:\r\n\
+         LOCATION: http://{}:{}/description.xml\r\n\
+         SERVER: Linux/3.14.0 UPnP/1.0 IpBridge/1.24.0\r\n\
+         hue-bridgeid: {}FFFE{}\r\n\
+         ST: {}\r\n\
+         USN: {}\r\n\r\n",
+        SSDP_ADDR,
+        SSDP_PORT,
+        ip,
+        port,
+        serial[..6].to_ascii_uppercase(),
+        serial[6..].to_ascii_uppercase(),
+        target,
+        usn
+    )
+}
+
+/// Answers SSDP searches for a Hue bridge while enabled. Exits cleanly when
+/// disabled.
+pub fn run_hue_discovery(ctx: &SubsystemContext) -> Result<(), String> {
+    let settings = Config::load()?.hue;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let socket = UdpSocket::bind(("0.0.0.0", SSDP_PORT))
+        .map_err(|e| format!("Failed to bind SSDP port {}: {}", SSDP_PORT, e))?;
+    socket
+        .join_multicast_v4(&SSDP_ADDR, &Ipv4Addr::UNSPECIFIED)
+        .map_err(|e| format!("Failed to join SSDP multicast group: {}", e))?;
+    let serial = serial();
+    ctx.mark_running();
+
+    let mut buf = [0u8; 2048];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).map_err(|e| format!("SSDP: {}", e))?;
+        let message = String::from_utf8_lossy(&buf[..len]);
+        if !message.starts_with("M-SEARCH") {
+            continue;
+        }
+        let target = message
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim().eq_ignore_ascii_case("st").then(|| value.trim().to_ascii_lowercase())
+            })
+            .unwrap_or_default();
+        if !SEARCH_TARGETS.contains(&target.as_str()) {
+            continue;
+        }
+        let Some(ip) = local_ip_towards(peer) else {
+            continue;
+        };
+        // An "all" search gets one answer per device type
+        let answers = match target.as_str() {
+            "ssdp:all" => SEARCH_TARGETS[1..].to_vec(),
+            target => vec![target],
+        };
+        for answer in answers {
+            let response = search_response(answer, ip, settings.port, &serial);
+            let _ = socket.send_to(response.as_bytes(), peer);
+        }
+    }
+}
+
+// ============================================================================
+// BRIDGE API
+// ============================================================================
+
+fn description(ip: IpAddr, port: u16, serial: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" ?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <URLBase>http://{ip}:{port}/</URLBase>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType>
+    <friendlyName>USB Power Relay ({ip})</friendlyName>
+    <manufacturer>Royal Philips Electronics</manufacturer>
+    <manufacturerURL>http://www.philips.com</manufacturerURL>
+    <modelDescription>Philips hue Personal Wireless Lighting</modelDescription>
+    <modelName>Philips hue bridge 2015</modelName>
+    <modelNumber>BSB002</modelNumber>
+    <modelURL>http://www.meethue.com</modelURL>
+    <serialNumber>{serial}</serialNumber>
+    <UDN>uuid:2f402f80-da50-11e1-9b23-{serial}</UDN>
+  </device>
+</root>
+"#
+    )
+}
+
+fn light_json(device: &DeviceConfig, channel: u8, state: RelayState, serial: &str) -> String {
+    format!(
+        "{{\"state\":{{\"on\":{},\"reachable\":{},\"alert\":\"none\",\"mode\":\"homeautomation\"}},\
+         \"type\":\"On/Off plug-in unit\",\"name\":{},\"modelid\":\"LOM001\",\
+         \"manufacturername\":\"Philips\",\"productname\":\"On/Off plug\",\
+         \"uniqueid\":\"00:17:88:01:{}:{}:{}:{:02x}-0b\",\"swversion\":\"1.0\"}}",
+        state == RelayState::On,
+        state != RelayState::Error,
+        json_string(&device.label(channel)),
+        &serial[6..8],
+        &serial[8..10],
+        &serial[10..12],
+        channel
+    )
+}
+
+fn lights(state: &AppState, device: &DeviceConfig, settings: &HueConfig, serial: &str) -> String {
+    let lights: Vec<String> = state
+        .snapshot()
+        .relay_states
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (i as u8 + 1, *s))
+        .filter(|(channel, _)| settings.exposes(device, *channel))
+        .map(|(channel, s)| format!("\"{}\":{}", channel, light_json(device, channel, s, serial)))
+        .collect();
+    format!("{{{}}}", lights.join(","))
+}
+
+/// Switches a channel from a `{"on":true}` body.
+fn set_state(state: &AppState, request: &Request, channel: u8) -> Response {
+    if state.read_only {
+        return Response::error(403, "Monitor mode is read-only");
+    }
+    let body: String = request.body.chars().filter(|c| !c.is_whitespace()).collect();
+    let (cmd, on) = if body.contains("\"on\":true") {
+        (Command::TurnOn(channel), true)
+    } else if body.contains("\"on\":false") {
+        (Command::TurnOff(channel), false)
+    } else {
+        return Response::error(400, "Expected {\"on\":true} or {\"on\":false}");
+    };
+    state.send_command(cmd, Source::Hue(request.client.clone()));
+    let success = format!("[{{\"success\":{{\"/lights/{}/state/on\":{}}}}}]", channel, on);
+    Response::json(200, success)
+}
+
+fn route(state: &AppState, request: &Request, local_ip: IpAddr, serial: &str) -> Response {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => return Response::error(500, &e),
+    };
+    let settings = &config.hue;
+    let device = &config.device;
+    let segments: Vec<&str> = request.bare_path().trim_matches('/').split('/').collect();
+    let light = |id: &str| id.parse::<u8>().ok().filter(|&c| settings.exposes(device, c));
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["description.xml"]) => Response::xml(description(local_ip, settings.port, serial)),
+        ("POST", ["api"]) => {
+            Response::json(200, format!("[{{\"success\":{{\"username\":\"{}\"}}}}]", USERNAME))
+        }
+        ("GET", ["api", _]) => {
+            let lights = lights(state, device, settings, serial);
+            Response::json(200, format!("{{\"lights\":{}}}", lights))
+        }
+        ("GET", ["api", _, "lights"]) => {
+            Response::json(200, lights(state, device, settings, serial))
+        }
+        ("GET", ["api", _, "lights", id]) => match light(id) {
+            Some(channel) => {
+                let states = state.snapshot().relay_states;
+                let relay_state = states.get(channel as usize - 1).copied();
+                let relay_state = relay_state.unwrap_or(RelayState::Unknown);
+                Response::json(200, light_json(device, channel, relay_state, serial))
+            }
+            None => Response::error(404, "No such light"),
+        },
+        ("PUT", ["api", _, "lights", id, "state"]) => match light(id) {
+            Some(channel) => set_state(state, request, channel),
+            None => Response::error(404, "No such light"),
+        },
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+fn handle(state: &AppState, stream: TcpStream, serial: &str) {
+    let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+    let local_ip = stream.local_addr().map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |a| a.ip());
+
+    let response = match read_request(&stream) {
+        Ok(request) => route(state, &request, local_ip, serial),
+        Err(response) => response,
+    };
+    let _ = write_response(&stream, &response);
+}
+
+/// Serves the emulated bridge while enabled. Exits cleanly when disabled.
+pub fn run_hue(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    let settings = Config::load()?.hue;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let listener = TcpListener::bind(("0.0.0.0", settings.port))
+        .map_err(|e| format!("Failed to bind port {}: {}", settings.port, e))?;
+    let serial = serial();
+    ctx.mark_running();
+
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| format!("Accept failed: {}", e))?;
+        let state = state.clone();
+        let serial = serial.clone();
+        std::thread::spawn(move || handle(&state, stream, &serial));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! Translations.
+//!
+//! UI text is looked up by message id in the bundle of the chosen language,
+//! or of the system locale (`LC_ALL`/`LC_MESSAGES`/`LANG` on Unix, the user
+//! locale on Windows). Messages a translation lacks fall back to English.
+//!
+//! Bundles use a subset of Fluent's `.ftl` syntax: `id = text` messages,
+//! `#` comments, indented continuation lines and `{ $name }` placeables.
+//! English and German are built in; a `locales/<language>.ftl` file in the
+//! config directory adds a language, or overrides messages of a built-in
+//! one, without recompiling. Files are named after a language (`fr`) or a
+//! language and region (`pt-BR`); the region-specific file wins.
+//!
+//! Until [`set_language`] is called everything is English, so the CLI's
+//! output stays the same on every system.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+
+use crate::config::Config;
+use crate::locale;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const ENGLISH: &str = include_str!("../locales/en.ftl");
+
+/// Built-in bundles by language tag.
+const BUILT_IN: &[(&str, &str)] = &[("en", ENGLISH), ("de", include_str!("../locales/de.ftl"))];
+
+/// The message each bundle names its language with.
+const LANGUAGE_NAME: &str = "language-name";
+
+// ============================================================================
+// BUNDLE
+// ============================================================================
+
+struct Bundle {
+    /// What [`set_language`] was last called with; `None` before that.
+    requested: Option<Option<String>>,
+    messages: HashMap<String, String>,
+}
+
+static BUNDLE: LazyLock<RwLock<Bundle>> = LazyLock::new(|| {
+    RwLock::new(Bundle {
+        requested: None,
+        messages: parse("en.ftl", ENGLISH),
+    })
+});
+
+/// Switches to `language` (a tag like `de`), or to the system locale's
+/// language for `None`. Cheap when nothing changed, so the GUI calls it
+/// every frame to follow the settings.
+pub fn set_language(language: Option<&str>) {
+    let requested = Some(language.map(str::to_string));
+    if BUNDLE.read().is_ok_and(|bundle| bundle.requested == requested) {
+        return;
+    }
+    let mut messages = parse("en.ftl", ENGLISH);
+    let tag = language
+        .map(str::to_string)
+        .or_else(|| locale::system_locale("LC_MESSAGES"));
+    if let Some(found) = tag.and_then(|tag| candidates(&tag).iter().find_map(|c| load(c))) {
+        messages.extend(found);
+    }
+    if let Ok(mut bundle) = BUNDLE.write() {
+        *bundle = Bundle { requested, messages };
+    }
+}
+
+/// The text of message `id`; the id itself if no bundle has it.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+/// The text of message `id` with its `{ $name }` placeables filled in.
+pub fn tr_args(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let Ok(bundle) = BUNDLE.read() else {
+        return id.to_string();
+    };
+    match bundle.messages.get(id) {
+        Some(pattern) => format(pattern, args),
+        None => id.to_string(),
+    }
+}
+
+/// The languages that have a bundle, as `(tag, name)`, sorted by tag.
+pub fn languages() -> Vec<(String, String)> {
+    let mut tags: Vec<String> = BUILT_IN.iter().map(|(tag, _)| tag.to_string()).collect();
+    if let Some(entries) = locales_dir().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "ftl") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    tags.push(stem.to_string());
+                }
+            }
+        }
+    }
+    tags.sort();
+    tags.dedup();
+    tags.into_iter()
+        .map(|tag| {
+            let name = load(&tag)
+                .and_then(|mut messages| messages.remove(LANGUAGE_NAME))
+                .unwrap_or_else(|| tag.clone());
+            (tag, name)
+        })
+        .collect()
+}
+
+/// Where translation files are looked for.
+pub fn locales_dir() -> Option<PathBuf> {
+    Some(Config::path().ok()?.parent()?.join("locales"))
+}
+
+/// `de_DE.UTF-8` or `de-DE` → `["de-DE", "de"]`.
+fn candidates(locale: &str) -> Vec<String> {
+    let name = locale.split(['.', '@']).next().unwrap_or("");
+    let mut parts = name.split(['_', '-']);
+    let language = parts.next().unwrap_or("").to_ascii_lowercase();
+    if language.is_empty() {
+        return Vec::new();
+    }
+    match parts.next_back() {
+        Some(region) => vec![format!("{}-{}", language, region.to_ascii_uppercase()), language],
+        None => vec![language],
+    }
+}
+
+/// The messages for `tag`: the built-in bundle with the user's file over
+/// it. `None` if there is neither.
+fn load(tag: &str) -> Option<HashMap<String, String>> {
+    let built_in = BUILT_IN.iter().find(|(t, _)| *t == tag);
+    let path = locales_dir().map(|dir| dir.join(format!("{}.ftl", tag)));
+    let file = path.as_ref().and_then(|path| match std::fs::read_to_string(path) {
+        Ok(text) => Some((path.display().to_string(), text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            None
+        }
+    });
+    if built_in.is_none() && file.is_none() {
+        return None;
+    }
+
+    let mut messages = HashMap::new();
+    if let Some((tag, text)) = built_in {
+        messages.extend(parse(&format!("{}.ftl", tag), text));
+    }
+    if let Some((name, text)) = file {
+        messages.extend(parse(&name, &text));
+    }
+    Some(messages)
+}
+
+// ============================================================================
+// SYNTAX
+// ============================================================================
+
+/// Parses the messages of a bundle; `name` is for warnings about lines
+/// that aren't messages.
+fn parse(name: &str, text: &str) -> HashMap<String, String> {
+    let mut messages: HashMap<String, String> = HashMap::new();
+    let mut last: Option<String> = None;
+    for (n, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            last = None;
+            continue;
+        }
+        // An indented line continues the message above it
+        if line.starts_with([' ', '\t']) {
+            if let Some(value) = last.as_ref().and_then(|id| messages.get_mut(id)) {
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(line.trim());
+                continue;
+            }
+        }
+        match line.split_once('=') {
+            Some((id, value)) if is_identifier(id.trim()) => {
+                let id = id.trim().to_string();
+                messages.insert(id.clone(), value.trim().to_string());
+                last = Some(id);
+            }
+            _ => {
+                eprintln!("{}:{}: not a message: {}", name, n + 1, line.trim());
+                last = None;
+            }
+        }
+    }
+    messages
+}
+
+fn is_identifier(id: &str) -> bool {
+    let mut chars = id.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Fills in `{ $name }` placeables; `{ "{" }` is a literal brace. Unknown
+/// placeables are left as they are.
+fn format(pattern: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        let placeable = &rest[start..=start + len];
+        let inner = placeable[1..placeable.len() - 1].trim();
+        let literal = inner.strip_prefix('"').and_then(|s| s.strip_suffix('"'));
+        let arg = inner
+            .strip_prefix('$')
+            .and_then(|name| args.iter().find(|(arg, _)| *arg == name));
+        match (literal, arg) {
+            (Some(literal), _) => text.push_str(literal),
+            (None, Some((_, value))) => text.push_str(&value.to_string()),
+            (None, None) => text.push_str(placeable),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    text.push_str(rest);
+    text
+}
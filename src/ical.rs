@@ -0,0 +1,495 @@
+//! iCalendar import.
+//!
+//! Turns the events of an `.ics` file into schedules: an ON at each event's
+//! start and an OFF at its end, on the channel the event is named after (its
+//! label, `CH 3` or `3`) or a default one. Daily and weekly repeating events
+//! become weekday schedules, others one-off schedules for their date.
+//!
+//! Schedules keep the event's time zone: UTC times become `UTC` schedules
+//! and times with a `TZID` schedules in that zone, so repeats stay at the
+//! same wall-clock time across DST changes. Times without a zone are local.
+//! Repeats every other week or month, and the end of a repeat (`UNTIL`,
+//! `COUNT`), can't be expressed as schedules; such events are skipped or
+//! noted.
+
+use std::collections::HashMap;
+
+use crate::config::DeviceConfig;
+use crate::schedule::{Date, Schedule, ScheduleAction, TimeOfDay, Weekday};
+use crate::tz::TimeZone;
+
+// ============================================================================
+// RESULT
+// ============================================================================
+
+/// What one calendar file brings in.
+#[derive(Debug, Default)]
+pub struct CalendarImport {
+    /// New schedules, without ids yet.
+    pub schedules: Vec<Schedule>,
+    /// Events that couldn't be imported, with the reason.
+    pub skipped: Vec<String>,
+    /// Events imported with a caveat.
+    pub notes: Vec<String>,
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+/// A local date and time, or a whole day.
+#[derive(Debug, Clone, Copy)]
+struct Moment {
+    date: Date,
+    time: TimeOfDay,
+}
+
+impl Moment {
+    fn minutes(&self) -> i64 {
+        self.date.days() * 1440 + i64::from(self.time.hour) * 60 + i64::from(self.time.minute)
+    }
+
+    /// The same instant in another zone.
+    fn convert(&self, from: &TimeZone, to: &TimeZone) -> Self {
+        let local = to.local(from.resolve(self.date, self.time));
+        Self {
+            date: local.date(),
+            time: TimeOfDay { hour: local.hour, minute: local.minute },
+        }
+    }
+
+    fn from_minutes(minutes: i64) -> Self {
+        let of_day = minutes.rem_euclid(1440);
+        Self {
+            date: Date::from_days(minutes.div_euclid(1440)),
+            time: TimeOfDay { hour: (of_day / 60) as u8, minute: (of_day % 60) as u8 },
+        }
+    }
+}
+
+/// A `DTSTART`-style value.
+struct Stamp {
+    at: Moment,
+    all_day: bool,
+    /// `UTC` or the `TZID`; `None` for local time.
+    zone: Option<String>,
+}
+
+/// The properties of one `VEVENT`: parameters and value by name.
+struct Event {
+    properties: HashMap<String, (String, String)>,
+}
+
+impl Event {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(|(_, value)| value.as_str())
+    }
+
+    fn name(&self) -> String {
+        self.get("SUMMARY").map_or_else(|| "(untitled)".to_string(), unescape)
+    }
+
+    fn stamp(&self, name: &str) -> Result<Option<Stamp>, String> {
+        match self.properties.get(name) {
+            Some((params, value)) => parse_stamp(params, value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Unfolds continuation lines and collects the events.
+fn events(text: &str) -> Vec<Event> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut current: Option<Event> = None;
+    for line in &lines {
+        match line.trim_end() {
+            "BEGIN:VEVENT" => current = Some(Event { properties: HashMap::new() }),
+            "END:VEVENT" => events.extend(current.take()),
+            line => {
+                let (Some(event), Some((key, value))) = (current.as_mut(), line.split_once(':'))
+                else {
+                    continue;
+                };
+                let (name, params) = key.split_once(';').unwrap_or((key, ""));
+                let entry = (params.to_string(), value.to_string());
+                event.properties.entry(name.to_ascii_uppercase()).or_insert(entry);
+            }
+        }
+    }
+    events
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// `20240105`, `20240105T083000` or `20240105T083000Z`, with `TZID=` among
+/// the parameters for a zoned time.
+fn parse_stamp(params: &str, value: &str) -> Result<Stamp, String> {
+    let invalid = || format!("invalid date '{}'", value);
+    let number = |range: std::ops::Range<usize>| -> Result<u32, String> {
+        value.get(range).and_then(|s| s.parse().ok()).ok_or_else(invalid)
+    };
+    let date = Date {
+        year: number(0..4)? as i32,
+        month: number(4..6)? as u8,
+        day: number(6..8)? as u8,
+    };
+    // Parameter names are case-insensitive, zone names aren't
+    let upper = params.to_ascii_uppercase();
+    if value.len() == 8 || upper.contains("VALUE=DATE") {
+        let at = Moment { date, time: TimeOfDay { hour: 0, minute: 0 } };
+        return Ok(Stamp { at, all_day: true, zone: None });
+    }
+    if value.as_bytes().get(8) != Some(&b'T') {
+        return Err(invalid());
+    }
+    let time = TimeOfDay { hour: number(9..11)? as u8, minute: number(11..13)? as u8 };
+    let zone = if value.ends_with('Z') {
+        Some("UTC".to_string())
+    } else {
+        upper.find("TZID=").map(|at| {
+            let id = &params[at + 5..];
+            id.split(';').next().unwrap_or("").trim_matches('"').to_string()
+        })
+    };
+    Ok(Stamp { at: Moment { date, time }, all_day: false, zone })
+}
+
+/// `PT1H30M`, `P1D`, `P2W`, in minutes.
+fn parse_duration(value: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid duration '{}'", value);
+    let rest = value.trim_start_matches('+').strip_prefix('P').ok_or_else(invalid)?;
+    let mut minutes = 0;
+    let mut number = String::new();
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: i64 = if c == 'T' { 0 } else { number.parse().map_err(|_| invalid())? };
+        number.clear();
+        minutes += match c {
+            'W' => n * 7 * 1440,
+            'D' => n * 1440,
+            'H' => n * 60,
+            'M' => n,
+            'S' => n / 60,
+            'T' => 0,
+            _ => return Err(invalid()),
+        };
+    }
+    Ok(minutes)
+}
+
+// ============================================================================
+// CONVERSION
+// ============================================================================
+
+/// The channel an event is named after: a label, `CH <n>` or a number.
+fn channel_for(name: &str, device: &DeviceConfig) -> Option<u8> {
+    let name = name.trim();
+    let labeled = (1..=device.channels).find(|&c| device.label(c).eq_ignore_ascii_case(name));
+    if labeled.is_some() {
+        return labeled;
+    }
+    let lower = name.to_ascii_lowercase();
+    let number = lower
+        .strip_prefix("channel")
+        .or_else(|| lower.strip_prefix("ch"))
+        .unwrap_or(&lower);
+    number.trim().parse().ok().filter(|c| (1..=device.channels).contains(c))
+}
+
+/// The weekdays from `BYDAY=MO,WE`; `None` if a day has a position like `1MO`.
+fn by_day(list: &str) -> Option<Vec<Weekday>> {
+    const CODES: [&str; 7] = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+    list.split(',')
+        .map(|code| CODES.iter().position(|c| *c == code.trim()).map(|i| Weekday::ALL[i]))
+        .collect()
+}
+
+/// The weekdays a repeating event falls on (empty for every day), or why
+/// schedules can't express its repeat.
+fn repeat_days(rule: &HashMap<&str, &str>, start: Date) -> Result<Vec<Weekday>, String> {
+    if rule.get("INTERVAL").is_some_and(|n| *n != "1") {
+        return Err("repeats at an interval schedules can't express".to_string());
+    }
+    let days = match rule.get("BYDAY") {
+        Some(list) => Some(by_day(list).ok_or("repeats on numbered weekdays")?),
+        None => None,
+    };
+    match (rule.get("FREQ").copied(), days) {
+        (Some("DAILY"), days) => Ok(days.unwrap_or_default()),
+        (Some("WEEKLY"), days) => Ok(days.unwrap_or_else(|| vec![start.weekday()])),
+        (freq, _) => Err(format!(
+            "repeats {}, only daily and weekly repeats are supported",
+            freq.unwrap_or("without a frequency").to_ascii_lowercase()
+        )),
+    }
+}
+
+/// Converts every event of `text`; events in the past are left out.
+pub fn import(
+    text: &str,
+    device: &DeviceConfig,
+    default_channel: Option<u8>,
+    source: &str,
+    today: Date,
+) -> CalendarImport {
+    let mut import = CalendarImport::default();
+    for event in events(text) {
+        let name = event.name();
+        match convert(&event, device, default_channel, source, today, &mut import.notes) {
+            Ok(schedules) => import.schedules.extend(schedules),
+            Err(reason) => import.skipped.push(format!("'{}': {}", name, reason)),
+        }
+    }
+    import
+}
+
+/// The ON and OFF schedules for one event; none for one in the past.
+fn convert(
+    event: &Event,
+    device: &DeviceConfig,
+    default_channel: Option<u8>,
+    source: &str,
+    today: Date,
+    notes: &mut Vec<String>,
+) -> Result<Vec<Schedule>, String> {
+    let name = event.name();
+    if event.get("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED")) {
+        return Err("cancelled".to_string());
+    }
+    let start_stamp = event.stamp("DTSTART")?.ok_or("no start")?;
+    let (start, all_day) = (start_stamp.at, start_stamp.all_day);
+    // Zones the system doesn't know fall back to local time
+    let zone_of = |name: &Option<String>| match name {
+        Some(name) => TimeZone::named(name).ok(),
+        None => Some(TimeZone::System),
+    };
+    let (zone, timezone) = match zone_of(&start_stamp.zone) {
+        Some(zone) => (zone, start_stamp.zone.clone()),
+        None => {
+            let unknown = start_stamp.zone.as_deref().unwrap_or("");
+            notes.push(format!("'{}': unknown time zone '{}', using local time", name, unknown));
+            (TimeZone::System, None)
+        }
+    };
+    let end = match (event.stamp("DTEND")?, event.get("DURATION")) {
+        (Some(end), _) if end.zone == timezone => end.at,
+        (Some(end), _) => end.at.convert(&zone_of(&end.zone).unwrap_or(TimeZone::System), &zone),
+        (None, Some(duration)) => Moment::from_minutes(start.minutes() + parse_duration(duration)?),
+        (None, None) if all_day => Moment::from_minutes(start.minutes() + 1440),
+        (None, None) => return Err("no end".to_string()),
+    };
+    if end.minutes() <= start.minutes() {
+        return Err("ends before it starts".to_string());
+    }
+
+    let rule: Option<HashMap<&str, &str>> =
+        event.get("RRULE").map(|r| r.split(';').filter_map(|p| p.split_once('=')).collect());
+    let (on_days, off_days, on_date, off_date) = match &rule {
+        Some(rule) => {
+            if let Some(until) = rule.get("UNTIL") {
+                if parse_stamp("", until)?.at.date < today {
+                    return Ok(Vec::new());
+                }
+            }
+            if rule.contains_key("UNTIL") || rule.contains_key("COUNT") {
+                notes.push(format!("'{}': the schedules keep repeating after it ends", name));
+            }
+            let days = repeat_days(rule, start.date)?;
+            // An event past midnight switches off on the following days
+            let shift = end.date.days() - start.date.days();
+            let shifted = days
+                .iter()
+                .map(|d| Weekday::ALL[(d.index() as i64 + shift).rem_euclid(7) as usize])
+                .collect();
+            (days, shifted, None, None)
+        }
+        None if end.date < today => return Ok(Vec::new()),
+        None => (Vec::new(), Vec::new(), Some(start.date), Some(end.date)),
+    };
+
+    let channel = channel_for(&name, device)
+        .or(default_channel)
+        .ok_or("no channel of that name; pass --channel for a default")?;
+    let schedule = |time, days, date, action| Schedule {
+        id: 0,
+        time,
+        days,
+        date,
+        action,
+        timezone: timezone.clone(),
+        enabled: true,
+        snoozed_until: None,
+        source: Some(source.to_string()),
+    };
+    Ok(vec![
+        schedule(start.time, on_days, on_date, ScheduleAction::On(channel)),
+        schedule(end.time, off_days, off_date, ScheduleAction::Off(channel)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BERLIN: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    const TODAY: Date = Date { year: 2026, month: 10, day: 14 };
+
+    fn run(events: &[&[&str]]) -> CalendarImport {
+        let mut text = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n");
+        for lines in events {
+            text.push_str("BEGIN:VEVENT\r\n");
+            for line in lines.iter() {
+                text.push_str(line);
+                text.push_str("\r\n");
+            }
+            text.push_str("END:VEVENT\r\n");
+        }
+        text.push_str("END:VCALENDAR\r\n");
+        let mut device = DeviceConfig { channels: 4, ..Default::default() };
+        device.channel_mut(2).label = "Heater".to_string();
+        import(&text, &device, Some(1), "test.ics", TODAY)
+    }
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date { year, month, day }
+    }
+
+    fn at(hour: u8, minute: u8) -> TimeOfDay {
+        TimeOfDay { hour, minute }
+    }
+
+    #[test]
+    fn unfolds_lines_and_finds_the_channel() {
+        let import = run(&[&[
+            "SUMMARY:Hea",
+            " ter",
+            "DTSTART:20261020T083000",
+            "DTEND:20261020T100000",
+        ]]);
+        assert!(import.skipped.is_empty());
+        let [on, off] = &import.schedules[..] else { panic!("{:?}", import.schedules) };
+        assert_eq!(on.action, ScheduleAction::On(2));
+        assert_eq!(off.action, ScheduleAction::Off(2));
+        assert_eq!((on.time, on.date), (at(8, 30), Some(date(2026, 10, 20))));
+        assert_eq!((off.time, off.date), (at(10, 0), Some(date(2026, 10, 20))));
+        assert_eq!(on.source.as_deref(), Some("test.ics"));
+    }
+
+    #[test]
+    fn keeps_the_event_zone() {
+        let zoned = format!("DTSTART;TZID={}:20261020T183000", BERLIN);
+        let zoned_end = format!("DTEND;TZID=\"{}\":20261020T200000", BERLIN);
+        let import = run(&[
+            &[&zoned, &zoned_end],
+            &["DTSTART:20261020T080000Z", "DTEND:20261020T090000Z"],
+            &["DTSTART:20261020T080000", "DTEND:20261020T090000"],
+        ]);
+        let zones: Vec<_> = import.schedules.iter().map(|s| s.timezone.as_deref()).collect();
+        assert_eq!(zones, [Some(BERLIN), Some(BERLIN), Some("UTC"), Some("UTC"), None, None]);
+        assert_eq!(import.schedules[0].time, at(18, 30));
+        assert_eq!(import.schedules[2].time, at(8, 0));
+    }
+
+    #[test]
+    fn converts_an_end_in_another_zone() {
+        // 11:00 summer time in Berlin is 09:00 UTC
+        let end = format!("DTEND;TZID={}:20261020T110000", BERLIN);
+        let import = run(&[&["DTSTART:20261020T080000Z", &end]]);
+        assert_eq!(import.schedules[1].time, at(9, 0));
+        assert_eq!(import.schedules[1].timezone.as_deref(), Some("UTC"));
+    }
+
+    #[test]
+    fn ends_by_duration_unless_there_is_an_end() {
+        let import = run(&[
+            &["DTSTART:20261020T083000", "DURATION:PT1H45M"],
+            &["DTSTART:20261020T083000", "DTEND:20261020T090000", "DURATION:PT5H"],
+            &["DTSTART:20261020T230000", "DURATION:P1DT1H"],
+        ]);
+        let offs = import.schedules.iter().skip(1).step_by(2);
+        let ends: Vec<_> = offs.map(|s| (s.time, s.date)).collect();
+        assert_eq!(
+            ends,
+            [
+                (at(10, 15), Some(date(2026, 10, 20))),
+                (at(9, 0), Some(date(2026, 10, 20))),
+                (at(0, 0), Some(date(2026, 10, 22))),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_day_events_last_until_midnight() {
+        let import = run(&[&["DTSTART;VALUE=DATE:20261020"]]);
+        let [on, off] = &import.schedules[..] else { panic!("{:?}", import.schedules) };
+        assert_eq!((on.time, on.date), (at(0, 0), Some(date(2026, 10, 20))));
+        assert_eq!((off.time, off.date), (at(0, 0), Some(date(2026, 10, 21))));
+    }
+
+    #[test]
+    fn overnight_repeats_switch_off_the_next_day() {
+        let import = run(&[&[
+            "DTSTART:20261016T220000",
+            "DTEND:20261017T060000",
+            "RRULE:FREQ=WEEKLY;BYDAY=FR,SA",
+        ]]);
+        let [on, off] = &import.schedules[..] else { panic!("{:?}", import.schedules) };
+        assert_eq!(on.days, [Weekday::Fri, Weekday::Sat]);
+        assert_eq!(off.days, [Weekday::Sat, Weekday::Sun]);
+        assert_eq!((on.date, off.date), (None, None));
+        assert!(import.notes.is_empty());
+    }
+
+    #[test]
+    fn leaves_out_what_has_ended() {
+        const START: &str = "DTSTART:20250101T080000";
+        let import = run(&[
+            &[START, "DTEND:20250101T090000"],
+            &[START, "DURATION:PT1H", "RRULE:FREQ=DAILY;UNTIL=20260101T000000Z"],
+            &[START, "DURATION:PT1H", "RRULE:FREQ=DAILY;UNTIL=20270101"],
+        ]);
+        assert!(import.skipped.is_empty());
+        assert_eq!(import.schedules.len(), 2);
+        assert!(import.schedules[0].days.is_empty());
+        assert_eq!(import.notes.len(), 1);
+        assert!(import.notes[0].contains("keep repeating"), "{:?}", import.notes);
+    }
+
+    #[test]
+    fn skips_what_schedules_cannot_express() {
+        const START: &str = "DTSTART:20261102T080000";
+        let import = run(&[
+            &["SUMMARY:First Monday", START, "DURATION:PT1H", "RRULE:FREQ=WEEKLY;BYDAY=1MO"],
+            &["SUMMARY:Biweekly", START, "DURATION:PT1H", "RRULE:FREQ=WEEKLY;INTERVAL=2"],
+            &["SUMMARY:Off", "STATUS:CANCELLED", START, "DURATION:PT1H"],
+            &["SUMMARY:Open", START],
+        ]);
+        assert!(import.schedules.is_empty());
+        assert_eq!(
+            import.skipped,
+            [
+                "'First Monday': repeats on numbered weekdays",
+                "'Biweekly': repeats at an interval schedules can't express",
+                "'Off': cancelled",
+                "'Open': no end",
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Importing setups from other relay tools.
+//!
+//! Reads the files people typically bring along and maps them onto the
+//! device settings. Every line is recognized on its own, so mixed files work:
+//!
+//! | Source | Example line | Imported as |
+//! |--------|--------------|-------------|
+//! | udev rule (usbrelay naming) | `SUBSYSTEM=="tty", ATTRS{serial}=="A5", SYMLINK+="relay"` | Board binding |
+//! | usbrelay status | `PSUIS_2=1` | Channel count |
+//! | CommandApp call | `CommandApp_USBRelay.exe PSUIS open 03` | Channel count |
+//! | Label export | `3=Desk lamp` or `3,Desk lamp` | Channel label |
+//!
+//! Anything else, such as comments and shell boilerplate, is skipped.
+
+use std::collections::BTreeMap;
+
+use crate::config::{DeviceBinding, DeviceConfig};
+
+// ============================================================================
+// RESULT
+// ============================================================================
+
+/// Everything recognized in one file.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// Highest channel number seen.
+    pub channels: Option<u8>,
+    pub labels: BTreeMap<u8, String>,
+    pub binding: Option<DeviceBinding>,
+    /// udev rules for further boards; only one board is driven.
+    pub extra_boards: usize,
+    /// Lines that matched no known format.
+    pub skipped: usize,
+}
+
+impl Import {
+    fn see_channel(&mut self, channel: u8) {
+        self.channels = Some(self.channels.map_or(channel, |c| c.max(channel)));
+    }
+
+    /// Applies the import to `device` and describes each change. The
+    /// channel count only ever grows.
+    pub fn apply(&self, device: &mut DeviceConfig) -> Vec<String> {
+        let mut changes = Vec::new();
+        let needed = self.channels.max(self.labels.keys().next_back().copied());
+        if let Some(channels) = needed.filter(|&c| c > device.channels) {
+            changes.push(format!("Channels: {} -> {}", device.channels, channels));
+            device.channels = channels;
+        }
+        for (&channel, label) in &self.labels {
+            let settings = device.channel_mut(channel);
+            if settings.label != *label {
+                changes.push(format!("CH {}: label '{}'", channel, label));
+                settings.label = label.clone();
+            }
+        }
+        if let Some(binding) = self.binding.as_ref().filter(|b| **b != device.bind) {
+            changes.push(format!("Board: {}", binding.describe()));
+            device.bind = binding.clone();
+        }
+        changes
+    }
+}
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+/// Recognizes every line of `text`.
+pub fn parse(text: &str) -> Import {
+    let mut import = Import::default();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(binding) = parse_udev_rule(line) {
+            if import.binding.is_none() {
+                import.binding = Some(binding);
+            } else {
+                import.extra_boards += 1;
+            }
+        } else if let Some(channel) =
+            parse_usbrelay_status(line).or_else(|| parse_commandapp(line))
+        {
+            import.see_channel(channel);
+        } else if let Some((channel, label)) = parse_label(line) {
+            import.labels.insert(channel, label);
+        } else {
+            import.skipped += 1;
+        }
+    }
+    import
+}
+
+/// A udev rule that names a serial device, e.g.
+/// `SUBSYSTEM=="tty", ATTRS{idVendor}=="1a86", ATTRS{serial}=="A5", SYMLINK+="relay"`.
+/// The serial number and IDs bind the board; without a serial number the
+/// symlink pins it instead.
+fn parse_udev_rule(line: &str) -> Option<DeviceBinding> {
+    let mut binding = DeviceBinding::default();
+    let mut symlink = None;
+    for part in line.split(',') {
+        let part = part.trim();
+        let operator = ["==", "+=", ":=", "="]
+            .iter()
+            .find_map(|op| part.find(op).map(|i| (i, op.len())));
+        let Some((at, len)) = operator else {
+            continue;
+        };
+        let key = part[..at].trim();
+        let value = part[at + len..].trim().trim_matches('"');
+        match key {
+            "ATTRS{idVendor}" => binding.vid = u16::from_str_radix(value, 16).ok(),
+            "ATTRS{idProduct}" => binding.pid = u16::from_str_radix(value, 16).ok(),
+            "ATTRS{serial}" | "ENV{ID_SERIAL_SHORT}" => {
+                binding.serial_number = Some(value.to_string());
+            }
+            // The first name only; udev allows several separated by spaces
+            "SYMLINK" => symlink = value.split_whitespace().next().map(str::to_string),
+            _ => {}
+        }
+    }
+    let symlink = symlink?;
+    if binding.serial_number.is_none() {
+        binding.port = Some(format!("/dev/{}", symlink));
+    }
+    Some(binding)
+}
+
+/// A line of `usbrelay` output: `<board>_<relay>=<0|1>`.
+fn parse_usbrelay_status(line: &str) -> Option<u8> {
+    let (name, state) = line.split_once('=')?;
+    let (board, relay) = name.rsplit_once('_')?;
+    let valid_board = !board.is_empty() && board.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid_board || !matches!(state.trim(), "0" | "1") {
+        return None;
+    }
+    relay.parse().ok().filter(|&r| r > 0)
+}
+
+/// A `CommandApp_USBRelay <id> open|close <relay>` invocation, possibly
+/// inside a batch script. Relay 255 means all relays and says nothing about
+/// the count.
+fn parse_commandapp(line: &str) -> Option<u8> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let app = words.iter().position(|word| {
+        let name = word.trim_matches('"').rsplit(['\\', '/']).next().unwrap_or(word);
+        name.to_ascii_lowercase().starts_with("commandapp")
+    })?;
+    match words.get(app + 1..app + 4)? {
+        [_, op, relay] if op.eq_ignore_ascii_case("open") || op.eq_ignore_ascii_case("close") => {
+            relay.parse().ok().filter(|&r| r > 0 && r < 255)
+        }
+        _ => None,
+    }
+}
+
+/// A label table row: `3=Desk lamp`, `3,Desk lamp`, `3;Desk lamp` or
+/// tab-separated.
+fn parse_label(line: &str) -> Option<(u8, String)> {
+    let i = line.find(['=', ',', ';', '\t'])?;
+    let channel = line[..i].trim().parse().ok().filter(|&c| c > 0)?;
+    let label = line[i + 1..].trim().trim_matches('"').trim();
+    (!label.is_empty()).then(|| (channel, label.to_string()))
+}
@@ -0,0 +1,216 @@
+//! InfluxDB export.
+//!
+//! With `influx.enabled`, every ON/OFF change and a sample of each channel
+//! every `sample_secs` are written to an InfluxDB v2 bucket in line
+//! protocol, for long-term time series:
+//!
+//! ```text
+//! relay_change,channel=2,label=Lamp on=true,previous_secs=5400i 1718005400
+//! relay_sample,channel=2,label=Lamp on=true,on_secs=120i 1718005520
+//! ```
+//!
+//! `on_secs` is how long the channel has been on, 0 while it's off. Points
+//! that can't be written are kept, up to a limit, and sent with the next
+//! write, so a short outage of the database leaves no gap.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, DeviceConfig};
+use crate::history::unix_now;
+use crate::http;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, LastChange, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Points kept while the database is unreachable; the oldest go first.
+const MAX_PENDING: usize = 10_000;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InfluxConfig {
+    pub enabled: bool,
+    /// Base `http://` URL of the InfluxDB server.
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    /// API token with write access to the bucket.
+    pub token: String,
+    /// How often to sample every channel; 0 only writes changes.
+    pub sample_secs: u64,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://localhost:8086".to_string(),
+            org: String::new(),
+            bucket: "relay".to_string(),
+            token: String::new(),
+            sample_secs: 60,
+        }
+    }
+}
+
+impl InfluxConfig {
+    fn write_url(&self) -> String {
+        format!(
+            "{}/api/v2/write?org={}&bucket={}&precision=s",
+            self.url.trim_end_matches('/'),
+            query_escape(&self.org),
+            query_escape(&self.bucket)
+        )
+    }
+}
+
+// ============================================================================
+// LINE PROTOCOL
+// ============================================================================
+
+/// Escapes a tag value: commas, spaces and equals signs.
+fn tag_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | ' ' | '=') {
+            out.push('\\');
+        }
+        out.push(if c == '\n' { ' ' } else { c });
+    }
+    out
+}
+
+fn query_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn tags(device: &DeviceConfig, channel: u8) -> String {
+    format!("channel={},label={}", channel, tag_escape(&device.label(channel)))
+}
+
+/// `previous_secs` is how long the channel was in the state it left.
+fn change_line(
+    device: &DeviceConfig,
+    channel: u8,
+    on: bool,
+    previous_secs: u64,
+    time: u64,
+) -> String {
+    let tags = tags(device, channel);
+    format!("relay_change,{} on={},previous_secs={}i {}", tags, on, previous_secs, time)
+}
+
+fn sample_line(device: &DeviceConfig, channel: u8, change: LastChange, now: u64) -> String {
+    let (on, on_secs) = match change {
+        Some((RelayState::On, since)) => (true, now.saturating_sub(since)),
+        _ => (false, 0),
+    };
+    format!("relay_sample,{} on={},on_secs={}i {}", tags(device, channel), on, on_secs, now)
+}
+
+// ============================================================================
+// EXPORTER
+// ============================================================================
+
+/// Writes changes and samples while `influx.enabled` is set.
+pub fn run_influx(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    // Only changes from now on; history before the start isn't replayed
+    let mut seen = state.snapshot().last_changes;
+    let mut pending: Vec<String> = Vec::new();
+    let mut last_sample: Option<Instant> = None;
+    let mut failing = false;
+    ctx.mark_running();
+
+    loop {
+        std::thread::sleep(TICK);
+        let config = Config::load()?;
+        let settings = &config.influx;
+        let current = state.snapshot().last_changes;
+        if !settings.enabled {
+            seen = current;
+            pending.clear();
+            continue;
+        }
+
+        for (i, change) in current.iter().enumerate() {
+            let channel = i as u8 + 1;
+            if seen.get(i) == Some(change) {
+                continue;
+            }
+            if let Some((relay_state, time)) = *change {
+                let previous = seen.get(i).copied().flatten();
+                let previous_secs = previous.map_or(0, |(_, since)| time.saturating_sub(since));
+                let on = relay_state == RelayState::On;
+                pending.push(change_line(&config.device, channel, on, previous_secs, time));
+            }
+        }
+        seen = current.clone();
+
+        let due = last_sample.is_none_or(|t| t.elapsed().as_secs() >= settings.sample_secs);
+        if settings.sample_secs > 0 && due {
+            last_sample = Some(Instant::now());
+            let now = unix_now();
+            for (i, change) in current.iter().enumerate() {
+                pending.push(sample_line(&config.device, i as u8 + 1, *change, now));
+            }
+        }
+
+        if pending.is_empty() {
+            continue;
+        }
+        let overflow = pending.len().saturating_sub(MAX_PENDING);
+        pending.drain(..overflow);
+
+        match write(settings, &pending.join("\n")) {
+            Ok(()) => {
+                if failing {
+                    eprintln!("InfluxDB: writing again");
+                }
+                failing = false;
+                pending.clear();
+            }
+            Err(e) => {
+                // Log the start of an outage, not every retry
+                if !failing {
+                    eprintln!("InfluxDB: {}", e);
+                }
+                failing = true;
+            }
+        }
+    }
+}
+
+fn write(settings: &InfluxConfig, lines: &str) -> Result<(), String> {
+    let authorization = format!("Token {}", settings.token);
+    let response = http::request(
+        "POST",
+        &settings.write_url(),
+        &[
+            ("Authorization", &authorization),
+            ("Content-Type", "text/plain; charset=utf-8"),
+        ],
+        lines.as_bytes(),
+        WRITE_TIMEOUT,
+    )?;
+    match response.status {
+        200..=299 => Ok(()),
+        status => Err(format!("HTTP {}", status)),
+    }
+}
@@ -0,0 +1,404 @@
+//! Local control socket.
+//!
+//! A running instance holds the serial port, so a second invocation of the
+//! binary can't open it. Instead the CLI hands device commands to the
+//! running instance over a Unix socket (in the runtime directory) or a
+//! Windows named pipe, and falls back to the port itself when nobody is
+//! listening. The socket also keeps the app to one instance: a second
+//! launch asks the first to bring its window forward and exits.
+//!
+//! One request per connection, as a single line:
+//!
+//! | Request             | Effect                                  |
+//! |---------------------|-----------------------------------------|
+//! | `ping`              | Nothing; tells whether anyone listens   |
+//! | `focus`             | Bring the window to the front           |
+//! | `status`            | State of every channel                  |
+//! | `batch <steps>`     | Run a batch (`on:1,off:2`), wait        |
+//! | `scene <name>`      | Activate a scene, wait                  |
+//! | `schedule <change>` | A CLI `schedule` change (tab-separated) |
+//! | `raw <hex>`         | Send raw bytes (needs `advanced`)       |
+//! | `allow <ch>`        | Lift a daily switch limit for today     |
+//!
+//! The socket is served from the start, so instances are detected even
+//! while the board is missing; device requests are refused until the
+//! startup policy lets command sources run.
+//!
+//! The reply is zero or more output lines followed by `ok` or
+//! `error: <message>`.
+
+use std::io::{Read, Write};
+
+use crate::audit::Source;
+use crate::batch::{self, Batch};
+use crate::cli;
+use crate::config::Config;
+use crate::raw::{self, format_hex, parse_hex};
+use crate::startup::DeviceGate;
+use crate::supervisor::SubsystemContext;
+use crate::window::Window;
+use crate::AppState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\usb-power-relay";
+
+// ============================================================================
+// SERVER
+// ============================================================================
+
+#[derive(Clone)]
+pub struct IpcContext {
+    pub state: AppState,
+    pub gate: DeviceGate,
+    /// `None` when running headless.
+    pub window: Option<Window>,
+}
+
+impl IpcContext {
+    fn handle(&self, mut stream: platform::Stream) {
+        let reply = match read_line(&mut stream) {
+            Ok(line) => self.route(&line),
+            Err(e) => Err(e),
+        };
+        let text = match reply {
+            Ok(lines) => lines.into_iter().map(|l| l + "\n").collect::<String>() + "ok\n",
+            Err(e) => format!("error: {}\n", e),
+        };
+        if stream.write_all(text.as_bytes()).is_ok() {
+            platform::flush(&stream);
+        }
+    }
+
+    fn route(&self, line: &str) -> Result<Vec<String>, String> {
+        let (request, argument) = line.split_once(' ').unwrap_or((line, ""));
+        match request {
+            "ping" => Ok(Vec::new()),
+            "focus" => self.focus(),
+            "status" => Ok(self.status()),
+            "batch" | "scene" | "raw" | "allow" if self.state.read_only => {
+                Err("The running instance is a read-only monitor".to_string())
+            }
+            "batch" | "scene" | "raw" if !self.gate.is_open() => {
+                Err("The running instance is waiting for the relay board".to_string())
+            }
+            "batch" => self.run_batch(Batch::parse(argument)?),
+            "scene" => self.scene(argument),
+            "schedule" => cli::change_schedule(&argument.split('\t').collect::<Vec<_>>())
+                .map(|message| vec![message]),
+            "raw" => self.raw(argument),
+            "allow" => self.allow(argument),
+            _ => Err(format!("Unknown request '{}'", request)),
+        }
+    }
+
+    fn focus(&self) -> Result<Vec<String>, String> {
+        let window = self
+            .window
+            .as_ref()
+            .ok_or("The running instance is headless and has no window")?;
+        window.show();
+        Ok(Vec::new())
+    }
+
+    fn status(&self) -> Vec<String> {
+        self.state
+            .snapshot()
+            .relay_states
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("Channel {}: {}", i + 1, s.text()))
+            .collect()
+    }
+
+    fn scene(&self, name: &str) -> Result<Vec<String>, String> {
+        let config = Config::load()?;
+        let scene = config
+            .scene(name)
+            .ok_or_else(|| format!("Unknown scene '{}'", name))?;
+        self.run_batch(Batch::from_scene(scene))
+    }
+
+    fn run_batch(&self, batch: Batch) -> Result<Vec<String>, String> {
+        batch.validate(self.state.snapshot.borrow().relay_states.len() as u8)?;
+
+        match batch::submit(&self.state, batch, Source::Cli) {
+            Some(Ok(changes)) => Ok(changes
+                .iter()
+                .map(|(channel, s)| format!("Channel {}: {}", channel, s.text()))
+                .collect()),
+            Some(Err(e)) => Err(e.to_string()),
+            None => Err("No result from the device".to_string()),
+        }
+    }
+
+    fn allow(&self, channel: &str) -> Result<Vec<String>, String> {
+        let channel: u8 = channel
+            .parse()
+            .map_err(|_| format!("Invalid channel '{}'", channel))?;
+        if channel == 0 || channel as usize > self.state.snapshot.borrow().relay_states.len() {
+            return Err(format!("No channel {}", channel));
+        }
+        self.state.switches.allow(channel);
+        Ok(vec![format!("Channel {} may switch on again today", channel)])
+    }
+
+    fn raw(&self, hex: &str) -> Result<Vec<String>, String> {
+        if !Config::load()?.advanced {
+            return Err("Raw frames are disabled; set advanced: true in the config".to_string());
+        }
+        let bytes = parse_hex(hex)?;
+        let reply = raw::submit(&self.state, bytes.clone(), Source::Cli)
+            .ok_or_else(|| "No result from the device".to_string())??;
+        let received = if reply.is_empty() {
+            "(no reply)".to_string()
+        } else {
+            format_hex(&reply)
+        };
+        Ok(vec![format!("> {}", format_hex(&bytes)), format!("< {}", received)])
+    }
+}
+
+/// Serves local requests until the listener fails.
+pub fn run_ipc(ctx: &SubsystemContext, ipc: &IpcContext) -> Result<(), String> {
+    let listener = platform::Listener::bind()?;
+    ctx.mark_running();
+
+    loop {
+        let stream = listener.accept()?;
+        let ipc = ipc.clone();
+        std::thread::spawn(move || ipc.handle(stream));
+    }
+}
+
+fn read_line(stream: &mut impl Read) -> Result<String, String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) if line.len() < MAX_REQUEST_BYTES => line.push(byte[0]),
+            Ok(_) => return Err("Request too long".to_string()),
+            Err(e) => return Err(format!("Read failed: {}", e)),
+        }
+    }
+    String::from_utf8(line)
+        .map(|line| line.trim().to_string())
+        .map_err(|_| "Request is not UTF-8".to_string())
+}
+
+// ============================================================================
+// CLIENT
+// ============================================================================
+
+/// Sends `request` to a running instance and returns its output lines.
+/// `None` when no instance is listening.
+pub fn request(request: &str) -> Option<Result<Vec<String>, String>> {
+    let mut stream = platform::connect()?;
+    Some(exchange(&mut stream, request))
+}
+
+/// Whether another instance is serving the socket.
+pub fn running() -> bool {
+    request("ping").is_some()
+}
+
+fn exchange(stream: &mut platform::Stream, request: &str) -> Result<Vec<String>, String> {
+    stream
+        .write_all(format!("{}\n", request).as_bytes())
+        .map_err(|e| format!("Failed to reach the running instance: {}", e))?;
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|e| format!("Failed to read from the running instance: {}", e))?;
+
+    let mut lines: Vec<String> = reply.lines().map(str::to_string).collect();
+    match lines.pop().as_deref() {
+        Some("ok") => Ok(lines),
+        Some(last) => Err(last.strip_prefix("error: ").unwrap_or(last).to_string()),
+        None => Err("The running instance closed the connection".to_string()),
+    }
+}
+
+/// Where the socket lives: the per-user runtime directory where there is
+/// one, else the data directory.
+#[cfg(unix)]
+fn socket_path() -> Result<std::path::PathBuf, String> {
+    let dirs = directories::ProjectDirs::from("", "", "usb-power-relay")
+        .ok_or("No home directory")?;
+    let dir = dirs.runtime_dir().unwrap_or(dirs.data_dir());
+    Ok(dir.join("control.sock"))
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(unix)]
+mod platform {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    pub type Stream = UnixStream;
+
+    pub struct Listener(UnixListener);
+
+    impl Listener {
+        pub fn bind() -> Result<Self, String> {
+            if let Some(listener) = crate::systemd::unix_listener() {
+                return Ok(Listener(listener));
+            }
+            let path = super::socket_path()?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            }
+            if path.exists() {
+                if UnixStream::connect(&path).is_ok() {
+                    return Err(format!("Another instance is listening on {}", path.display()));
+                }
+                // Left behind by an instance that didn't exit cleanly
+                let _ = std::fs::remove_file(&path);
+            }
+            UnixListener::bind(&path)
+                .map(Listener)
+                .map_err(|e| format!("Failed to bind {}: {}", path.display(), e))
+        }
+
+        pub fn accept(&self) -> Result<Stream, String> {
+            self.0
+                .accept()
+                .map(|(stream, _)| stream)
+                .map_err(|e| format!("Accept failed: {}", e))
+        }
+    }
+
+    pub fn connect() -> Option<Stream> {
+        UnixStream::connect(super::socket_path().ok()?).ok()
+    }
+
+    /// Sockets deliver what was written even after they are closed.
+    pub fn flush(_stream: &Stream) {}
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, RawHandle};
+
+    pub type Stream = File;
+
+    const PIPE_ACCESS_DUPLEX: u32 = 0x3;
+    const FILE_FLAG_FIRST_PIPE_INSTANCE: u32 = 0x0008_0000;
+    const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x8;
+    const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+    const BUFFER_SIZE: u32 = 4096;
+    const ERROR_PIPE_CONNECTED: i32 = 535;
+    const INVALID_HANDLE_VALUE: RawHandle = -1isize as RawHandle;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateNamedPipeW(
+            name: *const u16,
+            open_mode: u32,
+            pipe_mode: u32,
+            max_instances: u32,
+            out_buffer_size: u32,
+            in_buffer_size: u32,
+            default_timeout: u32,
+            security_attributes: *mut std::ffi::c_void,
+        ) -> RawHandle;
+        fn ConnectNamedPipe(pipe: RawHandle, overlapped: *mut std::ffi::c_void) -> i32;
+        fn CloseHandle(handle: RawHandle) -> i32;
+        fn FlushFileBuffers(file: RawHandle) -> i32;
+    }
+
+    /// Named pipes have no listener object; each client connects to its own
+    /// pipe instance. The next instance is created before the current one
+    /// is handed out, so the name stays claimed.
+    pub struct Listener {
+        next: Cell<RawHandle>,
+    }
+
+    impl Listener {
+        pub fn bind() -> Result<Self, String> {
+            // Fails if another instance already owns the name
+            let pipe = create(true)?;
+            Ok(Listener { next: Cell::new(pipe) })
+        }
+
+        pub fn accept(&self) -> Result<Stream, String> {
+            let pipe = self.next.replace(create(false)?);
+            // SAFETY: `pipe` is a valid pipe handle; no overlapped I/O
+            let connected = unsafe { ConnectNamedPipe(pipe, std::ptr::null_mut()) } != 0
+                || std::io::Error::last_os_error().raw_os_error() == Some(ERROR_PIPE_CONNECTED);
+            if !connected {
+                let e = std::io::Error::last_os_error();
+                // SAFETY: `pipe` is a valid handle we own
+                unsafe {
+                    CloseHandle(pipe);
+                }
+                return Err(format!("Accept failed: {}", e));
+            }
+            // SAFETY: the handle is owned by the returned File from here on
+            Ok(unsafe { File::from_raw_handle(pipe) })
+        }
+    }
+
+    fn create(first: bool) -> Result<RawHandle, String> {
+        let name: Vec<u16> = std::ffi::OsStr::new(super::PIPE_NAME)
+            .encode_wide()
+            .chain(Some(0))
+            .collect();
+        let open_mode = if first {
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE
+        } else {
+            PIPE_ACCESS_DUPLEX
+        };
+        // SAFETY: `name` is NUL-terminated and outlives the call
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                open_mode,
+                PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if pipe == INVALID_HANDLE_VALUE {
+            return Err(format!(
+                "Failed to create {}: {}",
+                super::PIPE_NAME,
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(pipe)
+    }
+
+    /// Waits until the client has read the reply; closing a pipe discards
+    /// anything unread.
+    pub fn flush(stream: &Stream) {
+        // SAFETY: the handle stays valid while `stream` is borrowed
+        unsafe {
+            FlushFileBuffers(stream.as_raw_handle());
+        }
+    }
+
+    pub fn connect() -> Option<Stream> {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(super::PIPE_NAME)
+            .ok()
+    }
+}
@@ -0,0 +1,165 @@
+//! Minimal JSON reader.
+//!
+//! Just enough JSON for the replies of the Bot API and of other instances'
+//! HTTP APIs: values are read into a tree, without a schema. Writing JSON
+//! is left to `format!` and [`json_string`](crate::api::json_string).
+
+use std::collections::BTreeMap;
+
+// ============================================================================
+// VALUES
+// ============================================================================
+
+/// A parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    List(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Option<Json> {
+        Self::value(&mut text.chars().peekable())
+    }
+
+    fn value(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<Json> {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match *chars.peek()? {
+            '{' => {
+                chars.next();
+                let mut fields = BTreeMap::new();
+                loop {
+                    while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+                    if chars.next_if_eq(&'}').is_some() {
+                        return Some(Json::Object(fields));
+                    }
+                    let Json::Text(key) = Self::value(chars)? else {
+                        return None;
+                    };
+                    while chars.next_if(|c| c.is_whitespace() || *c == ':').is_some() {}
+                    fields.insert(key, Self::value(chars)?);
+                }
+            }
+            '[' => {
+                chars.next();
+                let mut items = Vec::new();
+                loop {
+                    while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+                    if chars.next_if_eq(&']').is_some() {
+                        return Some(Json::List(items));
+                    }
+                    items.push(Self::value(chars)?);
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => return Some(Json::Text(text)),
+                        '\\' => match chars.next()? {
+                            'n' => text.push('\n'),
+                            't' => text.push('\t'),
+                            'r' => text.push('\r'),
+                            'b' | 'f' => {}
+                            'u' => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                let unit = u32::from_str_radix(&hex, 16).ok()?;
+                                // Surrogate pairs (emoji) aren't needed for commands
+                                text.push(char::from_u32(unit).unwrap_or('\u{fffd}'));
+                            }
+                            c => text.push(c),
+                        },
+                        c => text.push(c),
+                    }
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}' | ']')) {
+                    word.push(c);
+                }
+                match word.trim() {
+                    "null" => Some(Json::Null),
+                    "true" => Some(Json::Bool(true)),
+                    "false" => Some(Json::Bool(false)),
+                    number => number.parse().ok().map(Json::Number),
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self) -> Option<i64> {
+        match self {
+            Json::Number(n) => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self) -> &[Json] {
+        match self {
+            Json::List(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Json::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let json = Json::parse(
+            r#" {"ok": true, "result": [{"update_id": 12, "text": null}, -1.5], "empty": {}} "#,
+        )
+        .unwrap();
+        assert_eq!(json.get("ok"), Some(&Json::Bool(true)));
+        let result = json.get("result").unwrap().list();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].get("update_id").and_then(Json::number), Some(12));
+        assert_eq!(result[0].get("text"), Some(&Json::Null));
+        assert_eq!(result[1], Json::Number(-1.5));
+        assert_eq!(json.get("empty"), Some(&Json::Object(BTreeMap::new())));
+    }
+
+    #[test]
+    fn unescapes_text() {
+        let json = Json::parse(r#""a\"b\\c\/d\neä\ud83d""#).unwrap();
+        assert_eq!(json.text(), Some("a\"b\\c/d\neä\u{fffd}"));
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for text in ["", "{", r#"{"a": "#, r#"{"a": "text"#, "[1, 2", r#""\u12"#, "nope"] {
+            assert_eq!(Json::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn accessors_ignore_other_kinds() {
+        let json = Json::Text("1".to_string());
+        assert_eq!(json.get("key"), None);
+        assert_eq!(json.number(), None);
+        assert!(json.list().is_empty());
+        assert_eq!(Json::Number(2.0).text(), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! Kiosk mode for wall-mounted touchscreens.
+//!
+//! Fullscreen without window chrome, one tile per channel with huge ON and
+//! OFF targets and a full-width ALL OFF. Nothing else is reachable, so a
+//! passer-by can't wander into the settings: leaving kiosk mode takes a
+//! long press on the bar at the top (or holding Enter or Space on it).
+//! While the PIN lock is on, the tiles stay greyed out under the unlock
+//! field; ALL OFF keeps working.
+
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+use crate::config::DeviceConfig;
+use crate::i18n::tr;
+use crate::theme::ThemeConfig;
+use crate::{indicator_label, screen_reader_label, switch_label, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long the exit bar must be held.
+const EXIT_HOLD: Duration = Duration::from_secs(3);
+const EXIT_BAR_HEIGHT: f32 = 28.0;
+const ALL_OFF_HEIGHT: f32 = 72.0;
+const GAP: f32 = 12.0;
+
+// ============================================================================
+// LAYOUT
+// ============================================================================
+
+/// What the user tapped this frame.
+pub enum KioskAction {
+    Press(u8, Command),
+    AllOff,
+    Exit,
+}
+
+#[derive(Default)]
+pub struct Kiosk {
+    /// When the exit bar started being held.
+    hold: Option<Instant>,
+}
+
+impl Kiosk {
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        device: &DeviceConfig,
+        theme: &ThemeConfig,
+        relay_states: &[RelayState],
+        busy: &[bool],
+        locked: bool,
+    ) -> Vec<KioskAction> {
+        let mut actions = Vec::new();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.exit_bar(ui) {
+                actions.push(KioskAction::Exit);
+            }
+            ui.add_space(GAP);
+
+            // Tiles fill what the ALL OFF button leaves, in a near-square grid
+            let count = relay_states.len().max(1);
+            let columns = (count as f32).sqrt().ceil() as usize;
+            let rows = count.div_ceil(columns);
+            let area = ui.available_size() - egui::vec2(0.0, ALL_OFF_HEIGHT + GAP);
+            let tile = egui::vec2(
+                (area.x - GAP * (columns - 1) as f32) / columns as f32,
+                (area.y - GAP * (rows - 1) as f32) / rows as f32,
+            );
+
+            ui.add_enabled_ui(!locked, |ui| {
+                egui::Grid::new("kiosk_tiles")
+                    .spacing(egui::vec2(GAP, GAP))
+                    .show(ui, |ui| {
+                        for (i, relay_state) in relay_states.iter().enumerate() {
+                            let channel = i as u8 + 1;
+                            let busy = busy.get(i).copied().unwrap_or(false);
+                            let tile_state = (channel, *relay_state, busy);
+                            if let Some(cmd) = tile_ui(ui, tile, device, theme, tile_state) {
+                                actions.push(KioskAction::Press(channel, cmd));
+                            }
+                            if (i + 1).is_multiple_of(columns) {
+                                ui.end_row();
+                            }
+                        }
+                    });
+            });
+
+            ui.add_space(GAP);
+            let all_off = egui::Button::new(
+                egui::RichText::new(tr("all-off")).size(32.0).strong().color(egui::Color32::WHITE),
+            )
+            .fill(theme.all_off_fill())
+            .min_size(egui::vec2(ui.available_width(), ALL_OFF_HEIGHT));
+            if ui.add(all_off).clicked() {
+                actions.push(KioskAction::AllOff);
+            }
+        });
+        actions
+    }
+
+    /// The bar that leaves kiosk mode once held for [`EXIT_HOLD`]; it fills
+    /// up while held so the gesture is discoverable but not accidental.
+    fn exit_bar(&mut self, ui: &mut egui::Ui) -> bool {
+        let size = egui::vec2(ui.available_width(), EXIT_BAR_HEIGHT);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let keys_held = ui.input(|i| i.key_down(egui::Key::Enter) || i.key_down(egui::Key::Space));
+        let held = response.is_pointer_button_down_on() || (response.has_focus() && keys_held);
+        let progress = match (held, self.hold) {
+            (true, Some(since)) => since.elapsed().as_secs_f32() / EXIT_HOLD.as_secs_f32(),
+            (true, None) => {
+                self.hold = Some(Instant::now());
+                0.0
+            }
+            (false, _) => {
+                self.hold = None;
+                0.0
+            }
+        };
+
+        let painter = ui.painter();
+        painter.rect_filled(rect, 4.0, ui.visuals().faint_bg_color);
+        if progress > 0.0 {
+            let mut filled = rect;
+            filled.set_width(rect.width() * progress.min(1.0));
+            painter.rect_filled(filled, 4.0, ui.visuals().selection.bg_fill);
+        }
+        let text = tr(if held { "kiosk-keep-holding" } else { "kiosk-hold" });
+        screen_reader_label(&response, egui::WidgetType::Button, text.clone());
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::proportional(14.0),
+            ui.visuals().weak_text_color(),
+        );
+
+        if held {
+            ui.ctx().request_repaint();
+        }
+        if progress >= 1.0 {
+            self.hold = None;
+            return true;
+        }
+        false
+    }
+}
+
+/// One channel: its name and state over side-by-side ON and OFF buttons.
+fn tile_ui(
+    ui: &mut egui::Ui,
+    size: egui::Vec2,
+    device: &DeviceConfig,
+    theme: &ThemeConfig,
+    (channel, relay_state, busy): (u8, RelayState, bool),
+) -> Option<Command> {
+    let mut pressed = None;
+    let stroke = egui::Stroke::new(4.0, theme.state(relay_state));
+    let frame = egui::Frame::group(ui.style()).stroke(stroke);
+    frame.show(ui, |ui| {
+        let inner = size - frame.total_margin().sum();
+        ui.set_min_size(inner);
+        ui.set_max_size(inner);
+        ui.vertical_centered(|ui| {
+            let title = format!("{}  {}", device.display_name(channel), relay_state.local_text());
+            let response = ui.label(egui::RichText::new(title).size(24.0).strong());
+            let label = indicator_label(device, channel, relay_state);
+            screen_reader_label(&response, egui::WidgetType::Label, label);
+            if busy {
+                ui.spinner();
+            }
+            ui.add_space(GAP);
+
+            let button = egui::vec2((ui.available_width() - GAP) / 2.0, ui.available_height());
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = GAP;
+                let big = |id| egui::RichText::new(tr(id)).size(40.0).strong();
+                let on = egui::Button::new(big("state-on"))
+                    .fill(theme.on_fill())
+                    .min_size(button);
+                let response = ui.add_enabled(!busy, on);
+                let label = switch_label(device, &Command::TurnOn(channel));
+                screen_reader_label(&response, egui::WidgetType::Button, label);
+                if response.clicked() {
+                    pressed = Some(Command::TurnOn(channel));
+                }
+                let off = egui::Button::new(big("state-off"))
+                    .fill(theme.off_fill())
+                    .min_size(button);
+                let response = ui.add_enabled(!busy, off);
+                let label = switch_label(device, &Command::TurnOff(channel));
+                screen_reader_label(&response, egui::WidgetType::Button, label);
+                if response.clicked() {
+                    pressed = Some(Command::TurnOff(channel));
+                }
+            });
+        });
+    });
+    pressed
+}
@@ -0,0 +1,172 @@
+//! Time and duration formatting.
+//!
+//! Clock times follow the user's 12/24-hour preference; `Auto` picks the
+//! convention of the system locale (`LC_ALL`/`LC_TIME`/`LANG` on Unix, the
+//! user locale on Windows). Durations and relative times ("3 min ago") are
+//! formatted the same way everywhere, in the GUI, CLI and notifications, in
+//! the language picked in [`crate::i18n`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{tr, tr_args};
+use crate::schedule::{LocalTime, TimeOfDay};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Regions where the 12-hour clock is the everyday convention.
+const TWELVE_HOUR_REGIONS: &[&str] = &[
+    "US", "CA", "AU", "NZ", "IN", "PH", "PK", "BD", "EG", "SA", "MY", "CO", "MX",
+];
+
+/// Languages that use the 24-hour clock even in the regions above.
+const TWENTY_FOUR_HOUR_LANGUAGES: &[&str] = &["fr"];
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClockFormat {
+    /// Follow the system locale.
+    #[default]
+    Auto,
+    TwelveHour,
+    TwentyFourHour,
+}
+
+impl ClockFormat {
+    pub const ALL: [ClockFormat; 3] = [
+        ClockFormat::Auto,
+        ClockFormat::TwelveHour,
+        ClockFormat::TwentyFourHour,
+    ];
+
+    pub fn text(&self) -> String {
+        tr(match self {
+            ClockFormat::Auto => "settings-system-default",
+            ClockFormat::TwelveHour => "clock-twelve-hour",
+            ClockFormat::TwentyFourHour => "clock-twenty-four-hour",
+        })
+    }
+
+    pub fn twelve_hour(&self) -> bool {
+        match self {
+            ClockFormat::Auto => {
+                system_locale("LC_TIME").is_some_and(|l| locale_uses_twelve_hour(&l))
+            }
+            ClockFormat::TwelveHour => true,
+            ClockFormat::TwentyFourHour => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub clock: ClockFormat,
+    /// Language tag like `de`; `None` follows the system locale.
+    pub language: Option<String>,
+}
+
+// ============================================================================
+// LOCALE DETECTION
+// ============================================================================
+
+/// The system locale for a category such as `LC_TIME`.
+#[cfg(not(windows))]
+pub fn system_locale(category: &str) -> Option<String> {
+    ["LC_ALL", category, "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+/// The system locale; Windows has one user locale for every category.
+#[cfg(windows)]
+pub fn system_locale(_category: &str) -> Option<String> {
+    const LOCALE_NAME_MAX_LENGTH: usize = 85;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetUserDefaultLocaleName(name: *mut u16, len: i32) -> i32;
+    }
+
+    let mut buf = [0u16; LOCALE_NAME_MAX_LENGTH];
+    // SAFETY: the buffer length passed matches the buffer
+    let len = unsafe { GetUserDefaultLocaleName(buf.as_mut_ptr(), buf.len() as i32) };
+    // The returned length includes the terminating NUL
+    (len > 1).then(|| String::from_utf16_lossy(&buf[..len as usize - 1]))
+}
+
+/// Whether a locale name like `en_US.UTF-8` or `en-US` uses a 12-hour clock.
+fn locale_uses_twelve_hour(locale: &str) -> bool {
+    let name = locale.split(['.', '@']).next().unwrap_or("");
+    let mut parts = name.split(['_', '-']);
+    let language = parts.next().unwrap_or("").to_ascii_lowercase();
+    let region = parts.next_back().unwrap_or("").to_ascii_uppercase();
+    TWELVE_HOUR_REGIONS.contains(&region.as_str())
+        && !TWENTY_FOUR_HOUR_LANGUAGES.contains(&language.as_str())
+}
+
+// ============================================================================
+// FORMATTING
+// ============================================================================
+
+/// Formats a clock time as `19:30` or `7:30 PM`.
+pub fn format_time(time: TimeOfDay, clock: ClockFormat) -> String {
+    if !clock.twelve_hour() {
+        return time.to_string();
+    }
+    let suffix = if time.hour < 12 { "AM" } else { "PM" };
+    let hour = match time.hour % 12 {
+        0 => 12,
+        h => h,
+    };
+    format!("{}:{:02} {}", hour, time.minute, suffix)
+}
+
+/// Formats a Unix time as a local clock time, with the date unless it's
+/// today: `18:00` or `2026-10-15 18:00`.
+pub fn format_moment(unix: u64, clock: ClockFormat) -> String {
+    let at = LocalTime::from_unix(unix);
+    let time = format_time(TimeOfDay { hour: at.hour, minute: at.minute }, clock);
+    if at.date() == LocalTime::now().date() {
+        time
+    } else {
+        format!("{} {}", at.date(), time)
+    }
+}
+
+/// Formats a Unix time in milliseconds as local `14:03:27.518`, for logs.
+pub fn format_timestamp(unix_millis: u64) -> String {
+    let time = LocalTime::from_unix(unix_millis / 1000);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        time.hour,
+        time.minute,
+        time.second,
+        unix_millis % 1000
+    )
+}
+
+/// Formats a duration as e.g. `3h 12m`.
+pub fn format_duration(secs: u64) -> String {
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) if secs > 0 => tr_args("duration-seconds", &[("seconds", &secs)]),
+        (0, m) => tr_args("duration-minutes", &[("minutes", &m)]),
+        (h, m) => tr_args("duration-hours", &[("hours", &h), ("minutes", &m)]),
+    }
+}
+
+/// Formats how long ago something happened, e.g. `3 min ago`.
+pub fn format_relative(secs_ago: u64) -> String {
+    match secs_ago {
+        0..=59 => tr("just-now"),
+        60..=3599 => tr_args("minutes-ago", &[("count", &(secs_ago / 60))]),
+        3600..=86_399 => tr_args("hours-ago", &[("count", &(secs_ago / 3600))]),
+        86_400..=172_799 => tr("yesterday"),
+        _ => tr_args("days-ago", &[("count", &(secs_ago / 86_400))]),
+    }
+}
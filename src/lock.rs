@@ -0,0 +1,136 @@
+//! PIN lock for the window.
+//!
+//! With `lock.pin` set, the window starts locked: it keeps showing every
+//! channel's state, but its buttons, editors and settings stay greyed out
+//! until the PIN or passphrase is entered, and it locks again after
+//! `lock.idle_secs` without input. ALL OFF keeps working while locked, as an
+//! emergency stop must. The lock guards the window of a shared computer, not
+//! the CLI, the API or the config file.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::i18n::tr;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockConfig {
+    /// PIN or passphrase; empty disables the lock.
+    pub pin: String,
+    /// Locks again after this long without input; 0 only locks by hand.
+    pub idle_secs: u64,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            pin: String::new(),
+            idle_secs: 300,
+        }
+    }
+}
+
+impl LockConfig {
+    pub fn enabled(&self) -> bool {
+        !self.pin.is_empty()
+    }
+}
+
+// ============================================================================
+// LOCK
+// ============================================================================
+
+pub struct ControlLock {
+    locked: bool,
+    last_input: Instant,
+    typed: String,
+    /// The last attempt was wrong.
+    wrong: bool,
+}
+
+impl ControlLock {
+    pub fn new(config: &LockConfig) -> Self {
+        Self {
+            locked: config.enabled(),
+            last_input: Instant::now(),
+            typed: String::new(),
+            wrong: false,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn lock(&mut self) {
+        self.locked = true;
+        self.typed.clear();
+        self.wrong = false;
+    }
+
+    /// Notes input and locks once the window has been idle too long.
+    /// Returns true when it locked just now.
+    pub fn update(&mut self, ctx: &egui::Context, config: &LockConfig) -> bool {
+        if !config.enabled() {
+            self.locked = false;
+            return false;
+        }
+        if ctx.input(|i| !i.events.is_empty()) {
+            self.last_input = Instant::now();
+        }
+        if self.locked || config.idle_secs == 0 {
+            return false;
+        }
+        let idle = Duration::from_secs(config.idle_secs);
+        match idle.checked_sub(self.last_input.elapsed()) {
+            Some(left) if !left.is_zero() => {
+                ctx.request_repaint_after(left);
+                false
+            }
+            _ => {
+                self.lock();
+                true
+            }
+        }
+    }
+
+    /// The unlock field while locked, or a button to lock now.
+    pub fn bar(&mut self, ui: &mut egui::Ui, config: &LockConfig) {
+        if !config.enabled() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            if !self.locked {
+                if ui.small_button(tr("lock-now")).clicked() {
+                    self.lock();
+                }
+                return;
+            }
+            ui.label(tr("lock-locked"));
+            let field = ui.add(
+                egui::TextEdit::singleline(&mut self.typed)
+                    .password(true)
+                    .hint_text(tr("lock-pin"))
+                    .desired_width(120.0),
+            );
+            let entered = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button(tr("lock-unlock")).clicked() || entered {
+                if self.typed == config.pin {
+                    self.locked = false;
+                    self.last_input = Instant::now();
+                } else {
+                    self.wrong = true;
+                }
+                self.typed.clear();
+            }
+            if self.wrong {
+                ui.colored_label(ui.visuals().error_fg_color, tr("lock-wrong"));
+            }
+        });
+    }
+}
@@ -4,28 +4,165 @@
 
 #![windows_subsystem = "windows"]
 
+mod anomaly;
+mod api;
+mod audit;
+mod bridge;
+mod autostart;
+mod batch;
+mod bulk;
+mod cli;
+mod completions;
+mod clock;
+mod config;
+mod confirm;
+mod console;
+mod cooldown;
+mod correlate;
+mod cycles;
+mod daemon;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod debounce;
+mod desired;
+mod email;
+mod emergency;
+mod gamepad;
+mod grid;
+mod history;
+mod hotkeys;
+mod hotplug;
+mod http;
+mod hue;
+mod i18n;
+mod ical;
+mod import;
+mod influx;
+mod ipc;
+mod json;
+mod kiosk;
+mod locale;
+mod lock;
+mod mdns;
+mod menubar;
+mod midi;
+mod monitor;
+mod notify;
+mod openapi;
+mod overrides;
+mod pending;
+mod poll;
+mod ports;
+mod process;
+mod push;
+mod queue;
+mod raw;
+mod remotes;
+mod report;
+mod rules;
+mod safety;
+mod schedule;
+mod sequence;
+mod session;
+mod settings;
+mod snmp;
+mod sound;
+mod startup;
+mod supervisor;
+mod systemd;
+mod tcp;
+mod telegram;
+mod theme;
+mod timing;
+mod traffic;
+mod tz;
+mod urlscheme;
+mod voice;
+mod watchdog;
+mod webhook;
+mod webui;
+mod winservice;
+mod window;
+
+use audit::Source;
+use batch::{Batch, BatchError, BatchErrorKind, BatchReply, BatchStep};
+use bulk::BulkActions;
+use config::{Config, DeviceConfig, ExitPolicy, InterlockGroup, LiveConfig};
+use confirm::{ConfirmOff, ConfirmOutcome, ConfirmPrompt};
+use console::HexConsole;
+use cooldown::{CooldownMode, Cooldowns};
+use correlate::{Correlator, RequestKind};
+use cycles::SwitchCounter;
+use debounce::Debouncer;
+use desired::DesiredState;
 use eframe::egui;
-use serialport::{SerialPort, SerialPortType};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tokio::sync::mpsc;
+use email::EmailEvent;
+use emergency::EmergencyStop;
+use grid::ScheduleGrid;
+use history::{unix_now, History, HistoryWindow};
+use i18n::{tr, tr_args};
+use kiosk::{Kiosk, KioskAction};
+use lock::ControlLock;
+use notify::Notifier;
+use overrides::{OverridePrompt, Overrides, PromptOutcome};
+use pending::PendingSwitches;
+use poll::DriftDetector;
+use queue::CommandQueue;
+use bridge::BridgeSession;
+use raw::RawReply;
+use remotes::Remotes;
+use rules::RulesEditor;
+use sequence::SequenceEditor;
+use settings::SettingsWindow;
+use sound::{Cue, SoundConfig};
+use startup::DeviceGate;
+use serialport::SerialPort;
+use std::time::{Duration, Instant};
+use supervisor::{SubsystemContext, SubsystemStatus, Supervisor};
+use timing::ResponseTimer;
+use traffic::{TrafficLog, TrafficWindow};
+use tokio::sync::{mpsc, watch};
+use webhook::{ChannelChange, WebhookQueue};
+use window::Window;
 
 // ============================================================================
 // CONSTANTS
 // ============================================================================
 
-const BAUD_RATE: u32 = 9600;
-const RESPONSE_DELAY_MS: u64 = 100;
+/// Pause after each frame to a write-only board, which never signals that
+/// it is done.
+const WRITE_ONLY_GAP: Duration = Duration::from_millis(100);
+
+/// A raw reply is complete once the line has been quiet this long.
+const RAW_QUIET_GAP: Duration = Duration::from_millis(50);
 const TIMEOUT: Duration = Duration::from_millis(500);
 
-const CMD_OFF: [u8; 4] = [0xA0, 0x01, 0x00, 0xA1];
-const CMD_ON: [u8; 4] = [0xA0, 0x01, 0x03, 0xA4];
-const CMD_STATUS: [u8; 4] = [0xA0, 0x01, 0x05, 0xA6];
+// Frames are [0xA0, CHANNEL, OP, CHECKSUM] where CHECKSUM is the byte sum
+const FRAME_START: u8 = 0xA0;
+const OP_OFF: u8 = 0x00;
+const OP_ON: u8 = 0x03;
+const OP_STATUS: u8 = 0x05;
 
-const RESPONSE_HEADER: [u8; 2] = [0xA0, 0x01];
+const STATE_OFF: u8 = 0x00;
 const STATE_ON: u8 = 0x01;
 
-const CH340_KEYWORDS: &[&str] = &["CH340", "CH341", "USB-SERIAL"];
+/// Granularity of interruptible waits inside a batch.
+const BATCH_WAIT_SLICE: Duration = Duration::from_millis(50);
+
+/// Polls closer together than this are dropped, so polls that queued up
+/// behind a long batch or a disconnect run only once.
+const POLL_MIN_GAP: Duration = Duration::from_secs(1);
+
+/// How long closing the window waits for the exit policy to be confirmed.
+const EXIT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Redraw interval for "3 min ago" style times while nothing else happens.
+const RELATIVE_TIME_REFRESH: Duration = Duration::from_secs(1);
+
+const ALL_OFF_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::X,
+);
 
 // ============================================================================
 // RELAY STATE
@@ -40,15 +177,6 @@ enum RelayState {
 }
 
 impl RelayState {
-    fn color(&self) -> egui::Color32 {
-        match self {
-            RelayState::On => egui::Color32::from_rgb(34, 197, 94),   // Modern green
-            RelayState::Off => egui::Color32::from_rgb(239, 68, 68),  // Modern red
-            RelayState::Unknown => egui::Color32::from_rgb(156, 163, 175), // Gray
-            RelayState::Error => egui::Color32::from_rgb(249, 115, 22), // Orange
-        }
-    }
-
     fn text(&self) -> &'static str {
         match self {
             RelayState::On => "ON",
@@ -57,291 +185,1957 @@ impl RelayState {
             RelayState::Error => "ERROR",
         }
     }
+
+    /// Stable machine-readable name, used by the API.
+    fn name(&self) -> &'static str {
+        match self {
+            RelayState::On => "on",
+            RelayState::Off => "off",
+            RelayState::Unknown => "unknown",
+            RelayState::Error => "error",
+        }
+    }
+
+    /// [`Self::text`] in the UI language.
+    fn local_text(&self) -> String {
+        tr(match self {
+            RelayState::On => "state-on",
+            RelayState::Off => "state-off",
+            RelayState::Unknown => "state-unknown",
+            RelayState::Error => "state-error",
+        })
+    }
 }
 
 // ============================================================================
 // RELAY CONTROLLER
 // ============================================================================
 
+fn frame(channel: u8, op: u8) -> [u8; 4] {
+    [
+        FRAME_START,
+        channel,
+        op,
+        FRAME_START.wrapping_add(channel).wrapping_add(op),
+    ]
+}
+
+/// The state reported by a reply frame. Lenient parsing only checks the
+/// header; `strict` also requires a valid checksum and state byte and logs
+/// anything that fails.
+fn parse_reply(reply: &[u8], channel: u8, strict: bool) -> Option<RelayState> {
+    let [start, ch, state, sum] = *reply else {
+        return None;
+    };
+    if start != FRAME_START || ch != channel {
+        return None;
+    }
+    if strict {
+        let valid_sum = start.wrapping_add(ch).wrapping_add(state) == sum;
+        if !valid_sum || !matches!(state, STATE_OFF | STATE_ON) {
+            eprintln!("Rejected malformed reply: {}", raw::format_hex(reply));
+            return None;
+        }
+    }
+    Some(if state == STATE_ON {
+        RelayState::On
+    } else {
+        RelayState::Off
+    })
+}
+
 struct RelayController {
     port: Box<dyn SerialPort>,
+    channels: u8,
+    interlocks: Vec<InterlockGroup>,
+    /// `None` waits the full [`TIMEOUT`] for every reply.
+    timer: Option<ResponseTimer>,
+    /// In write-only mode, the state each channel was last switched to;
+    /// nothing is ever read back.
+    assumed: Option<Vec<RelayState>>,
+    /// Validate reply checksums.
+    strict: bool,
+    /// Where frames are logged, when anywhere.
+    traffic: Option<TrafficLog>,
+    correlator: Correlator,
+    cooldowns: Cooldowns,
 }
 
 impl RelayController {
-    fn new() -> Result<Self, String> {
-        let port_info = Self::detect_device()?;
+    fn new(device: &DeviceConfig) -> Result<Self, String> {
+        let port_info = ports::select(device)?;
 
-        let port = serialport::new(&port_info.port_name, BAUD_RATE)
+        let port = device
+            .serial
+            .builder(&port_info.port_name)
             .timeout(TIMEOUT)
             .open()
-            .map_err(|e| format!("Failed to open port: {}", e))?;
+            .map_err(|e| {
+                format!("Failed to open port at {}: {}", device.serial.summary(), e)
+            })?;
 
-        Ok(Self { port })
+        Ok(Self {
+            port,
+            channels: device.channels,
+            interlocks: device.interlocks.clone(),
+            timer: device.adaptive_timing.then(|| ResponseTimer::new(TIMEOUT)),
+            assumed: device
+                .write_only
+                .then(|| vec![RelayState::Unknown; device.channels as usize]),
+            strict: device.strict,
+            traffic: None,
+            correlator: Correlator::default(),
+            cooldowns: Cooldowns::new(device),
+        })
     }
 
-    fn detect_device() -> Result<serialport::SerialPortInfo, String> {
-        let ports = serialport::available_ports()
-            .map_err(|e| format!("Failed to list ports: {}", e))?;
+    fn send_command(&mut self, channel: u8, op: u8) -> Result<Option<RelayState>, String> {
+        self.drain()?;
 
-        // Look for CH340/CH341 devices
-        for port in &ports {
-            if let SerialPortType::UsbPort(info) = &port.port_type {
-                let product = info.product.as_deref().unwrap_or("");
-                let manufacturer = info.manufacturer.as_deref().unwrap_or("");
-                let combined = format!("{} {}", product, manufacturer).to_uppercase();
+        let request = frame(channel, op);
+        let seq = self.correlator.begin();
+        self.port.write_all(&request)
+            .map_err(|e| format!("Write failed: {}", e))?;
+        self.log(traffic::Direction::Sent, &request);
 
-                if CH340_KEYWORDS.iter().any(|kw| combined.contains(kw)) {
-                    return Ok(port.clone());
-                }
-            }
+        self.port.flush()
+            .map_err(|e| format!("Flush failed: {}", e))?;
+
+        if self.assumed.is_some() {
+            // Nothing to wait for, but give the board time to act
+            std::thread::sleep(WRITE_ONLY_GAP);
+            return Ok(None);
+        }
+
+        // Accumulate bytes until our reply is complete or the deadline passes
+        let started = Instant::now();
+        let deadline = started + self.timer.map_or(TIMEOUT, |timer| timer.timeout());
+        let mut buf = [0u8; 32];
+        let (mut len, mut scanned) = (0, 0);
+        let mut reply = None;
+        while reply.is_none() && len < buf.len() {
+            let n = match self.read_until(deadline, &mut buf[len..])? {
+                0 => break,
+                n => n,
+            };
+            self.log(traffic::Direction::Received, &buf[len..len + n]);
+            len += n;
+            let (found, used) = self.match_reply(&buf[scanned..len], channel);
+            reply = found;
+            scanned += used;
         }
 
-        // Fallback to any USB serial device
-        for port in &ports {
-            if matches!(port.port_type, SerialPortType::UsbPort(_)) {
-                return Ok(port.clone());
+        let kind = if op == OP_STATUS { RequestKind::Status } else { RequestKind::Switch };
+        match reply {
+            Some(_) => self.correlator.answered(kind),
+            None => self.correlator.unanswered(seq, channel, kind),
+        }
+
+        // Only status queries are always answered, so only their silence
+        // means the timeout is too short
+        if let Some(timer) = self.timer.as_mut() {
+            match reply {
+                Some(_) => timer.answered(started.elapsed()),
+                None if op == OP_STATUS => timer.missed(),
+                None => {}
             }
         }
+        Ok(reply)
+    }
 
-        Err("No USB relay found".to_string())
+    /// One read that gives up at `deadline`; 0 means nothing more came.
+    fn read_until(&mut self, deadline: Instant, buf: &mut [u8]) -> Result<usize, String> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(0);
+        }
+        self.port.set_timeout(remaining)
+            .map_err(|e| format!("Timeout setup failed: {}", e))?;
+        match self.port.read(buf) {
+            Ok(n) => Ok(n),
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(0),
+            Err(e) => Err(format!("Read error: {}", e)),
+        }
     }
 
-    fn send_command(&mut self, command: &[u8; 4]) -> Result<Option<RelayState>, String> {
+    /// The answer for `channel` among the received frames, and how many
+    /// bytes were examined. Late replies to earlier requests are dropped
+    /// first; a trailing partial frame is left for the next read.
+    fn match_reply(&mut self, received: &[u8], channel: u8) -> (Option<RelayState>, usize) {
+        let (frames, used) = correlate::frames(received, FRAME_START);
+        for reply in frames {
+            if let Some(seq) = self.correlator.stale(reply[1]) {
+                eprintln!("Dropped late reply to request #{}: {}", seq, raw::format_hex(&reply));
+                continue;
+            }
+            if let Some(state) = parse_reply(&reply, channel, self.strict) {
+                return (Some(state), used);
+            }
+        }
+        (None, used)
+    }
+
+    /// Reads whatever arrived since the last request, settling late replies,
+    /// then empties both buffers so nothing left over is read as an answer.
+    fn drain(&mut self) -> Result<(), String> {
+        let waiting = self.port.bytes_to_read()
+            .map_err(|e| format!("Read error: {}", e))?;
+        if waiting > 0 {
+            let mut buf = [0u8; 256];
+            let len = (waiting as usize).min(buf.len());
+            if let Ok(n) = self.port.read(&mut buf[..len]) {
+                self.log(traffic::Direction::Received, &buf[..n]);
+                for reply in correlate::frames(&buf[..n], FRAME_START).0 {
+                    if let Some(seq) = self.correlator.stale(reply[1]) {
+                        eprintln!(
+                            "Dropped late reply to request #{}: {}",
+                            seq,
+                            raw::format_hex(&reply)
+                        );
+                    }
+                }
+            }
+        }
         self.port.clear(serialport::ClearBuffer::All)
-            .map_err(|e| format!("Clear failed: {}", e))?;
+            .map_err(|e| format!("Clear failed: {}", e))
+    }
 
-        self.port.write_all(command)
+    /// Writes `bytes` unchanged and returns whatever the board answers.
+    fn send_raw(&mut self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        self.drain()?;
+        self.port.write_all(bytes)
             .map_err(|e| format!("Write failed: {}", e))?;
-
+        self.log(traffic::Direction::Sent, bytes);
         self.port.flush()
             .map_err(|e| format!("Flush failed: {}", e))?;
 
-        std::thread::sleep(Duration::from_millis(RESPONSE_DELAY_MS));
+        // The reply length is unknown, so read until the line goes quiet.
+        // Unknown frames may take longer to answer than status queries.
+        let deadline = Instant::now() + TIMEOUT;
+        let mut buf = [0u8; 256];
+        let mut len = 0;
+        while len < buf.len() {
+            let wait_until = if len == 0 {
+                deadline
+            } else {
+                (Instant::now() + RAW_QUIET_GAP).min(deadline)
+            };
+            let n = self.read_until(wait_until, &mut buf[len..])?;
+            if n == 0 {
+                break;
+            }
+            self.log(traffic::Direction::Received, &buf[len..len + n]);
+            len += n;
+        }
+        Ok(buf[..len].to_vec())
+    }
 
-        let mut buf = [0u8; 32];
-        match self.port.read(&mut buf) {
-            Ok(n) if n >= 4 => {
-                if buf[0] == RESPONSE_HEADER[0] && buf[1] == RESPONSE_HEADER[1] {
-                    return Ok(Some(if buf[2] == STATE_ON {
-                        RelayState::On
-                    } else {
-                        RelayState::Off
-                    }));
-                }
-                Ok(None)
+    fn turn_on(&mut self, channel: u8) -> Result<RelayState, String> {
+        let state = self.send_command(channel, OP_ON)?.unwrap_or(RelayState::On);
+        self.assume(channel, state);
+        self.cooldowns.update(channel, state);
+        Ok(state)
+    }
+
+    fn turn_off(&mut self, channel: u8) -> Result<RelayState, String> {
+        let state = self.send_command(channel, OP_OFF)?.unwrap_or(RelayState::Off);
+        self.assume(channel, state);
+        self.cooldowns.update(channel, state);
+        Ok(state)
+    }
+
+    fn query_status(&mut self, channel: u8) -> Result<RelayState, String> {
+        if let Some(assumed) = &self.assumed {
+            let index = (channel as usize).wrapping_sub(1);
+            return Ok(assumed.get(index).copied().unwrap_or(RelayState::Unknown));
+        }
+        match self.send_command(channel, OP_STATUS)? {
+            Some(state) => {
+                self.cooldowns.update(channel, state);
+                Ok(state)
             }
-            Ok(_) => Ok(None),
-            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(None),
-            Err(e) => Err(format!("Read error: {}", e)),
+            None => Ok(RelayState::Unknown),
         }
     }
 
-    fn turn_on(&mut self) -> Result<RelayState, String> {
-        match self.send_command(&CMD_ON)? {
-            Some(state) => Ok(state),
-            None => Ok(RelayState::On),
+    fn log(&self, direction: traffic::Direction, bytes: &[u8]) {
+        if let Some(traffic) = &self.traffic {
+            traffic.record(direction, bytes);
         }
     }
 
-    fn turn_off(&mut self) -> Result<RelayState, String> {
-        match self.send_command(&CMD_OFF)? {
-            Some(state) => Ok(state),
-            None => Ok(RelayState::Off),
+    fn assume(&mut self, channel: u8, state: RelayState) {
+        let index = (channel as usize).wrapping_sub(1);
+        if let Some(slot) = self.assumed.as_mut().and_then(|a| a.get_mut(index)) {
+            *slot = state;
         }
     }
 
-    fn query_status(&mut self) -> Result<RelayState, String> {
-        match self.send_command(&CMD_STATUS)? {
-            Some(state) => Ok(state),
-            None => Ok(RelayState::Unknown),
+    /// Executes a command and returns the new state of every channel it
+    /// touched. Turning a channel on first switches off the other members of
+    /// its interlock groups (break-before-make), whichever client asked.
+    fn execute(&mut self, cmd: &Command) -> Result<Vec<(u8, RelayState)>, String> {
+        match *cmd {
+            Command::Batch(ref batch, _) => {
+                let (changes, result) = self.run_batch(batch, &|| false);
+                result.map(|()| changes).map_err(|e| e.to_string())
+            }
+            Command::TurnOn(channel) => {
+                let exclusive: Vec<u8> = self
+                    .interlocks
+                    .iter()
+                    .filter(|group| group.channels.contains(&channel))
+                    .flat_map(|group| group.channels.iter().copied())
+                    .filter(|&other| other != channel)
+                    .collect();
+
+                let mut changes = Vec::with_capacity(exclusive.len() + 1);
+                for other in exclusive {
+                    changes.push((other, self.turn_off(other)?));
+                }
+                changes.push((channel, self.turn_on(channel)?));
+                Ok(changes)
+            }
+            Command::TurnOff(channel) => Ok(vec![(channel, self.turn_off(channel)?)]),
+            Command::Toggle(channel) => match self.query_status(channel)? {
+                RelayState::On => self.execute(&Command::TurnOff(channel)),
+                _ => self.execute(&Command::TurnOn(channel)),
+            },
+            Command::AllOff => (1..=self.channels)
+                .map(|channel| Ok((channel, self.turn_off(channel)?)))
+                .collect(),
+            Command::Poll => (1..=self.channels)
+                .map(|channel| Ok((channel, self.query_status(channel)?)))
+                .collect(),
+            Command::Raw(ref bytes, _) => self.send_raw(bytes).map(|_| Vec::new()),
+            Command::Bridge(ref session) => session.run(self, &|| false).map(|()| Vec::new()),
         }
     }
+
+    /// Runs a batch as one unit, stopping at the first failed step or when
+    /// `interrupted` returns true. Returns every change made either way.
+    fn run_batch(
+        &mut self,
+        batch: &Batch,
+        interrupted: &dyn Fn() -> bool,
+    ) -> (Vec<(u8, RelayState)>, Result<(), BatchError>) {
+        let mut changes = Vec::new();
+        if batch.start_by.is_some_and(|deadline| Instant::now() > deadline) {
+            let error = BatchError {
+                step: 0,
+                kind: BatchErrorKind::Expired,
+                message: "Device was not ready in time".to_string(),
+            };
+            return (changes, Err(error));
+        }
+
+        // Sleeps in slices; false if interrupted first
+        let wait = |ms: u64| {
+            let deadline = Instant::now() + Duration::from_millis(ms);
+            while Instant::now() < deadline {
+                if interrupted() {
+                    return false;
+                }
+                std::thread::sleep(BATCH_WAIT_SLICE.min(deadline - Instant::now()));
+            }
+            true
+        };
+
+        for (step, &batch_step) in batch.steps.iter().enumerate() {
+            let error = |message: &str| BatchError {
+                step,
+                kind: BatchErrorKind::Device,
+                message: message.to_string(),
+            };
+            let stopped = || BatchError {
+                step,
+                kind: BatchErrorKind::Interrupted,
+                message: "Interrupted by emergency stop".to_string(),
+            };
+            if interrupted() {
+                return (changes, Err(stopped()));
+            }
+
+            // Switching on during a cooldown waits it out or fails the batch
+            let switched_on = match batch_step {
+                BatchStep::On(channel) | BatchStep::Pulse(channel, _) => Some(channel),
+                _ => None,
+            };
+            match switched_on.and_then(|channel| self.cooldowns.left(channel)) {
+                Some((left, CooldownMode::Delay)) if !wait(left.as_millis() as u64) => {
+                    return (changes, Err(stopped()));
+                }
+                Some((left, CooldownMode::Reject)) => {
+                    let error = BatchError {
+                        step,
+                        kind: BatchErrorKind::Rejected,
+                        message: format!("Cooling down for another {} s", left.as_secs() + 1),
+                    };
+                    return (changes, Err(error));
+                }
+                _ => {}
+            }
+
+            let commands: &[Command] = match batch_step {
+                BatchStep::On(channel) => &[Command::TurnOn(channel)],
+                BatchStep::Off(channel) => &[Command::TurnOff(channel)],
+                BatchStep::Pulse(channel, ms) => {
+                    match self.execute(&Command::TurnOn(channel)) {
+                        Ok(c) => changes.extend(c),
+                        Err(e) => return (changes, Err(error(&e))),
+                    }
+                    if !wait(ms) {
+                        return (changes, Err(stopped()));
+                    }
+                    &[Command::TurnOff(channel)]
+                }
+                BatchStep::Wait(ms) => {
+                    if !wait(ms) {
+                        return (changes, Err(stopped()));
+                    }
+                    &[]
+                }
+            };
+            for cmd in commands {
+                match self.execute(cmd) {
+                    Ok(c) => changes.extend(c),
+                    Err(e) => return (changes, Err(error(&e))),
+                }
+            }
+        }
+        (changes, Ok(()))
+    }
 }
 
 // ============================================================================
 // APPLICATION STATE
 // ============================================================================
 
+/// A relay operation; channels are 1-based.
+#[derive(Debug, Clone)]
 enum Command {
-    TurnOn,
-    TurnOff,
+    TurnOn(u8),
+    TurnOff(u8),
+    /// Switches the channel to the opposite of its reported state.
+    Toggle(u8),
+    AllOff,
+    /// Steps executed as one unit; the result goes to the reply, if any.
+    Batch(Batch, Option<BatchReply>),
+    /// Queries every channel without switching anything.
+    Poll,
+    /// Sends bytes as-is; the board's reply goes to the reply, if any.
+    Raw(Vec<u8>, Option<RawReply>),
+    /// Hands the port to a serial bridge client for one burst.
+    Bridge(BridgeSession),
+}
+
+/// The last definite state of a channel and when it was entered.
+type LastChange = Option<(RelayState, u64)>;
+
+/// The device state the GUI and the API show, published by the device
+/// worker.
+#[derive(Debug, Clone, Default)]
+struct Snapshot {
+    relay_states: Vec<RelayState>,
+    last_changes: Vec<LastChange>,
+    /// Commands the device worker has executed successfully.
+    completed: u64,
 }
 
+/// Handles shared by the GUI, the device worker and the command sources.
+/// The device state itself is published as a [`Snapshot`] on a watch
+/// channel, so readers never hold up the worker and the worker never waits
+/// for a frame to finish drawing.
+#[derive(Clone)]
 struct AppState {
-    relay_state: RelayState,
-    error_message: Option<String>,
-    command_tx: mpsc::UnboundedSender<Command>,
+    snapshot: watch::Sender<Snapshot>,
+    command_tx: mpsc::UnboundedSender<(Command, Source)>,
+    webhooks: WebhookQueue,
+    traffic: TrafficLog,
+    /// Today's switch counts, for the daily limits.
+    switches: SwitchCounter,
+    /// Monitor mode: nothing may switch.
+    read_only: bool,
+    /// Woken whenever a channel changes, so the GUI redraws only then.
+    repaint: egui::Context,
 }
 
 impl AppState {
-    fn new(command_tx: mpsc::UnboundedSender<Command>) -> Self {
+    fn new(
+        command_tx: mpsc::UnboundedSender<(Command, Source)>,
+        webhooks: WebhookQueue,
+        channels: u8,
+        read_only: bool,
+        repaint: egui::Context,
+    ) -> Self {
+        // Seed change times from history so "switched on 2 h ago" survives
+        // a restart
+        let mut last_changes = vec![None; channels as usize];
+        let history = History::load().unwrap_or_default();
+        let definite = history.iter().filter(|e| matches!(e.state, RelayState::On | RelayState::Off));
+        for event in definite {
+            if let Some(slot) = last_changes.get_mut((event.channel as usize).wrapping_sub(1)) {
+                *slot = Some((event.state, event.time));
+            }
+        }
+
+        let (snapshot, _) = watch::channel(Snapshot {
+            relay_states: vec![RelayState::Unknown; channels as usize],
+            last_changes,
+            completed: 0,
+        });
         Self {
-            relay_state: RelayState::Unknown,
-            error_message: None,
+            snapshot,
             command_tx,
+            webhooks,
+            traffic: TrafficLog::default(),
+            switches: SwitchCounter::new(&history),
+            read_only,
+            repaint,
         }
     }
 
-    fn send_command(&self, cmd: Command) {
-        let _ = self.command_tx.send(cmd);
+    /// A copy of the current device state.
+    fn snapshot(&self) -> Snapshot {
+        self.snapshot.borrow().clone()
+    }
+
+    fn set_state(&self, channel: u8, relay_state: RelayState) {
+        let index = (channel as usize).wrapping_sub(1);
+        let mut change = None;
+        self.snapshot.send_modify(|snapshot| {
+            if let Some(slot) = snapshot.relay_states.get_mut(index) {
+                *slot = relay_state;
+            }
+            if !matches!(relay_state, RelayState::On | RelayState::Off) {
+                return;
+            }
+            if let Some(slot) = snapshot.last_changes.get_mut(index) {
+                if slot.map(|(state, _)| state) != Some(relay_state) {
+                    let time = unix_now();
+                    change = Some(ChannelChange {
+                        channel,
+                        state: relay_state,
+                        previous: *slot,
+                        time,
+                    });
+                    *slot = Some((relay_state, time));
+                }
+            }
+        });
+        if let Some(change) = change {
+            if change.previous.is_some() {
+                self.switches.record(channel);
+            }
+            let _ = self.webhooks.send(change);
+        }
+        self.repaint.request_repaint();
+    }
+
+    fn set_all(&self, relay_state: RelayState) {
+        self.snapshot.send_modify(|snapshot| snapshot.relay_states.fill(relay_state));
+        self.repaint.request_repaint();
+    }
+
+    /// Counts a command the worker executed successfully.
+    fn complete(&self) {
+        self.snapshot.send_modify(|snapshot| snapshot.completed += 1);
+    }
+
+    fn send_command(&self, cmd: Command, source: Source) {
+        let _ = self.command_tx.send((cmd, source));
+    }
+}
+
+// ============================================================================
+// DEVICE WORKER
+// ============================================================================
+
+/// Owns the serial connection and executes queued commands. Any I/O failure
+/// is returned so the supervisor can reopen the device after a backoff.
+///
+/// `seen_epoch` tracks the last handled emergency stop across restarts, so a
+/// stop triggered while the device was unplugged still preempts the queue.
+fn run_device_worker(
+    ctx: &SubsystemContext,
+    queue: &CommandQueue,
+    state: &AppState,
+    stop: &EmergencyStop,
+    notifier: &Notifier,
+    gate: &DeviceGate,
+    seen_epoch: &mut u64,
+) -> Result<(), String> {
+    let device = Config::load()?.device;
+    let mut config = LiveConfig::load();
+
+    let mut history = History::open()
+        .inspect_err(|e| eprintln!("History disabled: {}", e))
+        .ok();
+    let mut record = |channel: u8, relay_state: RelayState, source: &Source| {
+        if let Some(history) = history.as_mut() {
+            if let Err(e) = history.record(channel, relay_state, source) {
+                eprintln!("{}", e);
+            }
+        }
+    };
+    let fail = |record: &mut dyn FnMut(u8, RelayState, &Source), sounds: &SoundConfig| {
+        for channel in 1..=device.channels {
+            record(channel, RelayState::Error, &Source::Device);
+        }
+        // Alert when the device goes down, not on every reconnect attempt
+        let states = state.snapshot.borrow().relay_states.clone();
+        if states.iter().any(|s| *s != RelayState::Error) {
+            sound::play(sounds, Cue::Error);
+        }
+        // Mail only when it was answering, not when it was never found
+        if states.iter().any(|s| matches!(s, RelayState::On | RelayState::Off)) {
+            let board = if device.bind.is_set() {
+                device.bind.describe()
+            } else {
+                "the first CH340 found".to_string()
+            };
+            let message = format!("Lost the board ({})", board);
+            email::send(EmailEvent::DeviceLost, vec![("message", message)]);
+        }
+        state.set_all(RelayState::Error);
+    };
+
+    let mut desired = DesiredState::load()
+        .inspect_err(|e| eprintln!("State restore disabled: {}", e))
+        .ok();
+
+    let mut controller = match RelayController::new(&device) {
+        Ok(controller) => controller,
+        Err(e) => {
+            fail(&mut record, &config.get().sound);
+            if !gate.is_open() {
+                startup::device_absent(device.startup, gate, &e);
+            }
+            return Err(e);
+        }
+    };
+    controller.traffic = Some(state.traffic.clone());
+
+    // Initial status query
+    let mut current = Vec::with_capacity(device.channels as usize);
+    for channel in 1..=device.channels {
+        let status = controller.query_status(channel).unwrap_or(RelayState::Unknown);
+        current.push((channel, status));
+        record(channel, status, &Source::Device);
+        state.set_state(channel, status);
+    }
+    controller.cooldowns.seed(&state.snapshot().last_changes, unix_now());
+    ctx.mark_running();
+    startup::device_ready(gate);
+
+    // Reapply the last commanded state, unless an emergency stop is pending
+    if device.restore_state && !state.read_only && stop.epoch() == *seen_epoch {
+        if let Some(desired) = &desired {
+            for cmd in desired.restore_commands(&current).into_iter().rev() {
+                queue.push_front(cmd, Source::Restore);
+            }
+        }
+    }
+
+    let mut drift = DriftDetector::default();
+    let mut last_poll: Option<Instant> = None;
+
+    // Command processing loop
+    while let Some((mut cmd, mut source)) = queue.pop() {
+        // Emergency stop: drop everything queued and switch all channels off
+        let epoch = stop.epoch();
+        if epoch != *seen_epoch {
+            *seen_epoch = epoch;
+            queue.clear();
+            cmd = Command::AllOff;
+            source = stop.source();
+        }
+
+        // Monitor mode: nothing but status queries reaches the board
+        if state.read_only && !matches!(cmd, Command::Poll) {
+            const READ_ONLY: &str = "Monitor mode is read-only";
+            match &cmd {
+                Command::Batch(_, Some(reply)) => {
+                    let _ = reply.send(Err(BatchError {
+                        step: 0,
+                        kind: BatchErrorKind::Rejected,
+                        message: READ_ONLY.to_string(),
+                    }));
+                }
+                Command::Raw(_, Some(reply)) => {
+                    let _ = reply.send(Err(READ_ONLY.to_string()));
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        // Raw frame: reply with the board's answer, then requery every
+        // channel since the frame may have switched any of them
+        if let Command::Raw(bytes, reply) = &cmd {
+            let result = controller.send_raw(bytes);
+            if let Some(reply) = reply {
+                let _ = reply.send(result.clone());
+            }
+            if let Err(e) = result {
+                fail(&mut record, &config.get().sound);
+                return Err(e);
+            }
+            last_poll = None;
+            queue.push_front(Command::Poll, source);
+            continue;
+        }
+
+        // Bridge session: the client has the port until it goes quiet, then
+        // every channel is requeried under its name
+        if let Command::Bridge(session) = &cmd {
+            if let Err(e) = session.run(&mut controller, &|| stop.epoch() != *seen_epoch) {
+                fail(&mut record, &config.get().sound);
+                return Err(e);
+            }
+            last_poll = None;
+            queue.push_front(Command::Poll, source);
+            continue;
+        }
+
+        // Status poll: show the reported states and warn where they differ
+        // from the last commanded ones
+        if let Command::Poll = cmd {
+            if last_poll.is_some_and(|t| t.elapsed() < POLL_MIN_GAP) {
+                continue;
+            }
+            last_poll = Some(Instant::now());
+
+            let polled = controller
+                .execute(&cmd)
+                .inspect_err(|_| fail(&mut record, &config.get().sound))?;
+            for &(channel, relay_state) in &polled {
+                record(channel, relay_state, &source);
+            }
+            for &(channel, relay_state) in &polled {
+                state.set_state(channel, relay_state);
+            }
+            if let Some(desired) = &desired {
+                for d in drift.check(&polled, desired) {
+                    notifier.notify(
+                        "State drift",
+                        format!(
+                            "{} is {} but was last switched {}",
+                            device.label(d.channel),
+                            d.reported.text(),
+                            d.desired.text()
+                        ),
+                    );
+                }
+            }
+            continue;
+        }
+
+        // Over the daily switch limit: switching on needs an override
+        let states = state.snapshot.borrow().relay_states.clone();
+        let limits = &config.get().device;
+        if let Some((channel, step)) = state.switches.blocking(limits, &cmd, &states) {
+            let message = format!(
+                "{} switched {} times today",
+                device.label(channel),
+                state.switches.count(channel)
+            );
+            if let Command::Batch(_, Some(reply)) = &cmd {
+                let _ = reply.send(Err(BatchError {
+                    step,
+                    kind: BatchErrorKind::Rejected,
+                    message: message.clone(),
+                }));
+            }
+            notifier.notify(
+                "Switch limit reached",
+                format!("{}; allow more with `channel allow {}`", message, channel),
+            );
+            continue;
+        }
+
+        // Switching on during a cooldown: wait in the queue or don't at all
+        if let Some((channel, left, mode)) = controller.cooldowns.blocking(&cmd) {
+            match mode {
+                CooldownMode::Delay => queue.push_delayed(cmd, source, Instant::now() + left),
+                CooldownMode::Reject => notifier.notify(
+                    "Switch rejected",
+                    format!(
+                        "{} is cooling down; it can switch on again in {} s",
+                        device.label(channel),
+                        left.as_secs() + 1
+                    ),
+                ),
+            }
+            continue;
+        }
+
+        let (changes, failure) = match &cmd {
+            Command::Batch(batch, reply) => {
+                let (changes, result) = controller.run_batch(batch, &|| stop.epoch() != *seen_epoch);
+                if let Some(reply) = reply {
+                    let _ = reply.send(result.clone().map(|()| changes.clone()));
+                }
+                let failure = result.err().filter(|e| e.kind == BatchErrorKind::Device);
+                (changes, failure.map(|e| e.to_string()))
+            }
+            cmd => match controller.execute(cmd) {
+                Ok(changes) => (changes, None),
+                Err(e) => (Vec::new(), Some(e)),
+            },
+        };
+
+        for &(channel, relay_state) in &changes {
+            record(channel, relay_state, &source);
+        }
+        if let Some(desired) = desired.as_mut() {
+            if let Err(e) = desired.update(&changes) {
+                eprintln!("{}", e);
+            }
+        }
+        let mut cue = None;
+        for (channel, relay_state) in changes {
+            let index = (channel as usize).wrapping_sub(1);
+            let before = state.snapshot.borrow().relay_states.get(index).copied();
+            if before != Some(relay_state) {
+                cue = match relay_state {
+                    RelayState::On => Some(Cue::On),
+                    RelayState::Off => cue.or(Some(Cue::Off)),
+                    _ => cue,
+                };
+            }
+            state.set_state(channel, relay_state);
+        }
+        if failure.is_none() {
+            state.complete();
+        }
+        if let Some(cue) = cue {
+            sound::play(&config.get().sound, cue);
+        }
+
+        if let Some(e) = failure {
+            fail(&mut record, &config.get().sound);
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// BACKGROUND SERVICES
+// ============================================================================
+
+/// Everything that runs without a window: the device worker, the command
+/// sources and the network APIs. Shared by the GUI and daemon mode.
+struct Core {
+    state: AppState,
+    supervisor: Supervisor,
+    notifier: Notifier,
+    queue: CommandQueue,
+    stop: EmergencyStop,
+    overrides: Overrides,
+}
+
+impl Core {
+    /// Starts every subsystem. `repaint` is woken on state changes; the
+    /// desktop-only ones (hotkeys, lock screen triggers) run only with a
+    /// `window`.
+    fn start(repaint: egui::Context, window: Option<Window>) -> Self {
+        let desktop = window.is_some();
+        let config = Config::load().unwrap_or_default();
+        let channels = config.device.channels;
+        let read_only = monitor::active(&config);
+        let remote = monitor::remote(&config);
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<(Command, Source)>();
+        let scheduler_tx = tx.clone();
+        let hotkey_tx = tx.clone();
+        let session_tx = tx.clone();
+        let poll_tx = tx.clone();
+        let watchdog_tx = tx.clone();
+        let process_tx = tx.clone();
+        let safety_tx = tx.clone();
+        let stop = EmergencyStop::new(tx.clone());
+        let rules_tx = tx.clone();
+        let (webhook_tx, webhook_rx) = std::sync::mpsc::channel();
+        let (rule_events, rule_rx) = std::sync::mpsc::channel();
+        let state = AppState::new(tx, webhook_tx, channels, read_only, repaint.clone());
+        let supervisor = Supervisor::new();
+        let notifier = Notifier::new(repaint.clone());
+        let overrides = Overrides::new(repaint.clone());
+        let gate = DeviceGate::new();
+
+        // Commands wait in a shared queue the GUI can show and cancel from
+        let queue = CommandQueue::new(repaint);
+        let forward_queue = queue.clone();
+        supervisor.spawn("queue", move |ctx| {
+            queue::run_forwarder(ctx, &mut rx, &forward_queue)
+        });
+
+        // Background thread for serial communication, reopened on failure.
+        // A remote monitor follows a daemon instead and leaves the board alone
+        if let Some(url) = remote {
+            let remote_state = state.clone();
+            supervisor.spawn("remote", move |ctx| {
+                monitor::run_remote(ctx, &url, &remote_state)
+            });
+        } else {
+            let worker_queue = queue.clone();
+            let state_clone = state.clone();
+            let worker_stop = stop.clone();
+            let worker_notifier = notifier.clone();
+            let worker_gate = gate.clone();
+            let mut seen_epoch = stop.epoch();
+            supervisor.spawn("device", move |ctx| {
+                let result = run_device_worker(
+                    ctx,
+                    &worker_queue,
+                    &state_clone,
+                    &worker_stop,
+                    &worker_notifier,
+                    &worker_gate,
+                    &mut seen_epoch,
+                );
+                worker_queue.finish();
+                result
+            });
+
+            // Reconnect as soon as the board is plugged back in
+            let hotplug_tx = poll_tx.clone();
+            let hotplug_supervisor = supervisor.clone();
+            let hotplug_rules = rule_events.clone();
+            supervisor.spawn("hotplug", move |ctx| {
+                hotplug::run_hotplug(ctx, &hotplug_tx, &hotplug_supervisor, &hotplug_rules)
+            });
+        }
+
+        // Command sources start once the device policy allows it, and not at
+        // all in monitor mode
+        let poll_state = state.clone();
+        supervisor.spawn(
+            "poller",
+            startup::gated(&gate, move |ctx| poll::run_poller(ctx, &poll_tx, &poll_state)),
+        );
+        if !read_only {
+            let scheduler_notifier = notifier.clone();
+            let scheduler_overrides = overrides.clone();
+            supervisor.spawn(
+                "scheduler",
+                startup::gated(&gate, move |ctx| {
+                    schedule::run_scheduler(
+                        ctx,
+                        &scheduler_tx,
+                        &scheduler_notifier,
+                        &scheduler_overrides,
+                    )
+                }),
+            );
+
+            let watchdog_notifier = notifier.clone();
+            supervisor.spawn(
+                "watchdogs",
+                startup::gated(&gate, move |ctx| {
+                    watchdog::run_watchdogs(ctx, &watchdog_tx, &watchdog_notifier)
+                }),
+            );
+
+            let safety_state = state.clone();
+            let safety_notifier = notifier.clone();
+            supervisor.spawn(
+                "safety",
+                startup::gated(&gate, move |ctx| {
+                    safety::run_safety_timers(ctx, &safety_tx, &safety_state, &safety_notifier)
+                }),
+            );
+
+            supervisor.spawn(
+                "processes",
+                startup::gated(&gate, move |ctx| {
+                    process::run_process_watcher(ctx, &process_tx)
+                }),
+            );
+
+            let rules = rules::RulesContext {
+                state: state.clone(),
+                stop: stop.clone(),
+                notifier: notifier.clone(),
+            };
+            supervisor.spawn(
+                "rules",
+                startup::gated(&gate, move |ctx| {
+                    rules::run_rules(ctx, &rules_tx, &rules, &rule_rx)
+                }),
+            );
+        }
+
+        let api = api::ApiContext {
+            state: state.clone(),
+            stop: stop.clone(),
+            rules: rule_events,
+        };
+        supervisor.spawn("api", startup::gated(&gate, move |ctx| api::run_api(ctx, &api)));
+        supervisor.spawn("mdns", startup::gated(&gate, mdns::run_mdns));
+        let telegram_state = state.clone();
+        supervisor.spawn(
+            "telegram",
+            startup::gated(&gate, move |ctx| telegram::run_telegram(ctx, &telegram_state)),
+        );
+        let hue_state = state.clone();
+        supervisor.spawn("hue", startup::gated(&gate, move |ctx| hue::run_hue(ctx, &hue_state)));
+        supervisor.spawn("hue-discovery", hue::run_hue_discovery);
+        let snmp_state = state.clone();
+        supervisor.spawn(
+            "snmp",
+            startup::gated(&gate, move |ctx| snmp::run_snmp(ctx, &snmp_state)),
+        );
+        let bridge_state = state.clone();
+        supervisor.spawn(
+            "bridge",
+            startup::gated(&gate, move |ctx| bridge::run_bridge(ctx, &bridge_state)),
+        );
+        let tcp_state = state.clone();
+        supervisor.spawn("tcp", startup::gated(&gate, move |ctx| tcp::run_tcp(ctx, &tcp_state)));
+        let (midi_state, midi_stop) = (state.clone(), stop.clone());
+        supervisor.spawn(
+            "midi",
+            startup::gated(&gate, move |ctx| midi::run_midi(ctx, &midi_state, &midi_stop)),
+        );
+        let (gamepad_state, gamepad_stop) = (state.clone(), stop.clone());
+        supervisor.spawn(
+            "gamepad",
+            startup::gated(&gate, move |ctx| {
+                gamepad::run_gamepad(ctx, &gamepad_state, &gamepad_stop)
+            }),
+        );
+        let (voice_state, voice_stop) = (state.clone(), stop.clone());
+        supervisor.spawn(
+            "voice",
+            startup::gated(&gate, move |ctx| voice::run_voice(ctx, &voice_state, &voice_stop)),
+        );
+
+        // Lets the CLI reach the board while this process holds the port, and
+        // a second launch find this one; served before the board appears
+        let ipc = ipc::IpcContext {
+            state: state.clone(),
+            gate: gate.clone(),
+            window,
+        };
+        supervisor.spawn("ipc", move |ctx| ipc::run_ipc(ctx, &ipc));
+
+        #[cfg(target_os = "linux")]
+        {
+            let dbus_state = state.clone();
+            supervisor.spawn(
+                "dbus",
+                startup::gated(&gate, move |ctx| dbus::run_dbus(ctx, &dbus_state)),
+            );
+        }
+
+        // Hotkeys and lock screen triggers need a desktop session
+        if desktop && !read_only {
+            let hotkey_stop = stop.clone();
+            supervisor.spawn(
+                "hotkeys",
+                startup::gated(&gate, move |ctx| {
+                    hotkeys::run_hotkeys(ctx, &hotkey_tx, &hotkey_stop)
+                }),
+            );
+            supervisor.spawn(
+                "session",
+                startup::gated(&gate, move |ctx| session::run_session_watcher(ctx, &session_tx)),
+            );
+        }
+
+        supervisor.spawn("webhooks", move |ctx| webhook::run_webhooks(ctx, &webhook_rx));
+        let influx_state = state.clone();
+        supervisor.spawn("influx", move |ctx| influx::run_influx(ctx, &influx_state));
+
+        let anomaly_notifier = notifier.clone();
+        supervisor.spawn("anomaly", move |ctx| {
+            anomaly::run_anomaly_detector(ctx, &anomaly_notifier)
+        });
+        let report_notifier = notifier.clone();
+        supervisor.spawn("report", move |ctx| report::run_reporter(ctx, &report_notifier));
+
+        Self {
+            state,
+            supervisor,
+            notifier,
+            queue,
+            stop,
+            overrides,
+        }
+    }
+}
+
+/// Applies the configured exit policy and blocks until the device worker
+/// has carried it out, or the confirmation times out.
+fn apply_exit_policy(config: &Config, state: &AppState, stop: &EmergencyStop) {
+    if state.read_only {
+        return;
+    }
+    let channels = config.device.channels;
+    let (target, expected) = match config.exit {
+        ExitPolicy::Leave => return,
+        ExitPolicy::ForceOff => (RelayState::Off, 1),
+        ExitPolicy::ForceOn => (RelayState::On, u64::from(channels)),
+    };
+
+    let before = state.snapshot.borrow().completed;
+    if target == RelayState::Off {
+        // Preempts anything still queued
+        stop.trigger_as(Source::Exit);
+    } else {
+        for channel in 1..=channels {
+            state.send_command(Command::TurnOn(channel), Source::Exit);
+        }
     }
+
+    let deadline = Instant::now() + EXIT_CONFIRM_TIMEOUT;
+    while Instant::now() < deadline {
+        let snapshot = state.snapshot();
+        if snapshot.completed >= before + expected {
+            if snapshot.relay_states.iter().any(|s| *s != target) {
+                eprintln!("Exit policy: not every channel reported {}", target.text());
+            }
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    eprintln!(
+        "Exit policy: no confirmation from the device within {}s",
+        EXIT_CONFIRM_TIMEOUT.as_secs()
+    );
 }
 
 // ============================================================================
 // GUI APPLICATION
 // ============================================================================
 
+/// Sets the name screen readers announce for a widget, for indicators and
+/// for buttons whose text alone ("ON") doesn't say which channel they switch.
+fn screen_reader_label(response: &egui::Response, typ: egui::WidgetType, label: String) {
+    let enabled = response.enabled();
+    response.widget_info(|| egui::WidgetInfo::labeled(typ, enabled, &label));
+}
+
+/// The announced name of a channel's indicator, e.g. "CH1: ON".
+fn indicator_label(device: &DeviceConfig, channel: u8, relay_state: RelayState) -> String {
+    let name = device.display_name(channel);
+    tr_args("channel-state", &[("channel", &name), ("state", &relay_state.local_text())])
+}
+
+/// The announced name of a switch button, e.g. "Switch CH1 on".
+fn switch_label(device: &DeviceConfig, cmd: &Command) -> String {
+    let (id, channel) = match cmd {
+        Command::TurnOn(channel) => ("switch-on", *channel),
+        Command::TurnOff(channel) => ("switch-off", *channel),
+        _ => return String::new(),
+    };
+    tr_args(id, &[("channel", &device.display_name(channel))])
+}
+
 struct RelayApp {
-    state: Arc<Mutex<AppState>>,
+    state: AppState,
+    supervisor: Supervisor,
+    notifier: Notifier,
+    sequence_editor: Option<SequenceEditor>,
+    schedule_grid: Option<ScheduleGrid>,
+    rules: Option<RulesEditor>,
+    settings: Option<SettingsWindow>,
+    console: Option<HexConsole>,
+    traffic: Option<TrafficWindow>,
+    history: Option<HistoryWindow>,
+    queue: CommandQueue,
+    debounce: Debouncer,
+    pending: PendingSwitches,
+    config: LiveConfig,
+    bulk: BulkActions,
+    stop: EmergencyStop,
+    overrides: Overrides,
+    /// Asking how long to override a scheduled channel's schedules.
+    override_prompt: Option<OverridePrompt>,
+    confirm_prompt: Option<ConfirmPrompt>,
+    lock: ControlLock,
+    /// Set by Quit, so closing isn't turned into minimizing.
+    quitting: bool,
+    mini: bool,
+    /// Present while in kiosk mode.
+    kiosk: Option<Kiosk>,
+    /// The interface scale setting last applied.
+    scale: u16,
+    /// The zoom factor the window is currently sized for.
+    zoom: f32,
+    remotes: Remotes,
 }
 
 impl RelayApp {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Configure style for cleaner look
-        let mut style = (*cc.egui_ctx.style()).clone();
-        style.visuals.window_rounding = 0.0.into();
-        style.visuals.window_shadow = egui::epaint::Shadow {
-            offset: egui::vec2(0.0, 0.0),
-            blur: 0.0,
-            spread: 0.0,
-            color: egui::Color32::TRANSPARENT,
+        let Core {
+            state,
+            supervisor,
+            notifier,
+            queue,
+            stop,
+            overrides,
+        } = Core::start(cc.egui_ctx.clone(), Some(Window::new(cc)));
+
+        let mut config = LiveConfig::load();
+        i18n::set_language(config.get().display.language.as_deref());
+        let visuals = config.get().theme.visuals(cc.integration_info.system_theme);
+        cc.egui_ctx.set_visuals(visuals);
+        let scale = config.get().window.scale;
+        cc.egui_ctx.set_zoom_factor(config.get().window.zoom());
+        let kiosk = config.get().window.kiosk.then(Kiosk::default);
+        let mini = config.get().window.mini && kiosk.is_none();
+        let lock = ControlLock::new(&config.get().lock);
+        let remotes = Remotes::new(cc.egui_ctx.clone());
+        let poller = remotes.clone();
+        supervisor.spawn("remotes", move |ctx| remotes::run_remotes(ctx, &poller));
+        if menubar::active(config.get()) {
+            menubar::start(state.clone(), Window::new(cc), stop.clone());
+        }
+        if kiosk.is_some() {
+            window::set_kiosk(&cc.egui_ctx, true);
+        } else if mini {
+            window::set_mini(&cc.egui_ctx, true, state.snapshot().relay_states.len() as u8);
+        }
+
+        Self {
+            state,
+            supervisor,
+            notifier,
+            sequence_editor: None,
+            schedule_grid: None,
+            rules: None,
+            settings: None,
+            console: None,
+            traffic: None,
+            history: None,
+            queue,
+            debounce: Debouncer::default(),
+            pending: PendingSwitches::default(),
+            config,
+            bulk: BulkActions::new(),
+            stop,
+            overrides,
+            override_prompt: None,
+            confirm_prompt: None,
+            lock,
+            quitting: false,
+            mini,
+            kiosk,
+            scale,
+            // The window was created for the system scale alone
+            zoom: 1.0,
+            remotes,
+        }
+    }
+}
+
+impl RelayApp {
+    fn send_command(&self, cmd: Command) {
+        self.state.send_command(cmd, Source::Window);
+    }
+
+    /// Sends a button press on `channel`, first asking for confirmation if
+    /// it switches off a channel that wants one.
+    fn press(&mut self, channel: u8, cmd: Command) {
+        let mode = self.config.get().device.channel(channel).confirm_off;
+        if matches!(cmd, Command::TurnOff(_)) && mode != ConfirmOff::Never {
+            self.confirm_prompt = Some(ConfirmPrompt::new(channel, cmd, mode));
+            return;
+        }
+        self.confirmed_press(channel, cmd);
+    }
+
+    /// In the full window, pressing a scheduled channel first asks how long
+    /// to override its schedules.
+    fn confirmed_press(&mut self, channel: u8, cmd: Command) {
+        let config = self.config.get();
+        let scheduled = config.schedules.iter().any(|s| s.enabled && s.affects(config, channel));
+        let full_window = !self.mini && self.kiosk.is_none();
+        if scheduled && full_window && self.overrides.get(channel).is_none() {
+            let next = schedule::next_event(config, channel).unwrap_or(None);
+            self.override_prompt = Some(OverridePrompt::new(channel, cmd, next));
+            return;
+        }
+        self.debounced_press(channel, cmd);
+    }
+
+    /// Sends a button press on `channel`, subject to its debounce setting.
+    fn debounced_press(&mut self, channel: u8, cmd: Command) {
+        let settings = self.config.get().device.channel(channel);
+        if let Some(cmd) = self.debounce.press(&settings, cmd) {
+            self.switch(cmd);
+        }
+    }
+
+    /// Sends a switch from a button and shows its target state right away.
+    fn switch(&mut self, cmd: Command) {
+        match cmd {
+            Command::TurnOn(channel) => self.pending.expect(channel, RelayState::On),
+            Command::TurnOff(channel) => self.pending.expect(channel, RelayState::Off),
+            _ => {}
+        }
+        self.send_command(cmd);
+    }
+
+    fn apply_exit_policy(&mut self) {
+        apply_exit_policy(self.config.get(), &self.state, &self.stop);
+    }
+
+    /// e.g. "Switched off 3 min ago".
+    fn last_change_text(change: LastChange) -> Option<String> {
+        let (relay_state, time) = change?;
+        let id = match relay_state {
+            RelayState::On => "switched-on",
+            RelayState::Off => "switched-off",
+            _ => return None,
         };
-        cc.egui_ctx.set_style(style);
-
-        let (tx, mut rx) = mpsc::unbounded_channel::<Command>();
-        let state = Arc::new(Mutex::new(AppState::new(tx)));
-        let state_clone = Arc::clone(&state);
-
-        // Background thread for serial communication
-        std::thread::spawn(move || {
-            let mut controller = match RelayController::new() {
-                Ok(c) => {
-                    if let Ok(mut state) = state_clone.lock() {
-                        state.error_message = None;
-                    }
-                    c
+        let ago = locale::format_relative(unix_now().saturating_sub(time));
+        Some(tr_args(id, &[("ago", &ago)]))
+    }
+
+    /// Large indicator and buttons for single-relay boards.
+    fn single_channel_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        relay_state: RelayState,
+        last_change: LastChange,
+        assumed: bool,
+        busy: bool,
+    ) {
+        ui.add_space(40.0);
+
+        let theme = self.config.get().theme.clone();
+        let device = self.config.get().device.clone();
+
+        // Status indicator - large circle
+        let (rect, indicator) = ui.allocate_exact_size(
+            egui::vec2(120.0, 120.0),
+            egui::Sense::hover()
+        );
+        let label = indicator_label(&device, 1, relay_state);
+        screen_reader_label(&indicator, egui::WidgetType::Label, label);
+
+        theme.indicator(
+            ui.painter(),
+            rect.center(),
+            60.0,
+            relay_state,
+            Some((&relay_state.local_text(), 32.0)),
+        );
+
+        ui.add_space(15.0);
+        if busy {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak(tr("pending"));
+            });
+        }
+        if assumed {
+            ui.weak(tr("assumed"));
+        }
+        ui.weak(Self::last_change_text(last_change).unwrap_or_default());
+        if let Some(active) = self.overrides.get(1) {
+            let clock = self.config.get().display.clock;
+            ui.horizontal(|ui| {
+                ui.add_space((ui.available_width() - 260.0).max(0.0) / 2.0);
+                ui.label(format!("⏸ {}", active.text(clock)));
+                if ui.small_button(tr("override-resume")).clicked() {
+                    self.overrides.clear(1);
                 }
-                Err(e) => {
-                    if let Ok(mut state) = state_clone.lock() {
-                        state.error_message = Some(e);
-                        state.relay_state = RelayState::Error;
-                    }
-                    return;
+            });
+        }
+        if self.state.switches.exhausted(&self.config.get().device, 1) {
+            let count = self.state.switches.count(1).to_string();
+            ui.horizontal(|ui| {
+                ui.add_space((ui.available_width() - 260.0).max(0.0) / 2.0);
+                ui.label(tr_args("limit-reached", &[("count", &count)]));
+                if ui.small_button(tr("limit-allow")).clicked() {
+                    self.state.switches.allow(1);
                 }
-            };
+            });
+        }
+        ui.add_space(15.0);
 
-            // Initial status query
-            if let Ok(status) = controller.query_status() {
-                if let Ok(mut state) = state_clone.lock() {
-                    state.relay_state = status;
-                    state.error_message = None;
-                }
+        // Control buttons - centered horizontally
+        ui.horizontal(|ui| {
+            // Calculate total width: 2 buttons (140px each) + gap (20px) = 300px
+            // Center in 350px window: (350 - 300) / 2 = 25px spacing
+            let available_width = ui.available_width();
+            let buttons_width = 140.0 + 20.0 + 140.0;
+            let spacing = (available_width - buttons_width) / 2.0;
+
+            ui.add_space(spacing.max(0.0));
+
+            // ON button
+            let on_button = egui::Button::new(
+                egui::RichText::new(tr("state-on")).size(28.0).strong()
+            )
+            .fill(theme.on_fill())
+            .min_size(egui::vec2(140.0, 70.0));
+
+            let response = ui.add_enabled(!busy, on_button);
+            let label = switch_label(&device, &Command::TurnOn(1));
+            screen_reader_label(&response, egui::WidgetType::Button, label);
+            if response.clicked() {
+                self.press(1, Command::TurnOn(1));
             }
 
-            // Command processing loop
-            while let Some(cmd) = rx.blocking_recv() {
-                let result = match cmd {
-                    Command::TurnOn => controller.turn_on(),
-                    Command::TurnOff => controller.turn_off(),
-                };
+            ui.add_space(20.0);
+
+            // OFF button
+            let off_button = egui::Button::new(
+                egui::RichText::new(tr("state-off")).size(28.0).strong()
+            )
+            .fill(theme.off_fill())
+            .min_size(egui::vec2(140.0, 70.0));
+
+            let response = ui.add_enabled(!busy, off_button);
+            let label = switch_label(&device, &Command::TurnOff(1));
+            screen_reader_label(&response, egui::WidgetType::Button, label);
+            if response.clicked() {
+                self.press(1, Command::TurnOff(1));
+            }
+        });
+
+        ui.add_space(30.0);
+    }
+
+    /// One compact row per channel for multi-relay boards, with a bulk
+    /// action bar for the selected channels.
+    fn channel_list_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        relay_states: &[RelayState],
+        last_changes: &[LastChange],
+        busy: &[bool],
+    ) {
+        ui.add_space(10.0);
+
+        self.bulk.show(ui, &mut self.config, relay_states, &self.stop);
 
-                if let Ok(mut state) = state_clone.lock() {
-                    match result {
-                        Ok(new_state) => {
-                            state.relay_state = new_state;
-                            state.error_message = None;
+        let device = self.config.get().device.clone();
+        let theme = self.config.get().theme.clone();
+
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            for (i, relay_state) in relay_states.iter().enumerate() {
+                let channel = i as u8 + 1;
+
+                ui.horizontal(|ui| {
+                    let mut selected = self.bulk.is_selected(channel);
+                    let checkbox = ui.checkbox(&mut selected, "");
+                    let name = device.display_name(channel);
+                    let label = tr_args("select-channel", &[("channel", &name)]);
+                    screen_reader_label(&checkbox, egui::WidgetType::Checkbox, label);
+                    if checkbox.changed() {
+                        self.bulk.set_selected(channel, selected);
+                    }
+
+                    let (rect, indicator) = ui.allocate_exact_size(
+                        egui::vec2(28.0, 28.0),
+                        egui::Sense::hover()
+                    );
+                    let label = indicator_label(&device, channel, *relay_state);
+                    screen_reader_label(&indicator, egui::WidgetType::Label, label);
+                    theme.indicator(ui.painter(), rect.center(), 14.0, *relay_state, None);
+
+                    // Ctrl-click the label to add/remove it from the selection
+                    let busy = busy.get(i).copied().unwrap_or(false);
+                    let note = if busy {
+                        format!(" {}", tr("pending-note"))
+                    } else if device.write_only {
+                        format!(" {}", tr("assumed-note"))
+                    } else {
+                        String::new()
+                    };
+                    let label = ui.add(
+                        egui::Label::new(
+                            egui::RichText::new(format!(
+                                "{}  {}{}",
+                                device.display_name(channel),
+                                relay_state.local_text(),
+                                note
+                            ))
+                            .size(18.0)
+                        )
+                        .sense(egui::Sense::click())
+                    );
+                    let label = match Self::last_change_text(last_changes.get(i).copied().flatten()) {
+                        Some(text) => label.on_hover_text(text),
+                        None => label,
+                    };
+                    if label.clicked() && ui.input(|i| i.modifiers.command) {
+                        self.bulk.toggle(channel);
+                    }
+                    if busy {
+                        ui.spinner();
+                    }
+                    if let Some(active) = self.overrides.get(channel) {
+                        let text = active.text(self.config.get().display.clock);
+                        let resume = ui
+                            .small_button(format!("⏸ {}", text))
+                            .on_hover_text(tr("override-resume-hint"));
+                        let label = tr_args("override-resume-channel", &[("channel", &name)]);
+                        screen_reader_label(&resume, egui::WidgetType::Button, label);
+                        if resume.clicked() {
+                            self.overrides.clear(channel);
                         }
-                        Err(e) => {
-                            state.relay_state = RelayState::Error;
-                            state.error_message = Some(e);
+                    }
+                    if self.state.switches.exhausted(&self.config.get().device, channel) {
+                        let count = self.state.switches.count(channel).to_string();
+                        let allow = ui
+                            .small_button(tr_args("limit-reached", &[("count", &count)]))
+                            .on_hover_text(tr("limit-allow-hint"));
+                        let label = tr_args("limit-allow-channel", &[("channel", &name)]);
+                        screen_reader_label(&allow, egui::WidgetType::Button, label);
+                        if allow.clicked() {
+                            self.state.switches.allow(channel);
                         }
                     }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.add_space(20.0);
+
+                        let off_button = egui::Button::new(
+                            egui::RichText::new(tr("state-off")).size(16.0).strong()
+                        )
+                        .fill(theme.off_fill())
+                        .min_size(egui::vec2(70.0, 32.0));
+
+                        let response = ui.add_enabled(!busy, off_button);
+                        let label = switch_label(&device, &Command::TurnOff(channel));
+                        screen_reader_label(&response, egui::WidgetType::Button, label);
+                        if response.clicked() {
+                            self.press(channel, Command::TurnOff(channel));
+                        }
+
+                        let on_button = egui::Button::new(
+                            egui::RichText::new(tr("state-on")).size(16.0).strong()
+                        )
+                        .fill(theme.on_fill())
+                        .min_size(egui::vec2(70.0, 32.0));
+
+                        let response = ui.add_enabled(!busy, on_button);
+                        let label = switch_label(&device, &Command::TurnOn(channel));
+                        screen_reader_label(&response, egui::WidgetType::Button, label);
+                        if response.clicked() {
+                            self.press(channel, Command::TurnOn(channel));
+                        }
+                    });
+                });
+
+                ui.add_space(6.0);
+            }
+        });
+
+        ui.add_space(10.0);
+    }
+
+    /// Mini mode: a dot and a toggle per channel. Drag anywhere to move the
+    /// window; right-click or press Escape to go back to the full window.
+    fn mini_ui(&mut self, ctx: &egui::Context, relay_states: &[RelayState], busy: &[bool]) {
+        let device = self.config.get().device.clone();
+        let theme = self.config.get().theme.clone();
+        let frame = egui::Frame::central_panel(&ctx.style()).inner_margin(6.0);
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+            let background = ui.interact(
+                ui.max_rect(),
+                egui::Id::new("mini_background"),
+                egui::Sense::click_and_drag(),
+            );
+            if background.drag_started() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+            }
+            // Escape stands in for the menu from the keyboard
+            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.set_mini(ctx, false);
+            }
+            background.context_menu(|ui| {
+                if ui.button(tr("full-window")).clicked() {
+                    self.set_mini(ctx, false);
+                    ui.close_menu();
                 }
+            });
+
+            for (i, relay_state) in relay_states.iter().enumerate() {
+                let channel = i as u8 + 1;
+                let busy = busy.get(i).copied().unwrap_or(false);
+                ui.horizontal(|ui| {
+                    ui.set_height(window::MINI_ROW - 6.0);
+                    let (rect, indicator) =
+                        ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+                    let label = indicator_label(&device, channel, *relay_state);
+                    screen_reader_label(&indicator, egui::WidgetType::Label, label);
+                    theme.indicator(ui.painter(), rect.center(), 7.0, *relay_state, None);
+                    ui.add(egui::Label::new(device.display_name(channel)).truncate());
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let (text, cmd) = match relay_state {
+                            RelayState::On => (tr("state-off"), Command::TurnOff(channel)),
+                            _ => (tr("state-on"), Command::TurnOn(channel)),
+                        };
+                        let toggle = egui::Button::new(egui::RichText::new(text).strong())
+                            .min_size(egui::vec2(44.0, 0.0));
+                        let response = ui.add_enabled(!busy && !self.state.read_only, toggle);
+                        let label = switch_label(&device, &cmd);
+                        screen_reader_label(&response, egui::WidgetType::Button, label);
+                        if response.clicked() {
+                            self.press(channel, cmd);
+                        }
+                    });
+                });
             }
         });
+    }
 
-        Self { state }
+    fn set_mini(&mut self, ctx: &egui::Context, mini: bool) {
+        self.mini = mini;
+        self.confirm_prompt = None;
+        self.override_prompt = None;
+        let channels = self.state.snapshot.borrow().relay_states.len() as u8;
+        window::set_mini(ctx, mini, channels);
+    }
+
+    /// Asks before a press switches off a channel that wants confirmation.
+    fn confirm_prompt_ui(&mut self, ctx: &egui::Context, device: &DeviceConfig) {
+        let Some(prompt) = self.confirm_prompt.as_mut() else {
+            return;
+        };
+        let (name, label) = (device.display_name(prompt.channel), device.label(prompt.channel));
+        match prompt.show(ctx, &name, &label) {
+            ConfirmOutcome::Open => {}
+            ConfirmOutcome::Cancelled => self.confirm_prompt = None,
+            ConfirmOutcome::Confirmed => {
+                let (channel, cmd) = (prompt.channel, prompt.cmd.clone());
+                self.confirm_prompt = None;
+                self.confirmed_press(channel, cmd);
+            }
+        }
     }
 }
 
 impl eframe::App for RelayApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint();
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.apply_exit_policy();
+    }
 
-        let state = self.state.lock().unwrap();
-        let relay_state = state.relay_state;
-        let error = state.error_message.clone();
-        drop(state);
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // The worker, queue and notifier wake the GUI when something
+        // changes; only clocks and countdowns need timed redraws
+        ctx.request_repaint_after(RELATIVE_TIME_REFRESH);
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                ui.add_space(40.0);
+        i18n::set_language(self.config.get().display.language.as_deref());
 
-                // Status indicator - large circle
-                let status_color = relay_state.color();
-                let (rect, _) = ui.allocate_exact_size(
-                    egui::vec2(120.0, 120.0),
-                    egui::Sense::hover()
-                );
+        // A changed scale setting replaces any Ctrl+Plus/Minus zoom; either
+        // way the window is resized to fit
+        let scale = self.config.get().window.scale;
+        if scale != self.scale {
+            self.scale = scale;
+            ctx.set_zoom_factor(self.config.get().window.zoom());
+        }
+        if ctx.zoom_factor() != self.zoom {
+            self.zoom = ctx.zoom_factor();
+            if self.kiosk.is_none() {
+                self.set_mini(ctx, self.mini);
+            }
+        }
 
-                ui.painter().circle_filled(
-                    rect.center(),
-                    60.0,
-                    status_color,
-                );
+        let theme = self.config.get().theme.clone();
+        let visuals = theme.visuals(frame.info().system_theme);
+        if ctx.style().visuals != visuals {
+            ctx.set_visuals(visuals);
+        }
 
-                ui.painter().text(
-                    rect.center(),
-                    egui::Align2::CENTER_CENTER,
-                    relay_state.text(),
-                    egui::FontId::proportional(32.0),
-                    egui::Color32::WHITE,
-                );
+        let Snapshot {
+            mut relay_states,
+            last_changes,
+            ..
+        } = self.state.snapshot();
+        let traffic = self.state.traffic.clone();
+        let subsystems = self.supervisor.statuses();
+        let notifications = self.notifier.pending();
+        let advanced = self.config.get().advanced;
+        let mut busy = self.queue.busy(relay_states.len() as u8);
 
-                ui.add_space(50.0);
+        // Show pressed channels in their target state until the board
+        // confirms or contradicts it
+        let device = self.config.get().device.clone();
+        for rollback in self.pending.settle(&busy, &relay_states) {
+            let id = match rollback.target {
+                RelayState::On => "switch-failed-on",
+                _ => "switch-failed-off",
+            };
+            let channel = device.label(rollback.channel);
+            let state = rollback.reported.local_text();
+            self.notifier.notify(
+                tr("switch-failed"),
+                tr_args(id, &[("channel", &channel), ("state", &state)]),
+            );
+        }
+        for (i, relay_state) in relay_states.iter_mut().enumerate() {
+            let (shown, pending) = self.pending.shown(i as u8 + 1, *relay_state);
+            *relay_state = shown;
+            busy[i] |= pending;
+        }
 
-                // Control buttons - centered horizontally
-                ui.horizontal(|ui| {
-                    // Calculate total width: 2 buttons (140px each) + gap (20px) = 300px
-                    // Center in 350px window: (350 - 300) / 2 = 25px spacing
-                    let available_width = ui.available_width();
-                    let buttons_width = 140.0 + 20.0 + 140.0;
-                    let spacing = (available_width - buttons_width) / 2.0;
+        // Presses held back by the debounce window
+        for cmd in self.debounce.due() {
+            self.switch(cmd);
+        }
+        let countdowns = self.debounce.next_due().into_iter().chain(self.pending.recheck());
+        if let Some(wait) = countdowns.min() {
+            ctx.request_repaint_after(wait);
+        }
 
-                    ui.add_space(spacing.max(0.0));
+        // Emergency stop hotkey
+        let all_off = ctx.input_mut(|i| i.consume_shortcut(&ALL_OFF_SHORTCUT));
+        if all_off && !self.state.read_only {
+            self.stop.trigger();
+        }
 
-                    // ON button
-                    let on_button = egui::Button::new(
-                        egui::RichText::new("ON").size(28.0).strong()
-                    )
-                    .fill(egui::Color32::from_rgb(22, 163, 74))
-                    .min_size(egui::vec2(140.0, 70.0));
+        // Closing only minimizes, or hides the window of the menu bar app, so
+        // the worker and schedules keep running
+        let menu_bar = menubar::active(self.config.get());
+        let background = self.config.get().window.close_to_background || menu_bar;
+        if background && !self.quitting && ctx.input(|i| i.viewport().close_requested()) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            match menu_bar {
+                true => ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false)),
+                false => ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true)),
+            }
+        }
 
-                    if ui.add(on_button).clicked() {
-                        let state = self.state.lock().unwrap();
-                        state.send_command(Command::TurnOn);
+        // Locked: only ALL OFF and the unlock field stay usable, so close
+        // whatever was open and leave mini mode, which has no room for them.
+        // A monitor is as good as locked for good
+        let lock = self.config.get().lock.clone();
+        self.lock.update(ctx, &lock);
+        let read_only = self.state.read_only;
+        if self.lock.is_locked() && self.mini {
+            self.set_mini(ctx, false);
+        }
+        let locked = self.lock.is_locked() || read_only;
+        if locked {
+            self.sequence_editor = None;
+            self.schedule_grid = None;
+            self.rules = None;
+            self.settings = None;
+            self.traffic = None;
+            self.history = None;
+            self.console = None;
+            self.override_prompt = None;
+            self.confirm_prompt = None;
+        }
+
+        if let Some(kiosk) = self.kiosk.as_mut() {
+            if lock.enabled() {
+                egui::TopBottomPanel::top("kiosk_lock").show(ctx, |ui| self.lock.bar(ui, &lock));
+            }
+            for action in kiosk.show(ctx, &device, &theme, &relay_states, &busy, locked) {
+                match action {
+                    KioskAction::Press(..) if locked => {}
+                    KioskAction::AllOff if read_only => {}
+                    KioskAction::Press(channel, cmd) => self.press(channel, cmd),
+                    KioskAction::AllOff => self.stop.trigger(),
+                    KioskAction::Exit => {
+                        self.kiosk = None;
+                        self.confirm_prompt = None;
+                        window::set_kiosk(ctx, false);
                     }
+                }
+            }
+            if self.kiosk.is_some() {
+                self.confirm_prompt_ui(ctx, &device);
+                return;
+            }
+        }
 
-                    ui.add_space(20.0);
+        // The prompt doesn't fit the mini window, so a press that wants
+        // confirmation brings back the full one to ask there
+        if self.mini {
+            self.mini_ui(ctx, &relay_states, &busy);
+            let Some(prompt) = self.confirm_prompt.take() else {
+                return;
+            };
+            self.set_mini(ctx, false);
+            self.confirm_prompt = Some(prompt);
+        }
 
-                    // OFF button
-                    let off_button = egui::Button::new(
-                        egui::RichText::new("OFF").size(28.0).strong()
-                    )
-                    .fill(egui::Color32::from_rgb(220, 38, 38))
-                    .min_size(egui::vec2(140.0, 70.0));
+        egui::TopBottomPanel::bottom("toolbar").show(ctx, |ui| {
+            let all_off = egui::Button::new(
+                egui::RichText::new(tr("all-off")).size(18.0).strong().color(egui::Color32::WHITE)
+            )
+            .fill(theme.all_off_fill())
+            .min_size(egui::vec2(ui.available_width(), 36.0));
 
-                    if ui.add(off_button).clicked() {
-                        let state = self.state.lock().unwrap();
-                        state.send_command(Command::TurnOff);
+            if ui
+                .add_enabled(!read_only, all_off)
+                .on_hover_text(tr_args(
+                    "all-off-hint",
+                    &[("shortcut", &ctx.format_shortcut(&ALL_OFF_SHORTCUT))],
+                ))
+                .clicked()
+            {
+                self.stop.trigger();
+            }
+
+            if read_only {
+                ui.label(tr("monitor-mode"));
+            } else {
+                self.lock.bar(ui, &lock);
+            }
+            ui.add_enabled_ui(!locked, |ui| ui.horizontal(|ui| {
+                let sequences = ui.small_button(tr("toolbar-sequences"));
+                if sequences.clicked() && self.sequence_editor.is_none() {
+                    self.sequence_editor = Some(SequenceEditor::new());
+                }
+                let schedules = ui.small_button(tr("toolbar-schedules"));
+                if schedules.clicked() && self.schedule_grid.is_none() {
+                    self.schedule_grid = Some(ScheduleGrid::new());
+                }
+                if ui.small_button(tr("toolbar-rules")).clicked() && self.rules.is_none() {
+                    self.rules = Some(RulesEditor::new());
+                }
+                if ui.small_button(tr("toolbar-settings")).clicked() && self.settings.is_none() {
+                    self.settings = Some(SettingsWindow::new());
+                }
+                if ui.small_button(tr("toolbar-traffic")).clicked() && self.traffic.is_none() {
+                    self.traffic = Some(TrafficWindow::new());
+                }
+                if ui.small_button(tr("toolbar-history")).clicked() && self.history.is_none() {
+                    self.history = Some(HistoryWindow::new());
+                }
+                if advanced
+                    && ui.small_button(tr("toolbar-console")).clicked()
+                    && self.console.is_none()
+                {
+                    self.console = Some(HexConsole::new());
+                }
+                let mini_button = ui.small_button(tr("toolbar-mini"));
+                if mini_button.on_hover_text(tr("toolbar-mini-hint")).clicked() {
+                    self.set_mini(ctx, true);
+                }
+                if background && ui.small_button(tr("toolbar-quit")).clicked() {
+                    self.quitting = true;
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            }));
+        });
+
+        if let Some(editor) = self.sequence_editor.as_mut() {
+            if !editor.show(ctx, &self.stop) {
+                self.sequence_editor = None;
+            }
+        }
+        if let Some(grid) = self.schedule_grid.as_mut() {
+            if !grid.show(ctx) {
+                self.schedule_grid = None;
+            }
+        }
+        if let Some(editor) = self.rules.as_mut() {
+            if !editor.show(ctx) {
+                self.rules = None;
+            }
+        }
+        if let Some(settings) = self.settings.as_mut() {
+            if !settings.show(ctx) {
+                self.settings = None;
+            }
+        }
+        if let Some(window) = self.traffic.as_mut() {
+            if !window.show(ctx, &traffic) {
+                self.traffic = None;
+            }
+        }
+        if let Some(window) = self.history.as_mut() {
+            if !window.show(ctx, &device, self.config.get().display.clock) {
+                self.history = None;
+            }
+        }
+        if let Some(console) = self.console.as_mut() {
+            if !advanced || !console.show(ctx, &self.stop) {
+                self.console = None;
+            }
+        }
+        self.confirm_prompt_ui(ctx, &device);
+        if let Some(prompt) = self.override_prompt.as_mut() {
+            let name = device.display_name(prompt.channel);
+            match prompt.show(ctx, &name, self.config.get().display.clock) {
+                PromptOutcome::Open => {}
+                PromptOutcome::Cancelled => self.override_prompt = None,
+                PromptOutcome::Override(resume) => {
+                    let (channel, cmd, next) = (prompt.channel, prompt.cmd.clone(), prompt.next);
+                    self.override_prompt = None;
+                    self.overrides.set(channel, resume, next);
+                    self.debounced_press(channel, cmd);
+                }
+            }
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if locked {
+                ui.disable();
+            }
+            ui.vertical_centered(|ui| {
+                match relay_states.as_slice() {
+                    [relay_state] => {
+                        let assumed = self.config.get().device.write_only;
+                        self.single_channel_ui(ui, *relay_state, last_changes[0], assumed, busy[0])
                     }
-                });
+                    states => self.channel_list_ui(ui, states, &last_changes, &busy),
+                }
+                self.remotes.show(ui, &theme);
 
-                ui.add_space(30.0);
+                // Commands the worker hasn't started yet
+                let queued = self.queue.snapshot(&self.config.get().device);
+                if !queued.is_empty() {
+                    ui.separator();
+                    ui.weak(tr_args("queued", &[("count", &queued.len())]));
+                    for (id, text) in &queued {
+                        ui.horizontal(|ui| {
+                            let cancel = ui.small_button("✕").on_hover_text(tr("cancel"));
+                            let label = tr_args("cancel-queued", &[("command", text)]);
+                            screen_reader_label(&cancel, egui::WidgetType::Button, label);
+                            if cancel.clicked() {
+                                self.queue.cancel(*id);
+                            }
+                            ui.label(text);
+                        });
+                    }
+                }
 
-                // Error message if any
-                if let Some(err) = error {
-                    ui.colored_label(egui::Color32::from_rgb(239, 68, 68), err);
+                // Subsystems that are down or restarting
+                for (name, status) in &subsystems {
+                    if matches!(status, SubsystemStatus::Restarting { .. }) {
+                        ui.colored_label(
+                            theme.error(),
+                            format!("{}: {}", name, status.text()),
+                        );
+                    }
+                }
+
+                // Notifications, newest first, until dismissed
+                for notification in notifications.iter().rev() {
+                    ui.horizontal_wrapped(|ui| {
+                        let dismiss = ui.small_button("✕").on_hover_text(tr("dismiss"));
+                        screen_reader_label(&dismiss, egui::WidgetType::Button, tr("dismiss"));
+                        if dismiss.clicked() {
+                            self.notifier.dismiss(notification.id);
+                        }
+                        ui.colored_label(
+                            theme.error(),
+                            format!(
+                                "{} ({}): {}",
+                                notification.title,
+                                locale::format_relative(unix_now().saturating_sub(notification.time)),
+                                notification.message
+                            ),
+                        );
+                    });
                 }
             });
         });
@@ -353,11 +2147,38 @@ impl eframe::App for RelayApp {
 // ============================================================================
 
 fn main() -> Result<(), eframe::Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Login entries may ask for the window to start minimized, dashboards
+    // for a read-only one
+    let window_flag = |a: &String| a == autostart::MINIMIZED_FLAG || a == monitor::MONITOR_FLAG;
+    if !args.iter().all(window_flag) {
+        std::process::exit(cli::run(&args));
+    }
+    if args.iter().any(|a| a == monitor::MONITOR_FLAG) {
+        monitor::force();
+    }
+    let flagged = args.iter().any(|a| a == autostart::MINIMIZED_FLAG);
+    let minimized = flagged || Config::load().is_ok_and(|c| c.window.start_minimized);
+    // The menu bar app's window waits for the menu
+    let menu_bar = Config::load().is_ok_and(|c| menubar::active(&c));
+
+    // A second launch brings the running window forward instead of fighting
+    // it for the serial port
+    match ipc::request("focus") {
+        Some(Ok(_)) => return Ok(()),
+        Some(Err(e)) => {
+            eprintln!("Already running: {}", e);
+            std::process::exit(1);
+        }
+        None => {}
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([350.0, 380.0])
+            .with_inner_size(window::FULL_SIZE)
             .with_resizable(false)
             .with_maximize_button(false)
+            .with_visible(!menu_bar)
             .with_title("USB Relay"),
         ..Default::default()
     };
@@ -365,6 +2186,11 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "USB Relay",
         options,
-        Box::new(|cc| Ok(Box::new(RelayApp::new(cc)))),
+        Box::new(move |cc| {
+            if minimized {
+                cc.egui_ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+            }
+            Ok(Box::new(RelayApp::new(cc)))
+        }),
     )
 }
@@ -0,0 +1,477 @@
+//! mDNS advertisement of the HTTP API.
+//!
+//! While the API listens on a LAN address, the app answers multicast DNS as
+//! `_usbpowerswitch._tcp`, the way printers and AirPlay speakers announce
+//! themselves, so other instances and phone apps find relays without typing
+//! an address. There's no mDNS daemon dependency: the responder shares port
+//! 5353 with Avahi or Bonjour when they run. `discover` on the CLI browses
+//! for relays.
+//!
+//! ```text
+//! _usbpowerswitch._tcp.local  PTR  USB Power Relay on bench._usbpowerswitch._tcp.local
+//! USB Power Relay on bench._usbpowerswitch._tcp.local  SRV  0 0 8787 bench.local
+//!                                                      TXT  "version=1.0.0" "auth=token" ...
+//! bench.local  A  192.168.1.20
+//! ```
+
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::supervisor::SubsystemContext;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+pub const SERVICE: &str = "_usbpowerswitch._tcp.local";
+const SERVICES: &str = "_services._dns-sd._udp.local";
+
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// On a class: the record replaces cached ones (in answers), or a unicast
+/// reply is wanted (in questions).
+const CLASS_TOP_BIT: u16 = 0x8000;
+
+const HOST_TTL: u32 = 120;
+const SERVICE_TTL: u32 = 4500;
+/// Replies to one-shot resolvers must not be cached for long.
+const LEGACY_TTL: u32 = 10;
+
+/// How often the responder checks whether its address changed.
+const TICK: Duration = Duration::from_secs(1);
+/// How long `discover` listens for answers.
+const DISCOVER_WAIT: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// HOST
+// ============================================================================
+
+/// This machine's host name, or an empty string.
+pub fn host_name() -> String {
+    std::process::Command::new("hostname")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// The address this machine uses to reach `peer`, for URLs it can follow.
+pub fn local_ip_towards(peer: SocketAddr) -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect(peer).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+fn lan_ip() -> Option<Ipv4Addr> {
+    match local_ip_towards(SocketAddr::V4(SocketAddrV4::new(MDNS_ADDR, MDNS_PORT)))? {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+        _ => None,
+    }
+}
+
+/// A UDP socket on `port` that other mDNS responders can bind as well.
+#[cfg(unix)]
+fn bind_shared(port: u16) -> std::io::Result<UdpSocket> {
+    use std::os::fd::FromRawFd;
+
+    let one: libc::c_int = 1;
+    let size = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: a fresh socket is configured and bound with properly sized
+    // arguments, and owned by the UdpSocket from then on
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let option = &one as *const libc::c_int as *const libc::c_void;
+        libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, option, size);
+        // BSDs, macOS among them, share multicast ports only with this
+        #[cfg(not(target_os = "linux"))]
+        libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_REUSEPORT, option, size);
+
+        let mut addr: libc::sockaddr_in = std::mem::zeroed();
+        addr.sin_family = libc::AF_INET as libc::sa_family_t;
+        addr.sin_port = port.to_be();
+        let len = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+        if libc::bind(fd, &addr as *const libc::sockaddr_in as *const libc::sockaddr, len) < 0 {
+            let error = std::io::Error::last_os_error();
+            libc::close(fd);
+            return Err(error);
+        }
+        Ok(UdpSocket::from_raw_fd(fd))
+    }
+}
+
+#[cfg(not(unix))]
+fn bind_shared(port: u16) -> std::io::Result<UdpSocket> {
+    UdpSocket::bind(("0.0.0.0", port))
+}
+
+// ============================================================================
+// DNS MESSAGES
+// ============================================================================
+
+fn encode_name(name: &str, out: &mut Vec<u8>) {
+    encode_labels(name.split('.'), out);
+}
+
+fn encode_labels<'a>(labels: impl IntoIterator<Item = &'a str>, out: &mut Vec<u8>) {
+    for label in labels {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend_from_slice(label);
+    }
+    out.push(0);
+}
+
+fn u16_at(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*packet.get(pos)?, *packet.get(pos + 1)?]))
+}
+
+/// The dotted name at `pos`, following compression pointers, and where the
+/// data after it starts.
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..32 {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            0xc0.. => {
+                end.get_or_insert(pos + 2);
+                pos = (u16_at(packet, pos)? & 0x3fff) as usize;
+            }
+            _ => {
+                let label = packet.get(pos + 1..pos + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len;
+            }
+        }
+    }
+    None
+}
+
+/// One resource record of a reply being built.
+fn record(out: &mut Vec<u8>, name: &[u8], kind: u16, class: u16, ttl: u32, data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend(kind.to_be_bytes());
+    out.extend(class.to_be_bytes());
+    out.extend(ttl.to_be_bytes());
+    out.extend((data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+// ============================================================================
+// RESPONDER
+// ============================================================================
+
+struct Advert {
+    /// The instance label, e.g. `USB Power Relay on bench`.
+    instance: String,
+    /// The host label, without `.local`.
+    host: String,
+    port: u16,
+    txt: Vec<String>,
+}
+
+impl Advert {
+    fn instance_name(&self) -> Vec<u8> {
+        let mut name = Vec::new();
+        encode_labels(std::iter::once(self.instance.as_str()).chain(SERVICE.split('.')), &mut name);
+        name
+    }
+
+    fn host_name(&self) -> Vec<u8> {
+        let mut name = Vec::new();
+        encode_labels([self.host.as_str(), "local"], &mut name);
+        name
+    }
+
+    /// Whether a question for `name` and `kind` is about this service.
+    fn answers(&self, name: &str, kind: u16) -> bool {
+        let name = name.to_ascii_lowercase();
+        let instance = format!("{}.{}", self.instance, SERVICE).to_ascii_lowercase();
+        let host = format!("{}.local", self.host).to_ascii_lowercase();
+        let is = |wanted: u16| kind == wanted || kind == TYPE_ANY;
+        (name == SERVICE && is(TYPE_PTR))
+            || (name == SERVICES && is(TYPE_PTR))
+            || (name == instance && (is(TYPE_SRV) || is(TYPE_TXT)))
+            || (name == host && is(TYPE_A))
+    }
+
+    /// Every record, as a reply with `id` that repeats `questions`. Legacy
+    /// replies, to resolvers not on port 5353, are short-lived and don't
+    /// flush caches.
+    fn reply(&self, ip: Ipv4Addr, id: u16, questions: (u16, &[u8]), legacy: bool) -> Vec<u8> {
+        let (shared_ttl, unique_ttl, unique) = match legacy {
+            true => (LEGACY_TTL, LEGACY_TTL, CLASS_IN),
+            false => (SERVICE_TTL, HOST_TTL, CLASS_IN | CLASS_TOP_BIT),
+        };
+        let mut out = Vec::with_capacity(512);
+        out.extend(id.to_be_bytes());
+        out.extend(0x8400u16.to_be_bytes()); // response, authoritative
+        out.extend(questions.0.to_be_bytes());
+        out.extend(5u16.to_be_bytes());
+        out.extend([0, 0, 0, 0]);
+        out.extend_from_slice(questions.1);
+
+        let (mut services, mut service) = (Vec::new(), Vec::new());
+        encode_name(SERVICES, &mut services);
+        encode_name(SERVICE, &mut service);
+        let (instance, host) = (self.instance_name(), self.host_name());
+
+        record(&mut out, &services, TYPE_PTR, CLASS_IN, shared_ttl, &service);
+        record(&mut out, &service, TYPE_PTR, CLASS_IN, shared_ttl, &instance);
+        let srv = [&[0, 0, 0, 0][..], &self.port.to_be_bytes(), &host].concat();
+        record(&mut out, &instance, TYPE_SRV, unique, unique_ttl, &srv);
+        let mut txt = Vec::new();
+        for entry in &self.txt {
+            let entry = &entry.as_bytes()[..entry.len().min(255)];
+            txt.push(entry.len() as u8);
+            txt.extend_from_slice(entry);
+        }
+        record(&mut out, &instance, TYPE_TXT, unique, shared_ttl, &txt);
+        record(&mut out, &host, TYPE_A, unique, unique_ttl, &ip.octets());
+        out
+    }
+
+    /// The reply `query` calls for, and whether it goes back to the sender
+    /// only.
+    fn reply_to(&self, query: &[u8], from: SocketAddr, ip: Ipv4Addr) -> Option<(Vec<u8>, bool)> {
+        let id = u16_at(query, 0)?;
+        if u16_at(query, 2)? & 0x8000 != 0 {
+            return None; // a response, not a query
+        }
+        let count = u16_at(query, 4)?;
+        let mut pos = 12;
+        let (mut wanted, mut unicast) = (false, false);
+        for _ in 0..count {
+            let (name, next) = read_name(query, pos)?;
+            let (kind, class) = (u16_at(query, next)?, u16_at(query, next + 2)?);
+            pos = next + 4;
+            if self.answers(&name, kind) {
+                wanted = true;
+                unicast |= class & CLASS_TOP_BIT != 0;
+            }
+        }
+        if !wanted {
+            return None;
+        }
+        let legacy = from.port() != MDNS_PORT;
+        let reply = match legacy {
+            true => self.reply(ip, id, (count, &query[12..pos]), true),
+            false => self.reply(ip, 0, (0, &[]), false),
+        };
+        Some((reply, legacy || unicast))
+    }
+}
+
+/// What the API's settings advertise, or `None` when there's nothing to
+/// reach from the LAN.
+fn advert(config: &Config) -> Option<Advert> {
+    let api = &config.api;
+    if !api.enabled || !api.advertise {
+        return None;
+    }
+    let bind: SocketAddr = api.bind.parse().ok()?;
+    if bind.ip().is_loopback() {
+        return None;
+    }
+    let name = host_name();
+    let host: String = name
+        .split('.')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let host = if host.is_empty() { "usb-power-relay".to_string() } else { host };
+    let instance = match api.name.is_empty() {
+        true => format!("USB Power Relay on {}", host),
+        false => api.name.clone(),
+    };
+    Some(Advert {
+        instance,
+        host,
+        port: bind.port(),
+        txt: vec![
+            "txtvers=1".to_string(),
+            format!("version={}", env!("CARGO_PKG_VERSION")),
+            format!("channels={}", config.device.channels),
+            format!("auth={}", if api.requires_token() { "token" } else { "none" }),
+            "path=/".to_string(),
+        ],
+    })
+}
+
+/// Advertises the API while it listens on a LAN address. Exits cleanly when
+/// there is nothing to advertise.
+pub fn run_mdns(ctx: &SubsystemContext) -> Result<(), String> {
+    let Some(advert) = advert(&Config::load()?) else {
+        return Ok(());
+    };
+    let socket = bind_shared(MDNS_PORT)
+        .map_err(|e| format!("Failed to bind mDNS port {}: {}", MDNS_PORT, e))?;
+    socket
+        .join_multicast_v4(&MDNS_ADDR, &Ipv4Addr::UNSPECIFIED)
+        .map_err(|e| format!("Failed to join mDNS multicast group: {}", e))?;
+    socket.set_read_timeout(Some(TICK)).map_err(|e| format!("mDNS: {}", e))?;
+    let group = SocketAddr::V4(SocketAddrV4::new(MDNS_ADDR, MDNS_PORT));
+    ctx.mark_running();
+
+    // Announced twice at start, as the RFC asks, and again on a new address
+    let mut announced: Option<Ipv4Addr> = None;
+    let mut announcements = 0;
+    let mut last_announcement = Instant::now();
+    let mut buf = [0u8; 9000];
+    loop {
+        let ip = lan_ip();
+        if ip != announced {
+            announced = ip;
+            announcements = 0;
+        }
+        if let Some(ip) = ip {
+            if announcements < 2 && (announcements == 0 || last_announcement.elapsed() >= TICK) {
+                let _ = socket.send_to(&advert.reply(ip, 0, (0, &[]), false), group);
+                announcements += 1;
+                last_announcement = Instant::now();
+            }
+        }
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(format!("mDNS: {}", e)),
+        };
+        let Some(ip) = ip else {
+            continue;
+        };
+        if let Some((reply, unicast)) = advert.reply_to(&buf[..len], from, ip) {
+            let _ = socket.send_to(&reply, if unicast { from } else { group });
+        }
+    }
+}
+
+// ============================================================================
+// DISCOVERY
+// ============================================================================
+
+/// A relay found on the LAN.
+#[derive(Debug, Clone, Default)]
+pub struct Found {
+    pub instance: String,
+    pub host: String,
+    pub ip: Option<Ipv4Addr>,
+    pub port: u16,
+    pub txt: Vec<String>,
+}
+
+/// Asks the LAN for relays and collects the answers for a moment.
+pub fn discover() -> Result<Vec<Found>, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("mDNS: {}", e))?;
+    let mut query = vec![0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    encode_name(SERVICE, &mut query);
+    query.extend(TYPE_PTR.to_be_bytes());
+    query.extend((CLASS_IN | CLASS_TOP_BIT).to_be_bytes());
+    socket
+        .send_to(&query, (MDNS_ADDR, MDNS_PORT))
+        .map_err(|e| format!("Failed to send the mDNS query: {}", e))?;
+
+    let mut found: Vec<Found> = Vec::new();
+    let mut addresses: Vec<(String, Ipv4Addr)> = Vec::new();
+    let deadline = Instant::now() + DISCOVER_WAIT;
+    let mut buf = [0u8; 9000];
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        let _ = socket.set_read_timeout(Some(left.max(Duration::from_millis(1))));
+        let Ok(len) = socket.recv(&mut buf) else {
+            break;
+        };
+        let _ = read_reply(&buf[..len], &mut found, &mut addresses);
+    }
+
+    for relay in &mut found {
+        let host = relay.host.to_ascii_lowercase();
+        relay.ip = addresses.iter().find(|(name, _)| *name == host).map(|(_, ip)| *ip);
+    }
+    found.retain(|relay| relay.port != 0);
+    Ok(found)
+}
+
+/// The relay called `instance`, added if it's new.
+fn entry(found: &mut Vec<Found>, instance: String) -> &mut Found {
+    let index = match found.iter().position(|f| f.instance == instance) {
+        Some(index) => index,
+        None => {
+            found.push(Found {
+                instance,
+                ..Found::default()
+            });
+            found.len() - 1
+        }
+    };
+    &mut found[index]
+}
+
+/// Gathers the relays and host addresses in one reply.
+fn read_reply(
+    packet: &[u8],
+    found: &mut Vec<Found>,
+    addresses: &mut Vec<(String, Ipv4Addr)>,
+) -> Option<()> {
+    let questions = u16_at(packet, 4)?;
+    let records = [6, 8, 10].iter().try_fold(0, |n, &at| Some(n + u16_at(packet, at)?))?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+    let suffix = format!(".{}", SERVICE);
+    for _ in 0..records {
+        let (name, next) = read_name(packet, pos)?;
+        let kind = u16_at(packet, next)?;
+        let len = u16_at(packet, next + 8)? as usize;
+        let data_at = next + 10;
+        let data = packet.get(data_at..data_at + len)?;
+        pos = data_at + len;
+
+        let instance = name.strip_suffix(&suffix).map(str::to_string);
+        match kind {
+            TYPE_PTR if name.eq_ignore_ascii_case(SERVICE) => {
+                let (target, _) = read_name(packet, data_at)?;
+                if let Some(instance) = target.strip_suffix(&suffix) {
+                    entry(found, instance.to_string());
+                }
+            }
+            TYPE_SRV if instance.is_some() => {
+                let relay = entry(found, instance?);
+                relay.port = u16_at(data, 4)?;
+                relay.host = read_name(packet, data_at + 6)?.0;
+            }
+            TYPE_TXT if instance.is_some() => {
+                let relay = entry(found, instance?);
+                let mut rest = data;
+                relay.txt.clear();
+                while let Some((&n, tail)) = rest.split_first() {
+                    let text = tail.get(..n as usize)?;
+                    relay.txt.push(String::from_utf8_lossy(text).into_owned());
+                    rest = &tail[n as usize..];
+                }
+            }
+            TYPE_A if len == 4 => {
+                let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+                addresses.push((name.to_ascii_lowercase(), ip));
+            }
+            _ => {}
+        }
+    }
+    Some(())
+}
@@ -0,0 +1,373 @@
+//! macOS menu bar mode.
+//!
+//! With `window.menu_bar` on macOS the app lives in the menu bar: a status
+//! item shows a dot per channel, and its menu switches them, turns
+//! everything off, brings the window back or quits. The window starts
+//! hidden, closing it hides it again, and there is no Dock icon. Switching
+//! from the menu follows the window's rules: a monitor or a control lock
+//! leaves the channels read-only, and an OFF that asks for confirmation
+//! opens the window to ask there. Elsewhere the setting is ignored.
+//!
+//! AppKit is reached through the Objective-C runtime directly; everything
+//! it is asked runs on the main thread.
+
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::window::Window;
+use crate::AppState;
+
+// ============================================================================
+// ENTRY POINTS
+// ============================================================================
+
+/// Whether the app runs from the menu bar; kiosk mode keeps the window.
+pub fn active(config: &Config) -> bool {
+    cfg!(target_os = "macos") && config.window.menu_bar && !config.window.kiosk
+}
+
+/// Adds the status item; call on the main thread once the app is running.
+pub fn start(state: AppState, window: Window, stop: EmergencyStop) {
+    platform::start(state, window, stop)
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use crate::emergency::EmergencyStop;
+    use crate::window::Window;
+    use crate::AppState;
+
+    pub fn start(_state: AppState, _window: Window, _stop: EmergencyStop) {}
+}
+
+#[cfg(target_os = "macos")]
+pub use platform::order_front;
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::cell::RefCell;
+    use std::ffi::{c_char, c_void, CString};
+    use std::time::Duration;
+
+    use crate::audit::Source;
+    use crate::config::LiveConfig;
+    use crate::confirm::ConfirmOff;
+    use crate::emergency::EmergencyStop;
+    use crate::i18n::tr;
+    use crate::window::Window;
+    use crate::{apply_exit_policy, AppState, Command, RelayState};
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+    type Action = extern "C" fn(this: Id, cmd: Sel, sender: Id);
+
+    /// How often the status item follows the channels.
+    const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+    const ACTIVATION_POLICY_ACCESSORY: isize = 1;
+    const VARIABLE_LENGTH: f64 = -1.0;
+    const CONTROL_STATE_ON: isize = 1;
+    const TARGET_CLASS: &str = "UsbPowerRelayMenuTarget";
+
+    #[link(name = "objc", kind = "dylib")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_allocateClassPair(superclass: Id, name: *const c_char, extra: usize) -> Id;
+        fn objc_registerClassPair(class: Id);
+        fn class_addMethod(class: Id, name: Sel, imp: Action, types: *const c_char) -> bool;
+        fn objc_msgSend();
+    }
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+
+    // libdispatch is part of libSystem
+    extern "C" {
+        static _dispatch_main_q: c_void;
+        fn dispatch_async_f(queue: *const c_void, context: *mut c_void, work: extern "C" fn(Id));
+    }
+
+    // ------------------------------------------------------------------------
+    // Objective-C messaging
+    // ------------------------------------------------------------------------
+
+    fn c_string(text: &str) -> CString {
+        CString::new(text.replace('\0', "")).unwrap_or_default()
+    }
+
+    unsafe fn class(name: &str) -> Id {
+        objc_getClass(c_string(name).as_ptr())
+    }
+
+    unsafe fn sel(name: &str) -> Sel {
+        sel_registerName(c_string(name).as_ptr())
+    }
+
+    /// `objc_msgSend` as the function type `F` of the method called.
+    unsafe fn msg_send<F: Copy>() -> F {
+        let send: unsafe extern "C" fn() = objc_msgSend;
+        std::mem::transmute_copy(&send)
+    }
+
+    unsafe fn send(receiver: Id, selector: &str) -> Id {
+        let f: unsafe extern "C" fn(Id, Sel) -> Id = msg_send();
+        f(receiver, sel(selector))
+    }
+
+    unsafe fn send_id(receiver: Id, selector: &str, arg: Id) -> Id {
+        let f: unsafe extern "C" fn(Id, Sel, Id) -> Id = msg_send();
+        f(receiver, sel(selector), arg)
+    }
+
+    unsafe fn send_int(receiver: Id, selector: &str, arg: isize) -> Id {
+        let f: unsafe extern "C" fn(Id, Sel, isize) -> Id = msg_send();
+        f(receiver, sel(selector), arg)
+    }
+
+    unsafe fn send_bool(receiver: Id, selector: &str, arg: bool) -> Id {
+        let f: unsafe extern "C" fn(Id, Sel, bool) -> Id = msg_send();
+        f(receiver, sel(selector), arg)
+    }
+
+    /// An autoreleased `NSString`.
+    unsafe fn ns_string(text: &str) -> Id {
+        let f: unsafe extern "C" fn(Id, Sel, *const c_char) -> Id = msg_send();
+        f(class("NSString"), sel("stringWithUTF8String:"), c_string(text).as_ptr())
+    }
+
+    unsafe fn shared_app() -> Id {
+        send(class("NSApplication"), "sharedApplication")
+    }
+
+    // ------------------------------------------------------------------------
+    // Status item
+    // ------------------------------------------------------------------------
+
+    /// The status item's symbol for a channel, in the indicator's shapes.
+    fn glyph(state: RelayState) -> &'static str {
+        match state {
+            RelayState::On => "●",
+            RelayState::Off => "○",
+            RelayState::Unknown => "?",
+            RelayState::Error => "▲",
+        }
+    }
+
+    struct MenuBar {
+        state: AppState,
+        window: Window,
+        stop: EmergencyStop,
+        config: LiveConfig,
+        /// The retained `NSStatusItem`.
+        item: Id,
+        /// Receives the menu's actions and the refresh timer.
+        target: Id,
+        /// The channels as last shown, and whether they could be switched.
+        shown: Option<(Vec<(String, RelayState)>, bool)>,
+    }
+
+    thread_local! {
+        static MENU_BAR: RefCell<Option<MenuBar>> = const { RefCell::new(None) };
+    }
+
+    fn with_menu_bar(f: impl FnOnce(&mut MenuBar)) {
+        MENU_BAR.with(|menu_bar| {
+            if let Some(menu_bar) = menu_bar.borrow_mut().as_mut() {
+                f(menu_bar);
+            }
+        });
+    }
+
+    extern "C" fn refresh(_this: Id, _cmd: Sel, _sender: Id) {
+        with_menu_bar(MenuBar::refresh);
+    }
+
+    extern "C" fn toggle(_this: Id, _cmd: Sel, sender: Id) {
+        // SAFETY: the sender is one of the channel items, tagged with its
+        // channel
+        let tag = unsafe {
+            let f: unsafe extern "C" fn(Id, Sel) -> isize = msg_send();
+            f(sender, sel("tag"))
+        };
+        with_menu_bar(|menu_bar| menu_bar.toggle(tag as u8));
+    }
+
+    extern "C" fn all_off(_this: Id, _cmd: Sel, _sender: Id) {
+        with_menu_bar(|menu_bar| menu_bar.stop.trigger());
+    }
+
+    extern "C" fn show_window(_this: Id, _cmd: Sel, _sender: Id) {
+        with_menu_bar(|menu_bar| menu_bar.window.show());
+    }
+
+    extern "C" fn quit(_this: Id, _cmd: Sel, _sender: Id) {
+        with_menu_bar(|menu_bar| {
+            apply_exit_policy(menu_bar.config.get(), &menu_bar.state, &menu_bar.stop);
+            std::process::exit(0);
+        });
+    }
+
+    /// The class of the object the menu's items and the timer call.
+    unsafe fn target_class() -> Id {
+        let class = objc_allocateClassPair(class("NSObject"), c_string(TARGET_CLASS).as_ptr(), 0);
+        let types = c_string("v@:@");
+        let methods: [(&str, Action); 5] = [
+            ("refresh:", refresh),
+            ("toggle:", toggle),
+            ("allOff:", all_off),
+            ("showWindow:", show_window),
+            ("quit:", quit),
+        ];
+        for (name, imp) in methods {
+            class_addMethod(class, sel(name), imp, types.as_ptr());
+        }
+        objc_registerClassPair(class);
+        class
+    }
+
+    pub fn start(state: AppState, window: Window, stop: EmergencyStop) {
+        // SAFETY: called on the main thread, inside the app's run loop, with
+        // selectors that match the argument types passed
+        let (item, target) = unsafe {
+            send_int(shared_app(), "setActivationPolicy:", ACTIVATION_POLICY_ACCESSORY);
+            let target = send(send(target_class(), "alloc"), "init");
+            let bar = send(class("NSStatusBar"), "systemStatusBar");
+            let with_length: unsafe extern "C" fn(Id, Sel, f64) -> Id = msg_send();
+            let item = with_length(bar, sel("statusItemWithLength:"), VARIABLE_LENGTH);
+            let item = send(item, "retain");
+            let schedule: unsafe extern "C" fn(Id, Sel, f64, Id, Sel, Id, bool) -> Id = msg_send();
+            schedule(
+                class("NSTimer"),
+                sel("scheduledTimerWithTimeInterval:target:selector:userInfo:repeats:"),
+                REFRESH_INTERVAL.as_secs_f64(),
+                target,
+                sel("refresh:"),
+                std::ptr::null_mut(),
+                true,
+            );
+            (item, target)
+        };
+        MENU_BAR.with(|menu_bar| {
+            *menu_bar.borrow_mut() = Some(MenuBar {
+                state,
+                window,
+                stop,
+                config: LiveConfig::load(),
+                item,
+                target,
+                shown: None,
+            });
+        });
+        with_menu_bar(MenuBar::refresh);
+    }
+
+    impl MenuBar {
+        /// Redraws the title and menu when a channel or its label changed.
+        fn refresh(&mut self) {
+            let config = self.config.get();
+            let states = self.state.snapshot().relay_states;
+            let channels: Vec<(String, RelayState)> = states
+                .iter()
+                .enumerate()
+                .map(|(i, state)| (config.device.display_name(i as u8 + 1), *state))
+                .collect();
+            let switchable = !self.state.read_only && !config.lock.enabled();
+            if self.shown.as_ref().is_some_and(|shown| *shown == (channels.clone(), switchable)) {
+                return;
+            }
+            let title: Vec<&str> = channels.iter().map(|(_, state)| glyph(*state)).collect();
+            // SAFETY: the timer and the menu's actions run on the main thread
+            unsafe {
+                let button = send(self.item, "button");
+                send_id(button, "setTitle:", ns_string(&title.join(" ")));
+                let menu = self.menu(&channels, switchable);
+                send_id(self.item, "setMenu:", menu);
+                send(menu, "release");
+            }
+            self.shown = Some((channels, switchable));
+        }
+
+        /// A retained menu with an item per channel and the app's actions.
+        unsafe fn menu(&self, channels: &[(String, RelayState)], switchable: bool) -> Id {
+            let menu = send(send(class("NSMenu"), "alloc"), "init");
+            send_bool(menu, "setAutoenablesItems:", false);
+            for (i, (name, state)) in channels.iter().enumerate() {
+                let item = self.add_item(menu, &format!("{} {}", glyph(*state), name), "toggle:");
+                send_int(item, "setTag:", i as isize + 1);
+                let on = if *state == RelayState::On { CONTROL_STATE_ON } else { 0 };
+                send_int(item, "setState:", on);
+                send_bool(item, "setEnabled:", switchable);
+            }
+            send_id(menu, "addItem:", send(class("NSMenuItem"), "separatorItem"));
+            let all_off = self.add_item(menu, &tr("all-off"), "allOff:");
+            send_bool(all_off, "setEnabled:", !self.state.read_only);
+            self.add_item(menu, &tr("menubar-show"), "showWindow:");
+            send_id(menu, "addItem:", send(class("NSMenuItem"), "separatorItem"));
+            self.add_item(menu, &tr("toolbar-quit"), "quit:");
+            menu
+        }
+
+        /// Adds an item calling `action` on the target; the menu keeps it.
+        unsafe fn add_item(&self, menu: Id, title: &str, action: &str) -> Id {
+            let init: unsafe extern "C" fn(Id, Sel, Id, Sel, Id) -> Id = msg_send();
+            let item = init(
+                send(class("NSMenuItem"), "alloc"),
+                sel("initWithTitle:action:keyEquivalent:"),
+                ns_string(title),
+                sel(action),
+                ns_string(""),
+            );
+            send_id(item, "setTarget:", self.target);
+            send_id(menu, "addItem:", item);
+            send(item, "release");
+            item
+        }
+
+        fn toggle(&mut self, channel: u8) {
+            let state = match &self.shown {
+                Some((channels, true)) => channels.get(usize::from(channel).wrapping_sub(1)),
+                _ => None,
+            };
+            let Some(&(_, state)) = state else {
+                return;
+            };
+            let cmd = match state {
+                RelayState::On => Command::TurnOff(channel),
+                _ => Command::TurnOn(channel),
+            };
+            // The window asks for confirmations
+            let confirm = self.config.get().device.channel(channel).confirm_off;
+            if matches!(cmd, Command::TurnOff(_)) && confirm != ConfirmOff::Never {
+                self.window.show();
+                return;
+            }
+            self.state.send_command(cmd, Source::Window);
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Window
+    // ------------------------------------------------------------------------
+
+    extern "C" fn order_front_now(view: Id) {
+        // SAFETY: runs on the main thread, where the view lives
+        unsafe {
+            let window = send(view, "window");
+            send_id(window, "makeKeyAndOrderFront:", std::ptr::null_mut());
+            send_bool(shared_app(), "activateIgnoringOtherApps:", true);
+        }
+    }
+
+    /// Shows and focuses the window holding the `NSView` at `view`, from
+    /// any thread.
+    pub fn order_front(view: usize) {
+        // SAFETY: the main queue lives as long as the process, and the view
+        // as long as the window
+        unsafe {
+            dispatch_async_f(&_dispatch_main_q, view as Id, order_front_now);
+        }
+    }
+}
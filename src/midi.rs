@@ -0,0 +1,320 @@
+//! MIDI control surfaces.
+//!
+//! With `midi.enabled`, notes and controllers from a control surface drive
+//! the same actions as hotkeys, and the mapped pads' LEDs follow the
+//! channels: the note or controller is sent back at 127 while its channel
+//! is on and at 0 otherwise, which most surfaces show as lit and dark.
+//!
+//! ```ron
+//! midi: (
+//!     enabled: true,
+//!     mappings: [
+//!         (input: Note(36), action: Toggle(1)),
+//!         (input: Cc(64), action: AllOff),
+//!     ],
+//! ),
+//! ```
+//!
+//! A note fires when it is pressed, a controller when it rises to 64 or
+//! above. The surface is opened as a raw MIDI device, on Linux
+//! `/dev/snd/midiC<card>D<device>`; the first one is used unless `device`
+//! names another.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::hotkeys::HotkeyAction;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long to wait when the surface has nothing to say.
+const READ_INTERVAL: Duration = Duration::from_millis(10);
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Controller values from here up count as pressed.
+const PRESSED: u8 = 64;
+const LED_ON: u8 = 127;
+
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xB0;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MidiInput {
+    Note(u8),
+    Cc(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiMapping {
+    pub input: MidiInput,
+    pub action: HotkeyAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiConfig {
+    pub enabled: bool,
+    /// Raw MIDI device; empty for the first one found.
+    pub device: String,
+    /// MIDI channel to listen and answer on, 1 to 16; `None` listens on
+    /// every channel and answers on 1.
+    pub channel: Option<u8>,
+    /// Light the mapped pads while their channels are on.
+    pub feedback: bool,
+    pub mappings: Vec<MidiMapping>,
+}
+
+impl Default for MidiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: String::new(),
+            channel: None,
+            feedback: true,
+            mappings: Vec::new(),
+        }
+    }
+}
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+/// An input this module acts on, with its MIDI channel counted from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    NoteOn { channel: u8, note: u8 },
+    Control { channel: u8, number: u8, value: u8 },
+}
+
+/// Splits a raw MIDI byte stream into messages, following running status
+/// and skipping system messages.
+#[derive(Default)]
+struct Parser {
+    status: Option<u8>,
+    data: Vec<u8>,
+    sysex: bool,
+}
+
+impl Parser {
+    fn push(&mut self, byte: u8) -> Option<Event> {
+        match byte {
+            // Real-time messages may arrive anywhere, even inside others
+            0xF8..=0xFF => return None,
+            0xF7 => self.sysex = false,
+            // Other system messages cancel running status
+            0xF0..=0xF6 => {
+                self.sysex = byte == 0xF0;
+                self.status = None;
+            }
+            0x80..=0xEF => {
+                self.sysex = false;
+                self.status = Some(byte);
+                self.data.clear();
+            }
+            _ if self.sysex => {}
+            _ => {
+                let status = self.status?;
+                self.data.push(byte);
+                let len = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                if self.data.len() < len {
+                    return None;
+                }
+                let data = std::mem::take(&mut self.data);
+                let channel = status & 0x0F;
+                return match status & 0xF0 {
+                    // Note On at velocity 0 is a release
+                    NOTE_ON if data[1] > 0 => Some(Event::NoteOn { channel, note: data[0] }),
+                    CONTROL_CHANGE => Some(Event::Control {
+                        channel,
+                        number: data[0],
+                        value: data[1],
+                    }),
+                    _ => None,
+                };
+            }
+        }
+        None
+    }
+}
+
+// ============================================================================
+// SURFACE
+// ============================================================================
+
+/// The configured device, or the first raw MIDI device.
+fn device_path(configured: &str) -> Result<String, String> {
+    if !configured.is_empty() {
+        return Ok(configured.to_string());
+    }
+    let mut found: Vec<String> = std::fs::read_dir("/dev/snd")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("midiC"))
+                .collect()
+        })
+        .unwrap_or_default();
+    found.sort();
+    found
+        .first()
+        .map(|name| format!("/dev/snd/{}", name))
+        .ok_or_else(|| "No raw MIDI device found; set midi.device".to_string())
+}
+
+#[cfg(unix)]
+fn open(path: &str, write: bool) -> Result<File, String> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(write)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+#[cfg(not(unix))]
+fn open(_path: &str, _write: bool) -> Result<File, String> {
+    Err("MIDI control needs a raw MIDI device, which this platform doesn't have".to_string())
+}
+
+fn fire(action: HotkeyAction, state: &AppState, stop: &EmergencyStop) {
+    let cmd = match action {
+        HotkeyAction::Toggle(channel) => Command::Toggle(channel),
+        HotkeyAction::On(channel) => Command::TurnOn(channel),
+        HotkeyAction::Off(channel) => Command::TurnOff(channel),
+        HotkeyAction::AllOff => return stop.trigger(),
+    };
+    state.send_command(cmd, Source::Midi);
+}
+
+/// The mapped input an event presses, if any. `controllers` holds the last
+/// value of each controller, to catch it rising.
+fn pressed(settings: &MidiConfig, event: Event, controllers: &mut [u8; 128]) -> Option<MidiInput> {
+    let (channel, input) = match event {
+        Event::NoteOn { channel, note } => (channel, MidiInput::Note(note)),
+        Event::Control { channel, number, value } => {
+            let previous = std::mem::replace(&mut controllers[usize::from(number & 0x7F)], value);
+            if value < PRESSED || previous >= PRESSED {
+                return None;
+            }
+            (channel, MidiInput::Cc(number))
+        }
+    };
+    match settings.channel {
+        Some(wanted) if wanted != channel + 1 => None,
+        _ => Some(input),
+    }
+}
+
+/// Lights the pads of the channels whose state changed since `shown`.
+fn send_feedback(
+    device: &mut File,
+    settings: &MidiConfig,
+    shown: Option<&[RelayState]>,
+    states: &[RelayState],
+) -> Result<(), String> {
+    let midi_channel = settings.channel.unwrap_or(1).clamp(1, 16) - 1;
+    let mut bytes = Vec::new();
+    for mapping in &settings.mappings {
+        let Some(channel) = mapping.action.channel() else {
+            continue;
+        };
+        let index = usize::from(channel).wrapping_sub(1);
+        let state = states.get(index).copied();
+        if shown.is_some_and(|shown| shown.get(index).copied() == state) {
+            continue;
+        }
+        let value = if state == Some(RelayState::On) { LED_ON } else { 0 };
+        match mapping.input {
+            MidiInput::Note(note) => bytes.extend([NOTE_ON | midi_channel, note & 0x7F, value]),
+            MidiInput::Cc(number) => {
+                bytes.extend([CONTROL_CHANGE | midi_channel, number & 0x7F, value])
+            }
+        }
+    }
+    match device.write_all(&bytes) {
+        // A full output buffer only loses a lamp update
+        Err(e) if e.kind() != ErrorKind::WouldBlock => Err(format!("MIDI write failed: {}", e)),
+        _ => Ok(()),
+    }
+}
+
+/// Follows the control surface while enabled, reopening it whenever the
+/// config file changes. Exits cleanly when disabled.
+pub fn run_midi(
+    ctx: &SubsystemContext,
+    state: &AppState,
+    stop: &EmergencyStop,
+) -> Result<(), String> {
+    loop {
+        let modified = Config::modified();
+        let settings = Config::load()?.midi;
+        if !settings.enabled {
+            return Ok(());
+        }
+        let path = device_path(&settings.device)?;
+        let mut device = open(&path, settings.feedback)?;
+        ctx.mark_running();
+
+        let mut parser = Parser::default();
+        let mut controllers = [0u8; 128];
+        let mut states = state.snapshot.subscribe();
+        let mut shown: Option<Vec<RelayState>> = None;
+        let mut checked = Instant::now();
+        loop {
+            let mut buf = [0u8; 256];
+            match device.read(&mut buf) {
+                Ok(0) => return Err(format!("{} was disconnected", path)),
+                Ok(n) => {
+                    for &byte in &buf[..n] {
+                        let Some(event) = parser.push(byte) else {
+                            continue;
+                        };
+                        let Some(input) = pressed(&settings, event, &mut controllers) else {
+                            continue;
+                        };
+                        if state.read_only {
+                            continue;
+                        }
+                        for mapping in settings.mappings.iter().filter(|m| m.input == input) {
+                            fire(mapping.action, state, stop);
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(READ_INTERVAL),
+                Err(e) => return Err(format!("Reading {} failed: {}", path, e)),
+            }
+
+            if settings.feedback && (shown.is_none() || states.has_changed().unwrap_or(false)) {
+                let current = states.borrow_and_update().relay_states.clone();
+                send_feedback(&mut device, &settings, shown.as_deref(), &current)?;
+                shown = Some(current);
+            }
+
+            if checked.elapsed() >= RELOAD_CHECK_INTERVAL {
+                checked = Instant::now();
+                if Config::modified() != modified {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Read-only monitor mode.
+//!
+//! Launched with `--monitor` or with `monitor.enabled`, the app only shows
+//! channel states, for a secondary dashboard. Either it opens the board and
+//! asks nothing but status (the device worker drops every switch, batch and
+//! raw frame, and no schedules, rules or other command sources run), or,
+//! with `monitor.remote` set, it leaves the board alone and follows a daemon
+//! elsewhere through its HTTP API's `/status`, presenting `monitor.token`
+//! when the daemon's API needs one.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::http;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Launch flag for a read-only window.
+pub const MONITOR_FLAG: &str = "--monitor";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_STATUS_BYTES: usize = 64 * 1024;
+
+/// Set by [`MONITOR_FLAG`], for the rest of the process.
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    pub enabled: bool,
+    /// Base URL of a daemon's HTTP API to follow instead of opening the
+    /// board, e.g. `http://pi.local:8787`.
+    pub remote: Option<String>,
+    /// Bearer token for the daemon's API.
+    pub token: Option<String>,
+    /// How often to ask for the states.
+    pub poll_secs: u64,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            remote: None,
+            token: None,
+            poll_secs: 2,
+        }
+    }
+}
+
+/// Turns monitor mode on regardless of the config.
+pub fn force() {
+    FORCED.store(true, Ordering::SeqCst);
+}
+
+pub fn active(config: &Config) -> bool {
+    FORCED.load(Ordering::SeqCst) || config.monitor.enabled
+}
+
+/// The daemon to follow, when monitoring one.
+pub fn remote(config: &Config) -> Option<String> {
+    let url = config.monitor.remote.as_deref()?.trim().trim_end_matches('/');
+    (active(config) && !url.is_empty()).then(|| url.to_string())
+}
+
+// ============================================================================
+// REMOTE
+// ============================================================================
+
+/// Mirrors the channel states a daemon's API reports. Fails, to be
+/// restarted, when the daemon can't be reached; the channels show errors
+/// meanwhile.
+pub fn run_remote(ctx: &SubsystemContext, url: &str, state: &AppState) -> Result<(), String> {
+    let status_url = format!("{}/status", url);
+    let mut running = false;
+    loop {
+        let settings = Config::load().map(|c| c.monitor).unwrap_or_default();
+        let states = fetch(&status_url, settings.token.as_deref())
+            .inspect_err(|_| state.set_all(RelayState::Error))?;
+        if !running {
+            ctx.mark_running();
+            running = true;
+        }
+        for (i, relay_state) in states.into_iter().enumerate() {
+            state.set_state(i as u8 + 1, relay_state);
+        }
+
+        std::thread::sleep(Duration::from_secs(settings.poll_secs.max(1)));
+    }
+}
+
+fn fetch(url: &str, token: Option<&str>) -> Result<Vec<RelayState>, String> {
+    let authorization = token.map(|token| format!("Bearer {}", token));
+    let headers: Vec<(&str, &str)> =
+        authorization.iter().map(|value| ("Authorization", value.as_str())).collect();
+    let response =
+        http::request_with_body("GET", url, &headers, REQUEST_TIMEOUT, MAX_STATUS_BYTES)?;
+    if response.status != 200 {
+        return Err(format!("{}: HTTP {}", url, response.status));
+    }
+    parse_status(&String::from_utf8_lossy(&response.body))
+        .ok_or_else(|| format!("{}: unexpected reply", url))
+}
+
+/// The states in a `/status` reply, `{"channels":[{"channel":1,"state":"on"},…]}`,
+/// in channel order.
+fn parse_status(body: &str) -> Option<Vec<RelayState>> {
+    let channels = body.split_once("\"channels\"")?.1;
+    channels
+        .split("\"state\"")
+        .skip(1)
+        .map(|rest| {
+            let name = rest.trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+            match name.split('"').next()? {
+                "on" => Some(RelayState::On),
+                "off" => Some(RelayState::Off),
+                "error" => Some(RelayState::Error),
+                _ => Some(RelayState::Unknown),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,91 @@
+//! In-app notifications.
+//!
+//! Subsystems raise notifications through a shared [`Notifier`]; the GUI
+//! shows the most recent ones until they are dismissed, and they are pushed
+//! to a phone or a chat webhook when that's configured.
+
+use eframe::egui;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::history::unix_now;
+use crate::push::{self, PushKind};
+use crate::webhook;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const MAX_NOTIFICATIONS: usize = 20;
+
+// ============================================================================
+// NOTIFIER
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub id: u64,
+    /// Unix timestamp in seconds.
+    pub time: u64,
+    pub title: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    pending: VecDeque<Notification>,
+}
+
+#[derive(Clone)]
+pub struct Notifier {
+    inner: Arc<Mutex<Inner>>,
+    /// Woken whenever a notification is raised.
+    repaint: egui::Context,
+}
+
+impl Notifier {
+    pub fn new(repaint: egui::Context) -> Self {
+        Self {
+            inner: Arc::default(),
+            repaint,
+        }
+    }
+
+    pub fn notify(&self, title: impl Into<String>, message: impl Into<String>) {
+        let title = title.into();
+        let message = message.into();
+        eprintln!("{}: {}", title, message);
+        push::push(PushKind::Notification, title.clone(), message.clone());
+        webhook::post_notification(title.clone(), message.clone());
+
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.next_id += 1;
+            let id = inner.next_id;
+            inner.pending.push_back(Notification {
+                id,
+                time: unix_now(),
+                title,
+                message,
+            });
+            while inner.pending.len() > MAX_NOTIFICATIONS {
+                inner.pending.pop_front();
+            }
+        }
+        self.repaint.request_repaint();
+    }
+
+    /// Undismissed notifications, oldest first.
+    pub fn pending(&self) -> Vec<Notification> {
+        self.inner
+            .lock()
+            .map(|inner| inner.pending.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn dismiss(&self, id: u64) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.pending.retain(|n| n.id != id);
+        }
+    }
+}
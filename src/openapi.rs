@@ -0,0 +1,250 @@
+//! OpenAPI description of the HTTP API.
+//!
+//! The API is small and hand-routed, so its OpenAPI 3 document is written
+//! out here rather than generated; keep it in step with the routes in
+//! `api.rs`. It is served at `/openapi.json`, and `/docs` shows it in
+//! Swagger UI, loaded from a CDN by the browser.
+
+// ============================================================================
+// DOCUMENTS
+// ============================================================================
+
+/// The OpenAPI 3 document for the routes in `api.rs`.
+const SPEC: &str = r##"{
+  "openapi": "3.0.3",
+  "info": {
+    "title": "USB Power Relay",
+    "description": "Switch the channels of a CH340 USB relay board.",
+    "version": "VERSION"
+  },
+  "components": {
+    "securitySchemes": {
+      "bearer": {
+        "type": "http",
+        "scheme": "bearer",
+        "description": "Needed once any token is configured in api.tokens."
+      }
+    },
+    "schemas": {
+      "ChannelState": {
+        "type": "object",
+        "properties": {
+          "channel": { "type": "integer", "minimum": 1 },
+          "state": { "type": "string", "enum": ["on", "off", "error", "unknown"] },
+          "label": { "type": "string" }
+        }
+      },
+      "Queued": {
+        "type": "object",
+        "properties": { "queued": { "type": "boolean" } }
+      },
+      "Error": {
+        "type": "object",
+        "properties": { "error": { "type": "string" } }
+      }
+    },
+    "responses": {
+      "Queued": {
+        "description": "Queued for the device",
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Queued" } } }
+      },
+      "Error": {
+        "description": "Refused; the body says why",
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+      }
+    }
+  },
+  "security": [{ "bearer": [] }],
+  "paths": {
+    "/status": {
+      "get": {
+        "summary": "State of every channel",
+        "responses": {
+          "200": {
+            "description": "Channel states",
+            "content": {
+              "application/json": {
+                "schema": {
+                  "type": "object",
+                  "properties": {
+                    "channels": {
+                      "type": "array",
+                      "items": { "$ref": "#/components/schemas/ChannelState" }
+                    }
+                  }
+                }
+              }
+            }
+          },
+          "401": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    },
+    "/watch": {
+      "get": {
+        "summary": "Channel states as server-sent events, sent again on every change",
+        "responses": {
+          "200": {
+            "description": "One data: line per event, in the same form as /status",
+            "content": { "text/event-stream": { "schema": { "type": "string" } } }
+          },
+          "401": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    },
+    "/channels/{channel}/{action}": {
+      "post": {
+        "summary": "Switch a channel on or off",
+        "parameters": [
+          {
+            "name": "channel",
+            "in": "path",
+            "required": true,
+            "schema": { "type": "integer", "minimum": 1 }
+          },
+          {
+            "name": "action",
+            "in": "path",
+            "required": true,
+            "schema": { "type": "string", "enum": ["on", "off"] }
+          }
+        ],
+        "responses": {
+          "202": { "$ref": "#/components/responses/Queued" },
+          "401": { "$ref": "#/components/responses/Error" },
+          "403": { "$ref": "#/components/responses/Error" },
+          "404": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    },
+    "/all-off": {
+      "post": {
+        "summary": "Emergency stop: drop queued commands, switch every channel off",
+        "responses": {
+          "202": { "$ref": "#/components/responses/Queued" },
+          "401": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    },
+    "/batch": {
+      "post": {
+        "summary": "Run steps as one unit and wait for the result",
+        "requestBody": {
+          "required": true,
+          "content": {
+            "text/plain": {
+              "schema": { "type": "string", "example": "off:1, on:3, pulse:4:500, wait:200" }
+            }
+          }
+        },
+        "responses": {
+          "200": {
+            "description": "Every step ran",
+            "content": {
+              "application/json": {
+                "schema": {
+                  "type": "object",
+                  "properties": {
+                    "ok": { "type": "boolean" },
+                    "changes": {
+                      "type": "array",
+                      "items": { "$ref": "#/components/schemas/ChannelState" }
+                    }
+                  }
+                }
+              }
+            }
+          },
+          "400": { "$ref": "#/components/responses/Error" },
+          "409": {
+            "description": "A step failed",
+            "content": {
+              "application/json": {
+                "schema": {
+                  "type": "object",
+                  "properties": {
+                    "ok": { "type": "boolean" },
+                    "step": { "type": "integer" },
+                    "reason": {
+                      "type": "string",
+                      "enum": ["device", "interrupted", "expired", "rejected"]
+                    },
+                    "error": { "type": "string" }
+                  }
+                }
+              }
+            }
+          },
+          "504": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    },
+    "/raw": {
+      "post": {
+        "summary": "Send raw bytes to the board and return its reply",
+        "description": "Needs advanced: true and an API token.",
+        "requestBody": {
+          "required": true,
+          "content": {
+            "text/plain": { "schema": { "type": "string", "example": "A0 01 05 A6" } }
+          }
+        },
+        "responses": {
+          "200": {
+            "description": "The board's reply",
+            "content": {
+              "application/json": {
+                "schema": {
+                  "type": "object",
+                  "properties": {
+                    "ok": { "type": "boolean" },
+                    "response": { "type": "string" }
+                  }
+                }
+              }
+            }
+          },
+          "400": { "$ref": "#/components/responses/Error" },
+          "401": { "$ref": "#/components/responses/Error" },
+          "403": { "$ref": "#/components/responses/Error" },
+          "409": { "$ref": "#/components/responses/Error" },
+          "504": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    },
+    "/rules/{name}": {
+      "post": {
+        "summary": "Fire a rule with an Api trigger",
+        "parameters": [
+          { "name": "name", "in": "path", "required": true, "schema": { "type": "string" } }
+        ],
+        "responses": {
+          "202": { "$ref": "#/components/responses/Queued" },
+          "401": { "$ref": "#/components/responses/Error" },
+          "404": { "$ref": "#/components/responses/Error" },
+          "409": { "$ref": "#/components/responses/Error" }
+        }
+      }
+    }
+  }
+}"##;
+
+/// Swagger UI for [`SPEC`].
+pub const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>USB Power Relay API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>"##;
+
+/// [`SPEC`] with this build's version filled in.
+pub fn spec() -> String {
+    SPEC.replace("\"VERSION\"", &format!("\"{}\"", env!("CARGO_PKG_VERSION")))
+}
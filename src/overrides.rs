@@ -0,0 +1,220 @@
+//! Manual overrides of schedules.
+//!
+//! Switching a scheduled channel from the window asks how long the manual
+//! state should stand: until the channel's next schedule, which then runs
+//! as usual, or for a number of hours. Schedules due during a timed
+//! override skip that channel; when it ends, the last one skipped is
+//! applied, so the channel is back where its schedules would have left it.
+
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+use crate::audit::Source;
+use crate::history::unix_now;
+use crate::i18n::{tr, tr_args};
+use crate::locale::{format_moment, ClockFormat};
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const DEFAULT_HOURS: u32 = 2;
+const MAX_HOURS: u32 = 72;
+
+// ============================================================================
+// OVERRIDES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// The channel's next schedule ends the override and runs.
+    NextEvent,
+    /// Schedules are skipped until this Unix time.
+    At(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub channel: u8,
+    pub resume: Resume,
+    /// When schedules take over again, if known.
+    pub until: Option<u64>,
+    /// The switch the last skipped schedule would have made, and which
+    /// schedule it was.
+    skipped: Option<(Command, Source)>,
+}
+
+impl Override {
+    /// e.g. "Overridden until 18:00".
+    pub fn text(&self, clock: ClockFormat) -> String {
+        match (self.resume, self.until) {
+            (Resume::NextEvent, None) => tr("override-until-next"),
+            (Resume::NextEvent, Some(until)) => {
+                tr_args("override-until-next-at", &[("time", &format_moment(until, clock))])
+            }
+            (Resume::At(until), _) => {
+                tr_args("override-until", &[("time", &format_moment(until, clock))])
+            }
+        }
+    }
+}
+
+/// The overrides in effect, shared by the GUI and the scheduler.
+#[derive(Clone)]
+pub struct Overrides {
+    inner: Arc<Mutex<Vec<Override>>>,
+    /// Woken whenever an override starts or ends.
+    repaint: egui::Context,
+}
+
+impl Overrides {
+    pub fn new(repaint: egui::Context) -> Self {
+        Self {
+            inner: Arc::default(),
+            repaint,
+        }
+    }
+
+    /// Starts an override of `channel`, replacing any it already has.
+    /// `next` is when its next schedule is due.
+    pub fn set(&self, channel: u8, resume: Resume, next: Option<u64>) {
+        let until = match resume {
+            Resume::NextEvent => next,
+            Resume::At(at) => Some(at),
+        };
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.retain(|o| o.channel != channel);
+            inner.push(Override {
+                channel,
+                resume,
+                until,
+                skipped: None,
+            });
+        }
+        self.repaint.request_repaint();
+    }
+
+    /// Ends `channel`'s override without switching anything.
+    pub fn clear(&self, channel: u8) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.retain(|o| o.channel != channel);
+        }
+        self.repaint.request_repaint();
+    }
+
+    pub fn get(&self, channel: u8) -> Option<Override> {
+        let inner = self.inner.lock().ok()?;
+        inner.iter().find(|o| o.channel == channel).cloned()
+    }
+
+    /// Passes a scheduled switch of `channel` through, or holds it back
+    /// while a timed override is in effect. A next-event override ends here.
+    pub fn admit(&self, channel: u8, cmd: Command, source: &Source) -> Option<Command> {
+        let mut inner = self.inner.lock().ok()?;
+        let index = inner.iter().position(|o| o.channel == channel);
+        let Some(index) = index else {
+            return Some(cmd);
+        };
+        match inner[index].resume {
+            Resume::NextEvent => {
+                inner.remove(index);
+                self.repaint.request_repaint();
+                Some(cmd)
+            }
+            Resume::At(_) => {
+                inner[index].skipped = Some((cmd, source.clone()));
+                None
+            }
+        }
+    }
+
+    /// Ends timed overrides that ran out by Unix time `now`, returning each
+    /// channel with the switch its schedules skipped meanwhile, if any.
+    pub fn expire(&self, now: u64) -> Vec<(u8, Option<(Command, Source)>)> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Vec::new();
+        };
+        let mut ended = Vec::new();
+        inner.retain(|o| match o.resume {
+            Resume::At(at) if at <= now => {
+                ended.push((o.channel, o.skipped.clone()));
+                false
+            }
+            _ => true,
+        });
+        if !ended.is_empty() {
+            self.repaint.request_repaint();
+        }
+        ended
+    }
+}
+
+// ============================================================================
+// PROMPT
+// ============================================================================
+
+pub enum PromptOutcome {
+    Open,
+    Cancelled,
+    /// Switch, overriding schedules this way.
+    Override(Resume),
+}
+
+/// Asks how long a manual switch of a scheduled channel should hold.
+pub struct OverridePrompt {
+    pub channel: u8,
+    pub cmd: Command,
+    /// When the channel's next schedule is due.
+    pub next: Option<u64>,
+    hours: u32,
+}
+
+impl OverridePrompt {
+    pub fn new(channel: u8, cmd: Command, next: Option<u64>) -> Self {
+        Self {
+            channel,
+            cmd,
+            next,
+            hours: DEFAULT_HOURS,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, name: &str, clock: ClockFormat) -> PromptOutcome {
+        let mut outcome = PromptOutcome::Open;
+        let mut open = true;
+        egui::Window::new(tr("override-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(tr_args("override-question", &[("channel", &name)]));
+                ui.add_space(6.0);
+
+                let next = match self.next {
+                    Some(at) => tr_args("override-next-at", &[("time", &format_moment(at, clock))]),
+                    None => tr("override-next"),
+                };
+                if ui.button(next).clicked() {
+                    outcome = PromptOutcome::Override(Resume::NextEvent);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(tr("override-for")).clicked() {
+                        let until = unix_now() + u64::from(self.hours) * 3600;
+                        outcome = PromptOutcome::Override(Resume::At(until));
+                    }
+                    ui.add(egui::DragValue::new(&mut self.hours).range(1..=MAX_HOURS));
+                    ui.label(tr("override-hours"));
+                });
+                ui.add_space(6.0);
+                if ui.button(tr("cancel")).clicked() {
+                    outcome = PromptOutcome::Cancelled;
+                }
+            });
+        if !open {
+            outcome = PromptOutcome::Cancelled;
+        }
+        outcome
+    }
+}
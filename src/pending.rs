@@ -0,0 +1,100 @@
+//! Optimistic switching.
+//!
+//! A pressed button shows its target state straight away instead of a full
+//! round-trip later. The expectation is kept until the channel's command has
+//! left the queue; if the board then reports anything else, the indicator
+//! falls back to the reported state and the caller raises an error.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::RelayState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long a press may take to show up in the queue before its channel
+/// not being busy means the command already ran.
+const QUEUE_GRACE: Duration = Duration::from_secs(1);
+
+// ============================================================================
+// PENDING SWITCHES
+// ============================================================================
+
+struct Expected {
+    target: RelayState,
+    since: Instant,
+    seen_busy: bool,
+}
+
+/// A switch that did not end in the state it was pressed for.
+pub struct Rollback {
+    pub channel: u8,
+    pub target: RelayState,
+    pub reported: RelayState,
+}
+
+#[derive(Default)]
+pub struct PendingSwitches {
+    expected: BTreeMap<u8, Expected>,
+}
+
+impl PendingSwitches {
+    /// Records that `channel` was just told to switch to `target`.
+    pub fn expect(&mut self, channel: u8, target: RelayState) {
+        self.expected.insert(
+            channel,
+            Expected {
+                target,
+                since: Instant::now(),
+                seen_busy: false,
+            },
+        );
+    }
+
+    /// Settles the expectations of channels whose commands are done, given
+    /// which channels are busy and the reported states. Returns the ones
+    /// that failed.
+    pub fn settle(&mut self, busy: &[bool], reported: &[RelayState]) -> Vec<Rollback> {
+        let mut rollbacks = Vec::new();
+        self.expected.retain(|&channel, expected| {
+            let index = (channel as usize).wrapping_sub(1);
+            if busy.get(index).copied().unwrap_or(false) {
+                expected.seen_busy = true;
+                return true;
+            }
+            if !expected.seen_busy && expected.since.elapsed() < QUEUE_GRACE {
+                return true;
+            }
+            let state = reported.get(index).copied().unwrap_or(RelayState::Unknown);
+            if state != expected.target {
+                rollbacks.push(Rollback {
+                    channel,
+                    target: expected.target,
+                    reported: state,
+                });
+            }
+            false
+        });
+        rollbacks
+    }
+
+    /// How long until an expectation that never showed up in the queue
+    /// should be settled.
+    pub fn recheck(&self) -> Option<Duration> {
+        self.expected
+            .values()
+            .filter(|e| !e.seen_busy)
+            .map(|e| QUEUE_GRACE.saturating_sub(e.since.elapsed()))
+            .min()
+    }
+
+    /// The state to show for `channel` and whether it is still pending.
+    pub fn shown(&self, channel: u8, reported: RelayState) -> (RelayState, bool) {
+        match self.expected.get(&channel) {
+            Some(expected) => (expected.target, true),
+            None => (reported, false),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Background status polling.
+//!
+//! With `device.poll_secs` set, the poller queues a status query of every
+//! channel at that interval. The device worker compares the answers with the
+//! last commanded state and warns about drift, e.g. when the board was
+//! switched with its own buttons or a relay dropped out.
+//!
+//! With `device.adaptive_timing` (the default) the interval follows activity:
+//! polls come faster for a while after a command, when a relay that didn't
+//! take is most likely, and slow down once the board has been idle for long.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::desired::DesiredState;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_secs(1);
+
+/// How long after a command polls come faster.
+const ACTIVE_WINDOW: Duration = Duration::from_secs(30);
+
+/// Divisor of the interval while active.
+const ACTIVE_SPEEDUP: u32 = 4;
+
+/// How long without commands before polls slow down.
+const IDLE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Multiplier of the interval while idle.
+const IDLE_SLOWDOWN: u32 = 4;
+
+// ============================================================================
+// POLLER
+// ============================================================================
+
+/// The poll interval after `idle` without commands.
+fn adaptive_interval(base: Duration, idle: Duration) -> Duration {
+    if idle < ACTIVE_WINDOW {
+        (base / ACTIVE_SPEEDUP).max(TICK)
+    } else if idle < IDLE_AFTER {
+        base
+    } else {
+        base * IDLE_SLOWDOWN
+    }
+}
+
+/// Queues a [`Command::Poll`] every `device.poll_secs` seconds; 0 disables
+/// polling. The interval is reread every tick, so changes apply immediately.
+pub fn run_poller(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+    state: &AppState,
+) -> Result<(), String> {
+    let completed = || state.snapshot.borrow().completed;
+    let started = Instant::now();
+    let mut last = started;
+    let mut seen = completed();
+    let mut last_command: Option<Instant> = None;
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let config = Config::load()?;
+        let device = config.device;
+
+        // Every completed command counts as activity
+        let now_completed = completed();
+        if now_completed != seen {
+            seen = now_completed;
+            last_command = Some(Instant::now());
+        }
+
+        // A write-only board has nothing to report; a monitor has nothing
+        // else to do
+        let poll_secs = if state.read_only {
+            config.monitor.poll_secs.max(1)
+        } else {
+            device.poll_secs
+        };
+        if poll_secs > 0 && !device.write_only {
+            let base = Duration::from_secs(poll_secs);
+            let interval = if device.adaptive_timing && !state.read_only {
+                // Startup itself is not activity, but doesn't count as idle
+                let idle = last_command
+                    .map_or(started.elapsed().max(ACTIVE_WINDOW), |t| t.elapsed());
+                adaptive_interval(base, idle)
+            } else {
+                base
+            };
+            if last.elapsed() >= interval {
+                let _ = commands.send((Command::Poll, Source::Device));
+                last = Instant::now();
+            }
+        }
+        std::thread::sleep(TICK);
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// DRIFT DETECTION
+// ============================================================================
+
+/// A channel whose reported state differs from the last commanded one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Drift {
+    pub channel: u8,
+    pub reported: RelayState,
+    pub desired: RelayState,
+}
+
+/// Remembers which channels have drifted so each drift is reported once,
+/// until the channel matches again.
+#[derive(Default)]
+pub struct DriftDetector {
+    drifted: BTreeSet<u8>,
+}
+
+impl DriftDetector {
+    /// Returns the channels that drifted since the last check.
+    pub fn check(&mut self, polled: &[(u8, RelayState)], desired: &DesiredState) -> Vec<Drift> {
+        let mut drifts = Vec::new();
+        for &(channel, reported) in polled {
+            let Some(wanted) = desired.get(channel) else {
+                continue;
+            };
+            if !matches!(reported, RelayState::On | RelayState::Off) {
+                continue;
+            }
+            if reported == wanted {
+                self.drifted.remove(&channel);
+            } else if self.drifted.insert(channel) {
+                drifts.push(Drift {
+                    channel,
+                    reported,
+                    desired: wanted,
+                });
+            }
+        }
+        drifts
+    }
+}
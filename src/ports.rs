@@ -0,0 +1,331 @@
+//! Serial port discovery and line settings.
+//!
+//! Classifies the serial ports present against the device binding and the
+//! detection lists. Without a binding the board is the first port with a
+//! known VID:PID, else the first whose USB product or manufacturer string
+//! contains a known keyword, else the first USB serial port at all. Users can
+//! extend both lists for clones the built-in ones miss.
+
+use serde::{Deserialize, Serialize};
+use serialport::{
+    DataBits, FlowControl, Parity, SerialPortBuilder, SerialPortInfo, SerialPortType, StopBits,
+    UsbPortInfo,
+};
+use std::fmt;
+
+use crate::config::{DeviceBinding, DeviceConfig};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// CH340 and CH341 in serial mode.
+const BUILTIN_IDS: &[UsbId] = &[
+    UsbId { vid: 0x1a86, pid: 0x7523 },
+    UsbId { vid: 0x1a86, pid: 0x5523 },
+];
+
+const BUILTIN_KEYWORDS: &[&str] = &["CH340", "CH341", "USB-SERIAL"];
+
+/// Common baud rates offered in the settings window; any value works in
+/// the config.
+pub const BAUD_RATES: [u32; 6] = [2400, 4800, 9600, 19200, 38400, 115_200];
+
+// ============================================================================
+// LINE SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineParity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineFlowControl {
+    #[default]
+    None,
+    /// XON/XOFF.
+    Software,
+    /// RTS/CTS.
+    Hardware,
+}
+
+impl LineParity {
+    pub const ALL: [LineParity; 3] = [LineParity::None, LineParity::Odd, LineParity::Even];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            LineParity::None => "None",
+            LineParity::Odd => "Odd",
+            LineParity::Even => "Even",
+        }
+    }
+}
+
+impl LineFlowControl {
+    pub const ALL: [LineFlowControl; 3] = [
+        LineFlowControl::None,
+        LineFlowControl::Software,
+        LineFlowControl::Hardware,
+    ];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            LineFlowControl::None => "None",
+            LineFlowControl::Software => "XON/XOFF",
+            LineFlowControl::Hardware => "RTS/CTS",
+        }
+    }
+}
+
+/// Serial framing; the stock boards use 9600 8N1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialConfig {
+    pub baud_rate: u32,
+    /// 5 to 8.
+    pub data_bits: u8,
+    pub parity: LineParity,
+    /// 1 or 2.
+    pub stop_bits: u8,
+    pub flow_control: LineFlowControl,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            baud_rate: 9600,
+            data_bits: 8,
+            parity: LineParity::None,
+            stop_bits: 1,
+            flow_control: LineFlowControl::None,
+        }
+    }
+}
+
+impl SerialConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.baud_rate == 0 {
+            return Err("device.serial.baud_rate must be positive".to_string());
+        }
+        if !(5..=8).contains(&self.data_bits) {
+            return Err("device.serial.data_bits must be 5 to 8".to_string());
+        }
+        if !(1..=2).contains(&self.stop_bits) {
+            return Err("device.serial.stop_bits must be 1 or 2".to_string());
+        }
+        Ok(())
+    }
+
+    /// Short form such as `9600 8N1`.
+    pub fn summary(&self) -> String {
+        let parity = match self.parity {
+            LineParity::None => 'N',
+            LineParity::Odd => 'O',
+            LineParity::Even => 'E',
+        };
+        format!("{} {}{}{}", self.baud_rate, self.data_bits, parity, self.stop_bits)
+    }
+
+    /// A builder for `port` with these settings.
+    pub fn builder(&self, port: &str) -> SerialPortBuilder {
+        let data_bits = match self.data_bits {
+            5 => DataBits::Five,
+            6 => DataBits::Six,
+            7 => DataBits::Seven,
+            _ => DataBits::Eight,
+        };
+        let parity = match self.parity {
+            LineParity::None => Parity::None,
+            LineParity::Odd => Parity::Odd,
+            LineParity::Even => Parity::Even,
+        };
+        let stop_bits = if self.stop_bits == 2 { StopBits::Two } else { StopBits::One };
+        let flow_control = match self.flow_control {
+            LineFlowControl::None => FlowControl::None,
+            LineFlowControl::Software => FlowControl::Software,
+            LineFlowControl::Hardware => FlowControl::Hardware,
+        };
+        serialport::new(port, self.baud_rate)
+            .data_bits(data_bits)
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .flow_control(flow_control)
+    }
+}
+
+// ============================================================================
+// DETECTION LISTS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: u16,
+}
+
+impl UsbId {
+    /// Parses `1a86:7523`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid USB id '{}', expected VID:PID in hex", text.trim());
+        let (vid, pid) = text.trim().split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            vid: u16::from_str_radix(vid, 16).map_err(|_| invalid())?,
+            pid: u16::from_str_radix(pid, 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for UsbId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vid, self.pid)
+    }
+}
+
+/// Additions to the built-in detection lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    pub usb_ids: Vec<UsbId>,
+    /// Matched case-insensitively against the USB product and manufacturer.
+    pub keywords: Vec<String>,
+}
+
+// ============================================================================
+// CANDIDATES
+// ============================================================================
+
+/// Why a port is considered a relay board, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PortMatch {
+    Bound,
+    UsbId,
+    Keyword,
+    AnyUsb,
+}
+
+impl PortMatch {
+    pub fn text(&self) -> &'static str {
+        match self {
+            PortMatch::Bound => "bound",
+            PortMatch::UsbId => "known VID:PID",
+            PortMatch::Keyword => "product name",
+            PortMatch::AnyUsb => "USB serial",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub info: SerialPortInfo,
+    /// `None` when the port would never be picked.
+    pub matched: Option<PortMatch>,
+}
+
+impl Candidate {
+    pub fn usb(&self) -> Option<&UsbPortInfo> {
+        match &self.info.port_type {
+            SerialPortType::UsbPort(usb) => Some(usb),
+            _ => None,
+        }
+    }
+
+    /// One-line summary such as `COM3  1a86:7523  USB-SERIAL CH340`.
+    pub fn describe(&self) -> String {
+        match self.usb() {
+            Some(usb) => {
+                let mut text = format!(
+                    "{}  {}",
+                    self.info.port_name,
+                    UsbId { vid: usb.vid, pid: usb.pid }
+                );
+                if let Some(product) = &usb.product {
+                    text.push_str(&format!("  {}", product));
+                }
+                if let Some(serial) = &usb.serial_number {
+                    text.push_str(&format!("  #{}", serial));
+                }
+                text
+            }
+            None => self.info.port_name.clone(),
+        }
+    }
+}
+
+fn classify(port: &SerialPortInfo, device: &DeviceConfig) -> Option<PortMatch> {
+    // A pinned device is never substituted with another board
+    if device.bind.is_set() {
+        return device.bind.matches(port).then_some(PortMatch::Bound);
+    }
+    let SerialPortType::UsbPort(usb) = &port.port_type else {
+        return None;
+    };
+
+    let id = UsbId { vid: usb.vid, pid: usb.pid };
+    if BUILTIN_IDS.contains(&id) || device.detection.usb_ids.contains(&id) {
+        return Some(PortMatch::UsbId);
+    }
+
+    let product = usb.product.as_deref().unwrap_or("");
+    let manufacturer = usb.manufacturer.as_deref().unwrap_or("");
+    let combined = format!("{} {}", product, manufacturer).to_uppercase();
+    let keywords = BUILTIN_KEYWORDS
+        .iter()
+        .map(|kw| kw.to_string())
+        .chain(device.detection.keywords.iter().map(|kw| kw.to_uppercase()));
+    for keyword in keywords {
+        if !keyword.is_empty() && combined.contains(&keyword) {
+            return Some(PortMatch::Keyword);
+        }
+    }
+
+    Some(PortMatch::AnyUsb)
+}
+
+/// Every serial port present, classified for `device`.
+pub fn candidates(device: &DeviceConfig) -> Result<Vec<Candidate>, String> {
+    let ports = serialport::available_ports().map_err(|e| format!("Failed to list ports: {}", e))?;
+    Ok(ports
+        .into_iter()
+        .map(|info| Candidate {
+            matched: classify(&info, device),
+            info,
+        })
+        .collect())
+}
+
+/// The port the board is opened on.
+pub fn select(device: &DeviceConfig) -> Result<SerialPortInfo, String> {
+    candidates(device)?
+        .into_iter()
+        .filter_map(|c| c.matched.map(|m| (m, c.info)))
+        .min_by_key(|(matched, _)| *matched)
+        .map(|(_, info)| info)
+        .ok_or_else(|| {
+            if device.bind.is_set() {
+                format!("No USB relay matches {}", device.bind.describe())
+            } else {
+                "No USB relay found".to_string()
+            }
+        })
+}
+
+/// A binding that pins `port`: by USB serial number when it has one, else
+/// by the port itself.
+pub fn binding_for(port: &SerialPortInfo) -> DeviceBinding {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) if usb.serial_number.is_some() => DeviceBinding {
+            serial_number: usb.serial_number.clone(),
+            vid: Some(usb.vid),
+            pid: Some(usb.pid),
+            port: None,
+        },
+        _ => DeviceBinding {
+            port: Some(port.port_name.clone()),
+            ..DeviceBinding::default()
+        },
+    }
+}
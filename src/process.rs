@@ -0,0 +1,363 @@
+//! Process triggers.
+//!
+//! Switches channels when a local process starts, exits or stops
+//! responding, e.g. the capture light off when OBS closes. Processes are
+//! matched by executable name (case-insensitive, `.exe` optional) or by PID.
+//! A process is not responding once it has been stopped or stuck in an
+//! uninterruptible wait (Unix) or its windows have been hung (Windows) for
+//! `hang_secs`. Like lock screen triggers, only changes act: whatever is
+//! running when the app starts doesn't switch anything.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::session::SessionAction;
+use crate::supervisor::SubsystemContext;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// TRIGGERS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessTrigger {
+    /// Executable name, e.g. `obs` or `obs64.exe`.
+    pub name: String,
+    /// A specific process; with a name as well, both must match.
+    pub pid: Option<u32>,
+    pub channel: u8,
+    pub on_start: SessionAction,
+    pub on_exit: SessionAction,
+    pub on_hang: SessionAction,
+    /// How long a process must be unresponsive to count as hung.
+    pub hang_secs: u64,
+}
+
+impl Default for ProcessTrigger {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pid: None,
+            channel: 1,
+            on_start: SessionAction::Nothing,
+            on_exit: SessionAction::Nothing,
+            on_hang: SessionAction::Nothing,
+            hang_secs: 30,
+        }
+    }
+}
+
+impl ProcessTrigger {
+    pub fn describe(&self) -> String {
+        match (self.name.is_empty(), self.pid) {
+            (false, _) => self.name.clone(),
+            (true, Some(pid)) => format!("PID {}", pid),
+            (true, None) => "(no process)".to_string(),
+        }
+    }
+
+    fn matches(&self, process: &Process) -> bool {
+        let base = |name: &str| {
+            let lower = name.to_ascii_lowercase();
+            lower.strip_suffix(".exe").map(str::to_string).unwrap_or(lower)
+        };
+        let wanted = base(&self.name);
+        self.pid.is_none_or(|pid| pid == process.pid)
+            && (self.name.is_empty() || process.names.iter().any(|n| base(n) == wanted))
+    }
+}
+
+// ============================================================================
+// PROCESSES
+// ============================================================================
+
+/// A running process as seen by one scan.
+struct Process {
+    pid: u32,
+    /// Names it goes by: the kernel's short name and the executable's.
+    names: Vec<String>,
+    /// Stopped or waiting in a way that looks hung right now.
+    stuck: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Observed {
+    Running,
+    Hung,
+    Gone,
+}
+
+/// What one trigger has seen so far.
+#[derive(Default)]
+struct Watch {
+    /// `None` until the first scan.
+    last: Option<Observed>,
+    /// When every matching process became stuck.
+    stuck_since: Option<Instant>,
+}
+
+impl Watch {
+    /// Updates from a scan; returns the action for a change.
+    fn update(&mut self, trigger: &ProcessTrigger, processes: &[Process]) -> SessionAction {
+        let mut matching = processes.iter().filter(|p| trigger.matches(p)).peekable();
+        let now = if matching.peek().is_none() {
+            self.stuck_since = None;
+            Observed::Gone
+        } else if matching.all(|p| p.stuck) {
+            let since = *self.stuck_since.get_or_insert_with(Instant::now);
+            if since.elapsed() >= Duration::from_secs(trigger.hang_secs) {
+                Observed::Hung
+            } else {
+                Observed::Running
+            }
+        } else {
+            self.stuck_since = None;
+            Observed::Running
+        };
+
+        let action = match (self.last, now) {
+            (Some(Observed::Gone), Observed::Running) => trigger.on_start,
+            (Some(Observed::Running | Observed::Hung), Observed::Gone) => trigger.on_exit,
+            (Some(Observed::Running), Observed::Hung) => trigger.on_hang,
+            _ => SessionAction::Nothing,
+        };
+        self.last = Some(now);
+        action
+    }
+}
+
+// ============================================================================
+// WATCHER
+// ============================================================================
+
+/// Scans the process list and queues the configured actions when a watched
+/// process starts, exits or hangs.
+pub fn run_process_watcher(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+) -> Result<(), String> {
+    let mut triggers: Vec<ProcessTrigger> = Vec::new();
+    let mut watches: Vec<Watch> = Vec::new();
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let loaded = Config::load()?.processes;
+        if loaded != triggers {
+            watches = loaded.iter().map(|_| Watch::default()).collect();
+            triggers = loaded;
+        }
+        if !triggers.is_empty() {
+            let processes = platform::processes()?;
+            for (trigger, watch) in triggers.iter().zip(&mut watches) {
+                if let Some(cmd) = watch.update(trigger, &processes).command(trigger.channel) {
+                    let _ = commands.send((cmd, Source::Process(trigger.name.clone())));
+                }
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Process;
+    use std::path::Path;
+
+    /// The basename of a process's first argument.
+    fn executable(dir: &Path) -> Option<String> {
+        let cmdline = std::fs::read(dir.join("cmdline")).ok()?;
+        let arg0 = cmdline.split(|&b| b == 0).next()?;
+        let arg0 = String::from_utf8_lossy(arg0);
+        let name = arg0.rsplit('/').next()?;
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    pub fn processes() -> Result<Vec<Process>, String> {
+        let entries =
+            std::fs::read_dir("/proc").map_err(|e| format!("Failed to read /proc: {}", e))?;
+        let mut processes = Vec::new();
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            // Gone since the directory was listed
+            let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+                continue;
+            };
+            // The short name is in parentheses and may contain them itself
+            let (Some(open), Some(close)) = (stat.find('('), stat.rfind(')')) else {
+                continue;
+            };
+            let state = stat[close + 1..].trim_start().chars().next().unwrap_or('R');
+            if matches!(state, 'Z' | 'X') {
+                continue;
+            }
+            // The short name is cut at 15 bytes, the executable's isn't
+            let mut names = vec![stat[open + 1..close].to_string()];
+            names.extend(executable(&entry.path()));
+            processes.push(Process {
+                pid,
+                names,
+                stuck: matches!(state, 'T' | 't' | 'D'),
+            });
+        }
+        Ok(processes)
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod platform {
+    use super::Process;
+    use std::process::Command;
+
+    pub fn processes() -> Result<Vec<Process>, String> {
+        let output = Command::new("ps")
+            .args(["-axo", "pid=,stat=,comm="])
+            .output()
+            .map_err(|e| format!("Failed to run ps: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("ps: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        let mut processes = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(pid), Some(stat)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Ok(pid) = pid.parse() else {
+                continue;
+            };
+            // comm is the executable's path
+            let command = fields.collect::<Vec<_>>().join(" ");
+            let name = command.rsplit('/').next().unwrap_or("").to_string();
+            if stat.starts_with('Z') || name.is_empty() {
+                continue;
+            }
+            processes.push(Process {
+                pid,
+                names: vec![name],
+                stuck: stat.starts_with(['T', 'U', 'D']),
+            });
+        }
+        Ok(processes)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Process;
+    use std::collections::HashSet;
+
+    type Handle = isize;
+
+    const TH32CS_SNAPPROCESS: u32 = 0x2;
+    const INVALID_HANDLE_VALUE: Handle = -1;
+
+    #[repr(C)]
+    struct ProcessEntry32W {
+        size: u32,
+        usage: u32,
+        process_id: u32,
+        default_heap_id: usize,
+        module_id: u32,
+        threads: u32,
+        parent_process_id: u32,
+        priority: i32,
+        flags: u32,
+        exe_file: [u16; 260],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateToolhelp32Snapshot(flags: u32, process_id: u32) -> Handle;
+        fn Process32FirstW(snapshot: Handle, entry: *mut ProcessEntry32W) -> i32;
+        fn Process32NextW(snapshot: Handle, entry: *mut ProcessEntry32W) -> i32;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    type EnumWindowsProc = unsafe extern "system" fn(hwnd: isize, lparam: isize) -> i32;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn EnumWindows(callback: EnumWindowsProc, lparam: isize) -> i32;
+        fn GetWindowThreadProcessId(hwnd: isize, process_id: *mut u32) -> u32;
+        fn IsWindowVisible(hwnd: isize) -> i32;
+        fn IsHungAppWindow(hwnd: isize) -> i32;
+    }
+
+    unsafe extern "system" fn collect_hung(hwnd: isize, lparam: isize) -> i32 {
+        // SAFETY: lparam is the set passed to EnumWindows below, which
+        // outlives the enumeration
+        let hung = unsafe { &mut *(lparam as *mut HashSet<u32>) };
+        let mut pid = 0;
+        // SAFETY: hwnd comes from EnumWindows; pid receives the owner
+        unsafe {
+            if IsWindowVisible(hwnd) != 0 && IsHungAppWindow(hwnd) != 0 {
+                GetWindowThreadProcessId(hwnd, &mut pid);
+                hung.insert(pid);
+            }
+        }
+        1
+    }
+
+    /// Processes with a visible window that stopped processing messages.
+    fn hung_processes() -> HashSet<u32> {
+        let mut hung = HashSet::new();
+        // SAFETY: the callback only touches `hung`, which lives past the call
+        unsafe {
+            EnumWindows(collect_hung, &mut hung as *mut HashSet<u32> as isize);
+        }
+        hung
+    }
+
+    pub fn processes() -> Result<Vec<Process>, String> {
+        let hung = hung_processes();
+        // SAFETY: the snapshot handle is checked and closed below
+        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+        if snapshot == INVALID_HANDLE_VALUE {
+            return Err(format!(
+                "Failed to list processes: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let mut processes = Vec::new();
+        // SAFETY: ProcessEntry32W is plain data; size is set as the API requires
+        let mut entry: ProcessEntry32W = unsafe { std::mem::zeroed() };
+        entry.size = std::mem::size_of::<ProcessEntry32W>() as u32;
+        // SAFETY: entry is a valid, sized buffer for the snapshot's entries
+        let mut more = unsafe { Process32FirstW(snapshot, &mut entry) } != 0;
+        while more {
+            let len = entry.exe_file.iter().position(|&c| c == 0).unwrap_or(entry.exe_file.len());
+            processes.push(Process {
+                pid: entry.process_id,
+                names: vec![String::from_utf16_lossy(&entry.exe_file[..len])],
+                stuck: hung.contains(&entry.process_id),
+            });
+            // SAFETY: as above
+            more = unsafe { Process32NextW(snapshot, &mut entry) } != 0;
+        }
+        // SAFETY: opened above
+        unsafe {
+            CloseHandle(snapshot);
+        }
+        Ok(processes)
+    }
+}
@@ -0,0 +1,183 @@
+//! Push notifications.
+//!
+//! Notifications can also go to a phone through [ntfy](https://ntfy.sh) or
+//! Pushover: every in-app notification (watchdog actions, device errors,
+//! drift, limits, rules) and, optionally, every schedule that fires. Both
+//! services want HTTPS, which the built-in HTTP client doesn't speak, so
+//! deliveries go through `curl`, fed its options on stdin so tokens don't
+//! show up in the process list. Each runs on its own thread; a failed one is
+//! logged and dropped.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+const TIMEOUT_SECS: u32 = 10;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PushConfig {
+    pub ntfy: Option<NtfyConfig>,
+    pub pushover: Option<PushoverConfig>,
+    /// Forward the app's notifications.
+    pub notifications: bool,
+    /// Also push every schedule that fires.
+    pub schedules: bool,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self {
+            ntfy: None,
+            pushover: None,
+            notifications: true,
+            schedules: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtfyConfig {
+    pub server: String,
+    pub topic: String,
+    /// Access token, for protected topics.
+    pub token: Option<String>,
+    /// 1 (min) to 5 (urgent); the server's default when unset.
+    pub priority: Option<u8>,
+}
+
+impl Default for NtfyConfig {
+    fn default() -> Self {
+        Self {
+            server: "https://ntfy.sh".to_string(),
+            topic: String::new(),
+            token: None,
+            priority: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PushoverConfig {
+    /// The application's API token.
+    pub token: String,
+    /// The user or group key to deliver to.
+    pub user: String,
+}
+
+// ============================================================================
+// DELIVERY
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushKind {
+    Notification,
+    Schedule,
+}
+
+/// Pushes a message to every configured service that wants `kind`, in the
+/// background.
+pub fn push(kind: PushKind, title: String, message: String) {
+    std::thread::spawn(move || {
+        let Ok(config) = Config::load() else {
+            return;
+        };
+        let settings = config.push;
+        let wanted = match kind {
+            PushKind::Notification => settings.notifications,
+            PushKind::Schedule => settings.schedules,
+        };
+        if !wanted {
+            return;
+        }
+        if let Some(ntfy) = settings.ntfy.filter(|n| !n.topic.is_empty()) {
+            if let Err(e) = curl(&ntfy_options(&ntfy, &title, &message)) {
+                eprintln!("ntfy: {}", e);
+            }
+        }
+        if let Some(pushover) = settings.pushover.filter(|p| !p.token.is_empty()) {
+            if let Err(e) = curl(&pushover_options(&pushover, &title, &message)) {
+                eprintln!("Pushover: {}", e);
+            }
+        }
+    });
+}
+
+fn ntfy_options(ntfy: &NtfyConfig, title: &str, message: &str) -> String {
+    let url = format!("{}/{}", ntfy.server.trim_end_matches('/'), ntfy.topic);
+    let mut options = curl_option("url", &url);
+    // Header values end at a line break
+    options += &curl_option("header", &format!("Title: {}", title.replace(['\r', '\n'], " ")));
+    if let Some(token) = &ntfy.token {
+        options += &curl_option("header", &format!("Authorization: Bearer {}", token));
+    }
+    if let Some(priority) = ntfy.priority {
+        options += &curl_option("header", &format!("Priority: {}", priority.clamp(1, 5)));
+    }
+    options + &curl_option("data-raw", message)
+}
+
+fn pushover_options(pushover: &PushoverConfig, title: &str, message: &str) -> String {
+    curl_option("url", PUSHOVER_URL)
+        + &curl_option("form-string", &format!("token={}", pushover.token))
+        + &curl_option("form-string", &format!("user={}", pushover.user))
+        + &curl_option("form-string", &format!("title={}", title))
+        + &curl_option("form-string", &format!("message={}", message))
+}
+
+/// One line of a curl config file, with the value quoted.
+pub fn curl_option(name: &str, value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    format!("{} = \"{}\"\n", name, quoted)
+}
+
+/// Runs one request with `options`, failing on HTTP errors.
+pub fn curl(options: &str) -> Result<(), String> {
+    curl_output(options).map(|_| ())
+}
+
+/// Like [`curl`], returning the response body.
+pub fn curl_output(options: &str) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail"])
+        .args(["--max-time", &TIMEOUT_SECS.to_string(), "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(options.as_bytes())
+            .map_err(|e| format!("Failed to pass options to curl: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("curl failed: {}", e))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
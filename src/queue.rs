@@ -0,0 +1,484 @@
+//! Visible command queue.
+//!
+//! Every command source still sends on the same channel; a forwarder moves
+//! each command into a shared queue as soon as it arrives, and the device
+//! worker takes them from there. The GUI lists what is waiting and can cancel
+//! anything the worker hasn't started. Cancelled batches and raw frames
+//! report back to whoever submitted them, so API callers aren't left waiting.
+//!
+//! Bursts are coalesced as they are queued: a switch of a channel that is
+//! already waiting to be switched by the same person, through the window, a
+//! hotkey or another interactive source, replaces the earlier one, so
+//! mashing ON/OFF/ON sends only the final intent and two toggles cancel out.
+//! Switches from schedules, rules, sequences, scripts and other sources all
+//! run, as do any from different sources. A status query is never queued
+//! twice. Batches (pulses among them), raw frames and ALL OFF are barriers
+//! that nothing is coalesced across.
+//!
+//! Status queries have the lowest priority: they run only when nothing else
+//! is waiting, so background polling never delays a click.
+//!
+//! A command can wait in the queue until a given time, e.g. an ON held for
+//! its channel's cooldown; later switches of the same channel wait behind
+//! it, so they still run in order.
+//!
+//! Each command keeps its [`Source`] on the way through, for the history;
+//! one that replaces an earlier switch brings its own.
+//!
+//! The queue also remembers the command the worker is running, so the GUI
+//! can tell which channels have a switch pending. Every change wakes the GUI
+//! to redraw.
+
+use eframe::egui;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::batch::{BatchError, BatchErrorKind};
+use crate::config::DeviceConfig;
+use crate::supervisor::SubsystemContext;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Only commands queued this close together form a burst; anything older
+/// was meant on its own.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+// ============================================================================
+// QUEUE
+// ============================================================================
+
+struct Entry {
+    id: u64,
+    command: Command,
+    source: Source,
+    queued: Instant,
+    /// Held back until then.
+    not_before: Option<Instant>,
+}
+
+impl Entry {
+    fn held(&self, now: Instant) -> bool {
+        self.not_before.is_some_and(|at| at > now)
+    }
+}
+
+/// The channel a plain switching command acts on.
+fn switched_channel(command: &Command) -> Option<u8> {
+    match *command {
+        Command::TurnOn(channel) | Command::TurnOff(channel) | Command::Toggle(channel) => {
+            Some(channel)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Background,
+    Control,
+}
+
+fn priority(command: &Command) -> Priority {
+    match command {
+        Command::Poll => Priority::Background,
+        _ => Priority::Control,
+    }
+}
+
+/// Whether `command` may switch `channel`.
+fn affects(command: &Command, channel: u8) -> bool {
+    match command {
+        Command::TurnOn(c) | Command::TurnOff(c) | Command::Toggle(c) => *c == channel,
+        Command::Batch(batch, _) => batch.steps.iter().any(|s| s.channel() == Some(channel)),
+        Command::AllOff | Command::Raw(..) | Command::Bridge(..) => true,
+        Command::Poll => false,
+    }
+}
+
+fn same_channel(a: &Command, b: &Command) -> bool {
+    switched_channel(a).is_some() && switched_channel(a) == switched_channel(b)
+}
+
+/// Folds `command` from `source` into the waiting burst; returns false when
+/// it is redundant and shouldn't be queued at all.
+fn coalesce(entries: &mut VecDeque<Entry>, command: &Command, source: &Source) -> bool {
+    for i in (0..entries.len()).rev() {
+        let entry = &entries[i];
+        if entry.queued.elapsed() > COALESCE_WINDOW {
+            break;
+        }
+        let same_intent = source.is_interactive() && entry.source == *source;
+        match (&entry.command, command) {
+            (Command::Poll, Command::Poll) => return false,
+            // Someone else's switch, or an automated one, has to run
+            (earlier, _) if same_channel(earlier, command) && !same_intent => break,
+            (Command::TurnOn(a) | Command::TurnOff(a), Command::TurnOn(b) | Command::TurnOff(b))
+                if a == b =>
+            {
+                entries.remove(i);
+                return true;
+            }
+            (Command::Toggle(a), Command::Toggle(b)) if a == b => {
+                entries.remove(i);
+                return false;
+            }
+            (Command::Batch(..) | Command::AllOff | Command::Raw(..) | Command::Bridge(..), _) => {
+                break
+            }
+            // A mix like ON then toggle has no simpler form
+            (earlier, _) if same_channel(earlier, command) => break,
+            _ => {}
+        }
+    }
+    true
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: VecDeque<Entry>,
+    /// Taken by the worker and not yet finished.
+    running: Option<Command>,
+    next_id: u64,
+    closed: bool,
+}
+
+/// Commands waiting for the device worker, in execution order.
+#[derive(Clone)]
+pub struct CommandQueue {
+    inner: Arc<(Mutex<Inner>, Condvar)>,
+    repaint: egui::Context,
+}
+
+impl CommandQueue {
+    pub fn new(repaint: egui::Context) -> Self {
+        Self {
+            inner: Arc::default(),
+            repaint,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn insert(&self, command: Command, source: Source, front: bool, not_before: Option<Instant>) {
+        let mut inner = self.lock();
+        if !front && !coalesce(&mut inner.entries, &command, &source) {
+            return;
+        }
+        inner.next_id += 1;
+        let entry = Entry {
+            id: inner.next_id,
+            command,
+            source,
+            queued: Instant::now(),
+            not_before,
+        };
+        if front {
+            inner.entries.push_front(entry);
+        } else {
+            inner.entries.push_back(entry);
+        }
+        self.inner.1.notify_all();
+        self.repaint.request_repaint();
+    }
+
+    /// Queues `command` last, coalescing it with the burst before it.
+    pub fn push(&self, command: Command, source: Source) {
+        self.insert(command, source, false, None);
+    }
+
+    /// Queues `command` ahead of everything else, as is.
+    pub fn push_front(&self, command: Command, source: Source) {
+        self.insert(command, source, true, None);
+    }
+
+    /// Queues `command` last, to run no earlier than `at`.
+    pub fn push_delayed(&self, command: Command, source: Source, at: Instant) {
+        self.insert(command, source, false, Some(at));
+    }
+
+    /// Waits for the next command and its source; `None` once the queue is
+    /// closed and empty.
+    /// Taking a command also finishes the previous one.
+    pub fn pop(&self) -> Option<(Command, Source)> {
+        let mut inner = self.lock();
+        inner.running = None;
+        self.repaint.request_repaint();
+        loop {
+            let now = Instant::now();
+            if let Some(index) = next_index(&inner.entries, now) {
+                let entry = inner.entries.remove(index).map(|e| (e.command, e.source));
+                inner.running = entry.as_ref().map(|(command, _)| command.clone());
+                self.repaint.request_repaint();
+                return entry;
+            }
+            if inner.closed {
+                return None;
+            }
+            // Wake for the first held command, if nothing comes sooner
+            let held = inner.entries.iter().filter_map(|e| e.not_before).min();
+            let condvar = &self.inner.1;
+            inner = match held {
+                Some(at) => {
+                    let wait = at.saturating_duration_since(now);
+                    condvar.wait_timeout(inner, wait).unwrap_or_else(|e| e.into_inner()).0
+                }
+                None => condvar.wait(inner).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    /// Marks the running command finished, for when the worker stops before
+    /// taking the next one.
+    pub fn finish(&self) {
+        self.lock().running = None;
+        self.repaint.request_repaint();
+    }
+
+    /// For each of `channels`, whether a command that may switch it is
+    /// waiting or running.
+    pub fn busy(&self, channels: u8) -> Vec<bool> {
+        let inner = self.lock();
+        let commands = inner.entries.iter().map(|e| &e.command).chain(&inner.running);
+        (1..=channels)
+            .map(|channel| commands.clone().any(|c| affects(c, channel)))
+            .collect()
+    }
+
+    /// Drops every waiting command, as cancelled.
+    pub fn clear(&self) {
+        let entries = std::mem::take(&mut self.lock().entries);
+        for entry in entries {
+            reject(entry.command);
+        }
+        self.repaint.request_repaint();
+    }
+
+    /// Cancels a waiting command; false if it has already been taken.
+    pub fn cancel(&self, id: u64) -> bool {
+        let entry = {
+            let mut inner = self.lock();
+            let index = inner.entries.iter().position(|e| e.id == id);
+            index.and_then(|i| inner.entries.remove(i))
+        };
+        match entry {
+            Some(entry) => {
+                reject(entry.command);
+                self.repaint.request_repaint();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ids and descriptions of the waiting commands, in the order they will
+    /// run.
+    pub fn snapshot(&self, device: &DeviceConfig) -> Vec<(u64, String)> {
+        let inner = self.lock();
+        let mut entries: Vec<&Entry> = inner.entries.iter().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(priority(&e.command)));
+        let now = Instant::now();
+        entries
+            .into_iter()
+            .map(|e| match e.not_before.filter(|_| e.held(now)) {
+                Some(at) => {
+                    let secs = at.saturating_duration_since(now).as_secs() + 1;
+                    (e.id, format!("{} (in {} s)", describe(&e.command, device), secs))
+                }
+                None => (e.id, describe(&e.command, device)),
+            })
+            .collect()
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.inner.1.notify_all();
+    }
+}
+
+/// The first of the highest-priority entries that may run at `now`. A held
+/// switch also holds back later switches of its channel.
+fn next_index(entries: &VecDeque<Entry>, now: Instant) -> Option<usize> {
+    let ready = |i: &usize| {
+        let entry = &entries[*i];
+        !entry.held(now)
+            && !entries
+                .iter()
+                .take(*i)
+                .any(|e| e.held(now) && same_channel(&e.command, &entry.command))
+    };
+    let top = (0..entries.len()).filter(ready).map(|i| priority(&entries[i].command)).max()?;
+    (0..entries.len()).filter(ready).find(|&i| priority(&entries[i].command) == top)
+}
+
+/// Tells the submitter of a dropped command, if it is waiting for a result.
+fn reject(command: Command) {
+    match command {
+        Command::Batch(_, Some(reply)) => {
+            let _ = reply.send(Err(BatchError {
+                step: 0,
+                kind: BatchErrorKind::Cancelled,
+                message: "Cancelled before it started".to_string(),
+            }));
+        }
+        Command::Raw(_, Some(reply)) => {
+            let _ = reply.send(Err("Cancelled before it was sent".to_string()));
+        }
+        _ => {}
+    }
+}
+
+fn describe(command: &Command, device: &DeviceConfig) -> String {
+    match command {
+        Command::TurnOn(channel) => format!("{} ON", device.label(*channel)),
+        Command::TurnOff(channel) => format!("{} OFF", device.label(*channel)),
+        Command::Toggle(channel) => format!("Toggle {}", device.label(*channel)),
+        Command::AllOff => "All off".to_string(),
+        Command::Batch(batch, _) => format!("Batch of {} step(s)", batch.steps.len()),
+        Command::Poll => "Status query".to_string(),
+        Command::Raw(bytes, _) => format!("Raw frame ({} bytes)", bytes.len()),
+        Command::Bridge(_) => "Serial bridge session".to_string(),
+    }
+}
+
+// ============================================================================
+// FORWARDER
+// ============================================================================
+
+/// Moves commands from the channel into the queue until every sender is
+/// gone, then closes the queue so the worker stops.
+pub fn run_forwarder(
+    ctx: &SubsystemContext,
+    commands: &mut mpsc::UnboundedReceiver<(Command, Source)>,
+    queue: &CommandQueue,
+) -> Result<(), String> {
+    ctx.mark_running();
+    while let Some((command, source)) = commands.blocking_recv() {
+        queue.push(command, source);
+    }
+    queue.close();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{Batch, BatchStep};
+
+    fn entry(command: Command, source: Source) -> Entry {
+        Entry { id: 0, command, source, queued: Instant::now(), not_before: None }
+    }
+
+    /// Queues each command the way `insert` does.
+    fn queue(commands: Vec<(Command, Source)>) -> Vec<Command> {
+        let mut entries = VecDeque::new();
+        for (command, source) in commands {
+            if coalesce(&mut entries, &command, &source) {
+                entries.push_back(entry(command, source));
+            }
+        }
+        entries.into_iter().map(|entry| entry.command).collect()
+    }
+
+    fn names(commands: &[Command]) -> Vec<String> {
+        commands.iter().map(|command| format!("{:?}", command)).collect()
+    }
+
+    fn assert_queued(commands: Vec<(Command, Source)>, expected: Vec<Command>) {
+        assert_eq!(names(&queue(commands)), names(&expected));
+    }
+
+    #[test]
+    fn mashing_keeps_the_final_intent() {
+        assert_queued(
+            vec![
+                (Command::TurnOn(1), Source::Window),
+                (Command::TurnOff(1), Source::Window),
+                (Command::TurnOn(1), Source::Window),
+            ],
+            vec![Command::TurnOn(1)],
+        );
+    }
+
+    #[test]
+    fn toggles_cancel_out() {
+        assert_queued(
+            vec![(Command::Toggle(2), Source::Hotkey), (Command::Toggle(2), Source::Hotkey)],
+            vec![],
+        );
+    }
+
+    #[test]
+    fn automated_switches_all_run() {
+        let rule = || Source::Rule("Pump".to_string());
+        assert_queued(
+            vec![(Command::TurnOn(1), rule()), (Command::TurnOff(1), rule())],
+            vec![Command::TurnOn(1), Command::TurnOff(1)],
+        );
+    }
+
+    #[test]
+    fn switches_from_different_sources_all_run() {
+        assert_queued(
+            vec![
+                (Command::TurnOn(1), Source::Schedule(3)),
+                (Command::TurnOff(1), Source::Window),
+                (Command::TurnOn(1), Source::Hotkey),
+            ],
+            vec![Command::TurnOn(1), Command::TurnOff(1), Command::TurnOn(1)],
+        );
+    }
+
+    #[test]
+    fn other_channels_are_looked_past() {
+        assert_queued(
+            vec![
+                (Command::TurnOn(1), Source::Window),
+                (Command::TurnOn(2), Source::Sequence("Start".to_string())),
+                (Command::TurnOff(1), Source::Window),
+            ],
+            vec![Command::TurnOn(2), Command::TurnOff(1)],
+        );
+    }
+
+    #[test]
+    fn status_queries_are_queued_once() {
+        assert_queued(
+            vec![(Command::Poll, Source::Device), (Command::Poll, Source::Device)],
+            vec![Command::Poll],
+        );
+    }
+
+    #[test]
+    fn batches_are_barriers() {
+        let pulse = || {
+            let steps = vec![BatchStep::On(1), BatchStep::Wait(500), BatchStep::Off(1)];
+            Command::Batch(Batch { steps, start_by: None }, None)
+        };
+        assert_queued(
+            vec![
+                (Command::TurnOn(1), Source::Window),
+                (pulse(), Source::Window),
+                (Command::TurnOff(1), Source::Window),
+            ],
+            vec![Command::TurnOn(1), pulse(), Command::TurnOff(1)],
+        );
+    }
+
+    #[test]
+    fn stale_entries_are_not_coalesced() {
+        let mut entries = VecDeque::new();
+        let mut stale = entry(Command::TurnOn(1), Source::Window);
+        stale.queued = Instant::now() - COALESCE_WINDOW - Duration::from_millis(10);
+        entries.push_back(stale);
+        assert!(coalesce(&mut entries, &Command::TurnOff(1), &Source::Window));
+        assert_eq!(entries.len(), 1);
+    }
+}
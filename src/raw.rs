@@ -0,0 +1,78 @@
+//! Raw frame passthrough.
+//!
+//! With `advanced: true` in the config, arbitrary bytes can be sent to the
+//! board and its raw reply returned, through `POST /raw` on the HTTP API
+//! (which also needs an API token) or `send-raw` on the CLI. This is for
+//! scripting board features the app doesn't know about; state tracking
+//! can't follow what a raw frame does, so channels are requeried afterwards.
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::audit::Source;
+use crate::{AppState, Command};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How long a raw frame may take, including time queued behind others.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// ============================================================================
+// HEX
+// ============================================================================
+
+/// Parses hex bytes like `A0 01 01 A2`, `a00101a2`, `A0:01:01:A2` or
+/// `0xA0 0x01`.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text
+        .split_whitespace()
+        .map(|word| word.trim_start_matches("0x").trim_start_matches("0X"))
+        .flat_map(str::chars)
+        .filter(|c| !matches!(c, ':' | ',' | '-'))
+        .collect();
+    if digits.is_empty() {
+        return Err("No bytes given".to_string());
+    }
+    if !digits.is_ascii() {
+        return Err(format!("Invalid hex in '{}'", text.trim()));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("Odd number of hex digits in '{}'", text.trim()));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("Invalid hex byte '{}'", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+/// Formats bytes as `A0 01 01 A2`.
+pub fn format_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// ============================================================================
+// REPLIES
+// ============================================================================
+
+/// The board's reply to a raw frame; empty when it sent nothing back.
+pub type RawResult = Result<Vec<u8>, String>;
+
+/// Where the device worker sends a raw frame's reply.
+pub type RawReply = mpsc::Sender<RawResult>;
+
+/// Queues `bytes` for the board and waits for its reply; None means none
+/// arrived within [`TIMEOUT`].
+pub fn submit(state: &AppState, bytes: Vec<u8>, source: Source) -> Option<RawResult> {
+    let (tx, rx) = mpsc::channel();
+    state.send_command(Command::Raw(bytes, Some(tx)), source);
+    rx.recv_timeout(TIMEOUT).ok()
+}
@@ -0,0 +1,260 @@
+//! Remote relays in the window.
+//!
+//! Daemons on other machines, listed in `remotes`, are shown below the local
+//! channels, so one window manages relays spread across several hosts. Each
+//! is followed through its HTTP API's `/status` and switched through
+//! `/channels/<n>/on|off`, presenting the remote's token when its API needs
+//! one; the switch is recorded in that daemon's history under this
+//! machine's address and the token's name. An unreachable daemon shows its
+//! error and is asked again on every poll.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::http;
+use crate::i18n::{tr, tr_args};
+use crate::json::Json;
+use crate::supervisor::SubsystemContext;
+use crate::theme::ThemeConfig;
+use crate::{screen_reader_label, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_STATUS_BYTES: usize = 64 * 1024;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Heading shown above its channels; empty for the URL.
+    pub name: String,
+    /// Base URL of the daemon's HTTP API, e.g. `http://pi.local:8787`.
+    pub url: String,
+    /// Bearer token for the daemon's API.
+    pub token: Option<String>,
+}
+
+impl RemoteConfig {
+    fn base_url(&self) -> &str {
+        self.url.trim().trim_end_matches('/')
+    }
+
+    fn title(&self) -> &str {
+        if self.name.is_empty() { self.base_url() } else { &self.name }
+    }
+
+    fn call(&self, method: &str, path: &str) -> Result<http::HttpResponse, String> {
+        let url = format!("{}{}", self.base_url(), path);
+        let authorization = self.token.as_ref().map(|token| format!("Bearer {}", token));
+        let headers: Vec<(&str, &str)> =
+            authorization.iter().map(|value| ("Authorization", value.as_str())).collect();
+        let response = match method {
+            "GET" => {
+                http::request_with_body(method, &url, &headers, REQUEST_TIMEOUT, MAX_STATUS_BYTES)?
+            }
+            _ => http::request(method, &url, &headers, b"", REQUEST_TIMEOUT)?,
+        };
+        match response.status {
+            200..=299 => Ok(response),
+            401 => Err("wrong or missing token".to_string()),
+            403 => Err("read-only".to_string()),
+            status => Err(format!("HTTP {}", status)),
+        }
+    }
+
+    /// The daemon's channels, as labels and states in channel order.
+    fn fetch(&self) -> Result<Vec<(String, RelayState)>, String> {
+        let response = self.call("GET", "/status")?;
+        let reply = Json::parse(&String::from_utf8_lossy(&response.body))
+            .ok_or_else(|| "unexpected reply".to_string())?;
+        let channels = reply.get("channels").map_or(&[][..], Json::list);
+        Ok(channels
+            .iter()
+            .enumerate()
+            .map(|(i, channel)| {
+                let label = channel.get("label").and_then(Json::text);
+                let label = label.map_or_else(|| format!("CH {}", i + 1), str::to_string);
+                let state = match channel.get("state").and_then(Json::text) {
+                    Some("on") => RelayState::On,
+                    Some("off") => RelayState::Off,
+                    Some("error") => RelayState::Error,
+                    _ => RelayState::Unknown,
+                };
+                (label, state)
+            })
+            .collect())
+    }
+}
+
+// ============================================================================
+// STATE
+// ============================================================================
+
+/// What was last heard from one remote.
+#[derive(Debug, Clone, Default)]
+struct RemoteView {
+    title: String,
+    channels: Vec<(String, RelayState)>,
+    error: Option<String>,
+    /// Why the last switch failed, until the next one.
+    failed: Option<String>,
+    /// Channels with a switch sent since the last poll.
+    busy: Vec<u8>,
+}
+
+/// The remotes' states, shared by the poller and the window.
+#[derive(Clone)]
+pub struct Remotes {
+    views: Arc<Mutex<Vec<RemoteView>>>,
+    /// Wakes the poller early, after a switch.
+    wake: mpsc::Sender<()>,
+    woken: Arc<Mutex<mpsc::Receiver<()>>>,
+    repaint: egui::Context,
+}
+
+impl Remotes {
+    pub fn new(repaint: egui::Context) -> Self {
+        let (wake, woken) = mpsc::channel();
+        Self {
+            views: Arc::default(),
+            wake,
+            woken: Arc::new(Mutex::new(woken)),
+            repaint,
+        }
+    }
+
+    fn views(&self) -> std::sync::MutexGuard<'_, Vec<RemoteView>> {
+        self.views.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sends a switch to remote `index` in the background.
+    fn switch(&self, index: usize, channel: u8, on: bool) {
+        if let Some(view) = self.views().get_mut(index) {
+            view.busy.push(channel);
+            view.failed = None;
+        }
+        let remotes = self.clone();
+        std::thread::spawn(move || {
+            let config = Config::load().map(|c| c.remotes).unwrap_or_default();
+            let Some(remote) = config.get(index) else {
+                return;
+            };
+            let action = if on { "on" } else { "off" };
+            let path = format!("/channels/{}/{}", channel, action);
+            if let Err(e) = remote.call("POST", &path) {
+                if let Some(view) = remotes.views().get_mut(index) {
+                    view.failed = Some(e);
+                }
+            }
+            let _ = remotes.wake.send(());
+            remotes.repaint.request_repaint();
+        });
+    }
+
+    /// Draws every remote's channels with ON/OFF buttons.
+    pub fn show(&self, ui: &mut egui::Ui, theme: &ThemeConfig) {
+        let views = self.views().clone();
+        for (index, view) in views.iter().enumerate() {
+            ui.separator();
+            ui.label(egui::RichText::new(&view.title).strong());
+            if let Some(error) = &view.error {
+                ui.colored_label(theme.error(), tr_args("remote-unreachable", &[("error", error)]));
+            }
+            if let Some(error) = &view.failed {
+                let text = tr_args("remote-switch-failed", &[("error", error)]);
+                ui.colored_label(theme.error(), text);
+            }
+            for (i, (label, relay_state)) in view.channels.iter().enumerate() {
+                let channel = i as u8 + 1;
+                let busy = view.busy.contains(&channel);
+                let name = format!("{}: {}", view.title, label);
+                ui.horizontal(|ui| {
+                    let (rect, indicator) =
+                        ui.allocate_exact_size(egui::vec2(20.0, 20.0), egui::Sense::hover());
+                    let text = tr_args(
+                        "channel-state",
+                        &[("channel", &name), ("state", &relay_state.local_text())],
+                    );
+                    screen_reader_label(&indicator, egui::WidgetType::Label, text);
+                    theme.indicator(ui.painter(), rect.center(), 10.0, *relay_state, None);
+                    ui.label(format!("{}  {}", label, relay_state.local_text()));
+                    if busy {
+                        ui.spinner();
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.add_space(20.0);
+                        for (on, id, fill) in [
+                            (false, "state-off", theme.off_fill()),
+                            (true, "state-on", theme.on_fill()),
+                        ] {
+                            let button = egui::Button::new(egui::RichText::new(tr(id)).strong())
+                                .fill(fill)
+                                .min_size(egui::vec2(56.0, 24.0));
+                            let response = ui.add_enabled(!busy, button);
+                            let label = match on {
+                                true => tr_args("switch-on", &[("channel", &name)]),
+                                false => tr_args("switch-off", &[("channel", &name)]),
+                            };
+                            screen_reader_label(&response, egui::WidgetType::Button, label);
+                            if response.clicked() {
+                                self.switch(index, channel, on);
+                            }
+                        }
+                    });
+                });
+            }
+        }
+    }
+}
+
+// ============================================================================
+// POLLER
+// ============================================================================
+
+/// Follows the configured remotes while the window is open. Exits cleanly
+/// when there are none.
+pub fn run_remotes(ctx: &SubsystemContext, remotes: &Remotes) -> Result<(), String> {
+    if Config::load()?.remotes.is_empty() {
+        return Ok(());
+    }
+    ctx.mark_running();
+
+    loop {
+        let config = Config::load().map(|c| c.remotes).unwrap_or_default();
+        let results: Vec<_> = config.iter().map(RemoteConfig::fetch).collect();
+        {
+            let mut views = remotes.views();
+            views.resize_with(config.len(), RemoteView::default);
+            for ((view, remote), result) in views.iter_mut().zip(&config).zip(results) {
+                view.title = remote.title().to_string();
+                view.busy.clear();
+                match result {
+                    Ok(channels) => {
+                        view.channels = channels;
+                        view.error = None;
+                    }
+                    Err(e) => {
+                        view.channels.iter_mut().for_each(|(_, s)| *s = RelayState::Error);
+                        view.error = Some(e);
+                    }
+                }
+            }
+        }
+        remotes.repaint.request_repaint();
+
+        let woken = remotes.woken.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = woken.recv_timeout(POLL_INTERVAL);
+        while woken.try_recv().is_ok() {}
+    }
+}
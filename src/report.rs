@@ -0,0 +1,202 @@
+//! Periodic usage reports.
+//!
+//! Summarizes the switching history per channel (time spent ON, number of
+//! switches, watchdog trips, device errors) for the last day or week. When
+//! enabled the report is raised as a notification at the configured time, so
+//! it reaches every notification sink, ntfy among them, and is mailed as the
+//! `Report` email event; `usb-power-relay report` prints one on demand.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config::{Config, DeviceConfig};
+use crate::email::{self, EmailEvent};
+use crate::history::{unix_now, History, HistoryEvent};
+use crate::locale::format_duration;
+use crate::notify::Notifier;
+use crate::schedule::{LocalTime, TimeOfDay, Weekday};
+use crate::supervisor::SubsystemContext;
+use crate::RelayState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_secs(1);
+const SECS_PER_DAY: u64 = 24 * 3600;
+/// How history records a watchdog's power cycles as their source.
+const WATCHDOG_SOURCE: &str = "watchdog '";
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportPeriod {
+    Daily,
+    Weekly,
+}
+
+impl ReportPeriod {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.to_ascii_lowercase().as_str() {
+            "daily" => Ok(ReportPeriod::Daily),
+            "weekly" => Ok(ReportPeriod::Weekly),
+            _ => Err(format!("Unknown period '{}', expected daily or weekly", text)),
+        }
+    }
+
+    fn secs(&self) -> u64 {
+        match self {
+            ReportPeriod::Daily => SECS_PER_DAY,
+            ReportPeriod::Weekly => 7 * SECS_PER_DAY,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ReportPeriod::Daily => "Daily report",
+            ReportPeriod::Weekly => "Weekly report",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    pub enabled: bool,
+    pub period: ReportPeriod,
+    /// Local time the report is sent.
+    pub time: TimeOfDay,
+    /// Day a weekly report is sent on.
+    pub weekday: Weekday,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            period: ReportPeriod::Daily,
+            time: TimeOfDay { hour: 8, minute: 0 },
+            weekday: Weekday::Mon,
+        }
+    }
+}
+
+impl ReportConfig {
+    fn is_due(&self, now: &LocalTime) -> bool {
+        self.enabled
+            && self.time.hour == now.hour
+            && self.time.minute == now.minute
+            && (self.period == ReportPeriod::Daily || self.weekday == now.weekday)
+    }
+}
+
+// ============================================================================
+// SUMMARY
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelSummary {
+    pub on_secs: u64,
+    pub switches: u32,
+    /// Power cycles by a watchdog.
+    pub trips: u32,
+    pub errors: u32,
+}
+
+/// Summarizes one channel's chronological events over `start..end`.
+fn summarize(events: &[&HistoryEvent], start: u64, end: u64) -> ChannelSummary {
+    let mut summary = ChannelSummary::default();
+    let mut state = RelayState::Unknown;
+    let mut last_switch_state = None;
+    let mut since = start;
+
+    for event in events {
+        if event.time >= end {
+            break;
+        }
+        if event.time >= start {
+            if state == RelayState::On {
+                summary.on_secs += event.time - since;
+            }
+            match event.state {
+                RelayState::Error => summary.errors += 1,
+                new => {
+                    if last_switch_state.is_some_and(|old| old != new) {
+                        summary.switches += 1;
+                    }
+                    // Each cycle starts by switching the load off
+                    if new == RelayState::Off && event.source.starts_with(WATCHDOG_SOURCE) {
+                        summary.trips += 1;
+                    }
+                }
+            }
+            since = event.time;
+        }
+        state = event.state;
+        if matches!(state, RelayState::On | RelayState::Off) {
+            last_switch_state = Some(state);
+        }
+    }
+    if state == RelayState::On {
+        summary.on_secs += end.saturating_sub(since);
+    }
+    summary
+}
+
+/// Renders the report for the period ending at `end`.
+pub fn render(events: &[HistoryEvent], device: &DeviceConfig, period: ReportPeriod, end: u64) -> String {
+    let start = end.saturating_sub(period.secs());
+    let mut lines = Vec::with_capacity(device.channels as usize);
+
+    for channel in 1..=device.channels {
+        let channel_events: Vec<&HistoryEvent> =
+            events.iter().filter(|e| e.channel == channel).collect();
+        let summary = summarize(&channel_events, start, end);
+        lines.push(format!(
+            "{}: on {}, {} switch{}, {} watchdog trip{}, {} error{}",
+            device.display_name(channel),
+            format_duration(summary.on_secs),
+            summary.switches,
+            if summary.switches == 1 { "" } else { "es" },
+            summary.trips,
+            if summary.trips == 1 { "" } else { "s" },
+            summary.errors,
+            if summary.errors == 1 { "" } else { "s" },
+        ));
+    }
+    lines.join("\n")
+}
+
+// ============================================================================
+// REPORTER
+// ============================================================================
+
+/// Raises the configured report as a notification, and mails it, when it is
+/// due.
+pub fn run_reporter(ctx: &SubsystemContext, notifier: &Notifier) -> Result<(), String> {
+    // Don't resend the current minute's report after a restart
+    let mut last_minute = Some(LocalTime::now().minute_key());
+    ctx.mark_running();
+
+    loop {
+        let now = LocalTime::now();
+        if last_minute != Some(now.minute_key()) {
+            last_minute = Some(now.minute_key());
+
+            let config = Config::load()?;
+            if config.report.is_due(&now) {
+                let text = render(&History::load()?, &config.device, config.report.period, unix_now());
+                let title = config.report.period.title();
+                email::send(
+                    EmailEvent::Report,
+                    vec![("title", title.to_string()), ("report", text.clone())],
+                );
+                notifier.notify(title, text);
+            }
+        }
+
+        std::thread::sleep(TICK);
+    }
+}
@@ -0,0 +1,714 @@
+//! Automation rules.
+//!
+//! A rule joins a trigger to a list of actions, with optional conditions
+//! that must all hold when the trigger fires: "when the router stops
+//! answering pings on a weekday between 08:00 and 18:00, pulse its channel
+//! and send a notification". Triggers are a time of day, a host failing
+//! pings, a USB serial device being plugged in or out, a call to
+//! `POST /rules/<name>` on the HTTP API and a channel switching. Rules are
+//! edited in the Rules window or the config file; the engine picks up
+//! changes without a restart.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::hotplug::HotplugEvent;
+use crate::i18n::{tr, tr_args};
+use crate::notify::Notifier;
+use crate::schedule::{LocalTime, TimeOfDay, Weekday};
+use crate::sequence::{self, Direction, Sequence};
+use crate::supervisor::SubsystemContext;
+use crate::watchdog;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_millis(250);
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// RULE MODEL
+// ============================================================================
+
+/// How the editor names a trigger, condition or action kind.
+trait Labeled {
+    fn label(&self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Switch {
+    On,
+    Off,
+}
+
+impl Switch {
+    pub const ALL: [Switch; 2] = [Switch::On, Switch::Off];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            Switch::On => "ON",
+            Switch::Off => "OFF",
+        }
+    }
+
+    fn matches(&self, state: RelayState) -> bool {
+        matches!(
+            (self, state),
+            (Switch::On, RelayState::On) | (Switch::Off, RelayState::Off)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// Every day at this time; a `Days` condition narrows it down.
+    Time(TimeOfDay),
+    /// `host` missed `failures` pings in a row, one every 30 s. Fires once,
+    /// then again only after the host has answered in between.
+    PingFailed { host: String, failures: u32 },
+    UsbPlugged,
+    UsbUnplugged,
+    /// `POST /rules/<name>` on the HTTP API.
+    Api,
+    /// `channel` switched to `state`.
+    Switched { channel: u8, state: Switch },
+}
+
+impl Trigger {
+    fn kinds() -> Vec<Trigger> {
+        vec![
+            Trigger::Time(TimeOfDay { hour: 8, minute: 0 }),
+            Trigger::PingFailed { host: String::new(), failures: 3 },
+            Trigger::UsbPlugged,
+            Trigger::UsbUnplugged,
+            Trigger::Api,
+            Trigger::Switched { channel: 1, state: Switch::On },
+        ]
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Time(time) => write!(f, "at {}", time),
+            Trigger::PingFailed { host, failures } => {
+                write!(f, "{} missing {} ping(s) in a row", host, failures)
+            }
+            Trigger::UsbPlugged => write!(f, "a USB serial device plugged in"),
+            Trigger::UsbUnplugged => write!(f, "a USB serial device unplugged"),
+            Trigger::Api => write!(f, "POST /rules/<name>"),
+            Trigger::Switched { channel, state } => {
+                write!(f, "channel {} switching {}", channel, state.text())
+            }
+        }
+    }
+}
+
+impl Labeled for Trigger {
+    fn label(&self) -> String {
+        tr(match self {
+            Trigger::Time(_) => "rules-trigger-time",
+            Trigger::PingFailed { .. } => "rules-trigger-ping",
+            Trigger::UsbPlugged => "rules-trigger-usb-plugged",
+            Trigger::UsbUnplugged => "rules-trigger-usb-unplugged",
+            Trigger::Api => "rules-trigger-api",
+            Trigger::Switched { .. } => "rules-trigger-switched",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Condition {
+    Channel { channel: u8, state: Switch },
+    /// From `from` up to `to` in local time; past midnight when `to` is
+    /// earlier.
+    Between { from: TimeOfDay, to: TimeOfDay },
+    Days(Vec<Weekday>),
+}
+
+impl Condition {
+    fn kinds() -> Vec<Condition> {
+        vec![
+            Condition::Channel { channel: 1, state: Switch::On },
+            Condition::Between {
+                from: TimeOfDay { hour: 8, minute: 0 },
+                to: TimeOfDay { hour: 18, minute: 0 },
+            },
+            Condition::Days(Weekday::ALL[..5].to_vec()),
+        ]
+    }
+
+    fn holds(&self, states: &[RelayState], now: &LocalTime) -> bool {
+        match self {
+            Condition::Channel { channel, state } => states
+                .get((*channel as usize).wrapping_sub(1))
+                .is_some_and(|s| state.matches(*s)),
+            Condition::Between { from, to } => {
+                let now = (now.hour, now.minute);
+                let (from, to) = ((from.hour, from.minute), (to.hour, to.minute));
+                if from <= to {
+                    from <= now && now < to
+                } else {
+                    now >= from || now < to
+                }
+            }
+            Condition::Days(days) => days.contains(&now.weekday),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Channel { channel, state } => {
+                write!(f, "channel {} is {}", channel, state.text())
+            }
+            Condition::Between { from, to } => write!(f, "between {} and {}", from, to),
+            Condition::Days(days) => {
+                let days: Vec<&str> = days.iter().map(Weekday::text).collect();
+                write!(f, "on {}", days.join(","))
+            }
+        }
+    }
+}
+
+impl Labeled for Condition {
+    fn label(&self) -> String {
+        tr(match self {
+            Condition::Channel { .. } => "rules-condition-channel",
+            Condition::Between { .. } => "rules-condition-between",
+            Condition::Days(_) => "rules-condition-days",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleAction {
+    On(u8),
+    Off(u8),
+    /// On, then off again after `ms`.
+    Pulse { channel: u8, ms: u64 },
+    /// Runs the named sequence's startup order.
+    Sequence(String),
+    Notify(String),
+}
+
+impl RuleAction {
+    fn kinds() -> Vec<RuleAction> {
+        vec![
+            RuleAction::On(1),
+            RuleAction::Off(1),
+            RuleAction::Pulse { channel: 1, ms: 1000 },
+            RuleAction::Sequence(String::new()),
+            RuleAction::Notify(String::new()),
+        ]
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleAction::On(channel) => write!(f, "switch channel {} on", channel),
+            RuleAction::Off(channel) => write!(f, "switch channel {} off", channel),
+            RuleAction::Pulse { channel, ms } => {
+                write!(f, "pulse channel {} for {} ms", channel, ms)
+            }
+            RuleAction::Sequence(name) => write!(f, "start sequence '{}'", name),
+            RuleAction::Notify(message) => write!(f, "notify '{}'", message),
+        }
+    }
+}
+
+impl Labeled for RuleAction {
+    fn label(&self) -> String {
+        tr(match self {
+            RuleAction::On(_) => "rules-action-on",
+            RuleAction::Off(_) => "rules-action-off",
+            RuleAction::Pulse { .. } => "rules-action-pulse",
+            RuleAction::Sequence(_) => "rules-action-sequence",
+            RuleAction::Notify(_) => "rules-action-notify",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    /// Also the `<name>` in `POST /rules/<name>`.
+    pub name: String,
+    pub enabled: bool,
+    pub trigger: Trigger,
+    /// All must hold for the actions to run.
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<RuleAction>,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            trigger: Trigger::Api,
+            conditions: Vec::new(),
+            actions: Vec::new(),
+        }
+    }
+}
+
+impl Rule {
+    /// Every channel the rule refers to.
+    pub fn channels(&self) -> Vec<u8> {
+        let mut channels = Vec::new();
+        if let Trigger::Switched { channel, .. } = self.trigger {
+            channels.push(channel);
+        }
+        for condition in &self.conditions {
+            if let Condition::Channel { channel, .. } = condition {
+                channels.push(*channel);
+            }
+        }
+        for action in &self.actions {
+            match action {
+                RuleAction::On(channel)
+                | RuleAction::Off(channel)
+                | RuleAction::Pulse { channel, .. } => channels.push(*channel),
+                RuleAction::Sequence(_) | RuleAction::Notify(_) => {}
+            }
+        }
+        channels
+    }
+
+    /// Whether each condition holds for `states` at `now`, in order.
+    pub fn check(&self, states: &[RelayState], now: &LocalTime) -> Vec<(&Condition, bool)> {
+        self.conditions.iter().map(|c| (c, c.holds(states, now))).collect()
+    }
+
+    /// Checks everything but the channels, which the config checks.
+    pub fn validate(&self, sequences: &[Sequence]) -> Result<(), String> {
+        let context = |e: &str| format!("Rule '{}': {}", self.name, e);
+        if self.name.trim().is_empty() {
+            return Err("Rules need a name".to_string());
+        }
+        if let Trigger::PingFailed { host, failures } = &self.trigger {
+            if host.is_empty() || host.starts_with('-') {
+                return Err(context(&format!("invalid host '{}'", host)));
+            }
+            if *failures == 0 {
+                return Err(context("failures must be at least 1"));
+            }
+        }
+        for action in &self.actions {
+            if let RuleAction::Sequence(name) = action {
+                if !sequences.iter().any(|s| &s.name == name) {
+                    return Err(context(&format!("unknown sequence '{}'", name)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+/// Triggers that other subsystems see first.
+#[derive(Debug, Clone)]
+pub enum RuleEvent {
+    /// `POST /rules/<name>`.
+    Api(String),
+    Usb(HotplugEvent),
+}
+
+/// Where subsystems queue events for the rules engine.
+pub type RuleEvents = std::sync::mpsc::Sender<RuleEvent>;
+
+/// What the engine needs to carry out actions.
+#[derive(Clone)]
+pub struct RulesContext {
+    pub state: AppState,
+    pub stop: EmergencyStop,
+    pub notifier: Notifier,
+}
+
+/// Pings `host` for one `PingFailed` rule.
+struct PingWatch {
+    next: Instant,
+    misses: u32,
+}
+
+impl PingWatch {
+    /// Pings when due; true when this miss is the one that fires.
+    fn due(&mut self, host: &str, failures: u32) -> Result<bool, String> {
+        if Instant::now() < self.next {
+            return Ok(false);
+        }
+        self.next = Instant::now() + PING_INTERVAL;
+        match watchdog::ping(host, PING_TIMEOUT)? {
+            Some(_) => {
+                self.misses = self.misses.saturating_add(1);
+                Ok(self.misses == failures)
+            }
+            None => {
+                self.misses = 0;
+                Ok(false)
+            }
+        }
+    }
+}
+
+// ============================================================================
+// ENGINE
+// ============================================================================
+
+impl RulesContext {
+    fn run_actions(&self, config: &Config, rule: &Rule) {
+        let source = || Source::Rule(rule.name.clone());
+        for action in &rule.actions {
+            match action {
+                RuleAction::On(channel) => {
+                    self.state.send_command(Command::TurnOn(*channel), source())
+                }
+                RuleAction::Off(channel) => {
+                    self.state.send_command(Command::TurnOff(*channel), source())
+                }
+                RuleAction::Pulse { channel, ms } => {
+                    let duration = Duration::from_millis(*ms);
+                    sequence::pulse(&[*channel], duration, &self.stop, source())
+                }
+                RuleAction::Sequence(name) => {
+                    match config.sequences.iter().find(|s| &s.name == name) {
+                        Some(found) => {
+                            sequence::spawn(found, Direction::Startup, &self.stop, source())
+                        }
+                        None => eprintln!("Rule '{}': unknown sequence '{}'", rule.name, name),
+                    }
+                }
+                RuleAction::Notify(message) => self.notifier.notify(&rule.name, message),
+            }
+        }
+    }
+}
+
+/// A fresh watch per rule, pinging at once.
+fn ping_watches(config: &Config) -> Vec<PingWatch> {
+    let now = Instant::now();
+    config.rules.iter().map(|_| PingWatch { next: now, misses: 0 }).collect()
+}
+
+/// Runs the actions of enabled rules whose trigger fired and whose
+/// conditions hold, reloading the rules whenever the config file changes.
+pub fn run_rules(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+    rules: &RulesContext,
+    events: &std::sync::mpsc::Receiver<RuleEvent>,
+) -> Result<(), String> {
+    let mut modified = Config::modified();
+    let mut config = Config::load()?;
+    let mut pings = ping_watches(&config);
+    // Neither the current minute nor current states fire after a restart
+    let mut last_minute = LocalTime::now().minute_key();
+    let mut snapshot = rules.state.snapshot.subscribe();
+    let mut states = snapshot.borrow_and_update().relay_states.clone();
+    // Nor do events from before the engine was running
+    while events.try_recv().is_ok() {}
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let current = Config::modified();
+        if current != modified {
+            config = Config::load()?;
+            modified = current;
+            pings = ping_watches(&config);
+        }
+
+        let mut fired = vec![false; config.rules.len()];
+        let now = LocalTime::now();
+        let new_minute = now.minute_key() != last_minute;
+        last_minute = now.minute_key();
+
+        let event = events.recv_timeout(TICK).ok();
+        let changed: Vec<(u8, RelayState)> = if snapshot.has_changed().unwrap_or(false) {
+            let latest = snapshot.borrow_and_update().relay_states.clone();
+            let changes = latest
+                .iter()
+                .zip(&states)
+                .enumerate()
+                .filter(|(_, (new, old))| {
+                    // Unknown or failed states aren't a switch either way
+                    new != old
+                        && matches!(old, RelayState::On | RelayState::Off)
+                        && matches!(new, RelayState::On | RelayState::Off)
+                })
+                .map(|(i, (new, _))| (i as u8 + 1, *new))
+                .collect();
+            states = latest;
+            changes
+        } else {
+            Vec::new()
+        };
+
+        for (i, rule) in config.rules.iter().enumerate().filter(|(_, r)| r.enabled) {
+            fired[i] = match (&rule.trigger, &event) {
+                (Trigger::Time(time), _) => {
+                    new_minute && time.hour == now.hour && time.minute == now.minute
+                }
+                (Trigger::PingFailed { host, failures }, _) => {
+                    pings[i].due(host, *failures)?
+                }
+                (Trigger::UsbPlugged, Some(RuleEvent::Usb(HotplugEvent::Arrived))) => true,
+                (Trigger::UsbUnplugged, Some(RuleEvent::Usb(HotplugEvent::Removed))) => true,
+                (Trigger::Api, Some(RuleEvent::Api(name))) => name.eq_ignore_ascii_case(&rule.name),
+                (Trigger::Switched { channel, state }, _) => changed
+                    .iter()
+                    .any(|(c, s)| c == channel && state.matches(*s)),
+                _ => false,
+            };
+        }
+
+        for (rule, _) in config.rules.iter().zip(&fired).filter(|(_, fired)| **fired) {
+            if rule.conditions.iter().all(|c| c.holds(&states, &now)) {
+                rules.run_actions(&config, rule);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// EDITOR
+// ============================================================================
+
+/// Picks the variant of `value` from `kinds`, keeping it if it is already
+/// of the picked kind.
+fn kind_combo<T: Labeled>(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    value: &mut T,
+    kinds: Vec<T>,
+) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(value.label())
+        .show_ui(ui, |ui| {
+            for kind in kinds {
+                let same = std::mem::discriminant(value) == std::mem::discriminant(&kind);
+                if ui.selectable_label(same, kind.label()).clicked() && !same {
+                    *value = kind;
+                }
+            }
+        });
+}
+
+
+fn time_ui(ui: &mut egui::Ui, time: &mut TimeOfDay) {
+    ui.add(egui::DragValue::new(&mut time.hour).range(0..=23));
+    ui.label(":");
+    ui.add(egui::DragValue::new(&mut time.minute).range(0..=59));
+}
+
+fn switch_ui(ui: &mut egui::Ui, id: impl std::hash::Hash, state: &mut Switch) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(state.text())
+        .show_ui(ui, |ui| {
+            for option in Switch::ALL {
+                ui.selectable_value(state, option, option.text());
+            }
+        });
+}
+
+/// GUI editor for the rules stored in the config file.
+pub struct RulesEditor {
+    config: Config,
+    selected: usize,
+    status: Option<String>,
+}
+
+impl RulesEditor {
+    pub fn new() -> Self {
+        let (config, status) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        Self { config, selected: 0, status }
+    }
+
+    /// Draws the editor window; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        egui::Window::new(tr("rules-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(420.0)
+            .show(ctx, |ui| self.contents(ui));
+        open
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let channels = self.config.device.channels;
+        let sequences: Vec<String> = self.config.sequences.iter().map(|s| s.name.clone()).collect();
+
+        ui.horizontal(|ui| {
+            let selected_name = self
+                .config
+                .rules
+                .get(self.selected)
+                .map_or(tr("rules-none"), |r| r.name.clone());
+            egui::ComboBox::from_id_source("rule_select")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for (i, rule) in self.config.rules.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected, i, &rule.name);
+                    }
+                });
+
+            if ui.button(tr("rules-new")).clicked() {
+                let number = self.config.rules.len() + 1;
+                self.config.rules.push(Rule {
+                    name: tr_args("rules-default-name", &[("number", &number)]),
+                    ..Rule::default()
+                });
+                self.selected = self.config.rules.len() - 1;
+            }
+            if ui.button(tr("rules-delete")).clicked() && self.selected < self.config.rules.len() {
+                self.config.rules.remove(self.selected);
+                self.selected = self.selected.saturating_sub(1);
+            }
+        });
+
+        let Some(rule) = self.config.rules.get_mut(self.selected) else {
+            ui.label(tr("rules-empty"));
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(tr("rules-name"));
+            ui.text_edit_singleline(&mut rule.name);
+            ui.checkbox(&mut rule.enabled, tr("rules-enabled"));
+        });
+        ui.separator();
+
+        ui.strong(tr("rules-when"));
+        ui.horizontal(|ui| {
+            kind_combo(ui, "rule_trigger", &mut rule.trigger, Trigger::kinds());
+            match &mut rule.trigger {
+                Trigger::Time(time) => time_ui(ui, time),
+                Trigger::PingFailed { host, failures } => {
+                    let host = egui::TextEdit::singleline(host).hint_text("192.168.1.1");
+                    ui.add(host.desired_width(140.0));
+                    ui.add(egui::DragValue::new(failures).range(1..=100).suffix("×"));
+                }
+                Trigger::Api => {
+                    ui.weak(format!("POST /rules/{}", rule.name));
+                }
+                Trigger::Switched { channel, state } => {
+                    ui.add(egui::DragValue::new(channel).range(1..=channels).prefix("CH "));
+                    switch_ui(ui, "rule_trigger_state", state);
+                }
+                Trigger::UsbPlugged | Trigger::UsbUnplugged => {}
+            }
+        });
+        ui.separator();
+
+        ui.strong(tr("rules-only-if"));
+        let mut remove = None;
+        for (i, condition) in rule.conditions.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                kind_combo(ui, ("rule_condition", i), condition, Condition::kinds());
+                match condition {
+                    Condition::Channel { channel, state } => {
+                        ui.add(egui::DragValue::new(channel).range(1..=channels).prefix("CH "));
+                        switch_ui(ui, ("rule_condition_state", i), state);
+                    }
+                    Condition::Between { from, to } => {
+                        time_ui(ui, from);
+                        ui.label("–");
+                        time_ui(ui, to);
+                    }
+                    Condition::Days(days) => {
+                        for day in Weekday::ALL {
+                            let mut on = days.contains(&day);
+                            if ui.toggle_value(&mut on, day.text()).changed() {
+                                days.retain(|d| *d != day);
+                                if on {
+                                    days.push(day);
+                                    days.sort_by_key(Weekday::index);
+                                }
+                            }
+                        }
+                    }
+                }
+                if ui.small_button("✕").on_hover_text(tr("rules-remove")).clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            rule.conditions.remove(i);
+        }
+        if ui.button(tr("rules-add-condition")).clicked() {
+            rule.conditions.push(Condition::kinds().remove(0));
+        }
+        ui.separator();
+
+        ui.strong(tr("rules-then"));
+        let mut remove = None;
+        for (i, action) in rule.actions.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                kind_combo(ui, ("rule_action", i), action, RuleAction::kinds());
+                match action {
+                    RuleAction::On(channel) | RuleAction::Off(channel) => {
+                        ui.add(egui::DragValue::new(channel).range(1..=channels).prefix("CH "));
+                    }
+                    RuleAction::Pulse { channel, ms } => {
+                        ui.add(egui::DragValue::new(channel).range(1..=channels).prefix("CH "));
+                        ui.add(egui::DragValue::new(ms).range(0..=600_000).speed(50).suffix(" ms"));
+                    }
+                    RuleAction::Sequence(name) => {
+                        egui::ComboBox::from_id_source(("rule_action_sequence", i))
+                            .selected_text(name.as_str())
+                            .show_ui(ui, |ui| {
+                                for sequence in &sequences {
+                                    ui.selectable_value(name, sequence.clone(), sequence);
+                                }
+                            });
+                    }
+                    RuleAction::Notify(message) => {
+                        ui.text_edit_singleline(message);
+                    }
+                }
+                if ui.small_button("✕").on_hover_text(tr("rules-remove")).clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            rule.actions.remove(i);
+        }
+        if ui.button(tr("rules-add-action")).clicked() {
+            rule.actions.push(RuleAction::kinds().remove(0));
+        }
+
+        ui.separator();
+        if ui.button(tr("settings-save")).clicked() {
+            self.status = Some(match self.config.validate().and_then(|()| self.config.save()) {
+                Ok(()) => tr("settings-saved"),
+                Err(e) => e,
+            });
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Maximum-ON safety timers.
+//!
+//! A channel with `max_on_secs` set is switched off once it has been on
+//! that long, however it was switched on, and a notification says so: a
+//! guard for soldering irons and heaters left running. The time on counts
+//! from the last change the board reported, which history carries over a
+//! restart, so a channel left on while the app was closed is caught too.
+
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::email::{self, EmailEvent};
+use crate::history::unix_now;
+use crate::locale::format_duration;
+use crate::notify::Notifier;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_secs(1);
+/// How long to wait for a safety OFF before sending it again.
+const RETRY_SECS: u64 = 30;
+
+// ============================================================================
+// WATCHER
+// ============================================================================
+
+/// Switches off channels that have been on longer than their limit.
+pub fn run_safety_timers(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+    state: &AppState,
+    notifier: &Notifier,
+) -> Result<(), String> {
+    let mut modified = Config::modified();
+    let mut config = Config::load()?;
+    // Per channel: the ON it was switched off for, and when
+    let mut tripped: Vec<Option<(u64, u64)>> = Vec::new();
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let current = Config::modified();
+        if current != modified {
+            config = Config::load()?;
+            modified = current;
+        }
+
+        let snapshot = state.snapshot();
+        tripped.resize(snapshot.relay_states.len(), None);
+        let now = unix_now();
+        for (i, change) in snapshot.last_changes.iter().enumerate() {
+            let channel = i as u8 + 1;
+            let limit = config.device.channel(channel).max_on_secs;
+            let on_since = match change {
+                Some((RelayState::On, since)) if snapshot.relay_states[i] == RelayState::On => {
+                    *since
+                }
+                _ => continue,
+            };
+            if limit == 0 || now.saturating_sub(on_since) < limit {
+                continue;
+            }
+            // Still on after a safety OFF: the board may have missed it
+            match tripped[i] {
+                Some((since, sent)) if since == on_since && now - sent < RETRY_SECS => continue,
+                Some((since, _)) if since == on_since => {}
+                _ => {
+                    let message = format!(
+                        "{} was on for {}, switching it off",
+                        config.device.label(channel),
+                        format_duration(now - on_since)
+                    );
+                    notifier.notify("Safety limit", message.clone());
+                    let values = vec![("channel", channel.to_string()), ("message", message)];
+                    email::send(EmailEvent::SafetyLimit, values);
+                }
+            }
+            tripped[i] = Some((on_since, now));
+            let _ = commands.send((Command::TurnOff(channel), Source::Safety));
+        }
+
+        std::thread::sleep(TICK);
+    }
+
+    Ok(())
+}
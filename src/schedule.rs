@@ -0,0 +1,642 @@
+//! Time-of-day schedules and the scheduler subsystem.
+//!
+//! Schedules fire once per matching minute, in local time or in the time
+//! zone they name; see [`crate::tz`] for how DST changes are handled. The
+//! scheduler re-reads the config whenever the file changes, so schedules
+//! added from the CLI take effect without restarting the app.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::batch::Batch;
+use crate::clock::ClockGuard;
+use crate::config::{Config, Scene, SceneStep};
+use crate::history::unix_now;
+use crate::notify::Notifier;
+use crate::overrides::Overrides;
+use crate::push::{self, PushKind};
+use crate::supervisor::SubsystemContext;
+use crate::tz::TimeZone;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_secs(1);
+/// How far back a late check still fires schedules, e.g. after the
+/// machine slept through them.
+const CATCH_UP_SECS: i64 = 90;
+/// The snooze lengths offered as quick actions, in hours.
+pub const SNOOZE_HOURS: [u64; 3] = [1, 4, 24];
+
+// ============================================================================
+// SCHEDULE MODEL
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Converts a `tm_wday` value (0 = Sunday).
+    fn from_tm(wday: i32) -> Self {
+        Self::ALL[(wday + 6).rem_euclid(7) as usize]
+    }
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            Weekday::Mon => "mon",
+            Weekday::Tue => "tue",
+            Weekday::Wed => "wed",
+            Weekday::Thu => "thu",
+            Weekday::Fri => "fri",
+            Weekday::Sat => "sat",
+            Weekday::Sun => "sun",
+        }
+    }
+
+    /// Parses `daily`, `weekdays`, `weekends` or a comma list like `mon,wed`.
+    pub fn parse_list(text: &str) -> Result<Vec<Weekday>, String> {
+        match text.to_ascii_lowercase().as_str() {
+            "daily" => Ok(Vec::new()),
+            "weekdays" => Ok(Self::ALL[..5].to_vec()),
+            "weekends" => Ok(Self::ALL[5..].to_vec()),
+            list => list
+                .split(',')
+                .map(|day| {
+                    Self::ALL
+                        .iter()
+                        .copied()
+                        .find(|d| d.text() == day.trim())
+                        .ok_or_else(|| format!("Unknown day '{}'", day))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    /// Parses `HH:MM` in 24-hour format, or `H:MM AM`/`H:MMpm`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid time '{}', expected HH:MM or H:MM AM/PM", text);
+        let lower = text.trim().to_ascii_lowercase();
+        let (clock, pm) = match (lower.strip_suffix("am"), lower.strip_suffix("pm")) {
+            (Some(rest), _) => (rest.trim_end(), Some(false)),
+            (_, Some(rest)) => (rest.trim_end(), Some(true)),
+            _ => (lower.as_str(), None),
+        };
+
+        let (h, m) = clock.split_once(':').ok_or_else(invalid)?;
+        let mut hour: u8 = h.parse().map_err(|_| invalid())?;
+        let minute: u8 = m.parse().map_err(|_| invalid())?;
+        match pm {
+            Some(_) if hour == 0 || hour > 12 => return Err(invalid()),
+            Some(pm) => hour = hour % 12 + if pm { 12 } else { 0 },
+            None if hour > 23 => return Err(invalid()),
+            None => {}
+        }
+        if minute > 59 {
+            return Err(invalid());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+/// A calendar date in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Days since 1970-01-01 in the proleptic Gregorian calendar.
+    pub fn days(&self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// The inverse of [`Date::days`].
+    pub fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted + 2) / 5 + 1) as u8;
+        let month = if shifted < 10 { shifted + 3 } else { shifted - 9 } as u8;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Self { year: year as i32, month, day }
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        Weekday::ALL[(self.days() + 3).rem_euclid(7) as usize]
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleAction {
+    On(u8),
+    Off(u8),
+    Scene(String),
+}
+
+impl ScheduleAction {
+    /// Parses `on`, `off`, `on:<channel>`, `off:<channel>` or `scene:<name>`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid action '{}', expected on[:<channel>], off[:<channel>] or scene:<name>",
+                text
+            )
+        };
+        let (kind, arg) = text.split_once(':').unwrap_or((text, ""));
+        let channel = || -> Result<u8, String> {
+            match arg {
+                "" => Ok(1),
+                n => n.parse().ok().filter(|&c| c > 0).ok_or_else(invalid),
+            }
+        };
+
+        match kind.to_ascii_lowercase().as_str() {
+            "on" => Ok(ScheduleAction::On(channel()?)),
+            "off" => Ok(ScheduleAction::Off(channel()?)),
+            "scene" if !arg.is_empty() => Ok(ScheduleAction::Scene(arg.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The channel switched directly by this action, if any.
+    pub fn channel(&self) -> Option<u8> {
+        match self {
+            ScheduleAction::On(channel) | ScheduleAction::Off(channel) => Some(*channel),
+            ScheduleAction::Scene(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ScheduleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleAction::On(1) => write!(f, "on"),
+            ScheduleAction::Off(1) => write!(f, "off"),
+            ScheduleAction::On(channel) => write!(f, "on:{}", channel),
+            ScheduleAction::Off(channel) => write!(f, "off:{}", channel),
+            ScheduleAction::Scene(name) => write!(f, "scene:{}", name),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: u32,
+    pub time: TimeOfDay,
+    /// Days the schedule runs on; empty means every day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Runs on this date only, ignoring `days`.
+    #[serde(default)]
+    pub date: Option<Date>,
+    /// IANA name (`Europe/Berlin`) or POSIX rule of the zone `time` and
+    /// `date` are in; the system's local time if `None`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    pub action: ScheduleAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Skipped until this Unix time, e.g. during maintenance.
+    #[serde(default)]
+    pub snoozed_until: Option<u64>,
+    /// The calendar file this was imported from; importing it again
+    /// replaces every schedule it brought in.
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl Schedule {
+    /// Whether the schedule is snoozed at Unix time `now`.
+    pub fn snoozed(&self, now: u64) -> bool {
+        self.snoozed_until.is_some_and(|until| now < until)
+    }
+
+    pub fn days_text(&self) -> String {
+        if let Some(date) = self.date {
+            return date.to_string();
+        }
+        if self.days.is_empty() {
+            return "daily".to_string();
+        }
+        self.days.iter().map(Weekday::text).collect::<Vec<_>>().join(",")
+    }
+
+    fn runs_on(&self, date: Date) -> bool {
+        match self.date {
+            Some(only) => only == date,
+            None => self.days.is_empty() || self.days.contains(&date.weekday()),
+        }
+    }
+
+    /// Whether the schedule switches `channel`, directly or through a scene.
+    pub fn affects(&self, config: &Config, channel: u8) -> bool {
+        match &self.action {
+            ScheduleAction::Scene(name) => config
+                .scene(name)
+                .is_some_and(|scene| scene.steps.iter().any(|step| step.channel == channel)),
+            action => action.channel() == Some(channel),
+        }
+    }
+
+    /// The first time after Unix time `after` the schedule fires, looking a
+    /// week ahead.
+    fn next_run(&self, zone: &TimeZone, after: i64) -> Option<i64> {
+        if !self.enabled {
+            return None;
+        }
+        let today = zone.local(after).date().days();
+        (today..=today + 7)
+            .map(Date::from_days)
+            .filter(|&date| self.runs_on(date))
+            .map(|date| zone.resolve(date, self.time))
+            .find(|&at| at > after && !self.snoozed(at as u64))
+    }
+
+    /// Whether the schedule fires after Unix time `from`, up to and
+    /// including `to`.
+    fn is_due(&self, zone: &TimeZone, from: i64, to: i64) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let (first, last) = (zone.local(from).date().days(), zone.local(to).date().days());
+        (first..=last).map(Date::from_days).filter(|&date| self.runs_on(date)).any(|date| {
+            let at = zone.resolve(date, self.time);
+            from < at && at <= to && !self.snoozed(at as u64)
+        })
+    }
+}
+
+// ============================================================================
+// LOCAL TIME
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub weekday: Weekday,
+}
+
+impl LocalTime {
+    pub fn now() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self::from_unix(secs)
+    }
+
+    pub fn from_unix(secs: u64) -> Self {
+        let tm = local_tm(secs as libc::time_t);
+
+        Self {
+            year: tm.tm_year + 1900,
+            month: (tm.tm_mon + 1) as u8,
+            day: tm.tm_mday as u8,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            second: tm.tm_sec as u8,
+            weekday: Weekday::from_tm(tm.tm_wday),
+        }
+    }
+
+    pub fn date(&self) -> Date {
+        Date { year: self.year, month: self.month, day: self.day }
+    }
+
+    /// Identifies the current minute; the scheduler fires at most once per key.
+    pub fn minute_key(&self) -> (i32, u8, u8, u8, u8) {
+        (self.year, self.month, self.day, self.hour, self.minute)
+    }
+}
+
+#[cfg(unix)]
+fn local_tm(secs: libc::time_t) -> libc::tm {
+    // SAFETY: localtime_r only writes into the provided tm struct
+    unsafe {
+        let mut tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut tm);
+        tm
+    }
+}
+
+#[cfg(windows)]
+fn local_tm(secs: libc::time_t) -> libc::tm {
+    // SAFETY: localtime_s only writes into the provided tm struct
+    unsafe {
+        let mut tm = std::mem::zeroed();
+        libc::localtime_s(&mut tm, &secs);
+        tm
+    }
+}
+
+// ============================================================================
+// SCHEDULER
+// ============================================================================
+
+/// The zone of every schedule that names one.
+fn load_zones(config: &Config) -> Result<HashMap<String, TimeZone>, String> {
+    let mut zones = HashMap::new();
+    for name in config.schedules.iter().filter_map(|s| s.timezone.as_ref()) {
+        if !zones.contains_key(name) {
+            zones.insert(name.clone(), TimeZone::named(name)?);
+        }
+    }
+    Ok(zones)
+}
+
+/// When a schedule next switches `channel`, as a Unix time.
+pub fn next_event(config: &Config, channel: u8) -> Result<Option<u64>, String> {
+    let zones = load_zones(config)?;
+    let now = unix_now() as i64;
+    let next = config.schedules.iter().filter(|s| s.affects(config, channel)).filter_map(|s| {
+        let zone = s.timezone.as_ref().and_then(|name| zones.get(name));
+        s.next_run(zone.unwrap_or(&TimeZone::System), now)
+    });
+    Ok(next.min().map(|at| at as u64))
+}
+
+/// The commands for `schedule`'s action, leaving out channels under a manual
+/// override.
+fn action_commands(
+    config: &Config,
+    schedule: &Schedule,
+    overrides: &Overrides,
+) -> Result<Vec<Command>, String> {
+    let source = Source::Schedule(schedule.id);
+    let admit = |channel: u8, cmd: Command| overrides.admit(channel, cmd, &source);
+    match &schedule.action {
+        ScheduleAction::On(channel) => {
+            Ok(admit(*channel, Command::TurnOn(*channel)).into_iter().collect())
+        }
+        ScheduleAction::Off(channel) => {
+            Ok(admit(*channel, Command::TurnOff(*channel)).into_iter().collect())
+        }
+        ScheduleAction::Scene(name) => {
+            let scene = config.scene(name).ok_or_else(|| format!("Unknown scene '{}'", name))?;
+            let steps: Vec<SceneStep> = scene
+                .steps
+                .iter()
+                .filter(|step| {
+                    let cmd = if step.on {
+                        Command::TurnOn(step.channel)
+                    } else {
+                        Command::TurnOff(step.channel)
+                    };
+                    admit(step.channel, cmd).is_some()
+                })
+                .cloned()
+                .collect();
+            if steps.is_empty() {
+                return Ok(Vec::new());
+            }
+            let scene = Scene { name: scene.name.clone(), steps };
+            Ok(vec![Command::Batch(Batch::from_scene(&scene), None)])
+        }
+    }
+}
+
+/// Fires due schedules into the command channel, reloading the config file
+/// whenever it changes on disk. Schedules are held while the system clock
+/// looks wrong, and skip channels under a manual override.
+pub fn run_scheduler(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+    notifier: &Notifier,
+    overrides: &Overrides,
+) -> Result<(), String> {
+    let mut modified = Config::modified();
+    let mut config = Config::load()?;
+    let mut zones = load_zones(&config)?;
+    // Don't re-fire the current minute after a restart
+    let mut last_check = unix_now() as i64;
+    let mut clock = ClockGuard::new();
+    let mut held = false;
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let current = Config::modified();
+        if current != modified {
+            config = Config::load()?;
+            zones = load_zones(&config)?;
+            modified = current;
+        }
+
+        // Timed overrides that ran out hand their channels back
+        for (channel, resumed) in overrides.expire(unix_now()) {
+            if let Some(resumed) = resumed {
+                let _ = commands.send(resumed);
+            }
+            let channel = config.device.label(channel);
+            notifier.notify("Schedules resumed", format!("Override of {} ended", channel));
+        }
+
+        let now = unix_now() as i64;
+        if now.div_euclid(60) != last_check.div_euclid(60) {
+            let from = last_check.max(now - CATCH_UP_SECS);
+            last_check = now;
+
+            // The network is only consulted when something would fire
+            let active = config.schedules.iter().any(|s| s.enabled);
+            let hold = active.then(|| clock.hold_reason(&config.clock_check)).flatten();
+            match (&hold, held) {
+                (Some(reason), false) => notifier.notify("Schedules held", reason.clone()),
+                (None, true) => notifier.notify("Schedules resumed", "The system clock looks right again"),
+                _ => {}
+            }
+            held = hold.is_some();
+            if held {
+                std::thread::sleep(TICK);
+                continue;
+            }
+
+            let due = config.schedules.iter().filter(|s| {
+                let zone = s.timezone.as_ref().and_then(|name| zones.get(name));
+                s.is_due(zone.unwrap_or(&TimeZone::System), from, now)
+            });
+            for schedule in due {
+                match action_commands(&config, schedule, overrides) {
+                    Ok(cmds) if cmds.is_empty() => {}
+                    Ok(cmds) => {
+                        for cmd in cmds {
+                            let _ = commands.send((cmd, Source::Schedule(schedule.id)));
+                        }
+                        let message =
+                            format!("#{} {} at {}", schedule.id, schedule.action, schedule.time);
+                        push::push(PushKind::Schedule, "Schedule".to_string(), message);
+                    }
+                    Err(e) => eprintln!("Schedule #{}: {}", schedule.id, e),
+                }
+            }
+        }
+
+        std::thread::sleep(TICK);
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BERLIN: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+
+    fn schedule(hour: u8, minute: u8, days: Vec<Weekday>) -> Schedule {
+        Schedule {
+            id: 1,
+            time: TimeOfDay { hour, minute },
+            days,
+            date: None,
+            timezone: Some(BERLIN.to_string()),
+            action: ScheduleAction::On(1),
+            enabled: true,
+            snoozed_until: None,
+            source: None,
+        }
+    }
+
+    /// Unix time of a UTC date and time.
+    fn utc(year: i32, month: u8, day: u8, hour: i64, minute: i64) -> i64 {
+        Date { year, month, day }.days() * 86_400 + hour * 3600 + minute * 60
+    }
+
+    /// Every time the scheduler would fire `schedule` between `from` and
+    /// `to`, checking once a minute like the scheduler does.
+    fn firings(schedule: &Schedule, from: i64, to: i64) -> Vec<i64> {
+        let zone = TimeZone::named(BERLIN).unwrap();
+        (from..to)
+            .step_by(60)
+            .filter(|&minute| schedule.is_due(&zone, minute - 60, minute))
+            .collect()
+    }
+
+    #[test]
+    fn skipped_time_runs_once_shifted_by_the_gap() {
+        // 02:30 doesn't exist on 2026-03-29; it runs at 03:30 CEST
+        let daily = schedule(2, 30, Vec::new());
+        let night = firings(&daily, utc(2026, 3, 28, 12, 0), utc(2026, 3, 29, 12, 0));
+        assert_eq!(night, vec![utc(2026, 3, 29, 1, 30)]);
+        // The days either side are ordinary
+        let before = firings(&daily, utc(2026, 3, 27, 12, 0), utc(2026, 3, 28, 12, 0));
+        assert_eq!(before, vec![utc(2026, 3, 28, 1, 30)]);
+        let after = firings(&daily, utc(2026, 3, 29, 12, 0), utc(2026, 3, 30, 12, 0));
+        assert_eq!(after, vec![utc(2026, 3, 30, 0, 30)]);
+    }
+
+    #[test]
+    fn repeated_time_runs_only_the_first_time() {
+        // 02:30 happens twice on 2026-10-25, at 00:30 and 01:30 UTC
+        let daily = schedule(2, 30, Vec::new());
+        let night = firings(&daily, utc(2026, 10, 24, 12, 0), utc(2026, 10, 25, 12, 0));
+        assert_eq!(night, vec![utc(2026, 10, 25, 0, 30)]);
+        // Times outside the repeated hour are unaffected
+        let early = schedule(1, 59, Vec::new());
+        let night = firings(&early, utc(2026, 10, 24, 12, 0), utc(2026, 10, 25, 12, 0));
+        assert_eq!(night, vec![utc(2026, 10, 24, 23, 59)]);
+    }
+
+    #[test]
+    fn wall_clock_time_holds_across_dst() {
+        // 08:00 on Mondays is 07:00 UTC in winter and 06:00 UTC in summer
+        let mondays = schedule(8, 0, vec![Weekday::Mon]);
+        let fortnight = firings(&mondays, utc(2026, 3, 22, 0, 0), utc(2026, 4, 5, 0, 0));
+        assert_eq!(fortnight, vec![utc(2026, 3, 23, 7, 0), utc(2026, 3, 30, 6, 0)]);
+        let fortnight = firings(&mondays, utc(2026, 10, 18, 0, 0), utc(2026, 11, 1, 0, 0));
+        assert_eq!(fortnight, vec![utc(2026, 10, 19, 6, 0), utc(2026, 10, 26, 7, 0)]);
+    }
+
+    #[test]
+    fn dates_are_in_the_schedule_zone() {
+        // Midnight in Berlin is still the previous day in UTC
+        let mut once = schedule(0, 30, Vec::new());
+        once.date = Some(Date { year: 2026, month: 7, day: 1 });
+        let week = firings(&once, utc(2026, 6, 28, 0, 0), utc(2026, 7, 5, 0, 0));
+        assert_eq!(week, vec![utc(2026, 6, 30, 22, 30)]);
+    }
+
+    #[test]
+    fn late_checks_fire_once() {
+        let daily = schedule(8, 0, Vec::new());
+        let zone = TimeZone::named(BERLIN).unwrap();
+        let at = utc(2026, 1, 15, 7, 0);
+        assert!(daily.is_due(&zone, at - 1, at + 30));
+        assert!(!daily.is_due(&zone, at, at + 60));
+        assert!(!daily.is_due(&zone, at - 120, at - 60));
+    }
+}
@@ -0,0 +1,225 @@
+//! Ordered power sequences.
+//!
+//! A sequence switches channels on one after another with a delay after each
+//! step (e.g. mixer, wait 2 s, amplifiers, wait 5 s, speakers). Shutdown runs
+//! the same steps in reverse, switching off, so loads come down in the
+//! opposite order to how they came up.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::audit::Source;
+use crate::batch::{Batch, BatchStep};
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::i18n::{tr, tr_args};
+use crate::Command;
+
+// ============================================================================
+// SEQUENCE MODEL
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SequenceStep {
+    pub channel: u8,
+    /// Pause after switching this channel before the next step.
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    pub steps: Vec<SequenceStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Startup,
+    Shutdown,
+}
+
+impl Sequence {
+    /// The commands to send, each paired with the delay that follows it.
+    pub fn plan(&self, direction: Direction) -> Vec<(Command, Duration)> {
+        match direction {
+            Direction::Startup => self
+                .steps
+                .iter()
+                .map(|s| (Command::TurnOn(s.channel), Duration::from_millis(s.delay_ms)))
+                .collect(),
+            // Mirror the startup timing: the gap after switching step i off
+            // is the gap that preceded it on the way up
+            Direction::Shutdown => (0..self.steps.len())
+                .rev()
+                .map(|i| {
+                    let delay = i
+                        .checked_sub(1)
+                        .map_or(0, |prev| self.steps[prev].delay_ms);
+                    (Command::TurnOff(self.steps[i].channel), Duration::from_millis(delay))
+                })
+                .collect(),
+        }
+    }
+}
+
+// ============================================================================
+// RUNNER
+// ============================================================================
+
+/// Feeds a sequence into the command channel on a background thread,
+/// abandoning it if the emergency stop fires.
+pub fn spawn(sequence: &Sequence, direction: Direction, stop: &EmergencyStop, source: Source) {
+    let plan = sequence.plan(direction);
+    let guard = stop.guard(source);
+    std::thread::spawn(move || {
+        for (cmd, delay) in plan {
+            if !guard.send(cmd) {
+                return;
+            }
+            std::thread::sleep(delay);
+        }
+    });
+}
+
+/// Switches `channels` on together, then off again after `duration`. The
+/// pulse is queued as one batch, so it is never coalesced with other
+/// switches and an emergency stop cuts its wait short.
+pub fn pulse(channels: &[u8], duration: Duration, stop: &EmergencyStop, source: Source) {
+    let on = channels.iter().map(|&channel| BatchStep::On(channel));
+    let off = channels.iter().map(|&channel| BatchStep::Off(channel));
+    let wait = BatchStep::Wait(duration.as_millis() as u64);
+    let steps = on.chain(Some(wait)).chain(off).collect();
+    stop.guard(source).send(Command::Batch(Batch { steps, start_by: None }, None));
+}
+
+// ============================================================================
+// EDITOR
+// ============================================================================
+
+/// GUI editor for the sequences stored in the config file.
+pub struct SequenceEditor {
+    config: Config,
+    selected: usize,
+    status: Option<String>,
+}
+
+impl SequenceEditor {
+    pub fn new() -> Self {
+        let (config, status) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        Self { config, selected: 0, status }
+    }
+
+    /// Draws the editor window; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context, stop: &EmergencyStop) -> bool {
+        let mut open = true;
+        egui::Window::new(tr("sequences-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| self.contents(ui, stop));
+        open
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, stop: &EmergencyStop) {
+        let channels = self.config.device.channels;
+
+        ui.horizontal(|ui| {
+            let selected_name = self
+                .config
+                .sequences
+                .get(self.selected)
+                .map_or(tr("sequences-none"), |s| s.name.clone());
+            egui::ComboBox::from_id_source("sequence_select")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for (i, sequence) in self.config.sequences.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected, i, &sequence.name);
+                    }
+                });
+
+            if ui.button(tr("sequences-new")).clicked() {
+                let number = self.config.sequences.len() + 1;
+                self.config.sequences.push(Sequence {
+                    name: tr_args("sequences-default-name", &[("number", &number)]),
+                    steps: vec![SequenceStep { channel: 1, delay_ms: 1000 }],
+                });
+                self.selected = self.config.sequences.len() - 1;
+            }
+            let delete = ui.button(tr("sequences-delete")).clicked();
+            if delete && self.selected < self.config.sequences.len() {
+                self.config.sequences.remove(self.selected);
+                self.selected = self.selected.saturating_sub(1);
+            }
+        });
+
+        let Some(sequence) = self.config.sequences.get_mut(self.selected) else {
+            ui.label(tr("sequences-empty"));
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(tr("sequences-name"));
+            ui.text_edit_singleline(&mut sequence.name);
+        });
+        ui.separator();
+
+        let mut remove = None;
+        let mut swap = None;
+        let count = sequence.steps.len();
+        for (i, step) in sequence.steps.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}.", i + 1));
+                ui.add(egui::DragValue::new(&mut step.channel).range(1..=channels).prefix("CH "));
+                ui.label(tr("sequences-then-wait"));
+                ui.add(egui::DragValue::new(&mut step.delay_ms).range(0..=600_000).speed(50).suffix(" ms"));
+                if ui.add_enabled(i > 0, egui::Button::new("⏶").small()).clicked() {
+                    swap = Some(i - 1);
+                }
+                if ui.add_enabled(i + 1 < count, egui::Button::new("⏷").small()).clicked() {
+                    swap = Some(i);
+                }
+                if ui.small_button("✕").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = swap {
+            sequence.steps.swap(i, i + 1);
+        }
+        if let Some(i) = remove {
+            sequence.steps.remove(i);
+        }
+        if ui.button(tr("sequences-add-step")).clicked() {
+            let channel = sequence.steps.last().map_or(1, |s| (s.channel % channels) + 1);
+            sequence.steps.push(SequenceStep { channel, delay_ms: 1000 });
+        }
+
+        ui.separator();
+        let mut save = false;
+        ui.horizontal(|ui| {
+            if ui.button(tr("sequences-start")).clicked() {
+                spawn(sequence, Direction::Startup, stop, Source::Sequence(sequence.name.clone()));
+            }
+            if ui.button(tr("sequences-shutdown")).clicked() {
+                spawn(sequence, Direction::Shutdown, stop, Source::Sequence(sequence.name.clone()));
+            }
+            save = ui.button(tr("sequences-save")).clicked();
+        });
+        if save {
+            self.status = Some(match self.config.save() {
+                Ok(()) => tr("sequences-saved"),
+                Err(e) => e,
+            });
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
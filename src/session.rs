@@ -0,0 +1,148 @@
+//! Desktop session lock triggers.
+//!
+//! Switches channels when the desktop session is locked or unlocked, e.g.
+//! bench lights off on lock and back on at unlock. The lock state comes from
+//! logind's `LockedHint` on Linux and from the input desktop on Windows
+//! (it can't be opened while the lock screen is up).
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::config::Config;
+use crate::supervisor::SubsystemContext;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// TRIGGERS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionAction {
+    #[default]
+    Nothing,
+    On,
+    Off,
+}
+
+impl SessionAction {
+    pub const ALL: [SessionAction; 3] = [SessionAction::Nothing, SessionAction::On, SessionAction::Off];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            SessionAction::Nothing => "-",
+            SessionAction::On => "ON",
+            SessionAction::Off => "OFF",
+        }
+    }
+
+    pub fn command(&self, channel: u8) -> Option<Command> {
+        match self {
+            SessionAction::Nothing => None,
+            SessionAction::On => Some(Command::TurnOn(channel)),
+            SessionAction::Off => Some(Command::TurnOff(channel)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionTrigger {
+    pub channel: u8,
+    pub on_lock: SessionAction,
+    pub on_unlock: SessionAction,
+}
+
+// ============================================================================
+// PLATFORM BACKENDS
+// ============================================================================
+
+#[cfg(target_os = "linux")]
+fn session_locked() -> Result<bool, String> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+    let output = std::process::Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint", "--value"])
+        .output()
+        .map_err(|e| format!("Failed to run loginctl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "loginctl: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "yes")
+}
+
+#[cfg(windows)]
+fn session_locked() -> Result<bool, String> {
+    use std::ffi::c_void;
+
+    const DESKTOP_SWITCHDESKTOP: u32 = 0x0100;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn OpenInputDesktop(flags: u32, inherit: i32, access: u32) -> *mut c_void;
+        fn SwitchDesktop(desktop: *mut c_void) -> i32;
+        fn CloseDesktop(desktop: *mut c_void) -> i32;
+    }
+
+    // SAFETY: the handle is checked for null and closed before returning
+    unsafe {
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+        if desktop.is_null() {
+            return Ok(true);
+        }
+        let locked = SwitchDesktop(desktop) == 0;
+        CloseDesktop(desktop);
+        Ok(locked)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn session_locked() -> Result<bool, String> {
+    Err("Session lock detection is not supported on this platform".to_string())
+}
+
+// ============================================================================
+// WATCHER
+// ============================================================================
+
+/// Watches the session lock state and queues the configured actions on
+/// every lock and unlock.
+pub fn run_session_watcher(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+) -> Result<(), String> {
+    // Only changes trigger actions, never the state found at startup
+    let mut locked = None;
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let triggers = Config::load()?.session;
+        if triggers.is_empty() {
+            locked = None;
+        } else {
+            let now = session_locked()?;
+            if locked.is_some_and(|before| before != now) {
+                for trigger in &triggers {
+                    let action = if now { trigger.on_lock } else { trigger.on_unlock };
+                    if let Some(cmd) = action.command(trigger.channel) {
+                        let _ = commands.send((cmd, Source::Session));
+                    }
+                }
+            }
+            locked = Some(now);
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,632 @@
+//! Settings window.
+//!
+//! Edits a copy of the config file and writes it back on Save; the running
+//! subsystems pick the change up from disk.
+
+use eframe::egui;
+
+use crate::autostart::{self, Autostart};
+use crate::config::{Config, ExitPolicy};
+use crate::confirm::ConfirmOff;
+use crate::cooldown::CooldownMode;
+use crate::debounce::DebounceMode;
+use crate::hotkeys::{HotkeyAction, HotkeyBinding, Shortcut};
+use crate::i18n::{self, tr};
+use crate::locale::ClockFormat;
+use crate::ports::{self, Candidate, LineFlowControl, LineParity, UsbId, BAUD_RATES};
+use crate::session::{SessionAction, SessionTrigger};
+use crate::sound::{self, Cue};
+use crate::theme::{ThemeConfig, ThemeMode};
+use crate::window;
+
+// ============================================================================
+// HOTKEY ACTIONS
+// ============================================================================
+
+const ACTION_NAMES: [&str; 4] = ["Toggle", "On", "Off", "All off"];
+
+fn action_index(action: HotkeyAction) -> usize {
+    match action {
+        HotkeyAction::Toggle(_) => 0,
+        HotkeyAction::On(_) => 1,
+        HotkeyAction::Off(_) => 2,
+        HotkeyAction::AllOff => 3,
+    }
+}
+
+fn action_from_index(index: usize, channel: u8) -> HotkeyAction {
+    match index {
+        0 => HotkeyAction::Toggle(channel),
+        1 => HotkeyAction::On(channel),
+        2 => HotkeyAction::Off(channel),
+        _ => HotkeyAction::AllOff,
+    }
+}
+
+// ============================================================================
+// WINDOW
+// ============================================================================
+
+pub struct SettingsWindow {
+    config: Config,
+    /// Serial ports found at the last scan.
+    ports: Vec<Candidate>,
+    /// Detection list additions, comma-separated, parsed on Save.
+    usb_ids: String,
+    keywords: String,
+    /// Hotkey row waiting for a key press.
+    capturing: Option<usize>,
+    /// Login registration as edited, and as found on the system.
+    autostart: Autostart,
+    registered: Autostart,
+    /// Languages with a translation, as `(tag, name)`.
+    languages: Vec<(String, String)>,
+    status: Option<String>,
+}
+
+impl SettingsWindow {
+    pub fn new() -> Self {
+        let (config, status) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        let detection = &config.device.detection;
+        let usb_ids: Vec<String> = detection.usb_ids.iter().map(UsbId::to_string).collect();
+        let keywords = detection.keywords.join(", ");
+        let registered = autostart::current();
+        let mut window = Self {
+            config,
+            ports: Vec::new(),
+            usb_ids: usb_ids.join(", "),
+            keywords,
+            capturing: None,
+            autostart: registered,
+            registered,
+            languages: i18n::languages(),
+            status,
+        };
+        window.rescan();
+        window
+    }
+
+    /// Lists the serial ports, classified as if the board weren't bound so
+    /// every port shows what detection would make of it.
+    fn rescan(&mut self) {
+        let mut device = self.config.device.clone();
+        device.bind = Default::default();
+        match ports::candidates(&device) {
+            Ok(candidates) => self.ports = candidates,
+            Err(e) => self.status = Some(e),
+        }
+    }
+
+    /// Applies the detection list text fields to the config.
+    fn apply_detection(&mut self) -> Result<(), String> {
+        let split = |text: &str| {
+            text.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let detection = &mut self.config.device.detection;
+        detection.usb_ids = split(&self.usb_ids)
+            .iter()
+            .map(|id| UsbId::parse(id))
+            .collect::<Result<_, _>>()?;
+        detection.keywords = split(&self.keywords);
+        Ok(())
+    }
+
+    /// Registers or unregisters the app for login if that was changed.
+    fn apply_autostart(&mut self) -> Result<(), String> {
+        if self.autostart != self.registered {
+            autostart::apply(self.autostart)?;
+            self.registered = self.autostart;
+        }
+        Ok(())
+    }
+
+    /// Draws the window; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        if let Some(row) = self.capturing {
+            self.capture(ctx, row);
+        }
+
+        let mut open = true;
+        egui::Window::new(tr("settings"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| self.contents(ui));
+            });
+        open
+    }
+
+    /// Records the next key press into the hotkey row being captured.
+    /// Escape without modifiers cancels.
+    fn capture(&mut self, ctx: &egui::Context, row: usize) {
+        let pressed = ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    repeat: false,
+                    modifiers,
+                    ..
+                } => Some((*key, *modifiers)),
+                _ => None,
+            })
+        });
+        let Some((key, modifiers)) = pressed else {
+            return;
+        };
+
+        self.capturing = None;
+        if key == egui::Key::Escape && modifiers.is_none() {
+            return;
+        }
+        match Shortcut::from_egui(key, modifiers) {
+            Ok(shortcut) => {
+                if let Some(binding) = self.config.hotkeys.get_mut(row) {
+                    binding.shortcut = shortcut.to_string();
+                }
+                self.status = None;
+            }
+            Err(e) => self.status = Some(e),
+        }
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui) {
+        let channels = self.config.device.channels;
+
+        ui.heading(tr("settings-device"));
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-port"));
+            let bind = &mut self.config.device.bind;
+            let selected = if bind.is_set() {
+                bind.describe()
+            } else {
+                tr("settings-detect")
+            };
+            egui::ComboBox::from_id_source("device_port")
+                .selected_text(selected)
+                .width(200.0)
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(!bind.is_set(), tr("settings-detect")).clicked() {
+                        *bind = Default::default();
+                    }
+                    for candidate in &self.ports {
+                        let binding = ports::binding_for(&candidate.info);
+                        let mut text = candidate.describe();
+                        if let Some(matched) = candidate.matched {
+                            text.push_str(&format!("  ({})", matched.text()));
+                        }
+                        if ui.selectable_label(*bind == binding, text).clicked() {
+                            *bind = binding;
+                        }
+                    }
+                });
+            if ui.small_button(tr("settings-rescan")).clicked() {
+                self.rescan();
+            }
+        });
+        ui.label(tr("settings-detection"));
+        egui::Grid::new("detection").show(ui, |ui| {
+            ui.label(tr("settings-usb-ids"));
+            ui.add(egui::TextEdit::singleline(&mut self.usb_ids).hint_text("1a86:7523"));
+            ui.end_row();
+            ui.label(tr("settings-keywords"));
+            ui.add(egui::TextEdit::singleline(&mut self.keywords).hint_text("RELAY"));
+            ui.end_row();
+        });
+        ui.checkbox(
+            &mut self.config.device.write_only,
+            tr("settings-write-only"),
+        );
+        ui.checkbox(
+            &mut self.config.device.strict,
+            tr("settings-strict"),
+        );
+        ui.label(tr("settings-serial"));
+        egui::Grid::new("serial_line").show(ui, |ui| {
+            let serial = &mut self.config.device.serial;
+            ui.label(tr("settings-baud-rate"));
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut serial.baud_rate).range(1..=4_000_000));
+                egui::ComboBox::from_id_source("baud_rate")
+                    .selected_text(tr("settings-common"))
+                    .show_ui(ui, |ui| {
+                        for rate in BAUD_RATES {
+                            ui.selectable_value(&mut serial.baud_rate, rate, rate.to_string());
+                        }
+                    });
+            });
+            ui.end_row();
+            ui.label(tr("settings-data-bits"));
+            ui.add(egui::DragValue::new(&mut serial.data_bits).range(5..=8));
+            ui.end_row();
+            ui.label(tr("settings-parity"));
+            egui::ComboBox::from_id_source("parity")
+                .selected_text(serial.parity.text())
+                .show_ui(ui, |ui| {
+                    for parity in LineParity::ALL {
+                        ui.selectable_value(&mut serial.parity, parity, parity.text());
+                    }
+                });
+            ui.end_row();
+            ui.label(tr("settings-stop-bits"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut serial.stop_bits, 1, "1");
+                ui.radio_value(&mut serial.stop_bits, 2, "2");
+            });
+            ui.end_row();
+            ui.label(tr("settings-flow-control"));
+            egui::ComboBox::from_id_source("flow_control")
+                .selected_text(serial.flow_control.text())
+                .show_ui(ui, |ui| {
+                    for flow in LineFlowControl::ALL {
+                        ui.selectable_value(&mut serial.flow_control, flow, flow.text());
+                    }
+                });
+            ui.end_row();
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-buttons"));
+        ui.label(tr("settings-debounce"));
+        egui::Grid::new("debounce").show(ui, |ui| {
+            for channel in 1..=channels {
+                let mut settings = self.config.device.channel(channel);
+                ui.label(self.config.device.display_name(channel));
+                ui.add(
+                    egui::DragValue::new(&mut settings.debounce_ms)
+                        .range(0..=10_000)
+                        .suffix(" ms"),
+                );
+                egui::ComboBox::from_id_source(("debounce_mode", channel))
+                    .selected_text(settings.debounce.text())
+                    .show_ui(ui, |ui| {
+                        for mode in DebounceMode::ALL {
+                            ui.selectable_value(&mut settings.debounce, mode, mode.text());
+                        }
+                    });
+                ui.end_row();
+                // Only channels that were touched get an entry of their own
+                if settings != self.config.device.channel(channel) {
+                    *self.config.device.channel_mut(channel) = settings;
+                }
+            }
+        });
+        ui.label(tr("settings-confirm-off"));
+        egui::Grid::new("confirm_off").show(ui, |ui| {
+            for channel in 1..=channels {
+                let mut settings = self.config.device.channel(channel);
+                ui.label(self.config.device.display_name(channel));
+                egui::ComboBox::from_id_source(("confirm_off", channel))
+                    .selected_text(settings.confirm_off.text())
+                    .show_ui(ui, |ui| {
+                        for mode in ConfirmOff::ALL {
+                            ui.selectable_value(&mut settings.confirm_off, mode, mode.text());
+                        }
+                    });
+                ui.end_row();
+                if settings != self.config.device.channel(channel) {
+                    *self.config.device.channel_mut(channel) = settings;
+                }
+            }
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-safety"));
+        ui.label(tr("settings-max-on"));
+        ui.label(tr("settings-cooldown"));
+        ui.label(tr("settings-switch-limit"));
+        egui::Grid::new("max_on").show(ui, |ui| {
+            ui.label("");
+            ui.weak(tr("settings-max-on-column"));
+            ui.weak(tr("settings-cooldown-column"));
+            ui.weak(tr("settings-switch-limit-column"));
+            ui.end_row();
+            for channel in 1..=channels {
+                let mut settings = self.config.device.channel(channel);
+                ui.label(self.config.device.display_name(channel));
+                let mut minutes = settings.max_on_secs / 60;
+                let limit = egui::DragValue::new(&mut minutes)
+                    .range(0..=7 * 24 * 60)
+                    .suffix(" min");
+                if ui.add(limit).changed() {
+                    settings.max_on_secs = minutes * 60;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut settings.cooldown_secs)
+                            .range(0..=3600)
+                            .suffix(" s"),
+                    );
+                    egui::ComboBox::from_id_source(("cooldown_mode", channel))
+                        .selected_text(settings.cooldown.text())
+                        .show_ui(ui, |ui| {
+                            for mode in CooldownMode::ALL {
+                                ui.selectable_value(&mut settings.cooldown, mode, mode.text());
+                            }
+                        });
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.max_switches_per_day)
+                        .range(0..=10_000)
+                        .suffix("×"),
+                );
+                ui.end_row();
+                if settings != self.config.device.channel(channel) {
+                    *self.config.device.channel_mut(channel) = settings;
+                }
+            }
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-lock"));
+        egui::Grid::new("lock").show(ui, |ui| {
+            let lock = &mut self.config.lock;
+            ui.label(tr("settings-lock-pin"));
+            ui.add(egui::TextEdit::singleline(&mut lock.pin).password(true).desired_width(120.0));
+            ui.end_row();
+            ui.label(tr("settings-lock-idle"));
+            let mut minutes = lock.idle_secs / 60;
+            let idle = egui::DragValue::new(&mut minutes).range(0..=24 * 60).suffix(" min");
+            if ui.add(idle).changed() {
+                lock.idle_secs = minutes * 60;
+            }
+            ui.end_row();
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-display"));
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-clock"));
+            let clock = &mut self.config.display.clock;
+            egui::ComboBox::from_id_source("clock_format")
+                .selected_text(clock.text())
+                .show_ui(ui, |ui| {
+                    for format in ClockFormat::ALL {
+                        ui.selectable_value(clock, format, format.text());
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-language"));
+            let language = &mut self.config.display.language;
+            let selected = match language {
+                Some(tag) => self
+                    .languages
+                    .iter()
+                    .find(|(t, _)| t == tag)
+                    .map_or_else(|| tag.clone(), |(_, name)| name.clone()),
+                None => tr("settings-system-default"),
+            };
+            egui::ComboBox::from_id_source("language")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(language, None, tr("settings-system-default"));
+                    for (tag, name) in &self.languages {
+                        ui.selectable_value(language, Some(tag.clone()), name);
+                    }
+                });
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-theme"));
+        let theme = &mut self.config.theme;
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-theme"));
+            egui::ComboBox::from_id_source("theme_mode")
+                .selected_text(theme.mode.text())
+                .show_ui(ui, |ui| {
+                    for mode in ThemeMode::ALL {
+                        ui.selectable_value(&mut theme.mode, mode, mode.text());
+                    }
+                });
+        });
+        egui::Grid::new("theme_colors").num_columns(2).show(ui, |ui| {
+            for (name, color) in [
+                (tr("state-on"), &mut theme.on),
+                (tr("state-off"), &mut theme.off),
+                (tr("settings-error"), &mut theme.error),
+            ] {
+                ui.label(name);
+                egui::color_picker::color_edit_button_srgb(ui, color);
+                ui.end_row();
+            }
+        });
+        ui.checkbox(&mut theme.shapes, tr("settings-shapes"))
+            .on_hover_text(tr("settings-shapes-hint"));
+        if ui.small_button(tr("settings-reset-colors")).clicked() {
+            *theme = ThemeConfig {
+                mode: theme.mode,
+                shapes: theme.shapes,
+                ..ThemeConfig::default()
+            };
+        }
+        ui.separator();
+
+        ui.heading(tr("settings-login"));
+        ui.checkbox(&mut self.autostart.enabled, tr("settings-login-enabled"));
+        ui.add_enabled(
+            self.autostart.enabled,
+            egui::Checkbox::new(&mut self.autostart.minimized, tr("settings-start-minimized")),
+        );
+        ui.separator();
+
+        ui.heading(tr("settings-window"));
+        let window = &mut self.config.window;
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-scale"));
+            let range = window::MIN_SCALE..=window::MAX_SCALE;
+            ui.add(egui::Slider::new(&mut window.scale, range).step_by(10.0).suffix("%"))
+                .on_hover_text(tr("settings-scale-hint"));
+        });
+        ui.checkbox(&mut window.start_minimized, tr("settings-start-minimized"));
+        ui.checkbox(&mut window.mini, tr("settings-start-mini"));
+        ui.checkbox(&mut window.kiosk, tr("settings-start-kiosk"));
+        ui.checkbox(&mut window.close_to_background, tr("settings-background"))
+            .on_hover_text(tr("settings-background-hint"));
+        if cfg!(target_os = "macos") {
+            ui.checkbox(&mut window.menu_bar, tr("settings-menu-bar"))
+                .on_hover_text(tr("settings-menu-bar-hint"));
+        }
+        ui.separator();
+
+        ui.heading(tr("settings-exit"));
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-exit-policy"));
+            let exit = &mut self.config.exit;
+            egui::ComboBox::from_id_source("exit_policy")
+                .selected_text(exit.text())
+                .show_ui(ui, |ui| {
+                    for policy in ExitPolicy::ALL {
+                        ui.selectable_value(exit, policy, policy.text());
+                    }
+                });
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-sounds"));
+        ui.checkbox(&mut self.config.sound.enabled, tr("settings-sound-enabled"));
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-preview"));
+            for cue in Cue::ALL {
+                if ui.small_button(cue.name()).clicked() {
+                    sound::play_now(&self.config.sound, cue);
+                }
+            }
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-voice"));
+        let voice = &mut self.config.voice;
+        ui.checkbox(&mut voice.enabled, tr("settings-voice-enabled"))
+            .on_hover_text(tr("settings-voice-hint"));
+        ui.horizontal(|ui| {
+            ui.label(tr("settings-voice-model"));
+            ui.add_enabled(
+                voice.enabled,
+                egui::TextEdit::singleline(&mut voice.model)
+                    .hint_text("vosk-model-small-en-us-0.15"),
+            );
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-lock-screen"));
+        ui.label(tr("settings-lock-help"));
+        egui::Grid::new("session_triggers").show(ui, |ui| {
+            ui.label("");
+            ui.label(tr("settings-on-lock"));
+            ui.label(tr("settings-on-unlock"));
+            ui.end_row();
+
+            for channel in 1..=channels {
+                let mut trigger = self
+                    .config
+                    .session
+                    .iter()
+                    .find(|t| t.channel == channel)
+                    .cloned()
+                    .unwrap_or(SessionTrigger { channel, ..Default::default() });
+                let before = trigger.clone();
+
+                ui.label(self.config.device.label(channel));
+                for (kind, action) in [("lock", &mut trigger.on_lock), ("unlock", &mut trigger.on_unlock)] {
+                    egui::ComboBox::from_id_source(("session", kind, channel))
+                        .width(50.0)
+                        .selected_text(action.text())
+                        .show_ui(ui, |ui| {
+                            for option in SessionAction::ALL {
+                                ui.selectable_value(action, option, option.text());
+                            }
+                        });
+                }
+                ui.end_row();
+
+                if trigger != before {
+                    self.config.session.retain(|t| t.channel != channel);
+                    if trigger.on_lock != SessionAction::Nothing || trigger.on_unlock != SessionAction::Nothing {
+                        self.config.session.push(trigger);
+                        self.config.session.sort_by_key(|t| t.channel);
+                    }
+                }
+            }
+        });
+        ui.separator();
+
+        ui.heading(tr("settings-hotkeys"));
+        ui.label(tr("settings-hotkeys-help"));
+        ui.add_space(4.0);
+
+        let mut remove = None;
+        for (i, binding) in self.config.hotkeys.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let capturing = self.capturing == Some(i);
+                let text = if capturing {
+                    tr("settings-press-keys")
+                } else if binding.shortcut.is_empty() {
+                    tr("settings-click-to-set")
+                } else {
+                    binding.shortcut.clone()
+                };
+                if ui
+                    .add(egui::Button::new(text).selected(capturing).min_size(egui::vec2(120.0, 0.0)))
+                    .clicked()
+                {
+                    self.capturing = if capturing { None } else { Some(i) };
+                }
+
+                let mut channel = binding.action.channel().unwrap_or(1);
+                let mut index = action_index(binding.action);
+                egui::ComboBox::from_id_source(("hotkey_action", i))
+                    .width(70.0)
+                    .show_index(ui, &mut index, ACTION_NAMES.len(), |n| ACTION_NAMES[n]);
+
+                if index != action_index(HotkeyAction::AllOff) {
+                    ui.add(egui::DragValue::new(&mut channel).range(1..=channels).prefix("CH "));
+                }
+                binding.action = action_from_index(index, channel);
+
+                let response = ui.small_button("✕").on_hover_text(tr("settings-remove-hotkey"));
+                if response.clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.config.hotkeys.remove(i);
+            self.capturing = None;
+        }
+
+        if ui.button(tr("settings-add-hotkey")).clicked() {
+            self.config.hotkeys.push(HotkeyBinding {
+                shortcut: String::new(),
+                action: HotkeyAction::Toggle(1),
+            });
+            self.capturing = Some(self.config.hotkeys.len() - 1);
+        }
+
+        ui.separator();
+        if ui.button(tr("settings-save")).clicked() {
+            self.config.hotkeys.retain(|b| !b.shortcut.is_empty());
+            let saved = self
+                .apply_detection()
+                .and_then(|()| self.config.save())
+                .and_then(|()| self.apply_autostart());
+            self.status = Some(match saved {
+                Ok(()) => tr("settings-saved"),
+                Err(e) => e,
+            });
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
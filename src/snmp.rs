@@ -0,0 +1,721 @@
+//! SNMP agent.
+//!
+//! With `snmp.enabled`, the app answers SNMP v1 and v2c on UDP, so network
+//! monitoring tools can read the channels like the outlets of a PDU and,
+//! given the write community, switch them. Every ON/OFF change can also be
+//! sent as a v2c trap. The objects live under `snmp.base_oid`, by default in
+//! Net-SNMP's experimental subtree:
+//!
+//! | OID                | Object                                     |
+//! |--------------------|--------------------------------------------|
+//! | `<base>.1.0`       | Number of channels                         |
+//! | `<base>.2.1.1.<n>` | Channel index                              |
+//! | `<base>.2.1.2.<n>` | Channel label                              |
+//! | `<base>.2.1.3.<n>` | on(1), off(2), error(3), unknown(4); set 1 |
+//! |                    | or 2 to switch                             |
+//! | `<base>.3.1`       | Change trap, with the state and the label  |
+//!
+//! `sysDescr`, `sysObjectID` and `sysUpTime` are answered too, for discovery.
+
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use crate::audit::Source;
+use crate::config::{Config, DeviceConfig};
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// How often the agent looks for changes to send as traps.
+const TICK: Duration = Duration::from_millis(250);
+const TRAP_PORT: u16 = 162;
+
+const SYS_DESCR: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 1, 0];
+const SYS_OBJECT_ID: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 2, 0];
+const SYS_UP_TIME: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 3, 0];
+const SNMP_TRAP_OID: [u32; 11] = [1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0];
+
+// PDU types
+const GET: u8 = 0xa0;
+const GET_NEXT: u8 = 0xa1;
+const RESPONSE: u8 = 0xa2;
+const SET: u8 = 0xa3;
+const GET_BULK: u8 = 0xa5;
+const TRAP_V2: u8 = 0xa7;
+
+// Error statuses
+const NO_SUCH_NAME: i64 = 2;
+const BAD_VALUE: i64 = 3;
+const GEN_ERR: i64 = 5;
+const WRONG_VALUE: i64 = 10;
+const NOT_WRITABLE: i64 = 17;
+
+const V1: i64 = 0;
+const V2C: i64 = 1;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnmpConfig {
+    pub enabled: bool,
+    /// UDP listen address; port 161 needs privileges.
+    pub bind: String,
+    /// Community for reading.
+    pub community: String,
+    /// Community for switching; read-only when unset.
+    pub write_community: Option<String>,
+    /// Trap receivers, `host` or `host:port`.
+    pub traps: Vec<String>,
+    /// Base of the agent's objects, dotted.
+    pub base_oid: String,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "0.0.0.0:161".to_string(),
+            community: "public".to_string(),
+            write_community: None,
+            traps: Vec::new(),
+            base_oid: "1.3.6.1.4.1.8072.9999.9999.1".to_string(),
+        }
+    }
+}
+
+fn parse_oid(text: &str) -> Result<Vec<u32>, String> {
+    let arcs = text.trim_start_matches('.').split('.');
+    let oid: Option<Vec<u32>> = arcs.map(|n| n.parse().ok()).collect();
+    match oid {
+        Some(oid) if oid.len() >= 2 => Ok(oid),
+        _ => Err(format!("Invalid SNMP base OID '{}'", text)),
+    }
+}
+
+// ============================================================================
+// BER
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Integer(i64),
+    Text(Vec<u8>),
+    Null,
+    Oid(Vec<u32>),
+    TimeTicks(u32),
+    NoSuchObject,
+    EndOfMibView,
+}
+
+impl Value {
+    fn is_exception(&self) -> bool {
+        matches!(self, Value::NoSuchObject | Value::EndOfMibView)
+    }
+}
+
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => out.push(len as u8),
+        len @ 0x80..=0xff => out.extend([0x81, len as u8]),
+        len => out.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn encode_integer(tag: u8, value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    // Drop leading bytes that only repeat the sign
+    let mut start = 0;
+    while start < 7 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    encode(tag, &bytes[start..])
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut content = Vec::new();
+    let first = oid.first().copied().unwrap_or(0) * 40 + oid.get(1).copied().unwrap_or(0);
+    for &arc in std::iter::once(&first).chain(oid.iter().skip(2)) {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        content.extend(chunk.iter().rev());
+    }
+    encode(0x06, &content)
+}
+
+fn encode_value(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Integer(n) => encode_integer(0x02, *n),
+        Value::Text(bytes) => encode(0x04, bytes),
+        Value::Null => encode(0x05, &[]),
+        Value::Oid(oid) => encode_oid(oid),
+        Value::TimeTicks(ticks) => encode_integer(0x43, *ticks as i64),
+        Value::NoSuchObject => encode(0x80, &[]),
+        Value::EndOfMibView => encode(0x82, &[]),
+    }
+}
+
+/// Reads tag-length-value items from a buffer.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        let (&tag, rest) = self.data.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (len, rest) = match first {
+            0..=0x7f => (first as usize, rest),
+            0x81..=0x84 => {
+                let count = (first & 0x7f) as usize;
+                let bytes = rest.get(..count)?;
+                let len = bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize);
+                (len, &rest[count..])
+            }
+            _ => return None,
+        };
+        let content = rest.get(..len)?;
+        self.data = &rest[len..];
+        Some((tag, content))
+    }
+
+    fn integer(&mut self) -> Option<i64> {
+        match self.next()? {
+            (0x02, bytes) if !bytes.is_empty() && bytes.len() <= 8 => Some(decode_integer(bytes)),
+            _ => None,
+        }
+    }
+}
+
+fn decode_integer(bytes: &[u8]) -> i64 {
+    let sign = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+    bytes.iter().fold(sign, |n, &b| (n << 8) | b as i64)
+}
+
+fn decode_oid(bytes: &[u8]) -> Option<Vec<u32>> {
+    // The last arc can't be cut off in the middle
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        return None;
+    }
+    let mut arcs = Vec::new();
+    let mut arc: u32 = 0;
+    for &b in bytes {
+        arc = arc.checked_mul(128)? | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    let first = *arcs.first()?;
+    let (a, b) = if first < 80 { (first / 40, first % 40) } else { (2, first - 80) };
+    Some([a, b].into_iter().chain(arcs.into_iter().skip(1)).collect())
+}
+
+fn decode_value(tag: u8, bytes: &[u8]) -> Value {
+    match tag {
+        0x02 if !bytes.is_empty() && bytes.len() <= 8 => Value::Integer(decode_integer(bytes)),
+        0x04 => Value::Text(bytes.to_vec()),
+        0x06 => decode_oid(bytes).map_or(Value::Null, Value::Oid),
+        _ => Value::Null,
+    }
+}
+
+// ============================================================================
+// MESSAGES
+// ============================================================================
+
+struct Message {
+    version: i64,
+    community: Vec<u8>,
+    pdu: u8,
+    request_id: i64,
+    /// The non-repeaters of a GetBulk.
+    error_status: i64,
+    /// The max-repetitions of a GetBulk.
+    error_index: i64,
+    bindings: Vec<(Vec<u32>, Value)>,
+}
+
+impl Message {
+    fn decode(packet: &[u8]) -> Option<Message> {
+        let (0x30, message) = (Reader { data: packet }).next()? else {
+            return None;
+        };
+        let mut message = Reader { data: message };
+        let version = message.integer()?;
+        let (0x04, community) = message.next()? else {
+            return None;
+        };
+        let (pdu, body) = message.next()?;
+        let mut body = Reader { data: body };
+        let request_id = body.integer()?;
+        let error_status = body.integer()?;
+        let error_index = body.integer()?;
+        let (0x30, list) = body.next()? else {
+            return None;
+        };
+        let mut list = Reader { data: list };
+        let mut bindings = Vec::new();
+        while let Some((0x30, binding)) = list.next() {
+            let mut binding = Reader { data: binding };
+            let (0x06, oid) = binding.next()? else {
+                return None;
+            };
+            let (tag, value) = binding.next()?;
+            bindings.push((decode_oid(oid)?, decode_value(tag, value)));
+        }
+        Some(Message {
+            version,
+            community: community.to_vec(),
+            pdu,
+            request_id,
+            error_status,
+            error_index,
+            bindings,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let bindings: Vec<u8> = self
+            .bindings
+            .iter()
+            .flat_map(|(oid, value)| {
+                encode(0x30, &[encode_oid(oid), encode_value(value)].concat())
+            })
+            .collect();
+        let body = [
+            encode_integer(0x02, self.request_id),
+            encode_integer(0x02, self.error_status),
+            encode_integer(0x02, self.error_index),
+            encode(0x30, &bindings),
+        ]
+        .concat();
+        let message = [
+            encode_integer(0x02, self.version),
+            encode(0x04, &self.community),
+            encode(self.pdu, &body),
+        ]
+        .concat();
+        encode(0x30, &message)
+    }
+}
+
+// ============================================================================
+// AGENT
+// ============================================================================
+
+/// The object after `oid`, or `oid` itself at the end of the MIB view.
+fn next_object(objects: &[(Vec<u32>, Value)], oid: &[u32]) -> (Vec<u32>, Value) {
+    let found = objects.iter().find(|(o, _)| o.as_slice() > oid);
+    found.map_or((oid.to_vec(), Value::EndOfMibView), |(o, v)| (o.clone(), v.clone()))
+}
+
+/// Answers a GetBulk: the first `non_repeaters` bindings once, then the
+/// rest `max_repetitions` times over.
+fn get_bulk(
+    objects: &[(Vec<u32>, Value)],
+    bindings: &[(Vec<u32>, Value)],
+    non_repeaters: i64,
+    max_repetitions: i64,
+) -> Vec<(Vec<u32>, Value)> {
+    let non_repeaters = non_repeaters.clamp(0, bindings.len() as i64);
+    let (single, repeated) = bindings.split_at(non_repeaters as usize);
+    let mut response: Vec<_> = single.iter().map(|(oid, _)| next_object(objects, oid)).collect();
+    let mut cursors: Vec<Vec<u32>> = repeated.iter().map(|(oid, _)| oid.clone()).collect();
+    // Past the end of the objects every repetition is the same
+    for _ in 0..max_repetitions.clamp(0, objects.len() as i64 + 1) {
+        for cursor in &mut cursors {
+            let (oid, value) = next_object(objects, cursor);
+            *cursor = oid.clone();
+            response.push((oid, value));
+        }
+    }
+    response
+}
+
+fn state_number(state: RelayState) -> i64 {
+    match state {
+        RelayState::On => 1,
+        RelayState::Off => 2,
+        RelayState::Error => 3,
+        RelayState::Unknown => 4,
+    }
+}
+
+struct Agent<'a> {
+    base: Vec<u32>,
+    started: Instant,
+    state: &'a AppState,
+}
+
+impl Agent<'_> {
+    fn oid(&self, arcs: &[u32]) -> Vec<u32> {
+        [self.base.as_slice(), arcs].concat()
+    }
+
+    fn uptime(&self) -> Value {
+        Value::TimeTicks((self.started.elapsed().as_millis() / 10) as u32)
+    }
+
+    /// Every object, in OID order.
+    fn objects(&self, device: &DeviceConfig) -> Vec<(Vec<u32>, Value)> {
+        let states = self.state.snapshot().relay_states;
+        let version = env!("CARGO_PKG_VERSION");
+        let descr = format!("USB Power Relay {}, {} channels", version, states.len());
+        let mut objects = vec![
+            (SYS_DESCR.to_vec(), Value::Text(descr.into_bytes())),
+            (SYS_OBJECT_ID.to_vec(), Value::Oid(self.base.clone())),
+            (SYS_UP_TIME.to_vec(), self.uptime()),
+            (self.oid(&[1, 0]), Value::Integer(states.len() as i64)),
+        ];
+        for (i, state) in states.iter().enumerate() {
+            let n = i as u32 + 1;
+            let label = device.label(n as u8).into_bytes();
+            objects.push((self.oid(&[2, 1, 1, n]), Value::Integer(n as i64)));
+            objects.push((self.oid(&[2, 1, 2, n]), Value::Text(label)));
+            objects.push((self.oid(&[2, 1, 3, n]), Value::Integer(state_number(*state))));
+        }
+        objects.sort_by(|a, b| a.0.cmp(&b.0));
+        objects
+    }
+
+    /// The channel a writable state OID belongs to.
+    fn state_channel(&self, oid: &[u32], channels: usize) -> Option<u8> {
+        match oid.strip_prefix(self.base.as_slice())? {
+            [2, 1, 3, n] if (1..=channels as u32).contains(n) => Some(*n as u8),
+            _ => None,
+        }
+    }
+
+    /// The response to `request`, or nothing when it should be dropped.
+    fn respond(&self, request: Message, settings: &SnmpConfig, client: &str) -> Option<Message> {
+        if request.version != V1 && request.version != V2C {
+            return None;
+        }
+        let community = String::from_utf8_lossy(&request.community);
+        let can_write = settings.write_community.as_deref() == Some(&*community);
+        if community != settings.community && !can_write {
+            return None;
+        }
+        let device = Config::load().ok()?.device;
+        let objects = self.objects(&device);
+        let get = |oid: &[u32]| {
+            let found = objects.iter().find(|(o, _)| o.as_slice() == oid);
+            found.map_or(Value::NoSuchObject, |(_, v)| v.clone())
+        };
+        let next = |oid: &[u32]| next_object(&objects, oid);
+
+        let mut response = Message {
+            version: request.version,
+            community: request.community.clone(),
+            pdu: RESPONSE,
+            request_id: request.request_id,
+            error_status: 0,
+            error_index: 0,
+            bindings: Vec::new(),
+        };
+        match request.pdu {
+            GET => {
+                response.bindings =
+                    request.bindings.iter().map(|(oid, _)| (oid.clone(), get(oid))).collect();
+            }
+            GET_NEXT => {
+                response.bindings = request.bindings.iter().map(|(oid, _)| next(oid)).collect();
+            }
+            GET_BULK if request.version == V2C => {
+                let (non_repeaters, max_repetitions) = (request.error_status, request.error_index);
+                response.bindings =
+                    get_bulk(&objects, &request.bindings, non_repeaters, max_repetitions);
+            }
+            SET if can_write => return Some(self.set(request, response, client)),
+            SET => return None,
+            _ => return None,
+        }
+
+        // Version 1 has no exceptions, only an error for the first miss
+        if request.version == V1 {
+            if let Some(i) = response.bindings.iter().position(|(_, v)| v.is_exception()) {
+                response.error_status = NO_SUCH_NAME;
+                response.error_index = i as i64 + 1;
+                response.bindings = request.bindings;
+            }
+        }
+        Some(response)
+    }
+
+    /// Switches channels; all the bindings are checked before any is acted on.
+    fn set(&self, request: Message, mut response: Message, client: &str) -> Message {
+        let channels = self.state.snapshot().relay_states.len();
+        let mut commands = Vec::new();
+        for (i, (oid, value)) in request.bindings.iter().enumerate() {
+            let error = match (self.state_channel(oid, channels), value) {
+                _ if self.state.read_only => Some(GEN_ERR),
+                (None, _) if request.version == V1 => Some(NO_SUCH_NAME),
+                (None, _) => Some(NOT_WRITABLE),
+                (Some(channel), Value::Integer(1)) => {
+                    commands.push(Command::TurnOn(channel));
+                    None
+                }
+                (Some(channel), Value::Integer(2)) => {
+                    commands.push(Command::TurnOff(channel));
+                    None
+                }
+                _ if request.version == V1 => Some(BAD_VALUE),
+                _ => Some(WRONG_VALUE),
+            };
+            if let Some(error) = error {
+                response.error_status = error;
+                response.error_index = i as i64 + 1;
+                break;
+            }
+        }
+        if response.error_status == 0 {
+            for cmd in commands {
+                self.state.send_command(cmd, Source::Snmp(client.to_string()));
+            }
+        }
+        response.bindings = request.bindings;
+        response
+    }
+
+    fn trap(&self, settings: &SnmpConfig, id: i64, channel: u8, state: RelayState) {
+        let Ok(device) = Config::load().map(|c| c.device) else {
+            return;
+        };
+        let n = channel as u32;
+        let trap = Message {
+            version: V2C,
+            community: settings.community.clone().into_bytes(),
+            pdu: TRAP_V2,
+            request_id: id,
+            error_status: 0,
+            error_index: 0,
+            bindings: vec![
+                (SYS_UP_TIME.to_vec(), self.uptime()),
+                (SNMP_TRAP_OID.to_vec(), Value::Oid(self.oid(&[3, 1]))),
+                (self.oid(&[2, 1, 3, n]), Value::Integer(state_number(state))),
+                (self.oid(&[2, 1, 2, n]), Value::Text(device.label(channel).into_bytes())),
+            ],
+        };
+        let packet = trap.encode();
+        for target in &settings.traps {
+            let target = match target.contains(':') {
+                true => target.clone(),
+                false => format!("{}:{}", target, TRAP_PORT),
+            };
+            let sent = UdpSocket::bind("0.0.0.0:0").and_then(|s| s.send_to(&packet, &target));
+            if let Err(e) = sent {
+                eprintln!("SNMP trap to {}: {}", target, e);
+            }
+        }
+    }
+}
+
+/// Answers SNMP requests and sends traps while enabled. Exits cleanly when
+/// disabled.
+pub fn run_snmp(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    let settings = Config::load()?.snmp;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let agent = Agent {
+        base: parse_oid(&settings.base_oid)?,
+        started: Instant::now(),
+        state,
+    };
+    let socket = UdpSocket::bind(&settings.bind)
+        .map_err(|e| format!("Failed to bind {}: {}", settings.bind, e))?;
+    socket.set_read_timeout(Some(TICK)).map_err(|e| format!("SNMP: {}", e))?;
+    let mut seen = state.snapshot().last_changes;
+    let mut traps_sent = 0;
+    ctx.mark_running();
+
+    let mut buf = [0u8; 4096];
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((len, peer)) => {
+                let Some(request) = Message::decode(&buf[..len]) else {
+                    continue;
+                };
+                let client = peer.ip().to_string();
+                if let Some(response) = agent.respond(request, &settings, &client) {
+                    let _ = socket.send_to(&response.encode(), peer);
+                }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(format!("SNMP: {}", e)),
+        }
+
+        let current = state.snapshot().last_changes;
+        for (i, change) in current.iter().enumerate() {
+            let Some((relay_state, _)) = change else {
+                continue;
+            };
+            if seen.get(i) != Some(change) && !settings.traps.is_empty() {
+                traps_sent += 1;
+                agent.trap(&settings, traps_sent, i as u8 + 1, *relay_state);
+            }
+        }
+        seen = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: [u32; 7] = [1, 3, 6, 1, 4, 1, 8072];
+
+    fn read(data: &[u8]) -> Option<(u8, &[u8])> {
+        Reader { data }.next()
+    }
+
+    #[test]
+    fn integers_round_trip() {
+        for n in [0, 1, 127, 128, 255, 256, -1, -128, -129, i64::MIN, i64::MAX] {
+            let encoded = encode_integer(0x02, n);
+            assert_eq!(Reader { data: &encoded }.integer(), Some(n), "{}", n);
+        }
+        assert_eq!(encode_integer(0x02, 128), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(encode_integer(0x02, -129), [0x02, 0x02, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn oids_round_trip() {
+        for oid in [BASE.to_vec(), vec![2, 100, 3], vec![1, 3, u32::MAX], vec![0, 0]] {
+            let encoded = encode_oid(&oid);
+            let (tag, content) = read(&encoded).unwrap();
+            assert_eq!(tag, 0x06);
+            assert_eq!(decode_oid(content), Some(oid));
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_and_cut_off_oids() {
+        // One arc past u32::MAX
+        assert_eq!(decode_oid(&[0x2b, 0x90, 0x80, 0x80, 0x80, 0x00]), None);
+        // The last arc continues beyond the content
+        assert_eq!(decode_oid(&[0x2b, 0x86]), None);
+        assert_eq!(decode_oid(&[]), None);
+    }
+
+    #[test]
+    fn reads_every_length_form() {
+        for len in [0, 0x7f, 0x80, 0xff, 0x100, 0x1234] {
+            let content = vec![0xaa; len];
+            let encoded = encode(0x04, &content);
+            assert_eq!(read(&encoded), Some((0x04, content.as_slice())), "{}", len);
+        }
+        let mut four = vec![0x04, 0x84, 0x00, 0x00, 0x01, 0x00];
+        four.extend([0x55; 0x100]);
+        assert_eq!(read(&four).map(|(_, content)| content.len()), Some(0x100));
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        // Longer than the data, with the length in four bytes
+        assert_eq!(read(&[0x04, 0x84, 0xff, 0xff, 0xff, 0xff, 0x00]), None);
+        // Length bytes missing
+        assert_eq!(read(&[0x04, 0x82, 0x01]), None);
+        // Indefinite and five-byte lengths
+        assert_eq!(read(&[0x04, 0x80, 0x00, 0x00]), None);
+        assert_eq!(read(&[0x04, 0x85, 0, 0, 0, 0, 1, 0]), None);
+        assert_eq!(read(&[0x04, 0x02, 0x00]), None);
+        assert_eq!(read(&[0x04]), None);
+    }
+
+    fn get_request() -> Message {
+        Message {
+            version: V2C,
+            community: b"public".to_vec(),
+            pdu: GET,
+            request_id: 0x1234_5678,
+            error_status: 0,
+            error_index: 0,
+            bindings: vec![
+                ([BASE.as_slice(), &[1, 0]].concat(), Value::Null),
+                (SYS_DESCR.to_vec(), Value::Text(b"relay".to_vec())),
+                (SYS_OBJECT_ID.to_vec(), Value::Oid(BASE.to_vec())),
+                ([BASE.as_slice(), &[2, 1, 3, 1]].concat(), Value::Integer(-2)),
+            ],
+        }
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let request = get_request();
+        let decoded = Message::decode(&request.encode()).unwrap();
+        assert_eq!(decoded.version, request.version);
+        assert_eq!(decoded.community, request.community);
+        assert_eq!(decoded.pdu, request.pdu);
+        assert_eq!(decoded.request_id, request.request_id);
+        assert_eq!(decoded.bindings, request.bindings);
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        let packet = get_request().encode();
+        for len in 0..packet.len() {
+            assert!(Message::decode(&packet[..len]).is_none(), "{}", len);
+        }
+        // A binding without a value
+        let binding = encode(0x30, &encode_oid(&BASE));
+        let body = [
+            encode_integer(0x02, 1),
+            encode_integer(0x02, 0),
+            encode_integer(0x02, 0),
+            encode(0x30, &binding),
+        ]
+        .concat();
+        let message =
+            [encode_integer(0x02, V1), encode(0x04, b"public"), encode(GET, &body)].concat();
+        assert!(Message::decode(&encode(0x30, &message)).is_none());
+    }
+
+    fn objects() -> Vec<(Vec<u32>, Value)> {
+        (1..=3).map(|n| ([BASE.as_slice(), &[2, 1, 3, n]].concat(), Value::Integer(1))).collect()
+    }
+
+    #[test]
+    fn get_bulk_walks_the_objects() {
+        let objects = objects();
+        let single = (BASE.to_vec(), Value::Null);
+        let walked = (objects[0].0.clone(), Value::Null);
+        let response = get_bulk(&objects, &[single, walked], 1, 2);
+        let oids: Vec<&Vec<u32>> = response.iter().map(|(oid, _)| oid).collect();
+        assert_eq!(oids, [&objects[0].0, &objects[1].0, &objects[2].0]);
+    }
+
+    #[test]
+    fn get_bulk_repetitions_are_bounded() {
+        let objects = objects();
+        let start = [(BASE.to_vec(), Value::Null)];
+        // No further than one past the end, however many are asked for
+        let response = get_bulk(&objects, &start, 0, i64::MAX);
+        assert_eq!(response.len(), objects.len() + 1);
+        assert_eq!(response.last().unwrap().1, Value::EndOfMibView);
+        assert!(get_bulk(&objects, &start, 0, -5).is_empty());
+        // Non-repeaters beyond the bindings are all of them
+        assert_eq!(get_bulk(&objects, &start, 7, i64::MAX).len(), 1);
+        assert_eq!(get_bulk(&objects, &start, -1, 1).len(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Audible feedback.
+//!
+//! Plays a short cue when a relay switches on or off and an alert when the
+//! device fails, so switching can be followed with the window minimized.
+//! The built-in cues are synthesized tones; any cue can be replaced with a
+//! WAV file. Playback runs on its own thread and never delays the device.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const SAMPLE_RATE: u32 = 22_050;
+const AMPLITUDE: f32 = 0.4;
+const FADE_MS: u32 = 5;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    pub enabled: bool,
+    /// WAV files replacing the built-in cues.
+    pub on_file: Option<PathBuf>,
+    pub off_file: Option<PathBuf>,
+    pub error_file: Option<PathBuf>,
+}
+
+impl SoundConfig {
+    fn file(&self, cue: Cue) -> Option<&PathBuf> {
+        match cue {
+            Cue::On => self.on_file.as_ref(),
+            Cue::Off => self.off_file.as_ref(),
+            Cue::Error => self.error_file.as_ref(),
+        }
+    }
+}
+
+// ============================================================================
+// CUES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    On,
+    Off,
+    Error,
+}
+
+impl Cue {
+    pub const ALL: [Cue; 3] = [Cue::On, Cue::Off, Cue::Error];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cue::On => "on",
+            Cue::Off => "off",
+            Cue::Error => "error",
+        }
+    }
+
+    /// Notes as (frequency in Hz, length in ms); a frequency of 0 is a rest.
+    fn notes(&self) -> &'static [(f32, u32)] {
+        match self {
+            Cue::On => &[(880.0, 60), (1320.0, 90)],
+            Cue::Off => &[(660.0, 60), (440.0, 90)],
+            Cue::Error => &[(220.0, 150), (0.0, 60), (220.0, 150), (0.0, 60), (220.0, 150)],
+        }
+    }
+
+    /// Renders the cue as a 16-bit mono WAV file.
+    fn wav(&self) -> Vec<u8> {
+        let fade = (SAMPLE_RATE * FADE_MS / 1000) as f32;
+        let mut samples: Vec<i16> = Vec::new();
+        for &(freq, ms) in self.notes() {
+            let count = SAMPLE_RATE * ms / 1000;
+            for i in 0..count {
+                let envelope = (i as f32 / fade).min((count - i) as f32 / fade).min(1.0);
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let value = (std::f32::consts::TAU * freq * t).sin() * envelope * AMPLITUDE;
+                samples.push((value * f32::from(i16::MAX)) as i16);
+            }
+        }
+
+        let data_len = (samples.len() * 2) as u32;
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+        wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+// ============================================================================
+// PLAYBACK
+// ============================================================================
+
+/// Plays `cue` in the background if sounds are enabled.
+pub fn play(settings: &SoundConfig, cue: Cue) {
+    if settings.enabled {
+        play_now(settings, cue);
+    }
+}
+
+/// Plays `cue` in the background regardless of the enabled flag, e.g. to
+/// preview it from the settings window.
+pub fn play_now(settings: &SoundConfig, cue: Cue) {
+    let file = settings.file(cue).cloned();
+    std::thread::spawn(move || {
+        if let Err(e) = platform::play(cue, file) {
+            eprintln!("Sound failed: {}", e);
+        }
+    });
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Cue;
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    const SND_NODEFAULT: u32 = 0x0002;
+    const SND_MEMORY: u32 = 0x0004;
+    const SND_FILENAME: u32 = 0x0002_0000;
+
+    #[link(name = "winmm")]
+    extern "system" {
+        fn PlaySoundW(sound: *const u16, module: *mut c_void, flags: u32) -> i32;
+    }
+
+    /// Plays synchronously; the caller is already on a background thread.
+    pub fn play(cue: Cue, file: Option<PathBuf>) -> Result<(), String> {
+        let played = match file {
+            Some(path) => {
+                let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+                // SAFETY: `wide` is NUL-terminated and outlives the call
+                unsafe { PlaySoundW(wide.as_ptr(), std::ptr::null_mut(), SND_FILENAME | SND_NODEFAULT) }
+            }
+            None => {
+                let wav = cue.wav();
+                // SAFETY: with SND_MEMORY the pointer is a complete WAV image
+                // that stays alive until the synchronous call returns
+                unsafe {
+                    PlaySoundW(wav.as_ptr().cast(), std::ptr::null_mut(), SND_MEMORY | SND_NODEFAULT)
+                }
+            }
+        };
+        if played == 0 {
+            return Err(format!("Could not play the {} sound", cue.name()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::Cue;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+
+    #[cfg(target_os = "macos")]
+    const PLAYERS: &[&str] = &["afplay"];
+    #[cfg(not(target_os = "macos"))]
+    const PLAYERS: &[&str] = &["pw-play", "paplay", "aplay"];
+
+    /// Built-in cues are written to the cache directory once so the system
+    /// player can open them.
+    fn builtin_file(cue: Cue) -> Result<PathBuf, String> {
+        let dir = directories::ProjectDirs::from("", "", "usb-power-relay")
+            .map(|dirs| dirs.cache_dir().join("sounds"))
+            .ok_or_else(|| "No cache directory available".to_string())?;
+        let path = dir.join(format!("{}.wav", cue.name()));
+        if !path.exists() {
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            std::fs::write(&path, cue.wav())
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(path)
+    }
+
+    /// Plays synchronously; the caller is already on a background thread.
+    pub fn play(cue: Cue, file: Option<PathBuf>) -> Result<(), String> {
+        let path = match file {
+            Some(path) => path,
+            None => builtin_file(cue)?,
+        };
+
+        for player in PLAYERS {
+            let status = Command::new(player)
+                .arg(&path)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            match status {
+                Ok(status) if status.success() => return Ok(()),
+                Ok(status) => return Err(format!("{} exited with {}", player, status)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Failed to run {}: {}", player, e)),
+            }
+        }
+        Err(format!("No audio player found (tried {})", PLAYERS.join(", ")))
+    }
+}
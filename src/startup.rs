@@ -0,0 +1,119 @@
+//! Startup when the relay board is absent.
+//!
+//! `device.startup` decides what happens when the board can't be opened at
+//! startup: start degraded (channels shown as unavailable, everything else
+//! running), hold the command sources (scheduler, API, hotkeys, ...) until
+//! the board appears, or exit so a service manager can restart the app.
+//! Progress is reported to systemd through `sd_notify` when the app runs as
+//! a `Type=notify` service.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::supervisor::SubsystemContext;
+use crate::systemd::sd_notify;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// `EX_UNAVAILABLE` from sysexits.h.
+const EXIT_DEVICE_ABSENT: i32 = 69;
+
+// ============================================================================
+// POLICY
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartupPolicy {
+    /// Start everything; channels are shown as errors until the board
+    /// appears.
+    #[default]
+    Degraded,
+    /// Keep the command sources waiting until the board appears.
+    WaitForDevice,
+    /// Exit with status 69 so the service manager can retry.
+    FailFast,
+}
+
+// ============================================================================
+// DEVICE GATE
+// ============================================================================
+
+/// Opened once start-up may proceed: when the board first connects, or
+/// right away in degraded mode.
+#[derive(Clone, Default)]
+pub struct DeviceGate {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl DeviceGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.inner.0.lock().map(|open| *open).unwrap_or(true)
+    }
+
+    fn open(&self) {
+        let (lock, cvar) = &*self.inner;
+        if let Ok(mut open) = lock.lock() {
+            *open = true;
+            cvar.notify_all();
+        }
+    }
+
+    /// Blocks until the gate is open.
+    pub fn wait(&self) {
+        let (lock, cvar) = &*self.inner;
+        if let Ok(mut open) = lock.lock() {
+            while !*open {
+                match cvar.wait(open) {
+                    Ok(guard) => open = guard,
+                    Err(_) => return,
+                }
+            }
+        }
+    }
+}
+
+/// Wraps a subsystem so it starts only once `gate` opens.
+pub fn gated<F>(
+    gate: &DeviceGate,
+    mut run: F,
+) -> impl FnMut(&SubsystemContext) -> Result<(), String> + Send + 'static
+where
+    F: FnMut(&SubsystemContext) -> Result<(), String> + Send + 'static,
+{
+    let gate = gate.clone();
+    move |ctx| {
+        gate.wait();
+        run(ctx)
+    }
+}
+
+/// Called by the device worker once the board is open.
+pub fn device_ready(gate: &DeviceGate) {
+    gate.open();
+    sd_notify("READY=1\nSTATUS=Relay board connected");
+}
+
+/// Called by the device worker when the board can't be opened before the
+/// gate was ever opened. Does not return under [`StartupPolicy::FailFast`].
+pub fn device_absent(policy: StartupPolicy, gate: &DeviceGate, error: &str) {
+    match policy {
+        StartupPolicy::Degraded => {
+            gate.open();
+            sd_notify(&format!("READY=1\nSTATUS=Degraded, relay board unavailable: {}", error));
+        }
+        StartupPolicy::WaitForDevice => {
+            sd_notify(&format!("STATUS=Waiting for the relay board: {}", error));
+        }
+        StartupPolicy::FailFast => {
+            eprintln!("Relay board unavailable, exiting: {}", error);
+            sd_notify(&format!("STATUS=Relay board unavailable: {}", error));
+            std::process::exit(EXIT_DEVICE_ABSENT);
+        }
+    }
+}
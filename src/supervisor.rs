@@ -0,0 +1,181 @@
+//! Lightweight supervision for long-running subsystems.
+//!
+//! Each subsystem runs on its own thread. When it returns an error or
+//! panics, the supervisor restarts it after an exponential backoff and
+//! records its status so the GUI can surface it. A subsystem that returns
+//! `Ok(())` has shut down cleanly and is not restarted.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Granularity of backoff sleeps, so [`Supervisor::retry_now`] takes effect
+/// promptly.
+const BACKOFF_SLICE: Duration = Duration::from_millis(100);
+
+// ============================================================================
+// SUBSYSTEM STATUS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubsystemStatus {
+    Starting,
+    Running,
+    Restarting {
+        attempt: u32,
+        retry_at: Instant,
+        last_error: String,
+    },
+    Stopped,
+}
+
+impl SubsystemStatus {
+    pub fn text(&self) -> String {
+        match self {
+            SubsystemStatus::Starting => "starting".to_string(),
+            SubsystemStatus::Running => "running".to_string(),
+            SubsystemStatus::Restarting { attempt, retry_at, last_error } => {
+                let secs = retry_at.saturating_duration_since(Instant::now()).as_secs();
+                format!("{} (retry #{} in {}s)", last_error, attempt, secs)
+            }
+            SubsystemStatus::Stopped => "stopped".to_string(),
+        }
+    }
+}
+
+type StatusTable = Arc<Mutex<Vec<(&'static str, SubsystemStatus)>>>;
+
+/// Passed to a running subsystem so it can report that start-up succeeded.
+pub struct SubsystemContext {
+    name: &'static str,
+    statuses: StatusTable,
+}
+
+impl SubsystemContext {
+    /// Marks the subsystem as healthy, which also resets its backoff.
+    pub fn mark_running(&self) {
+        set_status(&self.statuses, self.name, SubsystemStatus::Running);
+    }
+}
+
+fn set_status(statuses: &StatusTable, name: &'static str, status: SubsystemStatus) {
+    if let Ok(mut table) = statuses.lock() {
+        match table.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = status,
+            None => table.push((name, status)),
+        }
+    }
+}
+
+fn retry_at(statuses: &StatusTable, name: &'static str) -> Option<Instant> {
+    let table = statuses.lock().ok()?;
+    match table.iter().find(|(n, _)| *n == name)? {
+        (_, SubsystemStatus::Restarting { retry_at, .. }) => Some(*retry_at),
+        _ => None,
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        format!("panicked: {}", s)
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        format!("panicked: {}", s)
+    } else {
+        "panicked".to_string()
+    }
+}
+
+// ============================================================================
+// SUPERVISOR
+// ============================================================================
+
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    statuses: StatusTable,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `run` on a dedicated thread, restarting it with backoff whenever
+    /// it fails or panics.
+    pub fn spawn<F>(&self, name: &'static str, mut run: F)
+    where
+        F: FnMut(&SubsystemContext) -> Result<(), String> + Send + 'static,
+    {
+        let statuses = Arc::clone(&self.statuses);
+        set_status(&statuses, name, SubsystemStatus::Starting);
+
+        std::thread::spawn(move || {
+            let ctx = SubsystemContext { name, statuses: Arc::clone(&statuses) };
+            let mut attempt = 0u32;
+
+            loop {
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| run(&ctx)));
+
+                let error = match outcome {
+                    Ok(Ok(())) => {
+                        set_status(&statuses, name, SubsystemStatus::Stopped);
+                        return;
+                    }
+                    Ok(Err(e)) => e,
+                    Err(payload) => panic_message(payload.as_ref()),
+                };
+
+                // A subsystem that reached Running earned a fresh backoff
+                let was_running = statuses
+                    .lock()
+                    .map(|t| t.iter().any(|(n, s)| *n == name && *s == SubsystemStatus::Running))
+                    .unwrap_or(false);
+                if was_running {
+                    attempt = 0;
+                }
+                attempt = attempt.saturating_add(1);
+
+                let backoff = INITIAL_BACKOFF
+                    .saturating_mul(1 << (attempt - 1).min(5))
+                    .min(MAX_BACKOFF);
+
+                set_status(
+                    &statuses,
+                    name,
+                    SubsystemStatus::Restarting {
+                        attempt,
+                        retry_at: Instant::now() + backoff,
+                        last_error: error,
+                    },
+                );
+
+                while retry_at(&statuses, name).is_some_and(|at| Instant::now() < at) {
+                    std::thread::sleep(BACKOFF_SLICE);
+                }
+                set_status(&statuses, name, SubsystemStatus::Starting);
+            }
+        });
+    }
+
+    /// Ends a restarting subsystem's backoff early, e.g. when the device it
+    /// waits for was just plugged in.
+    pub fn retry_now(&self, name: &'static str) {
+        if let Ok(mut table) = self.statuses.lock() {
+            if let Some((_, SubsystemStatus::Restarting { retry_at, .. })) =
+                table.iter_mut().find(|(n, _)| *n == name)
+            {
+                *retry_at = Instant::now();
+            }
+        }
+    }
+
+    /// Snapshot of every subsystem's current status, in spawn order.
+    pub fn statuses(&self) -> Vec<(&'static str, SubsystemStatus)> {
+        self.statuses.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,144 @@
+//! systemd integration.
+//!
+//! Under a `Type=notify` unit the app reports readiness and status through
+//! `sd_notify`, and the daemon pings the watchdog when the unit sets
+//! `WatchdogSec=`. Listening sockets passed by socket activation are used
+//! instead of binding new ones: a TCP socket for the HTTP API and a Unix
+//! socket for the control socket, told apart by address family. Outside
+//! systemd every function here is a no-op.
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+// ============================================================================
+// SD_NOTIFY
+// ============================================================================
+
+/// Sends a state string to systemd; a no-op unless `NOTIFY_SOCKET` is set.
+#[cfg(target_os = "linux")]
+pub fn sd_notify(state: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        // A leading '@' names a socket in the abstract namespace
+        match path.as_bytes().strip_prefix(b"@") {
+            Some(name) => SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr)),
+            None => socket.send_to(state.as_bytes(), &path),
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("sd_notify failed: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sd_notify(_state: &str) {}
+
+// ============================================================================
+// WATCHDOG
+// ============================================================================
+
+/// How often to send `WATCHDOG=1`: half the unit's `WatchdogSec=`, so one
+/// late ping doesn't get the service killed. `None` when the watchdog is
+/// off or meant for another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.trim().parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.trim().parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+// ============================================================================
+// SOCKET ACTIVATION
+// ============================================================================
+
+/// The TCP listener passed by socket activation, if any.
+pub fn tcp_listener() -> Option<TcpListener> {
+    #[cfg(target_os = "linux")]
+    {
+        activation::find(&[libc::AF_INET, libc::AF_INET6]).map(TcpListener::from)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// The Unix socket listener passed by socket activation, if any.
+#[cfg(unix)]
+pub fn unix_listener() -> Option<std::os::unix::net::UnixListener> {
+    #[cfg(target_os = "linux")]
+    {
+        activation::find(&[libc::AF_UNIX]).map(std::os::unix::net::UnixListener::from)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod activation {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::sync::OnceLock;
+
+    /// The first descriptor systemd passes.
+    const LISTEN_FDS_START: i32 = 3;
+
+    static PASSED: OnceLock<Vec<OwnedFd>> = OnceLock::new();
+
+    /// Descriptors passed to this process, taken over on first use.
+    fn passed() -> &'static [OwnedFd] {
+        PASSED.get_or_init(|| {
+            let pid = std::env::var("LISTEN_PID").ok().and_then(|p| p.trim().parse().ok());
+            if pid != Some(std::process::id()) {
+                return Vec::new();
+            }
+            let count: i32 = std::env::var("LISTEN_FDS")
+                .ok()
+                .and_then(|n| n.trim().parse().ok())
+                .unwrap_or(0);
+            (LISTEN_FDS_START..LISTEN_FDS_START + count)
+                .map(|fd| {
+                    // SAFETY: systemd hands these descriptors to this process
+                    // and nothing else owns them
+                    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                    // Keep them out of programs started by webhooks or hooks
+                    // SAFETY: plain fcntl on a descriptor we own
+                    unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+                    fd
+                })
+                .collect()
+        })
+    }
+
+    fn family(fd: &OwnedFd) -> Option<i32> {
+        // SAFETY: sockaddr_storage is plain data and large enough for any
+        // address getsockname returns
+        let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        // SAFETY: addr and len describe a valid buffer
+        let result = unsafe {
+            libc::getsockname(fd.as_raw_fd(), std::ptr::addr_of_mut!(addr).cast(), &mut len)
+        };
+        (result == 0).then_some(i32::from(addr.ss_family))
+    }
+
+    /// A copy of the first passed socket in one of `families`, so a
+    /// restarted subsystem can take it again.
+    pub fn find(families: &[i32]) -> Option<OwnedFd> {
+        passed()
+            .iter()
+            .find(|fd| family(fd).is_some_and(|f| families.contains(&f)))
+            .and_then(|fd| fd.try_clone().ok())
+    }
+}
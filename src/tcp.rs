@@ -0,0 +1,157 @@
+//! Line-based TCP control.
+//!
+//! For automation systems that can't speak HTTP, and for quick scripts with
+//! `nc`, `tcp.enabled` accepts one text command per line and answers each
+//! with one line:
+//!
+//! ```text
+//! ON 2        -> OK
+//! OFF Lamp    -> OK
+//! STATUS 2    -> ON
+//! STATUS      -> 1:ON 2:OFF 3:ERROR 4:UNKNOWN
+//! ```
+//!
+//! Commands are case-insensitive, a channel is its number or its label, and
+//! it may be left out on a one-channel board. Failures answer `ERR` and a
+//! reason. There is no authentication, so the default bind is loopback.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::audit::Source;
+use crate::config::{Config, DeviceConfig};
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Longer lines are cut off, so a stray binary client can't grow a buffer.
+const MAX_LINE: u64 = 256;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TcpConfig {
+    pub enabled: bool,
+    pub bind: String,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:7777".to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// The channel a command names; optional with a single channel.
+fn channel(device: &DeviceConfig, argument: &str) -> Result<u8, String> {
+    if argument.is_empty() {
+        return match device.channels {
+            1 => Ok(1),
+            _ => Err("ERR Which channel?".to_string()),
+        };
+    }
+    device.find_channel(argument).ok_or_else(|| format!("ERR No channel '{}'", argument))
+}
+
+/// `ON`, `OFF`, `ERROR` or `UNKNOWN`.
+fn word(state: RelayState) -> String {
+    state.name().to_ascii_uppercase()
+}
+
+/// The reply to one line.
+fn handle(line: &str, client: &str, state: &AppState) -> String {
+    let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+    let argument = argument.trim();
+    let device = match Config::load() {
+        Ok(config) => config.device,
+        Err(e) => return format!("ERR {}", e),
+    };
+    let reply = match command.to_ascii_uppercase().as_str() {
+        "STATUS" if argument.is_empty() && device.channels != 1 => {
+            let states = state.snapshot().relay_states;
+            let states = states.iter().enumerate().map(|(i, s)| format!("{}:{}", i + 1, word(*s)));
+            Ok(states.collect::<Vec<_>>().join(" "))
+        }
+        "STATUS" => channel(&device, argument).map(|channel| {
+            let states = state.snapshot().relay_states;
+            word(states.get(channel as usize - 1).copied().unwrap_or(RelayState::Unknown))
+        }),
+        "ON" | "OFF" if state.read_only => Err("ERR Monitor mode is read-only".to_string()),
+        "ON" | "OFF" => channel(&device, argument).map(|channel| {
+            let cmd = match command.eq_ignore_ascii_case("ON") {
+                true => Command::TurnOn(channel),
+                false => Command::TurnOff(channel),
+            };
+            state.send_command(cmd, Source::Tcp(client.to_string()));
+            "OK".to_string()
+        }),
+        "" => Err("ERR Empty command".to_string()),
+        _ => Err("ERR Unknown command; use ON, OFF or STATUS".to_string()),
+    };
+    reply.unwrap_or_else(|e| e)
+}
+
+fn serve_client(stream: TcpStream, state: &AppState) {
+    let client = stream.peer_addr().map_or_else(|_| "?".to_string(), |a| a.ip().to_string());
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let reply = match (&mut reader).take(MAX_LINE).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            // Skip the rest, rather than read it as another command
+            Ok(n) if n as u64 == MAX_LINE && !line.ends_with('\n') => {
+                let mut rest = Vec::new();
+                while !rest.ends_with(b"\n") {
+                    rest.clear();
+                    match (&mut reader).take(MAX_LINE).read_until(b'\n', &mut rest) {
+                        Ok(0) | Err(_) => return,
+                        Ok(_) => {}
+                    }
+                }
+                "ERR Line too long".to_string()
+            }
+            Ok(_) => handle(line.trim(), &client, state),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+// ============================================================================
+// SERVER
+// ============================================================================
+
+/// Serves text commands while enabled. Exits cleanly when disabled.
+pub fn run_tcp(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    let settings = Config::load()?.tcp;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let listener = TcpListener::bind(&settings.bind)
+        .map_err(|e| format!("Failed to bind {}: {}", settings.bind, e))?;
+    ctx.mark_running();
+
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| format!("Accept failed: {}", e))?;
+        let state = state.clone();
+        std::thread::spawn(move || serve_client(stream, &state));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+//! Telegram bot.
+//!
+//! With `telegram.enabled` and a bot token from @BotFather, the app polls
+//! the Bot API for messages. Chats listed in `telegram.chats` can send
+//! `/on <ch>`, `/off <ch>` and `/status`, a channel given by number or
+//! label, and are told about every ON/OFF change. Messages from any other
+//! chat are ignored, with their chat ID logged so it can be added. Like the
+//! other HTTPS integrations this talks through `curl`, the token on its
+//! stdin rather than its command line.
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::json_string;
+use crate::audit::Source;
+use crate::config::Config;
+use crate::json::Json;
+use crate::push::{curl_output, curl_option};
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const API_URL: &str = "https://api.telegram.org";
+/// How long one `getUpdates` waits for a message; changes are announced
+/// between polls, so this bounds their delay.
+const POLL_SECS: u32 = 2;
+
+const HELP: &str = "/status - every channel's state\n\
+                    /on <channel> - switch a channel on\n\
+                    /off <channel> - switch a channel off\n\
+                    A channel is its number or its label.";
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelegramConfig {
+    pub enabled: bool,
+    /// Bot token from @BotFather.
+    pub token: String,
+    /// Chat IDs allowed to control the relay.
+    pub chats: Vec<i64>,
+    /// Tell the chats about every ON/OFF change.
+    pub notify_changes: bool,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token: String::new(),
+            chats: Vec::new(),
+            notify_changes: true,
+        }
+    }
+}
+
+// ============================================================================
+// BOT API
+// ============================================================================
+
+struct Bot<'a> {
+    token: &'a str,
+}
+
+impl Bot<'_> {
+    fn call(&self, method: &str, body: Option<&str>) -> Result<Json, String> {
+        let url = format!("{}/bot{}/{}", API_URL, self.token, method);
+        let mut options = curl_option("url", &url);
+        if let Some(body) = body {
+            options += &curl_option("header", "Content-Type: application/json");
+            options += &curl_option("data-raw", body);
+        }
+        // The error mentions the URL, and with it the token
+        let reply = curl_output(&options).map_err(|_| format!("Telegram {} failed", method))?;
+        let reply = Json::parse(&String::from_utf8_lossy(&reply))
+            .ok_or_else(|| format!("Telegram {}: unexpected reply", method))?;
+        match reply.get("ok") {
+            Some(Json::Bool(true)) => Ok(reply.get("result").cloned().unwrap_or(Json::Null)),
+            _ => {
+                let description = reply.get("description").and_then(Json::text).unwrap_or("");
+                Err(format!("Telegram {}: {}", method, description))
+            }
+        }
+    }
+
+    /// Updates after `offset`, waiting briefly for one to arrive.
+    fn updates(&self, offset: i64) -> Result<Vec<Json>, String> {
+        let method = format!("getUpdates?offset={}&timeout={}", offset, POLL_SECS);
+        match self.call(&method, None)? {
+            Json::List(updates) => Ok(updates),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn send(&self, chat: i64, text: &str) {
+        let body = format!("{{\"chat_id\":{},\"text\":{}}}", chat, json_string(text));
+        if let Err(e) = self.call("sendMessage", Some(&body)) {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// The reply to a message from an allowed chat.
+fn handle(text: &str, chat: i64, config: &Config, state: &AppState) -> String {
+    let device = &config.device;
+    let mut words = text.split_whitespace();
+    // Commands may carry the bot's name in groups, as in /on@relay_bot
+    let command = words.next().unwrap_or("").split('@').next().unwrap_or("");
+    let argument = words.collect::<Vec<_>>().join(" ");
+    match command {
+        "/status" => state
+            .snapshot()
+            .relay_states
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}: {}", device.label(i as u8 + 1), s.text()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "/on" | "/off" if state.read_only => "Monitor mode is read-only".to_string(),
+        "/on" | "/off" => match device.find_channel(&argument) {
+            Some(channel) => {
+                let (cmd, word) = match command {
+                    "/on" => (Command::TurnOn(channel), "on"),
+                    _ => (Command::TurnOff(channel), "off"),
+                };
+                state.send_command(cmd, Source::Telegram(chat));
+                format!("Switching {} {}", device.label(channel), word)
+            }
+            None if argument.is_empty() => format!("Which channel? {} <channel>", command),
+            None => format!("No channel '{}'", argument),
+        },
+        _ => HELP.to_string(),
+    }
+}
+
+// ============================================================================
+// SUBSYSTEM
+// ============================================================================
+
+/// Answers allowed chats and announces changes while enabled. Exits cleanly
+/// when disabled.
+pub fn run_telegram(ctx: &SubsystemContext, state: &AppState) -> Result<(), String> {
+    let settings = Config::load()?.telegram;
+    if !settings.enabled || settings.token.is_empty() {
+        return Ok(());
+    }
+    let bot = Bot {
+        token: &settings.token,
+    };
+
+    // Skip messages sent while the app wasn't running, rather than acting
+    // on a stale /on
+    let mut offset = bot
+        .updates(-1)?
+        .iter()
+        .filter_map(|u| u.get("update_id").and_then(Json::number))
+        .max()
+        .map_or(0, |id| id + 1);
+    let mut seen = state.snapshot().last_changes;
+    ctx.mark_running();
+
+    loop {
+        let updates = bot.updates(offset)?;
+        let config = Config::load()?;
+        let allowed = &config.telegram.chats;
+
+        for update in &updates {
+            if let Some(id) = update.get("update_id").and_then(Json::number) {
+                offset = offset.max(id + 1);
+            }
+            let Some(message) = update.get("message") else {
+                continue;
+            };
+            let chat = message.get("chat").and_then(|c| c.get("id")).and_then(Json::number);
+            let (Some(chat), Some(text)) = (chat, message.get("text").and_then(Json::text)) else {
+                continue;
+            };
+            if !allowed.contains(&chat) {
+                eprintln!("Telegram: ignoring a message from chat {}", chat);
+                continue;
+            }
+            bot.send(chat, &handle(text, chat, &config, state));
+        }
+
+        let current = state.snapshot().last_changes;
+        if config.telegram.notify_changes {
+            for (i, change) in current.iter().enumerate() {
+                let Some((relay_state, _)) = change else {
+                    continue;
+                };
+                let switched = matches!(relay_state, RelayState::On | RelayState::Off);
+                if seen.get(i) == Some(change) || !switched {
+                    continue;
+                }
+                let label = config.device.label(i as u8 + 1);
+                let text = format!("{} switched {}", label, relay_state.text());
+                for &chat in allowed {
+                    bot.send(chat, &text);
+                }
+            }
+        }
+        seen = current;
+    }
+}
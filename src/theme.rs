@@ -0,0 +1,182 @@
+//! Themes and state colors.
+//!
+//! The window follows the system's dark or light preference unless a theme
+//! is picked, and the colors for ON, OFF and errors can be changed. Every
+//! indicator and button takes its color from here; the button fills are
+//! slightly darker shades of the state colors so white labels stay legible.
+//!
+//! For color vision deficiencies, `shapes` makes the indicators differ in
+//! form too: ON is a filled disc, OFF a ring, an unknown state a ring with a
+//! question mark and an error a warning triangle.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::RelayState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const UNKNOWN: egui::Color32 = egui::Color32::from_rgb(156, 163, 175);
+const BUTTON_SHADE: f32 = 0.9;
+const ALL_OFF_SHADE: f32 = 0.77;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThemeMode {
+    /// Follow the system preference.
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl ThemeMode {
+    pub const ALL: [ThemeMode; 3] = [ThemeMode::System, ThemeMode::Dark, ThemeMode::Light];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            ThemeMode::System => "System default",
+            ThemeMode::Dark => "Dark",
+            ThemeMode::Light => "Light",
+        }
+    }
+}
+
+/// Colors are `(r, g, b)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub mode: ThemeMode,
+    pub on: [u8; 3],
+    pub off: [u8; 3],
+    pub error: [u8; 3],
+    /// Tell states apart by shape as well as color.
+    pub shapes: bool,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            mode: ThemeMode::System,
+            on: [34, 197, 94],
+            off: [239, 68, 68],
+            error: [249, 115, 22],
+            shapes: false,
+        }
+    }
+}
+
+fn color([r, g, b]: [u8; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(r, g, b)
+}
+
+fn shade([r, g, b]: [u8; 3], factor: f32) -> egui::Color32 {
+    let scale = |c: u8| (f32::from(c) * factor).round() as u8;
+    egui::Color32::from_rgb(scale(r), scale(g), scale(b))
+}
+
+impl ThemeConfig {
+    /// The indicator color for `state`.
+    pub fn state(&self, state: RelayState) -> egui::Color32 {
+        match state {
+            RelayState::On => color(self.on),
+            RelayState::Off => color(self.off),
+            RelayState::Unknown => UNKNOWN,
+            RelayState::Error => color(self.error),
+        }
+    }
+
+    /// Draws the indicator for `state`, with `label` in the middle; without
+    /// a label, shapes that need one get a glyph instead.
+    pub fn indicator(
+        &self,
+        painter: &egui::Painter,
+        center: egui::Pos2,
+        radius: f32,
+        state: RelayState,
+        label: Option<(&str, f32)>,
+    ) {
+        let fill = self.state(state);
+        let filled = !self.shapes || matches!(state, RelayState::On);
+        let glyph = match state {
+            RelayState::Unknown => "?",
+            RelayState::Error => "!",
+            _ => "",
+        };
+
+        if filled {
+            painter.circle_filled(center, radius, fill);
+        } else if state == RelayState::Error {
+            // Flat side down, centered on the circle it replaces
+            let point = |degrees: f32| {
+                let angle = degrees.to_radians();
+                center + radius * egui::vec2(angle.cos(), angle.sin())
+            };
+            let corners = vec![point(-90.0), point(30.0), point(150.0)];
+            painter.add(egui::Shape::convex_polygon(corners, fill, egui::Stroke::NONE));
+        } else {
+            let width = (radius * 0.25).max(2.0);
+            painter.circle_stroke(center, radius - width / 2.0, egui::Stroke::new(width, fill));
+        }
+
+        // A ring's middle shows the background, so its text takes the color
+        let text_color = if filled || state == RelayState::Error {
+            egui::Color32::WHITE
+        } else {
+            fill
+        };
+        let (text, size) = match label {
+            Some(label) => label,
+            None if self.shapes && !glyph.is_empty() => (glyph, radius * 1.2),
+            None => return,
+        };
+        painter.text(
+            center,
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::proportional(size),
+            text_color,
+        );
+    }
+
+    /// For error text and warnings.
+    pub fn error(&self) -> egui::Color32 {
+        color(self.error)
+    }
+
+    pub fn on_fill(&self) -> egui::Color32 {
+        shade(self.on, BUTTON_SHADE)
+    }
+
+    pub fn off_fill(&self) -> egui::Color32 {
+        shade(self.off, BUTTON_SHADE)
+    }
+
+    pub fn all_off_fill(&self) -> egui::Color32 {
+        shade(self.off, ALL_OFF_SHADE)
+    }
+
+    /// The window's visuals: the picked theme, or `system` when following
+    /// it, with the app's flat window style.
+    pub fn visuals(&self, system: Option<eframe::Theme>) -> egui::Visuals {
+        let theme = match self.mode {
+            ThemeMode::System => system.unwrap_or(eframe::Theme::Dark),
+            ThemeMode::Dark => eframe::Theme::Dark,
+            ThemeMode::Light => eframe::Theme::Light,
+        };
+        let mut visuals = theme.egui_visuals();
+        visuals.window_rounding = 0.0.into();
+        visuals.window_shadow = egui::epaint::Shadow {
+            offset: egui::vec2(0.0, 0.0),
+            blur: 0.0,
+            spread: 0.0,
+            color: egui::Color32::TRANSPARENT,
+        };
+        visuals
+    }
+}
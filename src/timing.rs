@@ -0,0 +1,67 @@
+//! Adaptive response timeouts.
+//!
+//! Boards differ a lot in how quickly they answer, and many never answer
+//! switching frames at all. Waiting the full worst-case timeout for every
+//! missing reply makes each click slow, so the controller measures how long
+//! status replies actually take and waits only a few multiples of that. A
+//! status query that goes unanswered widens the window again, so a board that
+//! slows down is never starved.
+
+use std::time::Duration;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Shortest read timeout, so scheduling jitter can't cause misses.
+const MIN_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// How many times the average response time is waited for.
+const HEADROOM: u32 = 3;
+
+/// Weight of the newest sample in the running average, in percent.
+const SAMPLE_WEIGHT: u32 = 20;
+
+// ============================================================================
+// RESPONSE TIMER
+// ============================================================================
+
+/// Running average of response times and the read timeout derived from it.
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseTimer {
+    /// Upper bound and starting value of the timeout.
+    max: Duration,
+    average: Option<Duration>,
+    timeout: Duration,
+}
+
+impl ResponseTimer {
+    pub fn new(max: Duration) -> Self {
+        Self {
+            max,
+            average: None,
+            timeout: max,
+        }
+    }
+
+    /// How long the next read should wait for a reply.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Records how long an answered read took.
+    pub fn answered(&mut self, elapsed: Duration) {
+        let average = match self.average {
+            Some(average) => (average * (100 - SAMPLE_WEIGHT) + elapsed * SAMPLE_WEIGHT) / 100,
+            None => elapsed,
+        };
+        self.average = Some(average);
+        self.timeout = (average * HEADROOM + MIN_TIMEOUT).clamp(MIN_TIMEOUT, self.max);
+    }
+
+    /// Records a read that should have been answered but timed out.
+    pub fn missed(&mut self) {
+        self.timeout = (self.timeout * 2).min(self.max);
+        self.average = self.average.map(|average| (average * 2).min(self.max));
+    }
+}
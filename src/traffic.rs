@@ -0,0 +1,177 @@
+//! Serial traffic log.
+//!
+//! Every frame written to or read from the board is kept, with its time and
+//! direction, in a ring buffer shown by the Traffic window. The buffer can be
+//! saved to a file so protocol problems with odd clones can be looked at
+//! offline or attached to a bug report.
+
+use eframe::egui;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::i18n::{tr, tr_args};
+use crate::locale::format_timestamp;
+use crate::raw::format_hex;
+use crate::schedule::LocalTime;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Frames kept before the oldest are dropped.
+const CAPACITY: usize = 2000;
+
+// ============================================================================
+// LOG
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub time_ms: u64,
+    pub direction: Direction,
+    pub bytes: Vec<u8>,
+}
+
+impl Frame {
+    /// `14:03:27.518 > A0 01 03 A4`.
+    pub fn line(&self) -> String {
+        let arrow = match self.direction {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+        };
+        format!("{} {} {}", format_timestamp(self.time_ms), arrow, format_hex(&self.bytes))
+    }
+}
+
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Shared ring buffer of recent frames.
+#[derive(Clone, Default)]
+pub struct TrafficLog {
+    frames: Arc<Mutex<VecDeque<Frame>>>,
+}
+
+impl TrafficLog {
+    pub fn record(&self, direction: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        if let Ok(mut frames) = self.frames.lock() {
+            if frames.len() == CAPACITY {
+                frames.pop_front();
+            }
+            frames.push_back(Frame {
+                time_ms: unix_millis(),
+                direction,
+                bytes: bytes.to_vec(),
+            });
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<Frame> {
+        self.frames.lock().map(|f| f.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut frames) = self.frames.lock() {
+            frames.clear();
+        }
+    }
+
+    /// Writes the buffer to a new file in the data directory and returns its
+    /// path.
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let dir = directories::ProjectDirs::from("", "", "usb-power-relay")
+            .map(|dirs| dirs.data_dir().to_path_buf())
+            .ok_or_else(|| "No data directory available".to_string())?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let now = LocalTime::now();
+        let path = dir.join(format!(
+            "traffic-{}{:02}{:02}-{:02}{:02}{:02}.log",
+            now.year, now.month, now.day, now.hour, now.minute, now.second
+        ));
+        let mut file = std::fs::File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        for frame in self.snapshot() {
+            writeln!(file, "{}", frame.line())
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(path)
+    }
+}
+
+// ============================================================================
+// WINDOW
+// ============================================================================
+
+pub struct TrafficWindow {
+    status: Option<String>,
+}
+
+impl TrafficWindow {
+    pub fn new() -> Self {
+        Self { status: None }
+    }
+
+    /// Draws the log; returns false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context, log: &TrafficLog) -> bool {
+        let mut open = true;
+        egui::Window::new(tr("traffic-title"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| self.contents(ui, log));
+        open
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, log: &TrafficLog) {
+        let frames = log.snapshot();
+
+        ui.horizontal(|ui| {
+            ui.label(tr_args("traffic-frames", &[("count", &frames.len())]));
+            if ui.button(tr("traffic-save")).clicked() {
+                self.status = Some(match log.save() {
+                    Ok(path) => tr_args("traffic-saved", &[("path", &path.display())]),
+                    Err(e) => e,
+                });
+            }
+            if ui.button(tr("traffic-clear")).clicked() {
+                log.clear();
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.weak(status);
+        }
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, frames.len(), |ui, rows| {
+                for frame in &frames[rows] {
+                    let color = match frame.direction {
+                        Direction::Sent => egui::Color32::from_rgb(96, 165, 250),
+                        Direction::Received => egui::Color32::from_rgb(34, 197, 94),
+                    };
+                    ui.label(egui::RichText::new(frame.line()).monospace().color(color));
+                }
+            });
+    }
+}
@@ -0,0 +1,540 @@
+//! Time zones for schedules.
+//!
+//! A schedule can name the zone its time is in, so equipment on Berlin time
+//! keeps switching at 08:00 there whatever the machine is set to. Zones are
+//! read from the system's zoneinfo database (`$TZDIR` or
+//! `/usr/share/zoneinfo`), given as a POSIX TZ rule such as
+//! `CET-1CEST,M3.5.0,M10.5.0/3` (which also works on Windows, where there is
+//! no database) or are `UTC`. Schedules without a zone use the system's
+//! local time.
+//!
+//! Wall-clock times around a DST change resolve like this: a time that
+//! spring-forward skips runs shifted by the length of the gap (02:30 runs at
+//! 03:30), and a time that fall-back repeats runs only the first time.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::schedule::{Date, LocalTime, TimeOfDay};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+const DAY: i64 = 86_400;
+/// Transition time of a POSIX rule that doesn't give one.
+const DEFAULT_RULE_TIME: i64 = 2 * 3600;
+
+// ============================================================================
+// ZONES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeZone {
+    /// Whatever the operating system uses for local time.
+    System,
+    Rules(ZoneRules),
+}
+
+/// Offsets in seconds east of UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneRules {
+    /// Unix times at which the offset changes, with the offset from then on.
+    transitions: Vec<(i64, i32)>,
+    /// The offset before the first transition.
+    initial: i32,
+    /// Rule for times after the last transition.
+    footer: Option<Posix>,
+}
+
+impl TimeZone {
+    /// Loads a zone by IANA name (`Europe/Berlin`), POSIX rule or `UTC`.
+    pub fn named(name: &str) -> Result<Self, String> {
+        if let Some(path) = zone_file(name) {
+            let data = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            return parse_tzif(&data)
+                .map(TimeZone::Rules)
+                .map_err(|e| format!("{}: {}", path.display(), e));
+        }
+        match name {
+            "UTC" | "Etc/UTC" | "Z" => Ok(TimeZone::Rules(ZoneRules::fixed(0))),
+            _ => Posix::parse(name)
+                .map(|posix| {
+                    TimeZone::Rules(ZoneRules {
+                        transitions: Vec::new(),
+                        initial: posix.std,
+                        footer: Some(posix),
+                    })
+                })
+                .map_err(|_| format!("Unknown time zone '{}'", name)),
+        }
+    }
+
+    /// The offset from UTC in effect at Unix time `utc`.
+    pub fn offset_at(&self, utc: i64) -> i32 {
+        match self {
+            TimeZone::System => {
+                let local = LocalTime::from_unix(utc.max(0) as u64);
+                let naive = local.date().days() * DAY
+                    + i64::from(local.hour) * 3600
+                    + i64::from(local.minute) * 60
+                    + i64::from(local.second);
+                (naive - utc) as i32
+            }
+            TimeZone::Rules(rules) => rules.offset_at(utc),
+        }
+    }
+
+    /// The wall-clock time at Unix time `utc`.
+    pub fn local(&self, utc: i64) -> LocalTime {
+        let naive = utc + i64::from(self.offset_at(utc));
+        let date = Date::from_days(naive.div_euclid(DAY));
+        let of_day = naive.rem_euclid(DAY);
+        LocalTime {
+            year: date.year,
+            month: date.month,
+            day: date.day,
+            hour: (of_day / 3600) as u8,
+            minute: (of_day / 60 % 60) as u8,
+            second: (of_day % 60) as u8,
+            weekday: date.weekday(),
+        }
+    }
+
+    /// The Unix time at which `date` `time` happens: its first occurrence
+    /// if fall-back repeats it, shifted by the gap if spring-forward skips
+    /// it.
+    pub fn resolve(&self, date: Date, time: TimeOfDay) -> i64 {
+        let naive = date.days() * DAY + i64::from(time.hour) * 3600 + i64::from(time.minute) * 60;
+        // Zones change offset at most once within a day either side
+        let before = self.offset_at(naive - DAY);
+        let after = self.offset_at(naive + DAY);
+        [before, after]
+            .into_iter()
+            .map(|offset| naive - i64::from(offset))
+            .filter(|&utc| i64::from(self.offset_at(utc)) == naive - utc)
+            .min()
+            .unwrap_or(naive - i64::from(before))
+    }
+}
+
+impl ZoneRules {
+    fn fixed(offset: i32) -> Self {
+        Self { transitions: Vec::new(), initial: offset, footer: None }
+    }
+
+    fn offset_at(&self, utc: i64) -> i32 {
+        let passed = self.transitions.partition_point(|&(at, _)| at <= utc);
+        match (passed, &self.footer) {
+            (n, Some(footer)) if n == self.transitions.len() => footer.offset_at(utc),
+            (0, _) => self.initial,
+            (n, _) => self.transitions[n - 1].1,
+        }
+    }
+}
+
+/// The zoneinfo file for an IANA name, if there is one.
+fn zone_file(name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let dir = std::env::var_os("TZDIR").map_or_else(|| PathBuf::from(ZONEINFO), PathBuf::from);
+    let path = dir.join(relative);
+    path.is_file().then_some(path)
+}
+
+// ============================================================================
+// TZIF FILES
+// ============================================================================
+
+/// Parses a zoneinfo file (RFC 8536), preferring the 64-bit data of
+/// version 2 and later.
+fn parse_tzif(data: &[u8]) -> Result<ZoneRules, String> {
+    let invalid = || "not a TZif file".to_string();
+    let header = |at: usize| -> Result<[usize; 6], String> {
+        if data.get(at..at + 4) != Some(b"TZif") {
+            return Err(invalid());
+        }
+        let mut counts = [0; 6];
+        for (i, count) in counts.iter_mut().enumerate() {
+            let start = at + 20 + i * 4;
+            let bytes = data.get(start..start + 4).ok_or_else(invalid)?;
+            *count = u32::from_be_bytes(bytes.try_into().map_err(|_| invalid())?) as usize;
+        }
+        Ok(counts)
+    };
+
+    // Counts: UT/local indicators, standard/wall indicators, leap seconds,
+    // transitions, local time types, abbreviation bytes
+    let [utc_count, std_count, leap_count, time_count, type_count, char_count] = header(0)?;
+    let v1_len = time_count * 5 + type_count * 6 + char_count + leap_count * 8 + std_count
+        + utc_count;
+    let (at, time_size) = if data.get(4).is_some_and(|&v| v >= b'2') {
+        (44 + v1_len, 8)
+    } else {
+        (0, 4)
+    };
+    let [utc_count, std_count, leap_count, time_count, type_count, char_count] = header(at)?;
+
+    let body = data.get(at + 44..).ok_or_else(invalid)?;
+    let times_len = time_count * time_size;
+    let types_at = times_len + time_count;
+    let block_len = types_at
+        + type_count * 6
+        + char_count
+        + leap_count * (time_size + 4)
+        + std_count
+        + utc_count;
+    if body.len() < block_len || type_count == 0 {
+        return Err(invalid());
+    }
+
+    let offset_of = |index: usize| -> Result<i32, String> {
+        let start = types_at + index * 6;
+        let bytes = body.get(start..start + 4).ok_or_else(invalid)?;
+        Ok(i32::from_be_bytes(bytes.try_into().map_err(|_| invalid())?))
+    };
+    let mut transitions = Vec::with_capacity(time_count);
+    for i in 0..time_count {
+        let bytes = &body[i * time_size..(i + 1) * time_size];
+        let at = match time_size {
+            8 => i64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?),
+            _ => i64::from(i32::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+        };
+        let index = usize::from(body[times_len + i]);
+        if index >= type_count {
+            return Err(invalid());
+        }
+        transitions.push((at, offset_of(index)?));
+    }
+
+    // Version 2+ files end with a POSIX rule for times after the table
+    let footer = if time_size == 8 {
+        std::str::from_utf8(&body[block_len..])
+            .ok()
+            .and_then(|text| text.trim_matches('\n').lines().next())
+            .filter(|rule| !rule.is_empty())
+            .and_then(|rule| Posix::parse(rule).ok())
+    } else {
+        None
+    };
+    Ok(ZoneRules { transitions, initial: offset_of(0)?, footer })
+}
+
+// ============================================================================
+// POSIX RULES
+// ============================================================================
+
+/// A POSIX TZ rule like `EST5EDT,M3.2.0,M11.1.0`.
+#[derive(Debug, Clone, PartialEq)]
+struct Posix {
+    std: i32,
+    /// The DST offset and the rules for when DST starts and ends.
+    dst: Option<(i32, RuleDay, RuleDay)>,
+}
+
+/// The day and local time of a DST change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleDay {
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (5 = last) of month `m`.
+    Month { month: u8, week: u8, weekday: u8, time: i64 },
+    /// `Jn`: day 1..=365, never counting February 29.
+    Julian { day: u16, time: i64 },
+    /// `n`: day 0..=365, counting February 29.
+    Zero { day: u16, time: i64 },
+}
+
+impl Posix {
+    fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid TZ rule '{}'", text);
+        let mut rest = text;
+        skip_name(&mut rest).ok_or_else(invalid)?;
+        let std = -parse_offset(&mut rest).ok_or_else(invalid)?;
+        if rest.is_empty() {
+            return Ok(Self { std: std as i32, dst: None });
+        }
+
+        skip_name(&mut rest).ok_or_else(invalid)?;
+        let dst = if rest.starts_with(|c: char| c.is_ascii_digit() || c == '+' || c == '-') {
+            -parse_offset(&mut rest).ok_or_else(invalid)?
+        } else {
+            std + 3600
+        };
+        let mut rule = || -> Option<RuleDay> {
+            rest = rest.strip_prefix(',')?;
+            parse_rule_day(&mut rest)
+        };
+        let (start, end) = (rule().ok_or_else(invalid)?, rule().ok_or_else(invalid)?);
+        if !rest.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { std: std as i32, dst: Some((dst as i32, start, end)) })
+    }
+
+    fn offset_at(&self, utc: i64) -> i32 {
+        let Some((dst, start, end)) = self.dst else {
+            return self.std;
+        };
+        let year = Date::from_days((utc + i64::from(self.std)).div_euclid(DAY)).year;
+        // Start times are in standard time, end times in DST
+        let starts = start.local(year) - i64::from(self.std);
+        let ends = end.local(year) - i64::from(dst);
+        let in_dst = if starts < ends {
+            starts <= utc && utc < ends
+        } else {
+            // Southern hemisphere: DST spans the new year
+            utc < ends || starts <= utc
+        };
+        if in_dst {
+            dst
+        } else {
+            self.std
+        }
+    }
+}
+
+impl RuleDay {
+    /// The naive local time of the change in `year`, in seconds.
+    fn local(&self, year: i32) -> i64 {
+        let first = |month: u8| Date { year, month, day: 1 }.days();
+        let (days, time) = match *self {
+            RuleDay::Month { month, week, weekday, time } => {
+                let next = match month {
+                    12 => Date { year: year + 1, month: 1, day: 1 }.days(),
+                    m => first(m + 1),
+                };
+                // Weekday indices count from Monday, POSIX ones from Sunday
+                let wanted = (i64::from(weekday) + 6) % 7;
+                let first_weekday = Date { year, month, day: 1 }.weekday().index() as i64;
+                let mut day = first(month) + (wanted - first_weekday).rem_euclid(7)
+                    + i64::from(week - 1) * 7;
+                while day >= next {
+                    day -= 7;
+                }
+                (day, time)
+            }
+            RuleDay::Julian { day, time } => {
+                let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+                let skip_leap_day = i64::from(leap && day >= 60);
+                (first(1) + i64::from(day) - 1 + skip_leap_day, time)
+            }
+            RuleDay::Zero { day, time } => (first(1) + i64::from(day), time),
+        };
+        days * DAY + time
+    }
+}
+
+/// Skips a zone abbreviation: letters, or anything in `<…>`.
+fn skip_name(rest: &mut &str) -> Option<()> {
+    let len = match rest.strip_prefix('<') {
+        Some(inner) => inner.find('>')? + 2,
+        None => rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len()),
+    };
+    if len < 3 {
+        return None;
+    }
+    *rest = &rest[len..];
+    Some(())
+}
+
+/// `[+-]hh[:mm[:ss]]` in seconds.
+fn parse_offset(rest: &mut &str) -> Option<i64> {
+    let sign = match rest.as_bytes().first() {
+        Some(b'-') => -1,
+        _ => 1,
+    };
+    *rest = rest.trim_start_matches(['+', '-']);
+    let len = rest.find(|c: char| !c.is_ascii_digit() && c != ':').unwrap_or(rest.len());
+    let (clock, remaining) = rest.split_at(len);
+    *rest = remaining;
+    let mut seconds = 0;
+    let mut parts = 0;
+    for (part, scale) in clock.split(':').zip([3600, 60, 1]) {
+        seconds += part.parse::<i64>().ok()? * scale;
+        parts += 1;
+    }
+    (parts > 0 && clock.split(':').count() <= 3).then_some(sign * seconds)
+}
+
+fn parse_rule_day(rest: &mut &str) -> Option<RuleDay> {
+    let len = rest.find([',', '/']).unwrap_or(rest.len());
+    let (day, remaining) = rest.split_at(len);
+    *rest = remaining;
+    let time = match rest.strip_prefix('/') {
+        Some(after) => {
+            *rest = after;
+            parse_offset(rest)?
+        }
+        None => DEFAULT_RULE_TIME,
+    };
+
+    if let Some(spec) = day.strip_prefix('M') {
+        let mut fields = spec.split('.').map(|f| f.parse::<u8>().ok());
+        let (month, week, weekday) = (fields.next()??, fields.next()??, fields.next()??);
+        let valid = (1..=12).contains(&month) && (1..=5).contains(&week) && weekday <= 6;
+        return (valid && fields.next().is_none())
+            .then_some(RuleDay::Month { month, week, weekday, time });
+    }
+    if let Some(n) = day.strip_prefix('J') {
+        let day = n.parse().ok().filter(|d| (1..=365).contains(d))?;
+        return Some(RuleDay::Julian { day, time });
+    }
+    let day = day.parse().ok().filter(|&d| d <= 365)?;
+    Some(RuleDay::Zero { day, time })
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BERLIN: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    const NEW_YORK: &str = "EST5EDT,M3.2.0,M11.1.0";
+    const SYDNEY: &str = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+
+    fn zone(rule: &str) -> TimeZone {
+        TimeZone::named(rule).unwrap()
+    }
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date { year, month, day }
+    }
+
+    fn at(hour: u8, minute: u8) -> TimeOfDay {
+        TimeOfDay { hour, minute }
+    }
+
+    /// Unix time of a UTC date and time.
+    fn utc(date: Date, hour: i64, minute: i64) -> i64 {
+        date.days() * DAY + hour * 3600 + minute * 60
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        for days in [-719_468, -1, 0, 59, 10_956, 20_000, 2_932_896] {
+            assert_eq!(Date::from_days(days).days(), days);
+        }
+        assert_eq!(date(1970, 1, 1).days(), 0);
+        assert_eq!(date(2000, 3, 1).days(), 11_017);
+        assert_eq!(date(2026, 3, 29).weekday().index(), 6);
+    }
+
+    #[test]
+    fn posix_offsets() {
+        let berlin = zone(BERLIN);
+        assert_eq!(berlin.offset_at(utc(date(2026, 1, 15), 12, 0)), 3600);
+        assert_eq!(berlin.offset_at(utc(date(2026, 7, 15), 12, 0)), 7200);
+        // EU changes happen at 01:00 UTC
+        assert_eq!(berlin.offset_at(utc(date(2026, 3, 29), 0, 59)), 3600);
+        assert_eq!(berlin.offset_at(utc(date(2026, 3, 29), 1, 0)), 7200);
+        assert_eq!(berlin.offset_at(utc(date(2026, 10, 25), 0, 59)), 7200);
+        assert_eq!(berlin.offset_at(utc(date(2026, 10, 25), 1, 0)), 3600);
+
+        let sydney = zone(SYDNEY);
+        assert_eq!(sydney.offset_at(utc(date(2026, 1, 15), 0, 0)), 11 * 3600);
+        assert_eq!(sydney.offset_at(utc(date(2026, 7, 15), 0, 0)), 10 * 3600);
+
+        assert_eq!(zone("UTC").offset_at(utc(date(2026, 7, 1), 0, 0)), 0);
+        assert_eq!(zone("<+0530>-5:30").offset_at(0), 5 * 3600 + 1800);
+        assert!(TimeZone::named("Nowhere/Invalid").is_err());
+    }
+
+    #[test]
+    fn ordinary_times_resolve_to_their_instant() {
+        let berlin = zone(BERLIN);
+        assert_eq!(berlin.resolve(date(2026, 1, 15), at(8, 0)), utc(date(2026, 1, 15), 7, 0));
+        assert_eq!(berlin.resolve(date(2026, 7, 15), at(8, 0)), utc(date(2026, 7, 15), 6, 0));
+    }
+
+    #[test]
+    fn spring_forward_shifts_skipped_times_by_the_gap() {
+        let berlin = zone(BERLIN);
+        let day = date(2026, 3, 29);
+        // 02:30 doesn't exist; it runs at 03:30 CEST, 01:30 UTC
+        assert_eq!(berlin.resolve(day, at(2, 30)), utc(day, 1, 30));
+        assert_eq!(berlin.resolve(day, at(2, 0)), utc(day, 1, 0));
+        // Either side of the gap is unaffected
+        assert_eq!(berlin.resolve(day, at(1, 59)), utc(day, 0, 59));
+        assert_eq!(berlin.resolve(day, at(3, 0)), utc(day, 1, 0));
+
+        let new_york = zone(NEW_YORK);
+        let day = date(2026, 3, 8);
+        assert_eq!(new_york.resolve(day, at(2, 15)), utc(day, 7, 15));
+    }
+
+    #[test]
+    fn fall_back_picks_the_first_of_repeated_times() {
+        let berlin = zone(BERLIN);
+        let day = date(2026, 10, 25);
+        // 02:30 happens at 00:30 UTC (CEST) and again at 01:30 UTC (CET)
+        assert_eq!(berlin.resolve(day, at(2, 30)), utc(day, 0, 30));
+        assert_eq!(berlin.resolve(day, at(3, 0)), utc(day, 2, 0));
+
+        let new_york = zone(NEW_YORK);
+        let day = date(2026, 11, 1);
+        assert_eq!(new_york.resolve(day, at(1, 30)), utc(day, 5, 30));
+    }
+
+    #[test]
+    fn local_time_follows_the_offset() {
+        let berlin = zone(BERLIN);
+        let local = berlin.local(utc(date(2026, 10, 25), 1, 30));
+        assert_eq!((local.hour, local.minute), (2, 30));
+        let local = berlin.local(utc(date(2026, 12, 31), 23, 30));
+        assert_eq!((local.year, local.month, local.day, local.hour), (2027, 1, 1, 0));
+    }
+
+    #[test]
+    fn rule_days() {
+        // Last Sunday of March 2026 is the 29th, first Sunday of November the 1st
+        let last_march = RuleDay::Month { month: 3, week: 5, weekday: 0, time: 0 };
+        assert_eq!(last_march.local(2026), date(2026, 3, 29).days() * DAY);
+        let first_november = RuleDay::Month { month: 11, week: 1, weekday: 0, time: 0 };
+        assert_eq!(first_november.local(2026), date(2026, 11, 1).days() * DAY);
+        // J60 is March 1 even in leap years, 59 is February 29 in them
+        assert_eq!(RuleDay::Julian { day: 60, time: 0 }.local(2028), date(2028, 3, 1).days() * DAY);
+        assert_eq!(RuleDay::Zero { day: 59, time: 0 }.local(2028), date(2028, 2, 29).days() * DAY);
+    }
+
+    #[test]
+    fn tzif_files() {
+        // Version 2 file: one 32-bit block (skipped) and a 64-bit block with
+        // two transitions, then a footer rule
+        fn block(time_size: usize) -> Vec<u8> {
+            let mut out = b"TZif2".to_vec();
+            out.resize(20, 0);
+            for count in [0u32, 0, 0, 2, 2, 8] {
+                out.extend(count.to_be_bytes());
+            }
+            for time in [1_000_000i64, 2_000_000] {
+                match time_size {
+                    8 => out.extend(time.to_be_bytes()),
+                    _ => out.extend((time as i32).to_be_bytes()),
+                }
+            }
+            out.extend([1, 0]);
+            out.extend(3600i32.to_be_bytes());
+            out.extend([0, 0]);
+            out.extend(7200i32.to_be_bytes());
+            out.extend([1, 4]);
+            out.extend(b"CET\0CES\0");
+            out
+        }
+        let mut data = block(4);
+        data.extend(block(8));
+        data.extend(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+
+        let rules = parse_tzif(&data).unwrap();
+        assert_eq!(rules.offset_at(0), 3600);
+        assert_eq!(rules.offset_at(1_500_000), 7200);
+        assert_eq!(rules.offset_at(2_500_000), 3600);
+        // After the table the footer applies
+        assert_eq!(rules.offset_at(utc(date(2026, 7, 1), 0, 0)), 7200);
+        assert!(parse_tzif(b"not a zone").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! The `usbrelay://` URL scheme.
+//!
+//! `--register-url-scheme` has the desktop open `usbrelay://` links with
+//! this executable, so browser bookmarks, Stream Deck "open URL" actions
+//! and other applications can switch the relay. The desktop starts the
+//! binary with the link as its only argument, which is carried out like the
+//! matching CLI command, through the running instance when there is one:
+//!
+//! | URL                             | Effect                  |
+//! |---------------------------------|-------------------------|
+//! | `usbrelay://on`                 | Switch channel 1 on     |
+//! | `usbrelay://off?channel=2`      | Switch channel 2 off    |
+//! | `usbrelay://toggle?channel=1,3` | Toggle channels 1 and 3 |
+//! | `usbrelay://scene?name=Movie`   | Activate a scene        |
+//!
+//! Registration is per user. On Windows it is a key under
+//! `HKEY_CURRENT_USER\Software\Classes`; elsewhere it is a desktop entry for
+//! `x-scheme-handler/usbrelay`, written to
+//! `~/.local/share/applications/usb-power-relay-url.desktop`. macOS hands
+//! links only to app bundles that declare the scheme, so it isn't supported
+//! there.
+
+use crate::api::percent_decode;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+pub const SCHEME: &str = "usbrelay";
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlAction {
+    On(Vec<u8>),
+    Off(Vec<u8>),
+    Toggle(Vec<u8>),
+    Scene(String),
+}
+
+/// Whether a command-line argument is one of our links.
+pub fn is_url(arg: &str) -> bool {
+    arg.split_once("://").is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case(SCHEME))
+}
+
+/// Reads a `usbrelay://<action>[?<query>]` link.
+pub fn parse(url: &str) -> Result<UrlAction, String> {
+    let rest = url
+        .split_once("://")
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|(_, rest)| rest)
+        .ok_or_else(|| format!("Not a {}:// link: {}", SCHEME, url))?;
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    // Browsers like to add a slash to a bare host
+    let action = action.trim_end_matches('/').to_ascii_lowercase();
+    let params: Vec<(String, String)> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (percent_decode(key), percent_decode(&value.replace('+', " "))))
+        .collect();
+    let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, v)| v.as_str());
+
+    let channels = || -> Result<Vec<u8>, String> {
+        let Some(list) = param("channel") else {
+            return Ok(vec![1]);
+        };
+        list.split(',')
+            .map(|c| c.trim().parse().map_err(|_| format!("Invalid channel '{}'", c)))
+            .collect()
+    };
+    match action.as_str() {
+        "on" => Ok(UrlAction::On(channels()?)),
+        "off" => Ok(UrlAction::Off(channels()?)),
+        "toggle" => Ok(UrlAction::Toggle(channels()?)),
+        "scene" => match param("name") {
+            Some(name) if !name.is_empty() => Ok(UrlAction::Scene(name.to_string())),
+            _ => Err("A scene link needs ?name=<scene>".to_string()),
+        },
+        _ => Err(format!("Unknown action '{}', expected on, off, toggle or scene", action)),
+    }
+}
+
+// ============================================================================
+// REGISTRATION
+// ============================================================================
+
+/// Makes this executable the handler for `usbrelay://` links.
+pub fn register() -> Result<(), String> {
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the executable: {}", e))?;
+    platform::register(&exe.to_string_lossy())
+}
+
+/// Removes the handler again.
+pub fn unregister() -> Result<(), String> {
+    platform::unregister()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use super::SCHEME;
+    use crate::autostart::quote_exec_arg;
+
+    const ENTRY_NAME: &str = "usb-power-relay-url.desktop";
+
+    fn entry_path() -> Result<PathBuf, String> {
+        let dirs = directories::BaseDirs::new().ok_or("No home directory")?;
+        Ok(dirs.data_dir().join("applications").join(ENTRY_NAME))
+    }
+
+    pub fn register(exe: &str) -> Result<(), String> {
+        let path = entry_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=USB Relay\nExec={} %u\n\
+             NoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+            quote_exec_arg(exe),
+            SCHEME
+        );
+        std::fs::write(&path, entry)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        let status = Command::new("xdg-mime")
+            .args(["default", ENTRY_NAME, &format!("x-scheme-handler/{}", SCHEME)])
+            .status()
+            .map_err(|e| format!("Failed to run xdg-mime: {}", e))?;
+        if !status.success() {
+            return Err(format!("xdg-mime failed to make {} the handler", ENTRY_NAME));
+        }
+        Ok(())
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        let path = entry_path()?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    const UNSUPPORTED: &str =
+        "On macOS links only open app bundles that declare the scheme in their Info.plist";
+
+    pub fn register(_exe: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::process::Command;
+
+    use super::SCHEME;
+
+    fn key() -> String {
+        format!(r"HKCU\Software\Classes\{}", SCHEME)
+    }
+
+    /// Runs `reg.exe`, which reports its own errors on stderr.
+    fn reg(args: &[&str]) -> Result<(), String> {
+        let output = Command::new("reg.exe")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run reg.exe: {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&output.stderr);
+        let reason = text.lines().map(str::trim).rfind(|l| !l.is_empty()).unwrap_or("");
+        Err(format!("reg.exe {} failed: {}", args[0], reason))
+    }
+
+    pub fn register(exe: &str) -> Result<(), String> {
+        let key = key();
+        let command = format!("\"{}\" \"%1\"", exe);
+        reg(&["add", &key, "/ve", "/d", "URL:USB Relay", "/f"])?;
+        // Marks the key as a URL scheme rather than a file type
+        reg(&["add", &key, "/v", "URL Protocol", "/d", "", "/f"])?;
+        reg(&["add", &format!(r"{}\shell\open\command", key), "/ve", "/d", &command, "/f"])
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        match reg(&["query", &key()]) {
+            // Not registered is fine
+            Err(_) => Ok(()),
+            Ok(()) => reg(&["delete", &key(), "/f"]),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Offline voice commands.
+//!
+//! With `voice.enabled`, the microphone is listened to for a few fixed
+//! phrases, recognized on this machine by [Vosk](https://alphacephei.com/vosk/)
+//! so nothing leaves it. The recognizer is held to a grammar of just the
+//! configured phrases, which makes it a keyword spotter: anything else is
+//! heard as unknown and ignored.
+//!
+//! ```ron
+//! voice: (
+//!     enabled: true,
+//!     model: "/home/me/vosk-model-small-en-us-0.15",
+//!     phrases: [
+//!         (phrase: "power on", action: On(1)),
+//!         (phrase: "power off", action: Off(1)),
+//!     ],
+//! ),
+//! ```
+//!
+//! `libvosk` is loaded when voice commands are turned on, so the build
+//! doesn't need it. Audio is read as 16 kHz mono 16-bit samples from a
+//! capture command's output: `arecord` on Linux and SoX's `rec` on macOS,
+//! unless `capture` names another.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::api::json_string;
+use crate::audit::Source;
+use crate::config::Config;
+use crate::emergency::EmergencyStop;
+use crate::hotkeys::HotkeyAction;
+use crate::json::Json;
+use crate::supervisor::SubsystemContext;
+use crate::{AppState, Command};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const SAMPLE_RATE: u32 = 16_000;
+/// Audio handed to the recognizer at a time: 100 ms of 16-bit samples.
+const CHUNK_BYTES: usize = SAMPLE_RATE as usize / 10 * 2;
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// The recognizer's word for anything outside the grammar.
+const UNKNOWN: &str = "[unk]";
+
+#[cfg(target_os = "macos")]
+const DEFAULT_LIBRARY: &str = "libvosk.dylib";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_LIBRARY: &str = "libvosk.so";
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoicePhrase {
+    pub phrase: String,
+    pub action: HotkeyAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceConfig {
+    pub enabled: bool,
+    /// Directory of an unpacked Vosk model.
+    pub model: String,
+    /// Path to `libvosk`; empty to look it up in the usual places.
+    pub library: String,
+    /// Command writing raw 16 kHz mono signed 16-bit audio to its output;
+    /// empty for the platform's usual recorder.
+    pub capture: Vec<String>,
+    pub phrases: Vec<VoicePhrase>,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: String::new(),
+            library: String::new(),
+            capture: Vec::new(),
+            phrases: vec![
+                VoicePhrase {
+                    phrase: "power on".to_string(),
+                    action: HotkeyAction::On(1),
+                },
+                VoicePhrase {
+                    phrase: "power off".to_string(),
+                    action: HotkeyAction::Off(1),
+                },
+            ],
+        }
+    }
+}
+
+impl VoiceConfig {
+    fn capture_command(&self) -> Vec<String> {
+        if !self.capture.is_empty() {
+            return self.capture.clone();
+        }
+        let rate = SAMPLE_RATE.to_string();
+        let args: &[&str] = if cfg!(target_os = "macos") {
+            &[
+                "rec", "-q", "-t", "raw", "-b", "16", "-e", "signed-integer", "-c", "1", "-r",
+                &rate, "-",
+            ]
+        } else {
+            &["arecord", "-q", "-t", "raw", "-f", "S16_LE", "-c", "1", "-r", &rate]
+        };
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// The phrases as the recognizer hears them: lower case, single spaces.
+    fn grammar(&self) -> Vec<String> {
+        self.phrases.iter().map(|p| normalize(&p.phrase)).filter(|p| !p.is_empty()).collect()
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// ============================================================================
+// RECOGNIZER
+// ============================================================================
+
+#[cfg(unix)]
+mod platform {
+    use std::ffi::{c_char, c_float, c_int, c_void, CStr, CString};
+
+    type ModelNew = unsafe extern "C" fn(path: *const c_char) -> *mut c_void;
+    type RecognizerNew = unsafe extern "C" fn(
+        model: *mut c_void,
+        rate: c_float,
+        grammar: *const c_char,
+    ) -> *mut c_void;
+    type AcceptWaveform =
+        unsafe extern "C" fn(recognizer: *mut c_void, data: *const c_char, len: c_int) -> c_int;
+    type RecognizerResult = unsafe extern "C" fn(recognizer: *mut c_void) -> *const c_char;
+    type Free = unsafe extern "C" fn(object: *mut c_void);
+    type SetLogLevel = unsafe extern "C" fn(level: c_int);
+
+    /// The functions used from `vosk_api.h`.
+    struct Api {
+        set_log_level: SetLogLevel,
+        model_new: ModelNew,
+        model_free: Free,
+        recognizer_new_grm: RecognizerNew,
+        recognizer_accept_waveform: AcceptWaveform,
+        recognizer_result: RecognizerResult,
+        recognizer_free: Free,
+    }
+
+    impl Api {
+        /// SAFETY: `library` has to be an open handle to libvosk.
+        unsafe fn load(library: *mut c_void) -> Result<Self, String> {
+            // SAFETY: the signatures follow vosk_api.h
+            unsafe {
+                Ok(Api {
+                    set_log_level: symbol(library, c"vosk_set_log_level")?,
+                    model_new: symbol(library, c"vosk_model_new")?,
+                    model_free: symbol(library, c"vosk_model_free")?,
+                    recognizer_new_grm: symbol(library, c"vosk_recognizer_new_grm")?,
+                    recognizer_accept_waveform: symbol(
+                        library,
+                        c"vosk_recognizer_accept_waveform",
+                    )?,
+                    recognizer_result: symbol(library, c"vosk_recognizer_result")?,
+                    recognizer_free: symbol(library, c"vosk_recognizer_free")?,
+                })
+            }
+        }
+    }
+
+    /// A Vosk recognizer limited to a grammar, with the library it came from.
+    pub struct Recognizer {
+        library: *mut c_void,
+        api: Api,
+        model: *mut c_void,
+        recognizer: *mut c_void,
+    }
+
+    fn c_string(text: &str) -> Result<CString, String> {
+        CString::new(text).map_err(|_| format!("'{}' contains a NUL byte", text))
+    }
+
+    /// Looks up a function in `library`.
+    ///
+    /// SAFETY: `T` has to be the function's signature.
+    unsafe fn symbol<T>(library: *mut c_void, name: &CStr) -> Result<T, String> {
+        // SAFETY: the handle is open and the name NUL-terminated
+        let address = unsafe { libc::dlsym(library, name.as_ptr()) };
+        if address.is_null() {
+            return Err(format!("libvosk has no {}", name.to_string_lossy()));
+        }
+        // SAFETY: the caller names the function's type
+        Ok(unsafe { std::mem::transmute_copy(&address) })
+    }
+
+    impl Recognizer {
+        pub fn new(
+            library: &str,
+            model: &str,
+            grammar: &str,
+            rate: f32,
+        ) -> Result<Self, String> {
+            let path = c_string(library)?;
+            // SAFETY: the path is NUL-terminated
+            let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW) };
+            if handle.is_null() {
+                // SAFETY: dlerror returns a message owned by the loader, or null
+                let error = unsafe { libc::dlerror() };
+                let reason = match error.is_null() {
+                    true => "not found".into(),
+                    // SAFETY: a non-null message is NUL-terminated
+                    false => unsafe { CStr::from_ptr(error) }.to_string_lossy(),
+                };
+                return Err(format!("Failed to load {}: {}", library, reason));
+            }
+            // SAFETY: the handle was just opened
+            let api = match unsafe { Api::load(handle) } {
+                Ok(api) => api,
+                Err(e) => {
+                    // SAFETY: nothing from the library is in use yet
+                    unsafe { libc::dlclose(handle) };
+                    return Err(e);
+                }
+            };
+            // Dropping it frees whatever was made so far
+            let mut recognizer = Recognizer {
+                library: handle,
+                api,
+                model: std::ptr::null_mut(),
+                recognizer: std::ptr::null_mut(),
+            };
+
+            let model_path = c_string(model)?;
+            let grammar = c_string(grammar)?;
+            // SAFETY: the strings are NUL-terminated and outlive the calls;
+            // the model is checked before the recognizer is made from it
+            unsafe {
+                // Kaldi's progress logging would fill the daemon's output
+                (recognizer.api.set_log_level)(-1);
+                recognizer.model = (recognizer.api.model_new)(model_path.as_ptr());
+                if recognizer.model.is_null() {
+                    return Err(format!("Failed to load the Vosk model from {}", model));
+                }
+                recognizer.recognizer =
+                    (recognizer.api.recognizer_new_grm)(recognizer.model, rate, grammar.as_ptr());
+            }
+            if recognizer.recognizer.is_null() {
+                return Err("Failed to create the Vosk recognizer".to_string());
+            }
+            Ok(recognizer)
+        }
+
+        /// Feeds 16-bit samples and returns the result once an utterance
+        /// has ended.
+        pub fn accept(&mut self, samples: &[u8]) -> Result<Option<String>, String> {
+            let len = c_int::try_from(samples.len()).map_err(|_| "Audio chunk too long")?;
+            // SAFETY: the recognizer is live and the buffer holds len bytes
+            let data = samples.as_ptr().cast();
+            let done = unsafe { (self.api.recognizer_accept_waveform)(self.recognizer, data, len) };
+            match done {
+                0 => Ok(None),
+                1 => {
+                    // SAFETY: the result is a NUL-terminated string owned by
+                    // the recognizer until the next call
+                    let text = unsafe {
+                        let result = (self.api.recognizer_result)(self.recognizer);
+                        CStr::from_ptr(result).to_string_lossy().into_owned()
+                    };
+                    Ok(Some(text))
+                }
+                _ => Err("Vosk failed to process the audio".to_string()),
+            }
+        }
+    }
+
+    impl Drop for Recognizer {
+        fn drop(&mut self) {
+            // SAFETY: each object came from this library and is freed once,
+            // the recognizer before its model
+            unsafe {
+                if !self.recognizer.is_null() {
+                    (self.api.recognizer_free)(self.recognizer);
+                }
+                if !self.model.is_null() {
+                    (self.api.model_free)(self.model);
+                }
+                libc::dlclose(self.library);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    pub struct Recognizer;
+
+    impl Recognizer {
+        pub fn new(
+            _library: &str,
+            _model: &str,
+            _grammar: &str,
+            _rate: f32,
+        ) -> Result<Self, String> {
+            Err("Voice commands are only available on Linux and macOS".to_string())
+        }
+
+        pub fn accept(&mut self, _samples: &[u8]) -> Result<Option<String>, String> {
+            Ok(None)
+        }
+    }
+}
+
+// ============================================================================
+// LISTENER
+// ============================================================================
+
+/// The recognizer with the recorder feeding it.
+struct Listener {
+    recognizer: platform::Recognizer,
+    capture: Child,
+}
+
+impl Listener {
+    fn start(settings: &VoiceConfig) -> Result<Self, String> {
+        if settings.model.is_empty() {
+            return Err("Set voice.model to the directory of a Vosk model".to_string());
+        }
+        let library = match settings.library.as_str() {
+            "" => DEFAULT_LIBRARY,
+            library => library,
+        };
+        let mut words: Vec<String> = settings.grammar().iter().map(|p| json_string(p)).collect();
+        words.push(json_string(UNKNOWN));
+        let grammar = format!("[{}]", words.join(","));
+        let recognizer =
+            platform::Recognizer::new(library, &settings.model, &grammar, SAMPLE_RATE as f32)?;
+
+        let command = settings.capture_command();
+        let capture = std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", command[0], e))?;
+        Ok(Self { recognizer, capture })
+    }
+
+    /// Blocks for the next chunk of audio and returns the phrase heard, if
+    /// an utterance ended with one.
+    fn listen(&mut self) -> Result<Option<String>, String> {
+        let mut chunk = [0u8; CHUNK_BYTES];
+        let stdout = self.capture.stdout.as_mut().ok_or("The recorder has no output")?;
+        stdout.read_exact(&mut chunk).map_err(|e| format!("Audio capture stopped: {}", e))?;
+        let Some(result) = self.recognizer.accept(&chunk)? else {
+            return Ok(None);
+        };
+        let text = Json::parse(&result)
+            .and_then(|reply| reply.get("text").and_then(Json::text).map(normalize))
+            .unwrap_or_default();
+        Ok(Some(text).filter(|text| !text.is_empty() && text != UNKNOWN))
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = self.capture.kill();
+        let _ = self.capture.wait();
+    }
+}
+
+fn fire(action: HotkeyAction, state: &AppState, stop: &EmergencyStop) {
+    let cmd = match action {
+        HotkeyAction::Toggle(channel) => Command::Toggle(channel),
+        HotkeyAction::On(channel) => Command::TurnOn(channel),
+        HotkeyAction::Off(channel) => Command::TurnOff(channel),
+        HotkeyAction::AllOff => return stop.trigger(),
+    };
+    state.send_command(cmd, Source::Voice);
+}
+
+/// Listens for the configured phrases while enabled. Waits for the config
+/// file to change while disabled, so voice commands can be turned on from
+/// the settings, and restarts the listener whenever it does.
+pub fn run_voice(
+    ctx: &SubsystemContext,
+    state: &AppState,
+    stop: &EmergencyStop,
+) -> Result<(), String> {
+    loop {
+        let modified = Config::modified();
+        let settings = Config::load()?.voice;
+        let mut listener = match settings.enabled {
+            true => Some(Listener::start(&settings)?),
+            false => None,
+        };
+        ctx.mark_running();
+
+        let mut checked = Instant::now();
+        loop {
+            match listener.as_mut() {
+                Some(listener) => {
+                    if let Some(heard) = listener.listen()? {
+                        let phrases = settings.phrases.iter();
+                        for phrase in phrases.filter(|p| normalize(&p.phrase) == heard) {
+                            if !state.read_only {
+                                fire(phrase.action, state, stop);
+                            }
+                        }
+                    }
+                }
+                None => std::thread::sleep(RELOAD_CHECK_INTERVAL),
+            }
+
+            if checked.elapsed() >= RELOAD_CHECK_INTERVAL {
+                checked = Instant::now();
+                if Config::modified() != modified {
+                    break;
+                }
+            }
+        }
+    }
+}
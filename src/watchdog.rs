@@ -0,0 +1,411 @@
+//! Watchdogs that power-cycle a hung device.
+//!
+//! A watchdog checks a device behind one of the relays, e.g. the router or
+//! NAS it powers, every `interval_secs`. After `failures` failed checks in a
+//! row it power-cycles the channel: off, `off_secs` of pause, on again. It
+//! then leaves the host `cooldown_secs` to boot before checking again, and
+//! gives up after `max_cycles` cycles without the host coming back; one good
+//! check resets both counts.
+//!
+//! A check either pings the host with the system's `ping`, or fetches an
+//! `http://` URL and expects a status (any 2xx by default) and optionally a
+//! text in the body, so a hung service counts even while its host still
+//! answers pings. Every failed check, cycle and recovery is appended to
+//! `watchdog.log` in the platform data directory as
+//! `<unix seconds>\t<watchdog>\t<message>`; cycles, give-ups and recoveries
+//! are raised as notifications too.
+
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::audit::Source;
+use crate::batch::{Batch, BatchStep};
+use crate::config::Config;
+use crate::email::{self, EmailEvent};
+use crate::history::unix_now;
+use crate::http;
+use crate::notify::Notifier;
+use crate::supervisor::SubsystemContext;
+use crate::Command;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const TICK: Duration = Duration::from_secs(1);
+const LOG_FILE: &str = "watchdog.log";
+/// How long past its own waits a power cycle may take before it is
+/// reported as lost.
+const CYCLE_GRACE: Duration = Duration::from_secs(30);
+/// How much of a response body is searched for `contains`.
+const MAX_BODY: usize = 64 * 1024;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WatchdogCheck {
+    /// The host answers a ping.
+    Ping { host: String },
+    /// A GET of the URL succeeds.
+    Http {
+        url: String,
+        /// Status that counts as healthy; any 2xx when unset.
+        #[serde(default)]
+        status: Option<u16>,
+        /// Text the body must contain.
+        #[serde(default)]
+        contains: Option<String>,
+    },
+}
+
+impl Default for WatchdogCheck {
+    fn default() -> Self {
+        WatchdogCheck::Ping { host: String::new() }
+    }
+}
+
+impl WatchdogCheck {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            WatchdogCheck::Ping { host } if host.is_empty() || host.starts_with('-') => {
+                Err(format!("invalid host '{}'", host))
+            }
+            WatchdogCheck::Ping { .. } => Ok(()),
+            WatchdogCheck::Http { url, .. } if !url.starts_with("http://") => {
+                Err(format!("{}: only http:// URLs are supported", url))
+            }
+            WatchdogCheck::Http { .. } => Ok(()),
+        }
+    }
+
+    /// Runs the check: why it failed, if it did, or `Err` if it couldn't
+    /// run at all.
+    fn run(&self, timeout: Duration) -> Result<Option<String>, String> {
+        match self {
+            WatchdogCheck::Ping { host } => ping(host, timeout),
+            WatchdogCheck::Http { url, status, contains } => {
+                Ok(check_http(url, *status, contains.as_deref(), timeout))
+            }
+        }
+    }
+}
+
+/// Pings `host` once: why it failed, if it did, or `Err` if `ping`
+/// couldn't run.
+pub fn ping(host: &str, timeout: Duration) -> Result<Option<String>, String> {
+    platform::ping(host, timeout)
+}
+
+/// Why an HTTP check failed, if it did. A device that can't be reached
+/// fails the check rather than the watchdog.
+fn check_http(
+    url: &str,
+    status: Option<u16>,
+    contains: Option<&str>,
+    timeout: Duration,
+) -> Option<String> {
+    let limit = if contains.is_some() { MAX_BODY } else { 0 };
+    let response = match http::request_with_body("GET", url, &[], timeout, limit) {
+        Ok(response) => response,
+        Err(e) => return Some(e),
+    };
+    let healthy = match status {
+        Some(expected) => response.status == expected,
+        None => (200..300).contains(&response.status),
+    };
+    if !healthy {
+        return Some(format!("{} answered {}", url, response.status));
+    }
+    match contains {
+        Some(text) if !String::from_utf8_lossy(&response.body).contains(text) => {
+            Some(format!("{}: body does not contain '{}'", url, text))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Name used in the log and notifications; defaults to the host or URL.
+    pub name: String,
+    pub check: WatchdogCheck,
+    /// Channel that powers the host.
+    pub channel: u8,
+    pub interval_secs: u64,
+    /// How long a single check may take.
+    pub timeout_secs: u64,
+    /// Failed checks in a row that trigger a power cycle.
+    pub failures: u32,
+    /// How long the channel stays off during a cycle.
+    pub off_secs: u64,
+    /// Time the host gets to come back after a cycle.
+    pub cooldown_secs: u64,
+    /// Cycles in a row before giving up; 0 for no limit.
+    pub max_cycles: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            check: WatchdogCheck::default(),
+            channel: 1,
+            interval_secs: 30,
+            timeout_secs: 2,
+            failures: 3,
+            off_secs: 10,
+            cooldown_secs: 300,
+            max_cycles: 3,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn display_name(&self) -> String {
+        if !self.name.is_empty() {
+            return self.name.clone();
+        }
+        match &self.check {
+            WatchdogCheck::Ping { host } => host.clone(),
+            WatchdogCheck::Http { url, .. } => url.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let context = |e: String| format!("Watchdog '{}': {}", self.display_name(), e);
+        self.check.validate().map_err(context)?;
+        if self.failures == 0 || self.interval_secs == 0 {
+            return Err(context("failures and interval_secs must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// LOG
+// ============================================================================
+
+pub fn log_path() -> Result<PathBuf, String> {
+    directories::ProjectDirs::from("", "", "usb-power-relay")
+        .map(|dirs| dirs.data_dir().join(LOG_FILE))
+        .ok_or_else(|| "No data directory available".to_string())
+}
+
+/// Appends a line to the watchdog log, and to stderr.
+fn log(watchdog: &str, message: &str) {
+    eprintln!("Watchdog {}: {}", watchdog, message);
+    let result = log_path().and_then(|path| {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        writeln!(file, "{}\t{}\t{}", unix_now(), watchdog, message)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    });
+    if let Err(e) = result {
+        eprintln!("{}", e);
+    }
+}
+
+// ============================================================================
+// WATCHDOG SUBSYSTEM
+// ============================================================================
+
+struct Watch {
+    config: WatchdogConfig,
+    /// Failed checks in a row.
+    failures: u32,
+    /// Power cycles since the last good check.
+    cycles: u32,
+    gave_up: bool,
+    next_check: Instant,
+}
+
+impl Watch {
+    fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            failures: 0,
+            cycles: 0,
+            gave_up: false,
+            next_check: Instant::now(),
+        }
+    }
+
+    fn check(
+        &mut self,
+        commands: &mpsc::UnboundedSender<(Command, Source)>,
+        notifier: &Notifier,
+    ) -> Result<(), String> {
+        let config = &self.config;
+        let name = config.display_name();
+        let result = config.check.run(Duration::from_secs(config.timeout_secs.max(1)))?;
+        self.next_check = Instant::now() + Duration::from_secs(config.interval_secs);
+
+        let reason = match result {
+            None => {
+                if self.cycles > 0 || self.gave_up {
+                    let message = format!("{} is back after {} power cycle(s)", name, self.cycles);
+                    log(&name, &message);
+                    notifier.notify("Watchdog recovered", message);
+                } else if self.failures > 0 {
+                    log(&name, "check passed again");
+                }
+                self.failures = 0;
+                self.cycles = 0;
+                self.gave_up = false;
+                return Ok(());
+            }
+            // Already reported; waits quietly for the host to answer
+            Some(_) if self.gave_up => return Ok(()),
+            Some(reason) => reason,
+        };
+
+        self.failures += 1;
+        log(&name, &format!("check failed ({}/{}): {}", self.failures, config.failures, reason));
+        if self.failures < config.failures {
+            return Ok(());
+        }
+        self.failures = 0;
+
+        if config.max_cycles > 0 && self.cycles >= config.max_cycles {
+            self.gave_up = true;
+            let message = format!(
+                "{} still fails after {} power cycle(s); giving up until it answers again",
+                name, self.cycles
+            );
+            log(&name, &message);
+            notifier.notify("Watchdog gave up", message);
+            return Ok(());
+        }
+
+        self.cycles += 1;
+        let channel = config.channel;
+        let message =
+            format!("power-cycling channel {} (cycle {}): {}", channel, self.cycles, reason);
+        log(&name, &message);
+        notifier.notify("Watchdog", format!("{}: {}", name, message));
+        email::send(
+            EmailEvent::WatchdogCycle,
+            vec![
+                ("watchdog", name.clone()),
+                ("channel", channel.to_string()),
+                ("message", reason.clone()),
+            ],
+        );
+
+        let batch = Batch {
+            steps: vec![
+                BatchStep::Off(channel),
+                BatchStep::Wait(config.off_secs * 1000),
+                BatchStep::On(channel),
+            ],
+            start_by: None,
+        };
+        let wait = batch.duration() + CYCLE_GRACE;
+        let (reply, result) = std::sync::mpsc::channel();
+        let source = Source::Watchdog(name.clone());
+        if commands.send((Command::Batch(batch, Some(reply)), source)).is_err() {
+            return Ok(());
+        }
+        match result.recv_timeout(wait) {
+            Ok(Ok(_)) => log(&name, &format!("channel {} is back on", channel)),
+            Ok(Err(e)) => {
+                log(&name, &format!("power cycle failed: {}", e));
+                notifier.notify("Watchdog", format!("{}: power cycle failed: {}", name, e));
+            }
+            Err(_) => log(&name, "power cycle not confirmed in time"),
+        }
+        self.next_check = Instant::now() + Duration::from_secs(config.cooldown_secs);
+        Ok(())
+    }
+}
+
+pub fn run_watchdogs(
+    ctx: &SubsystemContext,
+    commands: &mpsc::UnboundedSender<(Command, Source)>,
+    notifier: &Notifier,
+) -> Result<(), String> {
+    let mut modified = Config::modified();
+    let mut configs = Config::load()?.watchdogs;
+    let mut watches: Vec<Watch> = configs.iter().cloned().map(Watch::new).collect();
+    ctx.mark_running();
+
+    while !commands.is_closed() {
+        let current = Config::modified();
+        if current != modified {
+            modified = current;
+            let loaded = Config::load()?.watchdogs;
+            // Edits start the watchdogs over
+            if loaded != configs {
+                watches = loaded.iter().cloned().map(Watch::new).collect();
+                configs = loaded;
+            }
+        }
+
+        for watch in watches.iter_mut().filter(|w| Instant::now() >= w.next_check) {
+            watch.check(commands, notifier)?;
+        }
+
+        std::thread::sleep(TICK);
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(not(windows))]
+mod platform {
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    pub fn ping(host: &str, timeout: Duration) -> Result<Option<String>, String> {
+        // macOS spells the overall deadline -t, Linux -W
+        #[cfg(target_os = "macos")]
+        let deadline = "-t";
+        #[cfg(not(target_os = "macos"))]
+        let deadline = "-W";
+        let status = Command::new("ping")
+            .args(["-c", "1", deadline, &timeout.as_secs().to_string(), host])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("Failed to run ping: {}", e))?;
+        Ok((!status.success()).then(|| format!("no reply from {}", host)))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    pub fn ping(host: &str, timeout: Duration) -> Result<Option<String>, String> {
+        let output = Command::new("ping")
+            .args(["-n", "1", "-w", &timeout.as_millis().to_string(), host])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run ping: {}", e))?;
+        // ping exits 0 for "destination unreachable" replies from a router,
+        // so only an echo reply, which carries a TTL, counts
+        let replied = String::from_utf8_lossy(&output.stdout).contains("TTL=");
+        Ok((!replied).then(|| format!("no reply from {}", host)))
+    }
+}
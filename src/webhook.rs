@@ -0,0 +1,209 @@
+//! Channel webhooks.
+//!
+//! Every time a channel enters a new ON/OFF state, each webhook watching it
+//! receives a JSON POST describing the change, including the previous state
+//! and how long the channel was in it, so receivers need no state of their
+//! own:
+//!
+//! ```json
+//! {"channel":2,"label":"Lamp","state":"on","previous_state":"off",
+//!  "previous_since":1718000000,"previous_duration_secs":5400,"time":1718005400}
+//! ```
+//!
+//! A webhook with `format: Slack` or `Discord` gets a chat message made from
+//! its `template` instead, and with `notifications: true` the app's
+//! notifications and errors too, so a team channel can follow the bench.
+//!
+//! Deliveries run on their own thread; a slow or failing receiver never
+//! delays switching. `https://` URLs, which chat services use, are posted
+//! with `curl`.
+
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::api::json_string;
+use crate::config::Config;
+use crate::http;
+use crate::locale::format_duration;
+use crate::push::{curl, curl_option};
+use crate::supervisor::SubsystemContext;
+use crate::{LastChange, RelayState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TEMPLATE: &str = "{label} switched {state}";
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+/// What a webhook is sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookFormat {
+    /// The change as JSON, see the module docs.
+    #[default]
+    Json,
+    /// `{"text": ...}`, for Slack incoming webhooks.
+    Slack,
+    /// `{"content": ...}`, for Discord webhooks.
+    Discord,
+}
+
+impl WebhookFormat {
+    /// The payload carrying a chat message.
+    fn message(&self, text: &str) -> Option<String> {
+        match self {
+            WebhookFormat::Json => None,
+            WebhookFormat::Slack => Some(format!("{{\"text\":{}}}", json_string(text))),
+            WebhookFormat::Discord => Some(format!("{{\"content\":{}}}", json_string(text))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// `http://` or `https://` URL that receives the POSTs.
+    pub url: String,
+    /// Channels to report; empty means all.
+    pub channels: Vec<u8>,
+    pub format: WebhookFormat,
+    /// Chat message for a change, with `{label}`, `{channel}`, `{state}`,
+    /// `{previous_state}` and `{duration}`; empty uses "{label} switched
+    /// {state}".
+    pub template: String,
+    /// Also post the app's notifications, for chat formats.
+    pub notifications: bool,
+}
+
+impl WebhookConfig {
+    fn watches(&self, channel: u8) -> bool {
+        self.channels.is_empty() || self.channels.contains(&channel)
+    }
+
+    /// What to post for `change` of the channel labelled `label`.
+    fn change_payload(&self, change: &ChannelChange, label: &str) -> String {
+        let template = if self.template.is_empty() { DEFAULT_TEMPLATE } else { &self.template };
+        let (previous_state, duration) = match change.previous {
+            Some((state, since)) => {
+                (state.name(), format_duration(change.time.saturating_sub(since)))
+            }
+            None => ("unknown", "?".to_string()),
+        };
+        let text = template
+            .replace("{label}", label)
+            .replace("{channel}", &change.channel.to_string())
+            .replace("{state}", &change.state.name().to_uppercase())
+            .replace("{previous_state}", &previous_state.to_uppercase())
+            .replace("{duration}", &duration);
+        self.format.message(&text).unwrap_or_else(|| change.payload(label))
+    }
+}
+
+// ============================================================================
+// CHANGES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelChange {
+    pub channel: u8,
+    pub state: RelayState,
+    /// The state before, with the Unix time it was entered, if known.
+    pub previous: LastChange,
+    pub time: u64,
+}
+
+impl ChannelChange {
+    fn payload(&self, label: &str) -> String {
+        let (previous_state, since, duration) = match self.previous {
+            Some((state, since)) => (
+                format!("\"{}\"", state.name()),
+                since.to_string(),
+                self.time.saturating_sub(since).to_string(),
+            ),
+            None => ("null".to_string(), "null".to_string(), "null".to_string()),
+        };
+        format!(
+            "{{\"channel\":{},\"label\":{},\"state\":\"{}\",\"previous_state\":{},\
+             \"previous_since\":{},\"previous_duration_secs\":{},\"time\":{}}}",
+            self.channel,
+            json_string(label),
+            self.state.name(),
+            previous_state,
+            since,
+            duration,
+            self.time
+        )
+    }
+}
+
+/// Where the app queues changes for delivery.
+pub type WebhookQueue = mpsc::Sender<ChannelChange>;
+
+// ============================================================================
+// DELIVERY
+// ============================================================================
+
+/// Posts queued changes to every webhook watching the channel. Failed
+/// deliveries are logged and dropped.
+pub fn run_webhooks(
+    ctx: &SubsystemContext,
+    changes: &mpsc::Receiver<ChannelChange>,
+) -> Result<(), String> {
+    ctx.mark_running();
+
+    while let Ok(change) = changes.recv() {
+        let config = Config::load()?;
+        let hooks: Vec<_> = config.webhooks.iter().filter(|h| h.watches(change.channel)).collect();
+        if hooks.is_empty() {
+            continue;
+        }
+
+        let label = config.device.label(change.channel);
+        for hook in hooks {
+            post(&hook.url, &hook.change_payload(&change, &label));
+        }
+    }
+
+    Ok(())
+}
+
+/// Posts a notification to every chat webhook that wants them, in the
+/// background.
+pub fn post_notification(title: String, message: String) {
+    std::thread::spawn(move || {
+        let Ok(config) = Config::load() else {
+            return;
+        };
+        let text = format!("{}: {}", title, message);
+        for hook in config.webhooks.iter().filter(|h| h.notifications) {
+            if let Some(payload) = hook.format.message(&text) {
+                post(&hook.url, &payload);
+            }
+        }
+    });
+}
+
+/// Posts `payload` to `url`, logging failures.
+fn post(url: &str, payload: &str) {
+    let result = if url.starts_with("https://") {
+        let options = curl_option("url", url)
+            + &curl_option("header", "Content-Type: application/json")
+            + &curl_option("data-raw", payload);
+        curl(&options)
+    } else {
+        let headers = [("Content-Type", "application/json")];
+        match http::request("POST", url, &headers, payload.as_bytes(), DELIVERY_TIMEOUT) {
+            Ok(response) if (200..300).contains(&response.status) => Ok(()),
+            Ok(response) => Err(format!("HTTP {}", response.status)),
+            Err(e) => Err(e),
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Webhook {}: {}", url, e);
+    }
+}
@@ -0,0 +1,175 @@
+//! Browser control page.
+//!
+//! The HTTP API serves a single self-contained page at `/`, laid out for
+//! phones and tablets: every channel with its indicator and ON/OFF buttons,
+//! plus ALL OFF. It talks to the same JSON endpoints as any other client and
+//! follows `/watch` for live states. The page itself needs no token; when the
+//! API asks for one, the page prompts and keeps it in the browser.
+
+use crate::api::json_string;
+use crate::config::DeviceConfig;
+
+// ============================================================================
+// PAGE
+// ============================================================================
+
+/// The page, with `SETTINGS_JSON` standing in for the channel labels.
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <meta name="theme-color" content="#1e1e1e">
+  <title>USB Power Relay</title>
+  <style>
+    body { margin: 0; background: #1e1e1e; color: #ddd; font: 16px system-ui, sans-serif; }
+    main { max-width: 32rem; margin: 0 auto; padding: 1rem; }
+    h1 { font-size: 1.2rem; margin: 0 0 .25rem; }
+    #status { color: #999; font-size: .85rem; margin-bottom: 1rem; }
+    .channel { display: flex; align-items: center; gap: .75rem; padding: .75rem;
+               margin-bottom: .5rem; background: #2a2a2a; border-radius: .5rem; }
+    .dot { width: 1.1rem; height: 1.1rem; border-radius: 50%; flex: none; background: #555; }
+    .on .dot { background: #3c3; box-shadow: 0 0 .5rem #3c3; }
+    .error .dot { background: #d33; }
+    .unknown .dot { background: #c90; }
+    .label { flex: 1; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+    .state { width: 4.5rem; color: #999; font-size: .85rem; }
+    button { font: inherit; border: 0; border-radius: .4rem; padding: .6rem .9rem;
+             color: #fff; background: #444; }
+    button:active { filter: brightness(1.3); }
+    button:disabled { opacity: .4; }
+    .btn-on { background: #2a7a2a; }
+    #all-off { width: 100%; margin-top: .75rem; padding: .9rem; background: #a22;
+               font-weight: bold; }
+    #token { display: none; margin-bottom: 1rem; }
+    #token input { font: inherit; width: 100%; box-sizing: border-box; padding: .6rem;
+                   margin: .4rem 0; border-radius: .4rem; border: 1px solid #555;
+                   background: #111; color: #ddd; }
+  </style>
+</head>
+<body>
+<main>
+  <h1>USB Power Relay</h1>
+  <div id="status">Connecting…</div>
+  <form id="token">
+    <label for="token-input">This API needs a token:</label>
+    <input id="token-input" type="password" autocomplete="current-password">
+    <button type="submit">Save</button>
+  </form>
+  <div id="channels"></div>
+  <button id="all-off">ALL OFF</button>
+</main>
+<script>
+const SETTINGS = SETTINGS_JSON;
+const TEXT = { on: "ON", off: "OFF", error: "ERROR", unknown: "UNKNOWN" };
+const statusLine = document.getElementById("status");
+const tokenForm = document.getElementById("token");
+let token = localStorage.getItem("usb-power-relay-token") || "";
+
+function headers() {
+  return token ? { Authorization: "Bearer " + token } : {};
+}
+
+function askForToken() {
+  tokenForm.style.display = "block";
+  statusLine.textContent = "Waiting for a token";
+}
+
+tokenForm.addEventListener("submit", event => {
+  event.preventDefault();
+  token = document.getElementById("token-input").value.trim();
+  localStorage.setItem("usb-power-relay-token", token);
+  tokenForm.style.display = "none";
+  watch();
+});
+
+const rows = SETTINGS.labels.map((label, i) => {
+  const row = document.createElement("div");
+  row.className = "channel unknown";
+  row.innerHTML = '<span class="dot"></span><span class="label"></span>' +
+    '<span class="state">…</span>' +
+    '<button class="btn-on">ON</button><button class="btn-off">OFF</button>';
+  row.querySelector(".label").textContent = label;
+  row.querySelector(".btn-on").onclick = () => send("/channels/" + (i + 1) + "/on");
+  row.querySelector(".btn-off").onclick = () => send("/channels/" + (i + 1) + "/off");
+  document.getElementById("channels").appendChild(row);
+  return row;
+});
+document.getElementById("all-off").onclick = () => send("/all-off");
+if (SETTINGS.readOnly) {
+  document.querySelectorAll("button").forEach(button => button.disabled = true);
+}
+
+function show(channels) {
+  for (const { channel, state } of channels) {
+    const row = rows[channel - 1];
+    if (row) {
+      row.className = "channel " + state;
+      row.querySelector(".state").textContent = TEXT[state] || state;
+    }
+  }
+}
+
+async function send(path) {
+  try {
+    const response = await fetch(path, { method: "POST", headers: headers() });
+    if (response.status === 401) {
+      askForToken();
+    } else if (!response.ok) {
+      const body = await response.json().catch(() => ({}));
+      statusLine.textContent = body.error || "Request failed (" + response.status + ")";
+    }
+  } catch (e) {
+    statusLine.textContent = "Not connected";
+  }
+}
+
+// `/watch` is read with fetch, since EventSource can't send a token
+let watching = 0;
+async function watch() {
+  const run = ++watching;
+  try {
+    const response = await fetch("/watch", { headers: headers() });
+    if (response.status === 401) {
+      askForToken();
+      return;
+    }
+    statusLine.textContent = SETTINGS.readOnly ? "Live (read-only)" : "Live";
+    const reader = response.body.getReader();
+    const decoder = new TextDecoder();
+    let buffer = "";
+    while (run === watching) {
+      const { value, done } = await reader.read();
+      if (done) break;
+      buffer += decoder.decode(value, { stream: true });
+      let end;
+      while ((end = buffer.indexOf("\n\n")) >= 0) {
+        const event = buffer.slice(0, end);
+        buffer = buffer.slice(end + 2);
+        for (const line of event.split("\n")) {
+          if (line.startsWith("data:")) show(JSON.parse(line.slice(5)).channels);
+        }
+      }
+    }
+  } catch (e) {
+    // Reconnected below
+  }
+  if (run === watching) {
+    statusLine.textContent = "Not connected; retrying…";
+    setTimeout(() => { if (run === watching) watch(); }, 2000);
+  }
+}
+watch();
+</script>
+</body>
+</html>
+"##;
+
+/// The page for `device`'s channels.
+pub fn page(device: &DeviceConfig, read_only: bool) -> String {
+    let labels: Vec<String> =
+        (1..=device.channels).map(|ch| json_string(&device.label(ch))).collect();
+    let settings = format!("{{\"labels\":[{}],\"readOnly\":{}}}", labels.join(","), read_only);
+    // A label can't end the script early
+    PAGE.replace("SETTINGS_JSON", &settings.replace("</", "<\\/"))
+}
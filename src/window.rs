@@ -0,0 +1,237 @@
+//! Window behavior.
+//!
+//! The window can start minimized and can minimize instead of quitting when
+//! it is closed, so the device worker, schedules and the API keep running
+//! with the window out of the way. There is no tray icon, except for the
+//! macOS menu bar item (see [`crate::menubar`]); launching the app again (or
+//! `usb-power-relay show`) brings the window back.
+//!
+//! Everything is drawn at the system's display scale times the interface
+//! scale from the settings, and Ctrl+Plus/Minus zooms further for the
+//! session; the window grows or shrinks with it so nothing is clipped.
+//!
+//! In mini mode the window shrinks to a borderless, always-on-top strip
+//! with an indicator and a toggle per channel, for a screen corner. Kiosk
+//! mode (see [`crate::kiosk`]) takes the whole screen instead.
+//!
+//! egui only carries out window commands while it draws, and a minimized
+//! window doesn't draw, so the window is restored through the native window
+//! system where that is possible (X11, Windows and macOS). Elsewhere the
+//! egui commands apply once the window is drawn again.
+
+use eframe::egui;
+use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Size of the full window.
+pub const FULL_SIZE: egui::Vec2 = egui::vec2(350.0, 380.0);
+/// Width of the mini window and the height of one of its rows.
+pub const MINI_WIDTH: f32 = 170.0;
+pub const MINI_ROW: f32 = 26.0;
+/// Range of the interface scale, in percent.
+pub const MIN_SCALE: u16 = 50;
+pub const MAX_SCALE: u16 = 300;
+
+// ============================================================================
+// SETTINGS
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub start_minimized: bool,
+    /// Minimize instead of quitting when the window is closed; the toolbar
+    /// then has a Quit button.
+    pub close_to_background: bool,
+    /// Start in mini mode.
+    pub mini: bool,
+    /// Start in kiosk mode; takes precedence over mini mode.
+    pub kiosk: bool,
+    /// On macOS, run from a menu bar item with the window hidden; see
+    /// [`crate::menubar`].
+    pub menu_bar: bool,
+    /// Interface scale in percent, on top of the system's display scale.
+    pub scale: u16,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            start_minimized: false,
+            close_to_background: false,
+            mini: false,
+            kiosk: false,
+            menu_bar: false,
+            scale: 100,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// The interface scale as an egui zoom factor.
+    pub fn zoom(&self) -> f32 {
+        f32::from(self.scale.clamp(MIN_SCALE, MAX_SCALE)) / 100.0
+    }
+}
+
+/// Switches the window between the full layout and mini mode.
+pub fn set_mini(ctx: &egui::Context, mini: bool, channels: u8) {
+    let (size, level) = if mini {
+        let rows = f32::from(channels.max(1));
+        (egui::vec2(MINI_WIDTH, rows * MINI_ROW + 12.0), egui::WindowLevel::AlwaysOnTop)
+    } else {
+        (FULL_SIZE, egui::WindowLevel::Normal)
+    };
+    ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(!mini));
+    ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+}
+
+/// Switches kiosk mode, fullscreen without decorations, on or off.
+pub fn set_kiosk(ctx: &egui::Context, kiosk: bool) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(kiosk));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(!kiosk));
+    if !kiosk {
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(FULL_SIZE));
+    }
+}
+
+// ============================================================================
+// WINDOW HANDLE
+// ============================================================================
+
+/// Native handle of the window, for the platforms it can be restored on.
+#[derive(Debug, Clone, Copy)]
+enum Native {
+    #[cfg(target_os = "linux")]
+    X11(u32),
+    #[cfg(windows)]
+    Win32(isize),
+    /// The window's `NSView`.
+    #[cfg(target_os = "macos")]
+    AppKit(usize),
+}
+
+/// The GUI window, shareable with background threads.
+#[derive(Clone)]
+pub struct Window {
+    ctx: egui::Context,
+    native: Option<Native>,
+}
+
+impl Window {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let native = cc.window_handle().ok().and_then(|handle| match handle.as_raw() {
+            #[cfg(target_os = "linux")]
+            RawWindowHandle::Xlib(h) => u32::try_from(h.window).ok().map(Native::X11),
+            #[cfg(target_os = "linux")]
+            RawWindowHandle::Xcb(h) => Some(Native::X11(h.window.get())),
+            #[cfg(windows)]
+            RawWindowHandle::Win32(h) => Some(Native::Win32(h.hwnd.get())),
+            #[cfg(target_os = "macos")]
+            RawWindowHandle::AppKit(h) => Some(Native::AppKit(h.ns_view.as_ptr() as usize)),
+            _ => None,
+        });
+        Self {
+            ctx: cc.egui_ctx.clone(),
+            native,
+        }
+    }
+
+    /// Restores, shows and focuses the window.
+    pub fn show(&self) {
+        if let Some(native) = self.native {
+            if let Err(e) = platform::restore(native) {
+                eprintln!("Failed to restore the window: {}", e);
+            }
+        }
+        self.ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+        self.ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+        self.ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        self.ctx.request_repaint();
+    }
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Native;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt, EventMask};
+
+    /// Source indication for `_NET_ACTIVE_WINDOW`: a normal application.
+    const SOURCE_APPLICATION: u32 = 1;
+
+    pub fn restore(native: Native) -> Result<(), String> {
+        let Native::X11(window) = native;
+        let failed = |e: &dyn std::fmt::Display| format!("X11: {}", e);
+        let (conn, screen) = x11rb::connect(None).map_err(|e| failed(&e))?;
+        let root = conn.setup().roots[screen].root;
+        let active = conn
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+            .map_err(|e| failed(&e))?
+            .reply()
+            .map_err(|e| failed(&e))?
+            .atom;
+
+        // Window managers deiconify and raise a window they are asked to
+        // activate
+        conn.map_window(window).map_err(|e| failed(&e))?;
+        let event = ClientMessageEvent::new(32, window, active, [SOURCE_APPLICATION, 0, 0, 0, 0]);
+        let mask = EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY;
+        conn.send_event(false, root, mask, event).map_err(|e| failed(&e))?;
+        conn.flush().map_err(|e| failed(&e))?;
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Native;
+
+    const SW_RESTORE: i32 = 9;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn ShowWindow(hwnd: isize, show: i32) -> i32;
+        fn SetForegroundWindow(hwnd: isize) -> i32;
+    }
+
+    pub fn restore(native: Native) -> Result<(), String> {
+        let Native::Win32(hwnd) = native;
+        // SAFETY: the handle belongs to this process's window, which lives
+        // as long as the process; both calls tolerate a stale handle
+        unsafe {
+            ShowWindow(hwnd, SW_RESTORE);
+            SetForegroundWindow(hwnd);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Native;
+
+    pub fn restore(native: Native) -> Result<(), String> {
+        let Native::AppKit(view) = native;
+        crate::menubar::order_front(view);
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod platform {
+    use super::Native;
+
+    pub fn restore(native: Native) -> Result<(), String> {
+        match native {}
+    }
+}
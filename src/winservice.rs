@@ -0,0 +1,272 @@
+//! Running the daemon as a Windows service.
+//!
+//! `--install-service` registers the executable with the service control
+//! manager as an automatically started service under LocalSystem, so the
+//! scheduler, watchdogs and HTTP API keep running with nobody logged in.
+//! The service is restarted when it crashes or stops with an error. The
+//! manager starts it as `--run-as-service`, which runs the same loop as
+//! `--daemon` and stops it, applying the exit policy, when the service is
+//! stopped or Windows shuts down. `--uninstall-service` stops and removes
+//! it. Both need an elevated prompt.
+
+// ============================================================================
+// ENTRY POINTS
+// ============================================================================
+
+/// Registers the service, replacing nothing: an installed one is an error.
+pub fn install() -> Result<(), String> {
+    platform::install()
+}
+
+/// Stops the service if it is running and removes it.
+pub fn uninstall() -> Result<(), String> {
+    platform::uninstall()
+}
+
+/// Runs as the service until it is stopped and returns the exit code.
+pub fn run() -> i32 {
+    platform::run()
+}
+
+// ============================================================================
+// PLATFORM
+// ============================================================================
+
+#[cfg(not(windows))]
+mod platform {
+    const UNSUPPORTED: &str = "Windows services are only available on Windows";
+
+    pub fn install() -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn uninstall() -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn run() -> i32 {
+        eprintln!("Error: {}; use --daemon", UNSUPPORTED);
+        1
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::{c_void, OsStr};
+    use std::os::windows::ffi::OsStrExt;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicI32, AtomicIsize, Ordering};
+
+    use crate::{daemon, ipc};
+
+    /// The flag the service control manager starts the executable with.
+    const SERVICE_FLAG: &str = "--run-as-service";
+    const SERVICE_NAME: &str = "UsbPowerRelay";
+    const DISPLAY_NAME: &str = "USB Power Relay";
+    const DESCRIPTION: &str = "Runs the USB relay's schedules, watchdogs and HTTP API";
+    /// Restarts after 5 s, twice, then after a minute; the count resets
+    /// after a day without failures.
+    const RECOVERY: &[&str] =
+        &["reset=", "86400", "actions=", "restart/5000/restart/5000/restart/60000"];
+
+    type StatusHandle = isize;
+    type ServiceMain = unsafe extern "system" fn(argc: u32, argv: *mut *mut u16);
+    type HandlerEx = unsafe extern "system" fn(
+        control: u32,
+        event_type: u32,
+        event_data: *mut c_void,
+        context: *mut c_void,
+    ) -> u32;
+
+    const SERVICE_WIN32_OWN_PROCESS: u32 = 0x10;
+    const SERVICE_STOPPED: u32 = 1;
+    const SERVICE_START_PENDING: u32 = 2;
+    const SERVICE_STOP_PENDING: u32 = 3;
+    const SERVICE_RUNNING: u32 = 4;
+    const SERVICE_ACCEPT_STOP: u32 = 0x1;
+    const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x4;
+    const SERVICE_CONTROL_STOP: u32 = 1;
+    const SERVICE_CONTROL_INTERROGATE: u32 = 4;
+    const SERVICE_CONTROL_SHUTDOWN: u32 = 5;
+    const NO_ERROR: u32 = 0;
+    const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+    const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+    const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+    /// How long stopping may take before the manager gives up on it; the
+    /// exit policy switches the channels first.
+    const STOP_WAIT_HINT_MS: u32 = 30_000;
+
+    #[repr(C)]
+    struct ServiceTableEntry {
+        name: *const u16,
+        main: Option<ServiceMain>,
+    }
+
+    #[repr(C)]
+    struct ServiceStatus {
+        service_type: u32,
+        current_state: u32,
+        controls_accepted: u32,
+        win32_exit_code: u32,
+        service_specific_exit_code: u32,
+        check_point: u32,
+        wait_hint: u32,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn StartServiceCtrlDispatcherW(table: *const ServiceTableEntry) -> i32;
+        fn RegisterServiceCtrlHandlerExW(
+            name: *const u16,
+            handler: HandlerEx,
+            context: *mut c_void,
+        ) -> StatusHandle;
+        fn SetServiceStatus(handle: StatusHandle, status: *const ServiceStatus) -> i32;
+    }
+
+    /// Set once the service has registered its control handler.
+    static STATUS_HANDLE: AtomicIsize = AtomicIsize::new(0);
+    static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+    fn wide(text: &str) -> Vec<u16> {
+        OsStr::new(text).encode_wide().chain(Some(0)).collect()
+    }
+
+    fn report(state: u32, exit_code: i32, wait_hint: u32) {
+        let handle = STATUS_HANDLE.load(Ordering::SeqCst);
+        if handle == 0 {
+            return;
+        }
+        let status = ServiceStatus {
+            service_type: SERVICE_WIN32_OWN_PROCESS,
+            current_state: state,
+            controls_accepted: match state {
+                SERVICE_RUNNING => SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN,
+                _ => 0,
+            },
+            // A non-zero code counts as a failure for the recovery actions
+            win32_exit_code: if exit_code == 0 { NO_ERROR } else { ERROR_SERVICE_SPECIFIC_ERROR },
+            service_specific_exit_code: exit_code as u32,
+            check_point: 0,
+            wait_hint,
+        };
+        // SAFETY: the handle came from RegisterServiceCtrlHandlerExW and the
+        // status outlives the call
+        unsafe {
+            SetServiceStatus(handle, &status);
+        }
+    }
+
+    unsafe extern "system" fn handle_control(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut c_void,
+        _context: *mut c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                report(SERVICE_STOP_PENDING, 0, STOP_WAIT_HINT_MS);
+                daemon::request_stop();
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+        let name = wide(SERVICE_NAME);
+        // SAFETY: the name is a NUL-terminated wide string and the handler
+        // only touches atomics and the status handle
+        let handle = unsafe {
+            RegisterServiceCtrlHandlerExW(name.as_ptr(), handle_control, std::ptr::null_mut())
+        };
+        if handle == 0 {
+            EXIT_CODE.store(1, Ordering::SeqCst);
+            return;
+        }
+        STATUS_HANDLE.store(handle, Ordering::SeqCst);
+
+        report(SERVICE_START_PENDING, 0, STOP_WAIT_HINT_MS);
+        let code = if ipc::running() {
+            eprintln!("Another instance is already running");
+            1
+        } else {
+            report(SERVICE_RUNNING, 0, 0);
+            daemon::serve()
+        };
+        EXIT_CODE.store(code, Ordering::SeqCst);
+        report(SERVICE_STOPPED, code, 0);
+    }
+
+    pub fn run() -> i32 {
+        let name = wide(SERVICE_NAME);
+        let table = [
+            ServiceTableEntry {
+                name: name.as_ptr(),
+                main: Some(service_main),
+            },
+            ServiceTableEntry {
+                name: std::ptr::null(),
+                main: None,
+            },
+        ];
+        // SAFETY: the table is terminated by a null entry and outlives the
+        // dispatcher, which returns once the service has stopped
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) {
+                eprintln!("Error: {} is started by Windows; use --daemon instead", SERVICE_FLAG);
+            } else {
+                eprintln!("Error: Failed to start the service: {}", error);
+            }
+            return 1;
+        }
+        EXIT_CODE.load(Ordering::SeqCst)
+    }
+
+    /// Runs `sc.exe`, which reports its own errors on stdout.
+    fn sc(args: &[&str]) -> Result<(), String> {
+        let output = Command::new("sc.exe")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run sc.exe: {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        let reason = text.lines().map(str::trim).rfind(|l| !l.is_empty()).unwrap_or("");
+        Err(format!("sc.exe {} failed: {}", args[0], reason))
+    }
+
+    pub fn install() -> Result<(), String> {
+        let exe = std::env::current_exe()
+            .map_err(|e| format!("Failed to locate the executable: {}", e))?;
+        let command = format!("\"{}\" {}", exe.display(), SERVICE_FLAG);
+        sc(&[
+            "create",
+            SERVICE_NAME,
+            "binPath=",
+            &command,
+            "start=",
+            "auto",
+            "obj=",
+            "LocalSystem",
+            "DisplayName=",
+            DISPLAY_NAME,
+        ])?;
+        sc(&["description", SERVICE_NAME, DESCRIPTION])?;
+        let mut failure = vec!["failure", SERVICE_NAME];
+        failure.extend_from_slice(RECOVERY);
+        sc(&failure)?;
+        // Also recover when it stops with an error, not only when it crashes
+        sc(&["failureflag", SERVICE_NAME, "1"])?;
+        sc(&["start", SERVICE_NAME])
+    }
+
+    pub fn uninstall() -> Result<(), String> {
+        // Not running is fine
+        let _ = sc(&["stop", SERVICE_NAME]);
+        sc(&["delete", SERVICE_NAME])
+    }
+}